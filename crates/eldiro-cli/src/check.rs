@@ -0,0 +1,353 @@
+use crate::{diagnostics, repl};
+use eldiro::ast::Stmt;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Parses, type-checks and lints every `.eld` file under `paths` without running any of them,
+/// printing everything found to stderr. Fails if any file has an error, which includes using a
+/// name that nothing defines; other lint findings are only errors when `strict` is set. A file or directory that can't be read counts as a file with an
+/// error, and the rest are still checked.
+pub(crate) fn check(paths: &[&str], strict: bool) -> io::Result<ExitCode> {
+    let color = diagnostics::use_color();
+    let files = collect(paths)
+        .into_iter()
+        .map(|found| {
+            found.and_then(|file| match fs::read_to_string(&file) {
+                Ok(source) => Ok((file, source)),
+                Err(e) => Err((file, e)),
+            })
+        })
+        .collect::<Vec<_>>();
+    let checked = files.len();
+    let imported = imported(&files);
+    let mut failed = 0;
+
+    for read in files {
+        let (file, source) = match read {
+            Ok(read) => read,
+            Err((path, e)) => {
+                eprintln!("{}", diagnostics::render_io_error(&e, "read", &path, color));
+                failed += 1;
+                continue;
+            }
+        };
+
+        let module = fs::canonicalize(&file).is_ok_and(|path| imported.contains(&path));
+        let report = check_source(&source, &file.display().to_string(), strict, module, color);
+
+        for diagnostic in &report.diagnostics {
+            eprintln!("{}", diagnostic);
+        }
+        if report.failed {
+            failed += 1;
+        }
+    }
+
+    eprintln!(
+        "checked {} {}; {} with errors",
+        checked,
+        if checked == 1 { "file" } else { "files" },
+        failed
+    );
+
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+#[derive(Debug, PartialEq)]
+struct Report {
+    /// Rendered, in the order they were found.
+    diagnostics: Vec<String>,
+    failed: bool,
+}
+
+/// Names are checked against what `eldiro run` would define for the file at `origin`. Nothing is
+/// run, so the natives that `run` only installs when asked to count as defined too.
+///
+/// A `module` is imported by another file, which can use what it defines, so its top-level
+/// definitions aren't reported as unused. A file that does nothing but define things is taken
+/// to be a module too.
+fn check_source(source: &str, origin: &str, strict: bool, module: bool, color: bool) -> Report {
+    let program = match eldiro::parse(source) {
+        Ok(program) => program,
+        Err(e) => {
+            return Report {
+                diagnostics: vec![diagnostics::render_parse_error(&e, origin, source, color)],
+                failed: true,
+            }
+        }
+    };
+
+    let type_errors = program.check();
+    let mut env = repl::new_env();
+    env.set_current_file(origin);
+    env.allow_imports();
+    env.allow_files();
+    env.allow_env_vars();
+
+    let module = module
+        || program.stmts().iter().all(|stmt| {
+            matches!(
+                stmt,
+                Stmt::BindingDef(_) | Stmt::FuncDef(_) | Stmt::EnumDef(_) | Stmt::Import(_)
+            )
+        });
+    let lints = eldiro::lint_in(&program, eldiro::LintOptions { strict, module }, &env);
+
+    let failed = !type_errors.is_empty()
+        || lints
+            .iter()
+            .any(|diagnostic| diagnostic.severity == eldiro::Severity::Error);
+
+    let diagnostics = type_errors
+        .iter()
+        .map(|error| diagnostics::render_type_error(error, origin, source, color))
+        .chain(
+            lints
+                .iter()
+                .map(|diagnostic| diagnostics::render_lint(diagnostic, origin, source, color)),
+        )
+        .collect();
+
+    Report {
+        diagnostics,
+        failed,
+    }
+}
+
+/// A file to check, or a path that couldn't be read while looking for them along with why.
+type Found = Result<PathBuf, (PathBuf, io::Error)>;
+
+/// A file to check along with its source, or a path that couldn't be read along with why.
+type Read = Result<(PathBuf, String), (PathBuf, io::Error)>;
+
+/// The files that the top level of any of `files` imports, canonicalized so that they match
+/// however they were named. Files that don't parse are skipped, since they are reported anyway.
+fn imported(files: &[Read]) -> HashSet<PathBuf> {
+    let mut imported = HashSet::new();
+
+    for (file, source) in files.iter().flatten() {
+        let Ok(program) = eldiro::parse(source) else {
+            continue;
+        };
+        let dir = file.parent().unwrap_or(Path::new(""));
+
+        for stmt in program.stmts() {
+            if let Stmt::Import(import) = stmt {
+                imported.extend(fs::canonicalize(dir.join(&import.path)));
+            }
+        }
+    }
+
+    imported
+}
+
+/// The files named in `paths` along with the `.eld` files anywhere under the directories named
+/// in it. Each directory's entries are taken in order of name, so that output is the same on
+/// every machine. Paths that don't exist are kept as files, so that reading them reports them.
+fn collect(paths: &[&str]) -> Vec<Found> {
+    let mut found = Vec::new();
+
+    for path in paths {
+        let path = Path::new(path);
+
+        if path.is_dir() {
+            collect_dir(path, &mut found);
+        } else {
+            found.push(Ok(path.to_path_buf()));
+        }
+    }
+
+    found
+}
+
+fn collect_dir(dir: &Path, found: &mut Vec<Found>) {
+    let entries = fs::read_dir(dir).and_then(|entries| {
+        entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()
+    });
+    let mut entries = match entries {
+        Ok(entries) => entries,
+        Err(e) => return found.push(Err((dir.to_path_buf(), e))),
+    };
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            collect_dir(&entry, found);
+        } else if entry.extension().is_some_and(|ext| ext == "eld") {
+            found.push(Ok(entry));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_eld_files_in_order() {
+        let dir = std::env::temp_dir().join(format!("eldiro-check-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        for file in ["b.eld", "a.eld", "notes.txt", "nested/c.eld"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        let extra = dir.join("notes.txt");
+        let found = collect(&[dir.to_str().unwrap(), extra.to_str().unwrap()])
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(
+            found,
+            vec![
+                dir.join("a.eld"),
+                dir.join("b.eld"),
+                dir.join("nested").join("c.eld"),
+                extra,
+            ],
+        );
+    }
+
+    #[test]
+    fn keep_paths_that_cannot_be_read() {
+        let dir = std::env::temp_dir().join(format!("eldiro-check-io-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.eld"), "1").unwrap();
+        let missing = dir.join("missing.eld");
+
+        let found = collect(&[missing.to_str().unwrap(), dir.to_str().unwrap()]);
+
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].as_ref().unwrap(), &missing);
+        assert_eq!(found[1].as_ref().unwrap(), &dir.join("a.eld"));
+        assert!(fs::read_to_string(&missing).is_err());
+    }
+
+    #[test]
+    fn report_parse_errors() {
+        let report = check_source("let = 1", "bad.eld", false, false, false);
+
+        assert!(report.failed);
+        assert_eq!(report.diagnostics.len(), 1);
+        assert!(report.diagnostics[0].starts_with("parse error: "));
+    }
+
+    #[test]
+    fn report_type_errors_and_lints_together() {
+        let report = check_source(
+            "let unused = 2\nlet a = 1 + true\na",
+            "t.eld",
+            false,
+            false,
+            false,
+        );
+
+        assert!(report.failed);
+        assert_eq!(report.diagnostics.len(), 2);
+        assert!(report.diagnostics[0].starts_with("type error: "));
+        assert!(report.diagnostics[1].starts_with("warning: "));
+    }
+
+    #[test]
+    fn warnings_pass_unless_strict() {
+        let source = "let a = 1\nlet a = a + 1\na";
+
+        let relaxed = check_source(source, "t.eld", false, false, false);
+        assert!(!relaxed.failed);
+        assert!(relaxed.diagnostics[0].starts_with("warning: "));
+
+        let strict = check_source(source, "t.eld", true, false, false);
+        assert!(strict.failed);
+        assert!(strict.diagnostics[0].starts_with("error: "));
+    }
+
+    #[test]
+    fn report_undefined_names() {
+        let report = check_source("undefined_thing + 1\nnope(1)", "t.eld", false, false, false);
+
+        assert!(report.failed);
+        assert_eq!(report.diagnostics.len(), 2);
+        assert!(report.diagnostics[0].starts_with("error: 'undefined_thing' is not defined"));
+        assert!(report.diagnostics[1].starts_with("error: 'nope' is not defined"));
+    }
+
+    #[test]
+    fn names_that_run_defines_are_known() {
+        let dir = std::env::temp_dir().join(format!("eldiro-check-names-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("lib.eld"), "fn double x => x * 2\n").unwrap();
+        let main = dir.join("main.eld");
+        let source = "import \"lib.eld\"\nsleep_ms(0)\nread_file(\"x\")\ndouble(len([1]))";
+
+        let report = check_source(source, main.to_str().unwrap(), true, false, false);
+        assert_eq!(report.diagnostics, Vec::<String>::new());
+    }
+
+    #[test]
+    fn modules_can_leave_definitions_unused() {
+        let source = "fn double x => x * 2\nassert(true, \"ok\")";
+
+        assert_eq!(
+            check_source(source, "t.eld", true, false, false)
+                .diagnostics
+                .len(),
+            1
+        );
+        assert!(check_source(source, "t.eld", true, true, false)
+            .diagnostics
+            .is_empty());
+        assert!(check_source(
+            "fn double x => x * 2\nlet ten = 10",
+            "t.eld",
+            true,
+            false,
+            false
+        )
+        .diagnostics
+        .is_empty());
+    }
+
+    #[test]
+    fn find_imported_files() {
+        let dir = std::env::temp_dir().join(format!("eldiro-check-imports-{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(dir.join("lib").join("math.eld"), "fn double x => x * 2\n").unwrap();
+        fs::write(dir.join("main.eld"), "import \"lib/math.eld\"\ndouble(2)").unwrap();
+
+        let files = collect(&[dir.to_str().unwrap()])
+            .into_iter()
+            .map(|found| found.map(|file| (file.clone(), fs::read_to_string(file).unwrap())))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            imported(&files),
+            HashSet::from([fs::canonicalize(dir.join("lib").join("math.eld")).unwrap()]),
+        );
+    }
+
+    #[test]
+    fn clean_file_has_nothing_to_report() {
+        assert_eq!(
+            check_source(
+                "fn double x => x * 2\ndouble(3)",
+                "t.eld",
+                true,
+                false,
+                false
+            ),
+            Report {
+                diagnostics: Vec::new(),
+                failed: false,
+            },
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+const COMMANDS: &[&str] = &[
+    ":ast", ":clear", ":debug", ":doc", ":env", ":help", ":load", ":profile", ":quit", ":restore",
+    ":save", ":type",
+];
+
+/// Completes names defined in the REPL session, keywords, and REPL commands.
+#[derive(Default)]
+pub(crate) struct EldiroHelper {
+    names: Vec<String>,
+}
+
+impl EldiroHelper {
+    /// Refreshes the names offered as completions from the session's environment.
+    pub(crate) fn refresh(&mut self, env: &eldiro::Env) {
+        self.names = env
+            .names()
+            .into_iter()
+            .chain(eldiro::KEYWORDS.iter().copied())
+            .map(str::to_string)
+            .collect();
+        self.names.sort();
+        self.names.dedup();
+    }
+
+    fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let before_cursor = &line[..pos];
+
+        if before_cursor.starts_with(':') && !before_cursor.contains(char::is_whitespace) {
+            return (0, matching(COMMANDS.iter().copied(), before_cursor));
+        }
+
+        let start = before_cursor
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !(c.is_alphanumeric() || *c == '_'))
+            .map_or(0, |(idx, c)| idx + c.len_utf8());
+
+        let prefix = &before_cursor[start..];
+        if prefix.is_empty() {
+            return (pos, Vec::new());
+        }
+
+        (
+            start,
+            matching(self.names.iter().map(String::as_str), prefix),
+        )
+    }
+}
+
+fn matching<'a>(words: impl Iterator<Item = &'a str>, prefix: &str) -> Vec<String> {
+    words
+        .filter(|word| word.starts_with(prefix))
+        .map(str::to_string)
+        .collect()
+}
+
+impl Completer for EldiroHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for EldiroHelper {
+    type Hint = String;
+}
+
+impl Highlighter for EldiroHelper {}
+
+impl Validator for EldiroHelper {}
+
+impl Helper for EldiroHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn helper_with(source: &str) -> EldiroHelper {
+        let mut env = eldiro::Env::default();
+        eldiro::parse(source).unwrap().eval(&mut env).unwrap();
+
+        let mut helper = EldiroHelper::default();
+        helper.refresh(&env);
+        helper
+    }
+
+    #[test]
+    fn complete_binding_and_func_names() {
+        let helper = helper_with("let counter = 1\nfn count_up n => n + 1");
+
+        assert_eq!(
+            helper.candidates("1 + cou", 7),
+            (4, vec!["count_up".to_string(), "counter".to_string()]),
+        );
+    }
+
+    #[test]
+    fn complete_keywords_and_builtins() {
+        let helper = helper_with("");
+
+        assert_eq!(
+            helper.candidates("le", 2),
+            (0, vec!["len".to_string(), "let".to_string()])
+        );
+        assert_eq!(helper.candidates("now", 3), (0, vec!["now_ms".to_string()]));
+    }
+
+    #[test]
+    fn complete_commands() {
+        let helper = helper_with("");
+
+        assert_eq!(helper.candidates(":l", 2), (0, vec![":load".to_string()]));
+    }
+
+    #[test]
+    fn nothing_to_complete_after_operator() {
+        let helper = helper_with("");
+
+        assert_eq!(helper.candidates("1 + ", 4), (4, Vec::new()));
+    }
+}
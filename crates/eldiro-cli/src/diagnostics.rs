@@ -0,0 +1,361 @@
+use std::env;
+use std::io::{self, IsTerminal};
+use std::ops::Range;
+use std::path::Path;
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Whether diagnostics written to stderr should be colored, following https://no-color.org.
+pub(crate) fn use_color() -> bool {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|val| !val.is_empty());
+    !no_color && io::stderr().is_terminal()
+}
+
+/// Renders an error message, followed by the line of `source` that `span` points into with the
+/// span underlined. A span that doesn't fit in `source` leaves out the line rather than guess at
+/// it.
+pub(crate) fn render(
+    kind: &str,
+    message: &str,
+    origin: &str,
+    source: &str,
+    span: Option<Range<usize>>,
+    color: bool,
+) -> String {
+    let paint = |style: &str, text: &str| {
+        if color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    };
+
+    // Warnings are the only thing that doesn't stop the code from running.
+    let kind_style = if kind == "warning" { YELLOW } else { RED };
+
+    let mut rendered = format!(
+        "{}{}",
+        paint(kind_style, kind),
+        paint(BOLD, &format!(": {}", message))
+    );
+
+    let span = match span {
+        Some(span) if span.start <= span.end && source.get(span.start..span.end).is_some() => span,
+        _ => return rendered,
+    };
+
+    let line_start = source[..span.start].rfind('\n').map_or(0, |idx| idx + 1);
+    let line_end = source[span.start..]
+        .find('\n')
+        .map_or(source.len(), |idx| span.start + idx);
+    let line = &source[line_start..line_end];
+
+    let line_number = source[..span.start].matches('\n').count() + 1;
+    let column = source[line_start..span.start].chars().count();
+    let width = source[span.start..span.end.min(line_end)]
+        .chars()
+        .count()
+        .max(1);
+
+    let gutter = " ".repeat(line_number.to_string().len());
+
+    rendered += &format!(
+        "\n{}{} {}:{}:{}\n{} {}\n{} {} {}\n{} {} {}{}",
+        gutter,
+        paint(BLUE, "-->"),
+        origin,
+        line_number,
+        column + 1,
+        gutter,
+        paint(BLUE, "|"),
+        paint(BLUE, &line_number.to_string()),
+        paint(BLUE, "|"),
+        line,
+        gutter,
+        paint(BLUE, "|"),
+        " ".repeat(column),
+        paint(RED, &"^".repeat(width)),
+    );
+
+    rendered
+}
+
+/// Renders an error from parsing `source`.
+pub(crate) fn render_parse_error(
+    error: &eldiro::ParseError,
+    origin: &str,
+    source: &str,
+    color: bool,
+) -> String {
+    render(
+        "parse error",
+        &error.message,
+        origin,
+        source,
+        Some(error.span.clone()),
+        color,
+    )
+}
+
+/// Renders an error from evaluating `source`, or from the source that the error says its span
+/// points into, such as where a function it called was defined.
+pub(crate) fn render_runtime_error(
+    error: &eldiro::RuntimeError,
+    origin: &str,
+    source: &str,
+    color: bool,
+) -> String {
+    let (origin, source) = match error.source() {
+        Some((elsewhere, path)) => (path.unwrap_or(origin), elsewhere),
+        None => (origin, source),
+    };
+
+    render(
+        "error",
+        &error.to_string(),
+        origin,
+        source,
+        error.span(),
+        color,
+    )
+}
+
+/// Renders something the linter found in `source`.
+pub(crate) fn render_lint(
+    diagnostic: &eldiro::Diagnostic,
+    origin: &str,
+    source: &str,
+    color: bool,
+) -> String {
+    let kind = match diagnostic.severity {
+        eldiro::Severity::Warning => "warning",
+        eldiro::Severity::Error => "error",
+    };
+
+    render(
+        kind,
+        &diagnostic.message,
+        origin,
+        source,
+        Some(diagnostic.span.clone()),
+        color,
+    )
+}
+
+/// Renders an error found by type-checking `source`.
+pub(crate) fn render_type_error(
+    error: &eldiro::TypeError,
+    origin: &str,
+    source: &str,
+    color: bool,
+) -> String {
+    render(
+        "type error",
+        &error.message,
+        origin,
+        source,
+        error.span.clone(),
+        color,
+    )
+}
+
+/// Renders an error from doing something to `path`, where `doing` is what failed, such as
+/// `"read"` for reading or listing it.
+pub(crate) fn render_io_error(error: &io::Error, doing: &str, path: &Path, color: bool) -> String {
+    render(
+        "error",
+        &format!("cannot {} {}: {}", doing, path.display(), error),
+        &path.display().to_string(),
+        "",
+        None,
+        color,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_span_on_second_line() {
+        assert_eq!(
+            render(
+                "parse error",
+                "unexpected input",
+                "test.eld",
+                "let a = 1\nlet b = @@\n",
+                Some(18..20),
+                false,
+            ),
+            "parse error: unexpected input
+ --> test.eld:2:9
+  |
+2 | let b = @@
+  |         ^^",
+        );
+    }
+
+    #[test]
+    fn render_without_span() {
+        assert_eq!(
+            render("error", "division by zero", "<repl>", "1 / 0", None, false),
+            "error: division by zero",
+        );
+    }
+
+    #[test]
+    fn render_span_outside_source_without_line() {
+        assert_eq!(
+            render(
+                "error",
+                "division by zero",
+                "<repl>",
+                "f(1)",
+                Some(12..17),
+                false
+            ),
+            "error: division by zero",
+        );
+        assert_eq!(
+            render("error", "oops", "<repl>", "é", Some(1..2), false),
+            "error: oops",
+        );
+    }
+
+    #[test]
+    fn render_io_error_with_path() {
+        assert_eq!(
+            render_io_error(
+                &io::Error::new(io::ErrorKind::NotFound, "not found"),
+                "read",
+                Path::new("lib/a.eld"),
+                false,
+            ),
+            "error: cannot read lib/a.eld: not found",
+        );
+    }
+
+    #[test]
+    fn render_with_color() {
+        assert_eq!(
+            render("error", "oops", "<repl>", "x", Some(0..1), true),
+            "\x1b[1;31merror\x1b[0m\x1b[1m: oops\x1b[0m
+ \x1b[1;34m-->\x1b[0m <repl>:1:1
+  \x1b[1;34m|\x1b[0m
+\x1b[1;34m1\x1b[0m \x1b[1;34m|\x1b[0m x
+  \x1b[1;34m|\x1b[0m \x1b[1;31m^\x1b[0m",
+        );
+    }
+
+    #[test]
+    fn render_type_error_at_call() {
+        let source = "fn f x => x\nf(1, 2)\n";
+        let errors = eldiro::parse(source).unwrap().check();
+
+        assert_eq!(
+            render_type_error(&errors[0], "test.eld", source, false),
+            "type error: function `f` expects 1 argument but got 2
+ --> test.eld:2:1
+  |
+2 | f(1, 2)
+  | ^^^^^^^",
+        );
+    }
+
+    #[test]
+    fn render_unused_binding() {
+        let source = "let unused = 1\n2";
+        let warnings = eldiro::lint(&eldiro::parse(source).unwrap());
+
+        assert_eq!(
+            render_lint(&warnings[0], "test.eld", source, false),
+            "warning: unused binding 'unused'
+ --> test.eld:1:1
+  |
+1 | let unused = 1
+  | ^^^^^^^^^^^^^^",
+        );
+    }
+
+    #[test]
+    fn render_strict_shadowing_as_error() {
+        let source = "let a = 1\nlet a = a";
+        let program = eldiro::parse(source).unwrap();
+        let found = eldiro::lint_with(
+            &program,
+            eldiro::LintOptions {
+                strict: true,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            render_lint(&found[0], "test.eld", source, false),
+            "error: 'a' shadows an earlier definition in the same scope
+ --> test.eld:2:1
+  |
+2 | let a = a
+  | ^^^^^^^^^",
+        );
+    }
+
+    #[test]
+    fn render_division_by_zero() {
+        let source = "let a = 1\nlet b = a / 0\n";
+        let error = eldiro::parse(source)
+            .unwrap()
+            .eval(&mut eldiro::Env::default())
+            .unwrap_err();
+
+        assert_eq!(
+            render_runtime_error(&error, "test.eld", source, false),
+            "error: division by zero
+ --> test.eld:2:9
+  |
+2 | let b = a / 0
+  |         ^^^^^",
+        );
+    }
+
+    #[test]
+    fn render_error_in_function_from_other_source() {
+        let mut env = eldiro::Env::default();
+        eldiro::parse("fn bad x => x / 0")
+            .unwrap()
+            .eval(&mut env)
+            .unwrap();
+        let source = "bad(1)";
+        let error = eldiro::parse(source).unwrap().eval(&mut env).unwrap_err();
+
+        assert_eq!(
+            render_runtime_error(&error, "<repl>", source, false),
+            "error: division by zero
+ --> <repl>:1:13
+  |
+1 | fn bad x => x / 0
+  |             ^^^^^",
+        );
+    }
+
+    #[test]
+    fn render_arity_error_at_call() {
+        let source = "fn add x y => x + y\nlet a = add 1";
+        let error = eldiro::parse(source)
+            .unwrap()
+            .eval(&mut eldiro::Env::default())
+            .unwrap_err();
+
+        assert_eq!(
+            render_runtime_error(&error, "test.eld", source, false),
+            "error: function `add` expects 2 arguments but got 1
+ --> test.eld:2:9
+  |
+2 | let a = add 1
+  |         ^^^^^",
+        );
+    }
+}
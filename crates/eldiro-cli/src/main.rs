@@ -1,37 +1,379 @@
-use std::io::{self, Write};
+mod check;
+mod completion;
+mod diagnostics;
+mod repl;
+mod stepper;
+mod trace;
 
-fn main() -> io::Result<()> {
-    let stdin = io::stdin();
-    let mut stdout = io::stdout();
-    let mut stderr = io::stderr();
-    let mut input = String::new();
-    let mut env = eldiro::Env::default();
+use std::io::{self, IsTerminal, Read};
+use std::path::Path;
+use std::process::ExitCode;
+use std::{env, fs};
 
-    loop {
-        write!(stdout, "→ ")?;
-        stdout.flush()?;
+const RUN_FLAGS: &[&str] = &[
+    "--warn",
+    "--strict",
+    "--trace",
+    "--profile",
+    "--allow-files",
+    "--allow-env",
+];
 
-        stdin.read_line(&mut input)?;
-        match run(input.trim(), &mut env) {
-            Ok(Some(val)) => writeln!(stdout, "{}", val)?,
-            Ok(None) => {}
-            Err(msg) => writeln!(stderr, "{}", msg)?,
+/// The status to exit with when a program fails while running, which is `EX_SOFTWARE` from
+/// `sysexits.h`. It stands apart from the 1 given for bad input, such as a file that can't be read
+/// or doesn't parse, so that callers can tell the two apart. Scripts pick their own status with `exit`.
+const RUNTIME_ERROR: u8 = 70;
+
+fn main() -> io::Result<ExitCode> {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    // What comes after `--` is for the script that `run` runs, which gets it as `args`.
+    let (args, script_args) = match args.iter().position(|arg| arg == "--") {
+        Some(idx) => (&args[..idx], Some(&args[idx + 1..])),
+        None => (&args[..], None),
+    };
+    if script_args.is_some() && args.first().map(String::as_str) != Some("run") {
+        return Ok(usage());
+    }
+
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] if io::stdin().is_terminal() => repl::repl(),
+        [] => run_stdin(),
+        ["-e" | "--eval", source] => Ok(execute(source, "<eval>", &mut repl::new_env())),
+        ["run", flags @ .., path] if flags.iter().all(|flag| RUN_FLAGS.contains(flag)) => {
+            let strict = flags.contains(&"--strict");
+
+            run(
+                path,
+                script_args.unwrap_or_default(),
+                RunOptions {
+                    lint: (strict || flags.contains(&"--warn")).then_some(eldiro::LintOptions {
+                        strict,
+                        ..Default::default()
+                    }),
+                    trace: flags.contains(&"--trace"),
+                    profile: flags.contains(&"--profile"),
+                    allow_files: flags.contains(&"--allow-files"),
+                    allow_env: flags.contains(&"--allow-env"),
+                },
+            )
+        }
+        ["--dump-ast", path] => dump_ast(path),
+        ["--check", path] => check::check(&[path], false),
+        ["check", "--strict", paths @ ..] if !paths.is_empty() => check::check(paths, true),
+        ["check", paths @ ..] if !paths.is_empty() => check::check(paths, false),
+        ["test", path] => test(path),
+        ["fmt", "--check", paths @ ..] if !paths.is_empty() => fmt(paths, true),
+        ["fmt", paths @ ..] if !paths.is_empty() => fmt(paths, false),
+        _ => Ok(usage()),
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!(
+        "usage: eldiro [-e <code> | run [--warn] [--strict] [--trace] [--profile] [--allow-files] [--allow-env] <file> [-- <arg>...] | --dump-ast <file> | check [--strict] <file|dir>... | test <file> | fmt [--check] <file>...]"
+    );
+    ExitCode::FAILURE
+}
+
+/// How `eldiro run` runs a file.
+struct RunOptions {
+    /// Reports anything the linter finds before running. The file isn't run if the linter finds
+    /// an error, which only happens in strict mode.
+    lint: Option<eldiro::LintOptions>,
+    /// Prints each call and statement to stderr as it runs.
+    trace: bool,
+    /// Prints how often each function was called and how long the calls took to stderr at the
+    /// end.
+    profile: bool,
+    /// Lets the file use `read_file` and `write_file`.
+    allow_files: bool,
+    /// Lets the file use `env_var`.
+    allow_env: bool,
+}
+
+/// Runs a file with `args` bound to a list of `script_args`.
+fn run(path: &str, script_args: &[String], options: RunOptions) -> io::Result<ExitCode> {
+    let RunOptions {
+        lint,
+        trace,
+        profile,
+        allow_files,
+        allow_env,
+    } = options;
+    let source = match read_source(path) {
+        Ok(source) => source,
+        Err(code) => return Ok(code),
+    };
+
+    if let Some(options) = lint {
+        // Parse errors are reported when the file is run.
+        if let Ok(program) = eldiro::parse(&source) {
+            let found = eldiro::lint_with(&program, options);
+
+            for diagnostic in &found {
+                eprintln!(
+                    "{}",
+                    diagnostics::render_lint(diagnostic, path, &source, diagnostics::use_color())
+                );
+            }
+
+            if found
+                .iter()
+                .any(|diagnostic| diagnostic.severity == eldiro::Severity::Error)
+            {
+                return Ok(ExitCode::FAILURE);
+            }
         }
+    }
 
-        input.clear();
+    if trace {
+        if let Err(msg) = trace::install() {
+            eprintln!("{}", msg);
+            return Ok(ExitCode::FAILURE);
+        }
     }
+
+    let mut env = repl::new_env();
+    env.set_current_file(path);
+    env.allow_imports();
+    if profile {
+        env.enable_profiling();
+    }
+    if allow_files {
+        env.allow_files();
+    }
+    if allow_env {
+        env.allow_env_vars();
+    }
+
+    let script_args = script_args
+        .iter()
+        .map(|arg| eldiro::Val::Str(arg.clone()))
+        .collect();
+    env.set_binding("args", eldiro::Val::List(script_args))
+        .expect("`args` is not a constant in a new environment");
+
+    let code = execute(&source, path, &mut env);
+
+    if profile {
+        repl::print_profile(&env.profile(), &mut io::stderr())?;
+    }
+
+    Ok(code)
 }
 
-fn run(input: &str, env: &mut eldiro::Env) -> Result<Option<eldiro::Val>, String> {
-    let parse = eldiro::parse(input).map_err(|msg| format!("Parse error: {}", msg))?;
+fn dump_ast(path: &str) -> io::Result<ExitCode> {
+    let source = match read_source(path) {
+        Ok(source) => source,
+        Err(code) => return Ok(code),
+    };
 
-    let evaluated = parse
-        .eval(env)
-        .map_err(|msg| format!("Evaluation error: {}", msg))?;
+    match eldiro::parse(&source) {
+        Ok(program) => {
+            print!("{}", program.dump_ast());
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(e) => {
+            eprintln!(
+                "{}",
+                diagnostics::render_parse_error(&e, path, &source, diagnostics::use_color())
+            );
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
 
-    if evaluated == eldiro::Val::Unit {
-        Ok(None)
+/// Reads the file at `path`, reporting why it can't be read the way `check` does and giving the
+/// status to exit with if so.
+fn read_source(path: &str) -> Result<String, ExitCode> {
+    fs::read_to_string(path).map_err(|e| {
+        eprintln!(
+            "{}",
+            diagnostics::render_io_error(&e, "read", Path::new(path), diagnostics::use_color())
+        );
+        ExitCode::FAILURE
+    })
+}
+
+fn run_stdin() -> io::Result<ExitCode> {
+    let mut source = String::new();
+    io::stdin().read_to_string(&mut source)?;
+
+    Ok(execute(&source, "<stdin>", &mut repl::new_env()))
+}
+
+/// Parses and evaluates `source`, printing its final value unless it is unit. The exit status is
+/// whatever the program passed to `exit`, if it called it.
+fn execute(source: &str, origin: &str, env: &mut eldiro::Env) -> ExitCode {
+    let color = diagnostics::use_color();
+
+    let program = match eldiro::parse(source) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!(
+                "{}",
+                diagnostics::render_parse_error(&e, origin, source, color)
+            );
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match program.eval(env) {
+        Ok(eldiro::Val::Unit) => ExitCode::SUCCESS,
+        Ok(val) => {
+            println!("{}", val);
+            ExitCode::SUCCESS
+        }
+        Err(eldiro::RuntimeError::Exit(code)) => ExitCode::from(code),
+        Err(e) => {
+            eprintln!(
+                "{}",
+                diagnostics::render_runtime_error(&e, origin, source, color)
+            );
+            ExitCode::from(RUNTIME_ERROR)
+        }
+    }
+}
+
+fn test(path: &str) -> io::Result<ExitCode> {
+    let source = match read_source(path) {
+        Ok(source) => source,
+        Err(code) => return Ok(code),
+    };
+
+    let program = match eldiro::parse(&source) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!(
+                "{}",
+                diagnostics::render_parse_error(&e, path, &source, diagnostics::use_color())
+            );
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    let mut env = repl::new_env();
+    env.set_current_file(path);
+    env.allow_imports();
+
+    let outcomes = match program.run_tests(&mut env) {
+        Ok(outcomes) => outcomes,
+        Err(e) => {
+            eprintln!(
+                "{}",
+                diagnostics::render_runtime_error(&e, path, &source, diagnostics::use_color())
+            );
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    let mut failed = 0;
+    for outcome in &outcomes {
+        match &outcome.result {
+            Ok(()) => println!("test {} ... ok", outcome.name),
+            Err(msg) => {
+                failed += 1;
+                println!("test {} ... FAILED: {}", outcome.name, msg);
+            }
+        }
+    }
+
+    println!("\n{} passed; {} failed", outcomes.len() - failed, failed);
+
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
     } else {
-        Ok(Some(evaluated))
+        ExitCode::FAILURE
+    })
+}
+
+/// Rewrites each file in canonical form, or with `check` only reports the files that would
+/// change.
+fn fmt(paths: &[&str], check: bool) -> io::Result<ExitCode> {
+    let mut ok = true;
+
+    for path in paths {
+        let source = match read_source(path) {
+            Ok(source) => source,
+            Err(_) => {
+                ok = false;
+                continue;
+            }
+        };
+
+        let formatted = match eldiro::format(&source) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    diagnostics::render_parse_error(&e, path, &source, diagnostics::use_color())
+                );
+                ok = false;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            println!("{} is not formatted", path);
+            ok = false;
+        } else if let Err(e) = fs::write(path, formatted) {
+            eprintln!(
+                "{}",
+                diagnostics::render_io_error(
+                    &e,
+                    "write",
+                    Path::new(path),
+                    diagnostics::use_color()
+                )
+            );
+            ok = false;
+        }
+    }
+
+    Ok(if ok {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unreadable_files_are_bad_input() {
+        let missing =
+            std::env::temp_dir().join(format!("eldiro-missing-{}.eld", std::process::id()));
+        let missing = missing.to_str().unwrap();
+
+        assert_eq!(read_source(missing), Err(ExitCode::FAILURE));
+        for code in [
+            run(
+                missing,
+                &[],
+                RunOptions {
+                    lint: None,
+                    trace: false,
+                    profile: false,
+                    allow_files: false,
+                    allow_env: false,
+                },
+            ),
+            dump_ast(missing),
+            test(missing),
+            fmt(&[missing], false),
+        ] {
+            assert_eq!(code.unwrap(), ExitCode::FAILURE);
+        }
     }
 }
@@ -0,0 +1,481 @@
+use crate::completion::EldiroHelper;
+use crate::diagnostics;
+use crate::stepper::Stepper;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::Editor;
+use std::env;
+use std::fs;
+use std::io::{self, BufReader, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const HELP: &str = "\
+:help            show this message
+:env             list the bindings and functions defined so far
+:load <file>     evaluate a file into the current session
+:ast <code>      show how code is parsed, without evaluating it
+:type <code>     show the inferred type of code or a function, without evaluating it
+:debug <code>    evaluate code one statement at a time
+:doc <name>      show the doc comment of a binding or function
+:profile         show how often each function was called and how long the calls took
+:save <file>     write everything defined so far to a file
+:restore <file>  define again everything saved to a file with :save
+:clear           forget everything defined so far
+:quit            exit the REPL";
+
+#[derive(Debug, PartialEq)]
+enum Command<'a> {
+    Help,
+    Env,
+    Load(&'a str),
+    Ast(&'a str),
+    Type(&'a str),
+    Debug(&'a str),
+    Doc(&'a str),
+    Save(&'a str),
+    Restore(&'a str),
+    Profile,
+    Clear,
+    Quit,
+}
+
+impl<'a> Command<'a> {
+    fn parse(input: &'a str) -> Result<Self, String> {
+        let (name, arg) = match input.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (input, ""),
+        };
+
+        match (name, arg) {
+            (":help", "") => Ok(Self::Help),
+            (":env", "") => Ok(Self::Env),
+            (":load", "") => Err(":load expects a file name".to_string()),
+            (":load", path) => Ok(Self::Load(path)),
+            (":ast", "") => Err(":ast expects some code".to_string()),
+            (":ast", code) => Ok(Self::Ast(code)),
+            (":type", "") => Err(":type expects some code".to_string()),
+            (":type", code) => Ok(Self::Type(code)),
+            (":debug", "") => Err(":debug expects some code".to_string()),
+            (":debug", code) => Ok(Self::Debug(code)),
+            (":doc", "") => Err(":doc expects a name".to_string()),
+            (":doc", name) => Ok(Self::Doc(name)),
+            (":save", "") => Err(":save expects a file name".to_string()),
+            (":save", path) => Ok(Self::Save(path)),
+            (":restore", "") => Err(":restore expects a file name".to_string()),
+            (":restore", path) => Ok(Self::Restore(path)),
+            (":profile", "") => Ok(Self::Profile),
+            (":clear", "") => Ok(Self::Clear),
+            (":quit", "") => Ok(Self::Quit),
+            _ => Err(format!("unknown command {}; try :help", input)),
+        }
+    }
+}
+
+pub(crate) fn new_env() -> eldiro::Env<'static> {
+    let mut env = eldiro::Env::default();
+    env.allow_sleep();
+    env
+}
+
+/// The REPL always profiles, so that `:profile` can report on everything run so far. Whoever is
+/// typing can read their own files, so imports are on as well.
+fn new_session() -> eldiro::Env<'static> {
+    let mut env = new_env();
+    env.enable_profiling();
+    env.allow_imports();
+    env
+}
+
+pub(crate) fn repl() -> io::Result<ExitCode> {
+    let mut editor: Editor<EldiroHelper, DefaultHistory> = Editor::new().map_err(into_io_error)?;
+    let history = history_path();
+    if let Some(history) = &history {
+        // There won't be a history file the first time the REPL is started.
+        let _ = editor.load_history(history);
+    }
+
+    let mut stdout = io::stdout();
+    let mut stderr = io::stderr();
+    let mut input = String::new();
+    let mut env = new_session();
+    editor.set_helper(Some(EldiroHelper::default()));
+
+    loop {
+        if let Some(helper) = editor.helper_mut() {
+            helper.refresh(&env);
+        }
+
+        let prompt = if input.is_empty() { "→ " } else { "... " };
+
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                input.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(into_io_error(e)),
+        };
+
+        let first_line = input.is_empty();
+        input.push_str(&line);
+        input.push('\n');
+
+        if first_line && line.trim_start().starts_with(':') {
+            editor
+                .add_history_entry(line.trim())
+                .map_err(into_io_error)?;
+
+            match Command::parse(line.trim()) {
+                Ok(Command::Help) => writeln!(stdout, "{}", HELP)?,
+                Ok(Command::Env) => print_env(&env, &mut stdout)?,
+                Ok(Command::Load(path)) => match env.load_file(path) {
+                    Ok(eldiro::Val::Unit) => {}
+                    Ok(val) => writeln!(stdout, "{}", val)?,
+                    Err(msg) => writeln!(stderr, "{}", msg)?,
+                },
+                Ok(Command::Ast(code)) => match eldiro::parse(code) {
+                    Ok(program) => write!(stdout, "{}", program.dump_ast())?,
+                    Err(e) => writeln!(
+                        stderr,
+                        "{}",
+                        diagnostics::render_parse_error(
+                            &e,
+                            "<repl>",
+                            code,
+                            diagnostics::use_color()
+                        )
+                    )?,
+                },
+                Ok(Command::Type(code)) => match infer(code, &env) {
+                    Ok(ty) => writeln!(stdout, "{}", ty)?,
+                    Err(msg) => writeln!(stderr, "{}", msg)?,
+                },
+                Ok(Command::Debug(code)) => {
+                    env.set_debugger(Stepper::new(BufReader::new(io::stdin()), io::stdout()));
+                    let result = run(code, &mut env);
+                    env.remove_debugger();
+
+                    match result {
+                        Ok(Some(val)) => writeln!(stdout, "{}", val)?,
+                        Ok(None) => {}
+                        Err(msg) => writeln!(stderr, "{}", msg)?,
+                    }
+                }
+                Ok(Command::Doc(name)) => match doc(name, &env) {
+                    Ok(doc) => writeln!(stdout, "{}", doc)?,
+                    Err(msg) => writeln!(stderr, "{}", msg)?,
+                },
+                Ok(Command::Save(path)) => {
+                    if let Err(msg) = save(path, &env) {
+                        writeln!(stderr, "{}", msg)?;
+                    }
+                }
+                Ok(Command::Restore(path)) => {
+                    if let Err(msg) = restore(path, &mut env) {
+                        writeln!(stderr, "{}", msg)?;
+                    }
+                }
+                Ok(Command::Profile) => print_profile(&env.profile(), &mut stdout)?,
+                Ok(Command::Clear) => env = new_session(),
+                Ok(Command::Quit) => break,
+                Err(msg) => writeln!(stderr, "{}", msg)?,
+            }
+
+            input.clear();
+            continue;
+        }
+
+        // A blank line forces evaluation, so that a mistake can't leave the prompt stuck waiting
+        // for more input.
+        if eldiro::is_incomplete(&input) && !line.trim().is_empty() {
+            continue;
+        }
+
+        editor
+            .add_history_entry(input.trim())
+            .map_err(into_io_error)?;
+
+        match run(input.trim(), &mut env) {
+            Ok(Some(val)) => writeln!(stdout, "{}", val)?,
+            Ok(None) => {}
+            Err(msg) => writeln!(stderr, "{}", msg)?,
+        }
+
+        input.clear();
+    }
+
+    if let Some(history) = &history {
+        if let Err(e) = editor.save_history(history) {
+            writeln!(
+                stderr,
+                "could not save history to {}: {}",
+                history.display(),
+                e
+            )?;
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// History is kept in `~/.eldiro_history`, or not at all if there's no home directory.
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".eldiro_history"))
+}
+
+fn into_io_error(e: ReadlineError) -> io::Error {
+    match e {
+        ReadlineError::Io(e) => e,
+        e => io::Error::other(e),
+    }
+}
+
+pub(crate) fn print_env(env: &eldiro::Env, out: &mut impl Write) -> io::Result<()> {
+    for (name, val) in env.bindings() {
+        writeln!(out, "let {} = {}", name, val)?;
+    }
+
+    for (name, params) in env.funcs() {
+        if params.is_empty() {
+            writeln!(out, "fn {}", name)?;
+        } else {
+            writeln!(out, "fn {} {}", name, params.join(" "))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists functions by the time spent in them, with how often each was called.
+pub(crate) fn print_profile(
+    profile: &[eldiro::FuncProfile],
+    out: &mut impl Write,
+) -> io::Result<()> {
+    if profile.is_empty() {
+        return writeln!(out, "no functions have been called");
+    }
+
+    writeln!(out, "{:>8}  {:>10}  function", "calls", "ms")?;
+    for func in profile {
+        writeln!(
+            out,
+            "{:>8}  {:>10.3}  {}",
+            func.calls,
+            func.total.as_secs_f64() * 1000.0,
+            func.name
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Infers the type of `code` in the session so far, rendering any errors as diagnostics.
+fn infer(code: &str, env: &eldiro::Env) -> Result<String, String> {
+    let color = diagnostics::use_color();
+
+    let program = eldiro::parse(code)
+        .map_err(|e| diagnostics::render_parse_error(&e, "<repl>", code, color))?;
+
+    program.infer(env).map_err(|errors| {
+        errors
+            .iter()
+            .map(|e| diagnostics::render_type_error(e, "<repl>", code, color))
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+/// The doc comment of whatever `name` refers to in the session so far.
+fn doc(name: &str, env: &eldiro::Env) -> Result<String, String> {
+    match env.doc(name) {
+        Some(doc) => Ok(doc.to_string()),
+        None if env.names().contains(&name) => Ok(format!("'{}' has no documentation", name)),
+        None => Err(format!("'{}' is not defined", name)),
+    }
+}
+
+/// Writes a snapshot of the session so far to `path`, for `:restore` to read back.
+fn save(path: &str, env: &eldiro::Env) -> Result<(), String> {
+    fs::write(path, env.snapshot()).map_err(|e| format!("cannot save to '{}': {}", path, e))
+}
+
+/// Defines again everything in the snapshot at `path`.
+fn restore(path: &str, env: &mut eldiro::Env) -> Result<(), String> {
+    let snapshot =
+        fs::read_to_string(path).map_err(|e| format!("cannot restore '{}': {}", path, e))?;
+    env.restore(&snapshot).map_err(|e| e.to_string())
+}
+
+/// Parses and evaluates `input`, rendering any error as a diagnostic.
+fn run(input: &str, env: &mut eldiro::Env) -> Result<Option<eldiro::Val>, String> {
+    let color = diagnostics::use_color();
+
+    let program = eldiro::parse(input)
+        .map_err(|e| diagnostics::render_parse_error(&e, "<repl>", input, color))?;
+
+    let evaluated = program
+        .eval(env)
+        .map_err(|e| diagnostics::render_runtime_error(&e, "<repl>", input, color))?;
+
+    if evaluated == eldiro::Val::Unit {
+        Ok(None)
+    } else {
+        Ok(Some(evaluated))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(Command::parse(":env"), Ok(Command::Env));
+        assert_eq!(
+            Command::parse(":load  lib.eld "),
+            Ok(Command::Load("lib.eld"))
+        );
+        assert_eq!(
+            Command::parse(":load"),
+            Err(":load expects a file name".to_string()),
+        );
+        assert_eq!(Command::parse(":ast 1 + 2"), Ok(Command::Ast("1 + 2")));
+        assert_eq!(Command::parse(":type add"), Ok(Command::Type("add")));
+        assert_eq!(Command::parse(":debug f(1)"), Ok(Command::Debug("f(1)")));
+        assert_eq!(Command::parse(":doc  scale"), Ok(Command::Doc("scale")));
+        assert_eq!(
+            Command::parse(":doc"),
+            Err(":doc expects a name".to_string()),
+        );
+        assert_eq!(
+            Command::parse(":save session.eld-state"),
+            Ok(Command::Save("session.eld-state"))
+        );
+        assert_eq!(
+            Command::parse(":restore session.eld-state"),
+            Ok(Command::Restore("session.eld-state"))
+        );
+        assert_eq!(
+            Command::parse(":save"),
+            Err(":save expects a file name".to_string()),
+        );
+        assert_eq!(Command::parse(":profile"), Ok(Command::Profile));
+        assert_eq!(
+            Command::parse(":frobnicate"),
+            Err("unknown command :frobnicate; try :help".to_string()),
+        );
+    }
+
+    #[test]
+    fn print_bindings_then_funcs() {
+        let mut env = new_env();
+        run("let x = 1\nfn add a b => a + b\nfn one => 1", &mut env).unwrap();
+
+        let mut out = Vec::new();
+        print_env(&env, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "let x = 1\nfn add a b\nfn one\n",
+        );
+    }
+
+    #[test]
+    fn print_profile_table() {
+        let mut out = Vec::new();
+        print_profile(
+            &[eldiro::FuncProfile {
+                name: "fib".to_string(),
+                calls: 15,
+                total: std::time::Duration::from_micros(1500),
+            }],
+            &mut out,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "   calls          ms  function\n      15       1.500  fib\n",
+        );
+    }
+
+    #[test]
+    fn profile_session() {
+        let mut env = new_session();
+        run(
+            "fn one => 1
+one() + one()",
+            &mut env,
+        )
+        .unwrap();
+
+        let profile = env.profile();
+        assert_eq!((profile[0].name.as_str(), profile[0].calls), ("one", 2));
+    }
+
+    #[test]
+    fn infer_type_of_session_function() {
+        let mut env = new_env();
+        run("fn add a b => a + b", &mut env).unwrap();
+
+        assert_eq!(
+            infer("add", &env),
+            Ok("fn(number, number) -> number".to_string())
+        );
+    }
+
+    #[test]
+    fn show_doc_of_session_definitions() {
+        let mut env = new_env();
+        run(
+            "## Doubles x.\nfn double x => x * 2
+let a = 1",
+            &mut env,
+        )
+        .unwrap();
+
+        assert_eq!(doc("double", &env), Ok("Doubles x.".to_string()));
+        assert_eq!(doc("a", &env), Ok("'a' has no documentation".to_string()));
+        assert_eq!(doc("b", &env), Err("'b' is not defined".to_string()));
+    }
+
+    #[test]
+    fn save_and_restore_session() {
+        let path =
+            std::env::temp_dir().join(format!("eldiro-repl-{}.eld-state", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut env = new_env();
+        run(
+            "## Doubles x.\nfn double x => x * 2\nlet a = double(4)",
+            &mut env,
+        )
+        .unwrap();
+        save(path, &env).unwrap();
+
+        let mut restored = new_env();
+        restore(path, &mut restored).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(
+            run("double(a)", &mut restored),
+            Ok(Some(eldiro::Val::Number(16.into())))
+        );
+        assert_eq!(doc("double", &restored), Ok("Doubles x.".to_string()));
+        assert!(restore(path, &mut restored)
+            .unwrap_err()
+            .starts_with(&format!("cannot restore '{}'", path)));
+    }
+
+    #[test]
+    fn show_errors_in_functions_from_earlier_input() {
+        let mut env = new_env();
+        run("fn bad x => x / 0", &mut env).unwrap();
+
+        let error = run("bad(1)", &mut env).unwrap_err();
+        assert!(error.contains("division by zero"));
+        assert!(error.contains("fn bad x => x / 0"));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::repl;
+use eldiro::{Action, Debugger, Pause};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+step      run this statement and pause before the next one (or just press enter)
+continue  run the rest without pausing
+env       list the bindings and functions visible here
+where     show the functions being called
+abort     stop evaluating";
+
+/// Steps through code in `:debug` mode, showing each statement before it runs and reading what
+/// to do next from `input`.
+pub(crate) struct Stepper<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R, W> Stepper<R, W> {
+    pub(crate) fn new(input: R, output: W) -> Self {
+        Self { input, output }
+    }
+}
+
+impl<R, W> fmt::Debug for Stepper<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stepper").finish_non_exhaustive()
+    }
+}
+
+impl<R: BufRead, W: Write> Stepper<R, W> {
+    fn prompt(&mut self, pause: &Pause) -> io::Result<Action> {
+        writeln!(self.output, "{}", pause.code())?;
+
+        loop {
+            write!(self.output, "(debug) ")?;
+            self.output.flush()?;
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(Action::Abort);
+            }
+
+            match line.trim() {
+                "" | "s" | "step" => return Ok(Action::Step),
+                "c" | "continue" => return Ok(Action::Continue),
+                "a" | "abort" => return Ok(Action::Abort),
+                "e" | "env" => repl::print_env(pause.env, &mut self.output)?,
+                "w" | "where" => {
+                    let stack = pause.call_stack();
+                    if stack.is_empty() {
+                        writeln!(self.output, "at top level")?;
+                    }
+                    for callee in stack {
+                        writeln!(self.output, "in {}", callee)?;
+                    }
+                }
+                _ => writeln!(self.output, "{}", HELP)?,
+            }
+        }
+    }
+}
+
+/// Aborts if the terminal can't be read from or written to, since there's no way to ask what to
+/// do next.
+impl<R: BufRead + Send, W: Write + Send> Debugger for Stepper<R, W> {
+    fn pause(&mut self, pause: &Pause) -> Action {
+        self.prompt(pause).unwrap_or(Action::Abort)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Output that can still be read after the stepper that writes it is handed to an
+    /// environment.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn debug(
+        code: &str,
+        input: &'static str,
+    ) -> (Result<eldiro::Val, eldiro::RuntimeError>, String) {
+        let output = Shared::default();
+        let mut env = repl::new_env();
+        env.set_debugger(Stepper::new(input.as_bytes(), output.clone()));
+
+        let result = eldiro::parse(code).unwrap().eval(&mut env);
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        (result, output)
+    }
+
+    #[test]
+    fn step_and_show_env() {
+        assert_eq!(
+            debug("let x = 1\nfn f a => a\nf(x)", "s\nenv\n\nwhere\nc\n"),
+            (
+                Ok(eldiro::Val::Number(1.into())),
+                "let x = 1\n(debug) fn f a => a\n(debug) let x = 1\n(debug) f(x)\n\
+                 (debug) at top level\n(debug) "
+                    .to_string(),
+            ),
+        );
+    }
+
+    #[test]
+    fn abort_at_end_of_input() {
+        let (result, output) = debug("let x = 1\nx", "bogus\n");
+
+        assert_eq!(result, Err(eldiro::RuntimeError::Aborted));
+        assert!(output.contains("continue  run the rest without pausing"));
+    }
+}
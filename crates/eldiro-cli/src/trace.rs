@@ -0,0 +1,118 @@
+//! Prints an indented outline of what the interpreter does for `run --trace`, built from the
+//! spans that eldiro emits with its `tracing` feature.
+
+#[cfg(feature = "tracing")]
+pub(crate) use printer::install;
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn install() -> Result<(), String> {
+    Err("--trace needs eldiro to be built with the tracing feature".to_string())
+}
+
+#[cfg(feature = "tracing")]
+mod printer {
+    use std::io::Write;
+    use std::sync::Mutex;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id};
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+
+    /// Prints the outline to stderr for the rest of the process.
+    pub(crate) fn install() -> Result<(), String> {
+        let subscriber = tracing_subscriber::registry().with(Printer::new(std::io::stderr()));
+        tracing::subscriber::set_global_default(subscriber).map_err(|e| e.to_string())
+    }
+
+    /// Writes a line for each span as it is entered, indented by how many spans it is inside.
+    struct Printer<W> {
+        out: Mutex<W>,
+    }
+
+    /// A span's field values, rendered once when it is created.
+    #[derive(Default)]
+    struct Fields(String);
+
+    impl<W> Printer<W> {
+        fn new(out: W) -> Self {
+            Self {
+                out: Mutex::new(out),
+            }
+        }
+    }
+
+    impl<S, W> Layer<S> for Printer<W>
+    where
+        S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+        W: Write + 'static,
+    {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(fields);
+            }
+        }
+
+        fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+            let span = match ctx.span(id) {
+                Some(span) => span,
+                None => return,
+            };
+
+            let depth = span.scope().skip(1).count();
+            let extensions = span.extensions();
+            let fields = extensions.get::<Fields>().map_or("", |fields| &fields.0);
+
+            // There's nowhere to report a failure to write the trace.
+            if let Ok(mut out) = self.out.lock() {
+                let _ = writeln!(out, "{}{}{}", "  ".repeat(depth), span.name(), fields);
+            }
+        }
+    }
+
+    /// Only the first line of each value is kept, so that a function definition takes up one line.
+    impl Visit for Fields {
+        fn record_debug(&mut self, _field: &Field, value: &dyn std::fmt::Debug) {
+            let value = format!("{:?}", value);
+            self.0.push(' ');
+            self.0.push_str(value.lines().next().unwrap_or_default());
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::sync::Arc;
+
+        #[derive(Clone, Default)]
+        struct Shared(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn indent_calls_and_statements() {
+            let out = Shared::default();
+            let subscriber = tracing_subscriber::registry().with(Printer::new(out.clone()));
+
+            tracing::subscriber::with_default(subscriber, || {
+                let program = eldiro::parse("fn f x => {\n  x\n}\nf(1)").unwrap();
+                program.eval(&mut eldiro::Env::default()).unwrap();
+            });
+
+            assert_eq!(
+                String::from_utf8(out.0.lock().unwrap().clone()).unwrap(),
+                "parse\nstmt fn f x => {\nstmt f(1)\n  call f\n    stmt {\n      stmt x\n",
+            );
+        }
+    }
+}
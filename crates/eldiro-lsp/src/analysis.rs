@@ -0,0 +1,425 @@
+//! What the server works out about a document from its source text. Everything here works in
+//! byte offsets; converting to and from the positions that LSP uses happens in `main.rs`.
+//!
+//! Names are resolved on the lossless syntax tree rather than the one programs are evaluated
+//! from, so navigation and completion keep working while the code has syntax errors.
+
+use eldiro::syntax::ast::{self, AstNode};
+use eldiro::syntax::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken};
+use eldiro::{Env, Severity};
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// A parse error, type error or lint to show in the editor.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Problem {
+    pub(crate) span: Range<usize>,
+    pub(crate) severity: Severity,
+    pub(crate) message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DefKind {
+    Binding,
+    Func,
+    Param,
+    Variant,
+}
+
+/// A name that code can refer to.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Def {
+    pub(crate) name: String,
+    pub(crate) kind: DefKind,
+    /// Where the name itself is written.
+    pub(crate) name_span: Range<usize>,
+    /// All of the code that defines it, such as the whole `let`.
+    pub(crate) span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Completion {
+    pub(crate) label: String,
+    /// What kind of thing the document defines by this name, which is `None` for keywords and
+    /// names from the environment.
+    pub(crate) def: Option<DefKind>,
+}
+
+/// Everything wrong with `source`: the parse error if it doesn't parse, and otherwise its type
+/// errors and lints. Type errors that don't keep track of where they are point at the start.
+pub(crate) fn problems(source: &str) -> Vec<Problem> {
+    let program = match eldiro::parse(source) {
+        Ok(program) => program,
+        Err(e) => {
+            return vec![Problem {
+                span: e.span,
+                severity: Severity::Error,
+                message: e.message,
+            }]
+        }
+    };
+
+    let type_errors = program.check().into_iter().map(|error| Problem {
+        span: error.span.unwrap_or(0..0),
+        severity: Severity::Error,
+        message: error.message,
+    });
+    let lints = eldiro::lint(&program)
+        .into_iter()
+        .map(|diagnostic| Problem {
+            span: diagnostic.span,
+            severity: diagnostic.severity,
+            message: diagnostic.message,
+        });
+
+    let mut problems: Vec<_> = type_errors.chain(lints).collect();
+    problems.sort_by_key(|problem| problem.span.start);
+    problems
+}
+
+/// Where the name at `offset` is defined, if the document defines it.
+pub(crate) fn definition(source: &str, offset: usize) -> Option<Def> {
+    let root = eldiro::syntax::parse(source).syntax();
+    let token = name_at(&root, offset)?;
+
+    visible(&token.parent(), token.text_range().start)
+        .into_iter()
+        .find(|def| def.name == token.text())
+}
+
+/// Describes the name at `offset`, such as `a: number`, with its type inferred as it would be if
+/// the document were evaluated in `env`. Parameters and names defined in documents that don't
+/// parse are described without a type.
+pub(crate) fn hover(source: &str, offset: usize, env: &Env) -> Option<String> {
+    let root = eldiro::syntax::parse(source).syntax();
+    let token = name_at(&root, offset)?;
+    let name = token.text();
+
+    let ty = match definition(source, offset) {
+        Some(def) => eldiro::parse(source).ok().and_then(|program| {
+            program
+                .definition_types(env)
+                .into_iter()
+                .find_map(|(span, ty)| (span == def.span).then_some(ty))
+        }),
+        None if env.names().contains(&name) => eldiro::parse(name).ok()?.infer(env).ok(),
+        None => return None,
+    };
+
+    Some(match ty {
+        Some(ty) => format!("{}: {}", name, ty),
+        None => name.to_string(),
+    })
+}
+
+/// The names that could be written at `offset`: those the document defines where `offset` can
+/// see them, the names in `env`, and keywords.
+pub(crate) fn completions(source: &str, offset: usize, env: &Env) -> Vec<Completion> {
+    let root = eldiro::syntax::parse(source).syntax();
+    let node = node_at(&root, offset);
+
+    let defined = visible(&node, offset).into_iter().map(|def| Completion {
+        label: def.name,
+        def: Some(def.kind),
+    });
+    let others = env
+        .names()
+        .into_iter()
+        .chain(eldiro::KEYWORDS.iter().copied())
+        .map(|name| Completion {
+            label: name.to_string(),
+            def: None,
+        });
+
+    let mut seen = HashSet::new();
+    defined
+        .chain(others)
+        .filter(|completion| seen.insert(completion.label.clone()))
+        .collect()
+}
+
+/// The name at `offset`, counting a cursor just after a name as being on it. Field names and the
+/// names of named arguments aren't names of anything that can be looked up.
+fn name_at(root: &SyntaxNode, offset: usize) -> Option<SyntaxToken> {
+    let token = root.tokens().into_iter().find(|token| {
+        let range = token.text_range();
+        token.kind() == SyntaxKind::Ident && range.start <= offset && offset <= range.end
+    })?;
+
+    let parent = token.parent();
+    let is_label = match parent.kind() {
+        SyntaxKind::Name => parent.parent().is_some_and(|grandparent| {
+            matches!(
+                grandparent.kind(),
+                SyntaxKind::NamedArg | SyntaxKind::RecordField
+            )
+        }),
+        SyntaxKind::NameRef => parent
+            .parent()
+            .is_some_and(|grandparent| grandparent.kind() == SyntaxKind::FieldExpr),
+        SyntaxKind::Pattern => false,
+        _ => true,
+    };
+
+    (!is_label && !eldiro::KEYWORDS.contains(&token.text())).then_some(token)
+}
+
+/// The innermost node around `offset`.
+fn node_at(root: &SyntaxNode, offset: usize) -> SyntaxNode {
+    root.tokens()
+        .into_iter()
+        .find(|token| token.text_range().contains(&offset))
+        .map_or_else(|| root.clone(), |token| token.parent())
+}
+
+/// Every definition that code in `node` at `offset` can refer to, those that would be found
+/// first when looking up a name coming first.
+fn visible(node: &SyntaxNode, offset: usize) -> Vec<Def> {
+    let mut defs = Vec::new();
+    let mut scope = Some(node.clone());
+
+    while let Some(node) = scope {
+        let inside = |child: Option<&SyntaxNode>| {
+            child.is_some_and(|child| child.text_range().contains(&offset))
+        };
+
+        match node.kind() {
+            SyntaxKind::Root | SyntaxKind::Block => {
+                let (before, after): (Vec<_>, Vec<_>) = node
+                    .children()
+                    .flat_map(|stmt| stmt_defs(&stmt))
+                    .partition(|def| match def.kind {
+                        DefKind::Binding => def.span.end <= offset,
+                        _ => def.span.start <= offset,
+                    });
+
+                // Later definitions shadow earlier ones. Functions can use names defined after
+                // them, so those are found if nothing before matches.
+                defs.extend(before.into_iter().rev());
+                defs.extend(after);
+            }
+            SyntaxKind::FuncDef => {
+                let func_def = ast::FuncDef::cast(node.clone()).expect("kind was checked");
+                for param in func_def.params().iter().rev() {
+                    defs.extend(
+                        param
+                            .name()
+                            .map(|name| name_def(&name, param.syntax(), DefKind::Param)),
+                    );
+                }
+            }
+            SyntaxKind::ForExpr => {
+                let for_expr = ast::ForExpr::cast(node.clone()).expect("kind was checked");
+                if inside(for_expr.body().as_ref().map(AstNode::syntax)) {
+                    defs.extend(
+                        for_expr
+                            .name()
+                            .map(|name| name_def(&name, &node, DefKind::Binding)),
+                    );
+                }
+            }
+            SyntaxKind::TryExpr => {
+                let try_expr = ast::TryExpr::cast(node.clone()).expect("kind was checked");
+                if inside(try_expr.handler().as_ref().map(AstNode::syntax)) {
+                    defs.extend(
+                        try_expr
+                            .name()
+                            .map(|name| name_def(&name, &node, DefKind::Binding)),
+                    );
+                }
+            }
+            SyntaxKind::MatchArm => {
+                let arm = ast::MatchArm::cast(node.clone()).expect("kind was checked");
+                if inside(arm.body().as_ref().map(AstNode::syntax)) {
+                    if let Some(pattern) = arm.pattern() {
+                        pattern_defs(&pattern, &mut defs);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        scope = node.parent();
+    }
+
+    defs
+}
+
+/// The names that a statement in a block or at the top level defines.
+fn stmt_defs(stmt: &SyntaxNode) -> Vec<Def> {
+    match ast::Stmt::cast(stmt.clone()) {
+        Some(ast::Stmt::BindingDef(binding_def)) => binding_def
+            .name()
+            .map(|name| name_def(&name, stmt, DefKind::Binding))
+            .into_iter()
+            .collect(),
+        Some(ast::Stmt::FuncDef(func_def)) => func_def
+            .name()
+            .map(|name| name_def(&name, stmt, DefKind::Func))
+            .into_iter()
+            .collect(),
+        Some(ast::Stmt::EnumDef(enum_def)) => enum_def
+            .variants()
+            .filter_map(|variant| {
+                let name = variant.name()?;
+                Some(name_def(&name, variant.syntax(), DefKind::Variant))
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The bindings in a `match` pattern, which are the names that don't start with an uppercase
+/// letter.
+fn pattern_defs(pattern: &ast::Pattern, defs: &mut Vec<Def>) {
+    let ident = pattern
+        .syntax()
+        .children_with_tokens()
+        .into_iter()
+        .find_map(|element| match element {
+            SyntaxElement::Token(token) if token.kind() == SyntaxKind::Ident => Some(token),
+            _ => None,
+        });
+
+    match ident {
+        Some(ident)
+            if ident.text() != "_"
+                && !ident.text().starts_with(char::is_uppercase)
+                && !eldiro::KEYWORDS.contains(&ident.text()) =>
+        {
+            defs.push(Def {
+                name: ident.text().to_string(),
+                kind: DefKind::Binding,
+                name_span: ident.text_range(),
+                span: pattern.syntax().text_range(),
+            });
+        }
+        _ => {
+            for field in pattern.fields() {
+                pattern_defs(&field, defs);
+            }
+        }
+    }
+}
+
+fn name_def(name: &ast::Name, def: &SyntaxNode, kind: DefKind) -> Def {
+    Def {
+        name: name.text(),
+        kind,
+        name_span: name.syntax().text_range(),
+        span: def.text_range(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The span of the name that the first `|` in `marked` is on, after taking it out.
+    fn definition_of(marked: &str) -> Option<Range<usize>> {
+        let offset = marked.find('|').unwrap();
+        definition(&marked.replacen('|', "", 1), offset).map(|def| def.name_span)
+    }
+
+    fn hover_on(marked: &str) -> Option<String> {
+        let offset = marked.find('|').unwrap();
+        hover(&marked.replacen('|', "", 1), offset, &Env::default())
+    }
+
+    #[test]
+    fn report_parse_errors_alone() {
+        assert_eq!(
+            problems("let a = 1\n@ 2"),
+            [Problem {
+                span: 10..11,
+                severity: Severity::Error,
+                message: "expected statement".to_string(),
+            }],
+        );
+    }
+
+    #[test]
+    fn report_type_errors_and_lints_in_order() {
+        let messages: Vec<_> = problems("let a = 1\n1 + true")
+            .into_iter()
+            .map(|problem| (problem.span, problem.message))
+            .collect();
+
+        assert_eq!(
+            messages,
+            [
+                (0..9, "unused binding 'a'".to_string()),
+                (10..18, "cannot apply + to number and bool".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn find_latest_definition_before_use() {
+        assert_eq!(definition_of("let a = 1\nlet a = a + 1\n|a"), Some(14..15));
+        assert_eq!(definition_of("let a = 1\nlet b = |a + 1"), Some(4..5));
+        assert_eq!(definition_of("|a"), None);
+    }
+
+    #[test]
+    fn find_params_and_later_functions() {
+        assert_eq!(definition_of("fn f x => g(|x)\nfn g y => y"), Some(5..6));
+        assert_eq!(definition_of("fn f x => |g(x)\nfn g y => y"), Some(18..19));
+        assert_eq!(definition_of("fn f n => |f(n - 1)"), Some(3..4));
+    }
+
+    #[test]
+    fn find_names_from_loops_catches_and_patterns() {
+        assert_eq!(definition_of("for i in [1] { |i }"), Some(4..5));
+        assert_eq!(definition_of("let i = 1\nfor i in [|i] { i }"), Some(4..5));
+        assert_eq!(definition_of("try { 1 } catch e { |e }"), Some(16..17));
+        assert_eq!(
+            definition_of("enum Opt { Some(x), None }\nmatch None { Some(y) => |y, n => Some(n) }"),
+            Some(45..46),
+        );
+        assert_eq!(
+            definition_of("enum Opt { Some(x), None }\nmatch None { Some(y) => y, n => |Some(n) }"),
+            Some(11..15),
+        );
+    }
+
+    #[test]
+    fn labels_are_not_names() {
+        assert_eq!(definition_of("let x = 1\n{x: 2}.|x"), None);
+        assert_eq!(definition_of("let x = 1\nfn f x => x\nf(|x: 2)"), None);
+    }
+
+    #[test]
+    fn find_definitions_despite_syntax_errors() {
+        assert_eq!(definition_of("let a = 1\nlet b = @@\n|a"), Some(4..5));
+    }
+
+    #[test]
+    fn hover_shows_inferred_types() {
+        assert_eq!(
+            hover_on("fn add x y => x + y\n|add(1, 2)"),
+            Some("add: fn(number, number) -> number".to_string()),
+        );
+        assert_eq!(hover_on("let |s = \"s\""), Some("s: string".to_string()));
+        assert_eq!(hover_on("fn f x => |x"), Some("x".to_string()));
+        assert_eq!(hover_on("|len([])"), Some("len: fn(a) -> b".to_string()));
+        assert_eq!(hover_on("|unknown"), None);
+    }
+
+    #[test]
+    fn complete_visible_names_then_env_then_keywords() {
+        let labels = |marked: &str| -> Vec<String> {
+            let offset = marked.find('|').unwrap();
+            completions(&marked.replacen('|', "", 1), offset, &Env::default())
+                .into_iter()
+                .map(|completion| completion.label)
+                .collect()
+        };
+
+        let labels = labels("let total = 1\nfn f x => { |x }\nlet later = 2");
+        assert_eq!(labels[..4], ["x", "f", "total", "later"]);
+        assert!(labels.contains(&"len".to_string()));
+        assert!(labels.contains(&"match".to_string()));
+        assert_eq!(labels.iter().filter(|label| *label == "x").count(), 1);
+    }
+}
@@ -0,0 +1,95 @@
+use lsp_types::{Position, Range};
+
+/// The text of an open file, along with where its lines start so that LSP positions can be
+/// converted to byte offsets and back. Positions count columns in UTF-16 code units.
+#[derive(Debug)]
+pub(crate) struct Document {
+    text: String,
+    line_starts: Vec<usize>,
+}
+
+impl Document {
+    pub(crate) fn new(text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+
+        Self { text, line_starts }
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The byte offset of `position`, clamped to the end of its line or of the document.
+    pub(crate) fn offset(&self, position: Position) -> usize {
+        let line = match self.line_starts.get(position.line as usize) {
+            Some(&start) => start,
+            None => return self.text.len(),
+        };
+        let rest = &self.text[line..];
+        let rest = &rest[..rest.find('\n').unwrap_or(rest.len())];
+
+        let mut column = 0;
+        for (idx, c) in rest.char_indices() {
+            if column >= position.character as usize {
+                return line + idx;
+            }
+            column += c.len_utf16();
+        }
+
+        line + rest.len()
+    }
+
+    pub(crate) fn position(&self, offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.line_starts[line];
+        let character = self.text[start..offset]
+            .chars()
+            .map(char::len_utf16)
+            .sum::<usize>();
+
+        Position::new(line as u32, character as u32)
+    }
+
+    pub(crate) fn range(&self, span: std::ops::Range<usize>) -> Range {
+        Range::new(self.position(span.start), self.position(span.end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_between_offsets_and_positions() {
+        let document = Document::new("let a = 1\nlet größe = a\n".to_string());
+
+        for (offset, position) in [
+            (0, Position::new(0, 0)),
+            (9, Position::new(0, 9)),
+            (10, Position::new(1, 0)),
+            (22, Position::new(1, 10)),
+            (26, Position::new(2, 0)),
+        ] {
+            assert_eq!(document.position(offset), position);
+            assert_eq!(document.offset(position), offset);
+        }
+    }
+
+    #[test]
+    fn count_columns_in_utf16() {
+        let document = Document::new("\"🦀\" + 1".to_string());
+
+        assert_eq!(document.position(5), Position::new(0, 3));
+        assert_eq!(document.offset(Position::new(0, 3)), 5);
+    }
+
+    #[test]
+    fn clamp_positions_past_the_end() {
+        let document = Document::new("ab\ncd".to_string());
+
+        assert_eq!(document.offset(Position::new(0, 10)), 2);
+        assert_eq!(document.offset(Position::new(5, 0)), 5);
+    }
+}
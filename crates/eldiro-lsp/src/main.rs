@@ -0,0 +1,461 @@
+//! A language server for eldiro, which editors start and talk to over stdin and stdout. It
+//! reports problems as documents change, shows inferred types on hover, jumps to the definitions
+//! of bindings and functions, completes names and classifies tokens for highlighting.
+
+mod analysis;
+mod document;
+
+use analysis::DefKind;
+use document::Document;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationTrait, PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, GotoDefinition, HoverRequest, Request as RequestTrait, SemanticTokensFullRequest,
+};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    Diagnostic, DiagnosticSeverity, GotoDefinitionParams, GotoDefinitionResponse, Hover,
+    HoverContents, HoverParams, HoverProviderCapability, Location, MarkupContent, MarkupKind,
+    OneOf, PublishDiagnosticsParams, SemanticToken, SemanticTokenType, SemanticTokens,
+    SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions, SemanticTokensParams,
+    SemanticTokensResult, SemanticTokensServerCapabilities, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Uri,
+};
+use std::collections::HashMap;
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (connection, io_threads) = Connection::stdio();
+
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+    Server::new(&connection).run()?;
+    io_threads.join()?;
+
+    Ok(())
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions::default()),
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                legend: SemanticTokensLegend {
+                    token_types: TOKEN_TYPES.to_vec(),
+                    token_modifiers: Vec::new(),
+                },
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+                ..SemanticTokensOptions::default()
+            },
+        )),
+        ..ServerCapabilities::default()
+    }
+}
+
+/// The token types that semantic tokens refer to by index.
+const TOKEN_TYPES: [SemanticTokenType; 6] = [
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::STRING,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::OPERATOR,
+    SemanticTokenType::COMMENT,
+];
+
+struct Server<'a> {
+    connection: &'a Connection,
+    documents: HashMap<Uri, Document>,
+    /// What documents are checked against, so that builtins have types and can be completed.
+    env: eldiro::Env<'static>,
+}
+
+impl<'a> Server<'a> {
+    fn new(connection: &'a Connection) -> Self {
+        let mut env = eldiro::Env::default();
+        env.allow_sleep();
+        env.allow_files();
+        env.allow_env_vars();
+
+        Self {
+            connection,
+            documents: HashMap::new(),
+            env,
+        }
+    }
+
+    /// Handles messages until the client asks to shut down.
+    fn run(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        for message in &self.connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+
+                    let response = self.respond(request);
+                    self.connection.sender.send(response.into())?;
+                }
+                Message::Notification(notification) => self.notified(notification)?,
+                Message::Response(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn respond(&self, request: Request) -> Response {
+        let result = match request.method.as_str() {
+            HoverRequest::METHOD => {
+                handle::<HoverRequest>(request.params, |params| self.hover(params))
+            }
+            GotoDefinition::METHOD => {
+                handle::<GotoDefinition>(request.params, |params| self.definition(params))
+            }
+            Completion::METHOD => {
+                handle::<Completion>(request.params, |params| self.completion(params))
+            }
+            SemanticTokensFullRequest::METHOD => {
+                handle::<SemanticTokensFullRequest>(request.params, |params| {
+                    self.semantic_tokens(params)
+                })
+            }
+            method => {
+                return Response::new_err(
+                    request.id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unknown request '{}'", method),
+                )
+            }
+        };
+
+        match result {
+            Ok(result) => Response::new_ok(request.id, result),
+            Err(e) => Response::new_err(request.id, ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    fn notified(&mut self, notification: Notification) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: lsp_types::DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+
+                self.documents
+                    .insert(uri.clone(), Document::new(params.text_document.text));
+                self.publish_problems(uri)?;
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: lsp_types::DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+
+                // Documents are synced in full, so the last change has all of the text.
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents
+                        .insert(uri.clone(), Document::new(change.text));
+                    self.publish_problems(uri)?;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: lsp_types::DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+
+                self.documents.remove(&params.text_document.uri);
+                self.publish(params.text_document.uri, Vec::new())?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn publish_problems(&self, uri: Uri) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let document = &self.documents[&uri];
+        let diagnostics = analysis::problems(document.text())
+            .into_iter()
+            .map(|problem| Diagnostic {
+                range: document.range(problem.span),
+                severity: Some(match problem.severity {
+                    eldiro::Severity::Error => DiagnosticSeverity::ERROR,
+                    eldiro::Severity::Warning => DiagnosticSeverity::WARNING,
+                }),
+                source: Some("eldiro".to_string()),
+                message: problem.message,
+                ..Diagnostic::default()
+            })
+            .collect();
+
+        self.publish(uri, diagnostics)
+    }
+
+    fn publish(
+        &self,
+        uri: Uri,
+        diagnostics: Vec<Diagnostic>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        self.connection.sender.send(notification.into())?;
+
+        Ok(())
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let position = params.text_document_position_params;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let offset = document.offset(position.position);
+        let description = analysis::hover(document.text(), offset, &self.env)?;
+
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```eldiro\n{}\n```", description),
+            }),
+            range: None,
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = params.text_document_position_params;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let def = analysis::definition(document.text(), document.offset(position.position))?;
+
+        Some(GotoDefinitionResponse::Scalar(Location::new(
+            position.text_document.uri,
+            document.range(def.name_span),
+        )))
+    }
+
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let position = params.text_document_position;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let offset = document.offset(position.position);
+
+        let items = analysis::completions(document.text(), offset, &self.env)
+            .into_iter()
+            .map(|completion| CompletionItem {
+                kind: Some(match completion.def {
+                    Some(DefKind::Binding | DefKind::Param) => CompletionItemKind::VARIABLE,
+                    Some(DefKind::Func) => CompletionItemKind::FUNCTION,
+                    Some(DefKind::Variant) => CompletionItemKind::ENUM_MEMBER,
+                    None if eldiro::KEYWORDS.contains(&completion.label.as_str()) => {
+                        CompletionItemKind::KEYWORD
+                    }
+                    None => CompletionItemKind::FUNCTION,
+                }),
+                label: completion.label,
+                ..CompletionItem::default()
+            })
+            .collect();
+
+        Some(CompletionResponse::Array(items))
+    }
+
+    /// Classifies every token, leaving out punctuation, which editors don't highlight. Each
+    /// token's position is relative to the one before, as LSP has it.
+    fn semantic_tokens(&self, params: SemanticTokensParams) -> Option<SemanticTokensResult> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        let mut previous = lsp_types::Position::default();
+
+        let data = eldiro::highlight(document.text())
+            .into_iter()
+            .filter_map(|highlight| {
+                let token_type = match highlight.kind {
+                    eldiro::HighlightKind::Keyword => 0,
+                    eldiro::HighlightKind::Number => 1,
+                    eldiro::HighlightKind::String => 2,
+                    eldiro::HighlightKind::Identifier => 3,
+                    eldiro::HighlightKind::Operator => 4,
+                    eldiro::HighlightKind::Comment => 5,
+                    _ => return None,
+                };
+                let length = document.text()[highlight.span.clone()]
+                    .encode_utf16()
+                    .count();
+                let range = document.range(highlight.span);
+
+                let delta_line = range.start.line - previous.line;
+                let delta_start = if delta_line == 0 {
+                    range.start.character - previous.character
+                } else {
+                    range.start.character
+                };
+                previous = range.start;
+
+                Some(SemanticToken {
+                    delta_line,
+                    delta_start,
+                    length: length as u32,
+                    token_type,
+                    token_modifiers_bitset: 0,
+                })
+            })
+            .collect();
+
+        Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data,
+        }))
+    }
+}
+
+/// Decodes the parameters of a request of type `R`, and encodes what `respond` makes of them.
+fn handle<R: RequestTrait>(
+    params: serde_json::Value,
+    respond: impl FnOnce(R::Params) -> R::Result,
+) -> Result<serde_json::Value, serde_json::Error> {
+    serde_json::to_value(respond(serde_json::from_value(params)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_server::RequestId;
+    use serde_json::json;
+    use std::thread;
+
+    /// Sends `messages` to a server, followed by a shutdown and exit, and collects everything it
+    /// sends back.
+    fn exchange(messages: Vec<Message>) -> Vec<Message> {
+        let (server, client) = Connection::memory();
+        let server = thread::spawn(move || Server::new(&server).run().unwrap());
+
+        for message in messages {
+            client.sender.send(message).unwrap();
+        }
+        client
+            .sender
+            .send(Request::new(RequestId::from(0), "shutdown".to_string(), ()).into())
+            .unwrap();
+        client
+            .sender
+            .send(Notification::new("exit".to_string(), ()).into())
+            .unwrap();
+        server.join().unwrap();
+
+        client.receiver.try_iter().collect()
+    }
+
+    fn open(text: &str) -> Message {
+        Notification::new(
+            DidOpenTextDocument::METHOD.to_string(),
+            json!({
+                "textDocument": {
+                    "uri": "file:///main.eld",
+                    "languageId": "eldiro",
+                    "version": 1,
+                    "text": text,
+                },
+            }),
+        )
+        .into()
+    }
+
+    fn request(id: i32, method: &str, line: u32, character: u32) -> Message {
+        Request::new(
+            RequestId::from(id),
+            method.to_string(),
+            json!({
+                "textDocument": { "uri": "file:///main.eld" },
+                "position": { "line": line, "character": character },
+            }),
+        )
+        .into()
+    }
+
+    fn result(messages: &[Message], id: i32) -> serde_json::Value {
+        messages
+            .iter()
+            .find_map(|message| match message {
+                Message::Response(response) if response.id == RequestId::from(id) => {
+                    response.response_result.clone().ok()
+                }
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn publish_diagnostics_when_opened() {
+        let messages = exchange(vec![open("let a = 1\n1 + true")]);
+
+        let params = match &messages[0] {
+            Message::Notification(notification) => {
+                assert_eq!(notification.method, PublishDiagnostics::METHOD);
+                notification.params.clone()
+            }
+            message => panic!("expected diagnostics, got {:?}", message),
+        };
+        assert_eq!(
+            params["diagnostics"][1],
+            json!({
+                "range": {
+                    "start": { "line": 1, "character": 0 },
+                    "end": { "line": 1, "character": 8 },
+                },
+                "severity": 1,
+                "source": "eldiro",
+                "message": "cannot apply + to number and bool",
+            }),
+        );
+    }
+
+    #[test]
+    fn answer_hover_and_definition_requests() {
+        let messages = exchange(vec![
+            open("fn inc x => x + 1\ninc(2)"),
+            request(1, HoverRequest::METHOD, 1, 1),
+            request(2, GotoDefinition::METHOD, 1, 1),
+            request(3, "eldiro/unknown", 0, 0),
+        ]);
+
+        assert_eq!(
+            result(&messages, 1)["contents"]["value"],
+            "```eldiro\ninc: fn(number) -> number\n```",
+        );
+        assert_eq!(
+            result(&messages, 2),
+            json!({
+                "uri": "file:///main.eld",
+                "range": {
+                    "start": { "line": 0, "character": 3 },
+                    "end": { "line": 0, "character": 6 },
+                },
+            }),
+        );
+        assert!(messages.iter().any(|message| matches!(
+            message,
+            Message::Response(response)
+                if response.id == RequestId::from(3) && response.response_result.is_err()
+        )));
+    }
+
+    #[test]
+    fn classify_tokens_relative_to_the_one_before() {
+        let messages = exchange(vec![
+            open("let a = 1\n  a // b"),
+            request(1, SemanticTokensFullRequest::METHOD, 0, 0),
+        ]);
+
+        // Five numbers per token: line and start relative to the previous token, then the length,
+        // the type and the modifiers.
+        let data: Vec<u32> = serde_json::from_value(result(&messages, 1)["data"].clone()).unwrap();
+        assert_eq!(
+            data.chunks(5).collect::<Vec<_>>(),
+            [
+                [0, 0, 3, 0, 0],
+                [0, 4, 1, 3, 0],
+                [0, 2, 1, 4, 0],
+                [0, 2, 1, 1, 0],
+                [1, 2, 1, 3, 0],
+                [0, 2, 4, 5, 0],
+            ],
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! Eldiro for JavaScript, built with `wasm-pack build crates/eldiro-wasm`.
+//!
+//! ```js
+//! import init, { Interpreter } from "./pkg/eldiro_wasm.js";
+//!
+//! await init();
+//! const interpreter = new Interpreter();
+//! interpreter.eval("let a = 2");
+//! interpreter.eval("a * 21"); // "42"
+//!
+//! for (const token of highlight("let a = 1")) {
+//!   console.log(token.kind, token.start, token.end); // "keyword" 0 3, ...
+//! }
+//! ```
+
+use eldiro::{Clock, Env};
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+
+/// Reads the time from `Date.now()`, counting from when it was created. There's no way to block
+/// the browser's thread, so sleeping returns straight away.
+#[derive(Debug)]
+struct JsClock {
+    start: f64,
+}
+
+impl JsClock {
+    fn new() -> Self {
+        Self {
+            start: js_sys::Date::now(),
+        }
+    }
+}
+
+impl Clock for JsClock {
+    fn now_ms(&self) -> u64 {
+        (js_sys::Date::now() - self.start) as u64
+    }
+
+    fn sleep_ms(&self, _ms: u64) {}
+}
+
+/// An environment that keeps its bindings from one call to `eval` to the next.
+#[wasm_bindgen]
+pub struct Interpreter {
+    inner: eldiro::Interpreter,
+}
+
+#[wasm_bindgen]
+impl Interpreter {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(JsClock::new()))
+    }
+
+    /// Evaluates `source`, returning its value as it would be printed, or an empty string if it
+    /// has none. Parse and runtime errors are thrown as `Error`s.
+    pub fn eval(&mut self, source: &str) -> Result<String, JsError> {
+        self.run(source).map_err(|e| JsError::new(&e))
+    }
+}
+
+/// A piece of source classified for highlighting. Offsets count UTF-16 code units, as JavaScript
+/// strings do.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub start: u32,
+    pub end: u32,
+    kind: eldiro::HighlightKind,
+}
+
+#[wasm_bindgen]
+impl Token {
+    /// What the piece is, such as `keyword` or `comment`.
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> String {
+        self.kind.name().to_string()
+    }
+}
+
+/// Splits `source` into pieces for highlighting, leaving out whitespace. Code that doesn't parse
+/// can still be highlighted.
+#[wasm_bindgen]
+pub fn highlight(source: &str) -> Vec<Token> {
+    // The pieces are in order, so each offset is counted on from the one before.
+    let mut counted = (0, 0);
+    let mut utf16 = |offset: usize| {
+        let (bytes, units) = counted;
+        let units = units + source[bytes..offset].encode_utf16().count();
+        counted = (offset, units);
+        units as u32
+    };
+
+    eldiro::highlight(source)
+        .into_iter()
+        .map(|highlight| Token {
+            start: utf16(highlight.span.start),
+            end: utf16(highlight.span.end),
+            kind: highlight.kind,
+        })
+        .collect()
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            inner: eldiro::Interpreter::new().with_env(Env::with_clock(clock)),
+        }
+    }
+
+    fn run(&mut self, source: &str) -> Result<String, String> {
+        let program = eldiro::parse(source).map_err(|e| {
+            let (line, _) = e.line_col(source);
+            format!("at line {}: {}", line, e)
+        })?;
+
+        match self.inner.eval(&program) {
+            Ok(eldiro::Val::Unit) => Ok(String::new()),
+            Ok(val) => Ok(val.to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eldiro::ManualClock;
+
+    fn interpreter() -> Interpreter {
+        Interpreter::with_clock(Arc::new(ManualClock::default()))
+    }
+
+    #[test]
+    fn keep_bindings_between_calls() {
+        let mut interpreter = interpreter();
+
+        assert_eq!(interpreter.run("let a = 2"), Ok(String::new()));
+        assert_eq!(interpreter.run("a * 21"), Ok("42".to_string()));
+    }
+
+    #[test]
+    fn report_the_line_of_parse_errors() {
+        assert_eq!(
+            interpreter().run("let a = 1\nlet = 2"),
+            Err("at line 2: expected statement".to_string()),
+        );
+    }
+
+    #[test]
+    fn highlight_in_utf16_offsets() {
+        assert_eq!(
+            highlight("\"🦀\" + x")
+                .iter()
+                .map(|token| (token.kind(), token.start, token.end))
+                .collect::<Vec<_>>(),
+            [
+                ("string".to_string(), 0, 4),
+                ("operator".to_string(), 5, 6),
+                ("identifier".to_string(), 7, 8),
+            ],
+        );
+    }
+
+    #[test]
+    fn report_runtime_errors() {
+        assert_eq!(
+            interpreter().run("1 + true"),
+            Err("cannot apply + to number and bool".to_string()),
+        );
+    }
+}
@@ -0,0 +1,55 @@
+//! Times chains of nested function calls of increasing depth.
+//!
+//! Run with `cargo bench --bench calls`. Each call looks up the next function by name, so if call
+//! scopes grew with the depth of the call stack the time per call would grow with it too; it
+//! should stay roughly flat instead.
+//!
+//! The VM runs the program compiled once beforehand, as a host running it more than once would,
+//! so that only running it is timed for both backends.
+
+use eldiro::{Backend, Env, Interpreter};
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 20;
+
+/// Defines `f0` through `f{depth}`, where each one calls the one before it.
+fn call_chain(depth: usize) -> String {
+    let mut source = String::from("fn f0 x => x\n");
+
+    for i in 1..=depth {
+        source += &format!("fn f{} x => f{}(x)\n", i, i - 1);
+    }
+
+    source + &format!("f{}(1)\n", depth)
+}
+
+fn time(backend: Backend, depth: usize) -> Duration {
+    let program = eldiro::parse(&call_chain(depth)).unwrap();
+    let compiled = program.compile();
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        match backend {
+            Backend::TreeWalk => Interpreter::with_backend(backend).eval(&program),
+            Backend::Vm => compiled.eval(&mut Env::default()),
+        }
+        .unwrap();
+    }
+
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    for backend in [Backend::TreeWalk, Backend::Vm] {
+        for depth in [50, 100, 200, 400] {
+            let elapsed = time(backend, depth);
+            println!(
+                "{:?} depth {:>3}: {:>10.2?} total, {:>8.2?} per call",
+                backend,
+                depth,
+                elapsed,
+                elapsed / depth as u32,
+            );
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Times a program that mostly looks up names.
+//!
+//! Run with `cargo bench --bench lookups`. The bindings have long names and are read from inside
+//! nested blocks, so each lookup tries several scopes before finding its binding.
+
+use eldiro::{Backend, Interpreter};
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 20;
+const BINDINGS: usize = 100;
+const NESTING: usize = 10;
+
+fn program() -> String {
+    let names: Vec<_> = (0..BINDINGS)
+        .map(|i| format!("a_fairly_long_binding_name_{}", i))
+        .collect();
+
+    let mut source = String::new();
+
+    for name in &names {
+        source += &format!("let {} = 1\n", name);
+    }
+
+    let sum = names.join(" + ");
+    source += &"{ ".repeat(NESTING);
+
+    for i in 0..BINDINGS {
+        source += &format!("let sum_{} = {}\n", i, sum);
+    }
+
+    source + "sum_0" + &" }".repeat(NESTING)
+}
+
+fn time(backend: Backend) -> Duration {
+    let program = eldiro::parse(&program()).unwrap();
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        Interpreter::with_backend(backend).eval(&program).unwrap();
+    }
+
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let lookups = (BINDINGS * BINDINGS) as u32;
+
+    for backend in [Backend::TreeWalk, Backend::Vm] {
+        let elapsed = time(backend);
+        println!(
+            "{:?}: {:>10.2?} total, {:>8.2?} per lookup",
+            backend,
+            elapsed,
+            elapsed / lookups,
+        );
+    }
+}
@@ -0,0 +1,55 @@
+//! Times evaluating the same formula against many different inputs.
+//!
+//! Run with `cargo bench --bench repeated`. Parsing the formula again for every input spends most
+//! of its time in the parser, so parsing it once into a `Program` or compiling it once into a
+//! `CompiledProgram` should be many times faster.
+
+use eldiro::{Env, Program, Val};
+use std::time::{Duration, Instant};
+
+const INPUTS: i64 = 10_000;
+const FORMULA: &str = "let subtotal = price * quantity
+let discounted = subtotal - subtotal * discount / 100
+discounted + discounted * 20 / 100";
+
+/// Evaluates the formula once per input in the same environment, which `eval` is given after
+/// that input's bindings have been set.
+fn time(mut eval: impl FnMut(&mut Env) -> Val) -> Duration {
+    let mut env = Env::default();
+    let start = Instant::now();
+
+    for i in 0..INPUTS {
+        env.set_binding("price", Val::Number((i % 100).into()))
+            .unwrap();
+        env.set_binding("quantity", Val::Number((i % 7).into()))
+            .unwrap();
+        env.set_binding("discount", Val::Number((i % 30).into()))
+            .unwrap();
+        eval(&mut env);
+    }
+
+    start.elapsed()
+}
+
+fn main() {
+    let reparsed = time(|env| eldiro::parse(FORMULA).unwrap().eval(env).unwrap());
+
+    let program: Program = eldiro::parse(FORMULA).unwrap();
+    let parsed = time(|env| program.eval(env).unwrap());
+
+    let compiled = program.compile();
+    let compiled = time(|env| compiled.eval(env).unwrap());
+
+    for (name, elapsed) in [
+        ("parsed every time", reparsed),
+        ("parsed once", parsed),
+        ("compiled once", compiled),
+    ] {
+        println!(
+            "{:>17}: {:>10.2?} total, {:>8.2?} per input",
+            name,
+            elapsed,
+            elapsed / INPUTS as u32,
+        );
+    }
+}
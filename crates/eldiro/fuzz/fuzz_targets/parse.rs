@@ -0,0 +1,21 @@
+//! Runs everything that takes source text straight from the user, none of which may panic,
+//! overflow the stack or take more than linear time, whatever the input.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(source) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    if let Ok(program) = eldiro::parse(source) {
+        let _ = program.format();
+        let _ = eldiro::lint(&program);
+    }
+    let _ = eldiro::syntax::parse(source);
+    let _ = eldiro::format(source);
+    let _ = eldiro::is_incomplete(source);
+    let _ = eldiro::highlight(source);
+});
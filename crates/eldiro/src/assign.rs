@@ -0,0 +1,191 @@
+use crate::compat::prelude::*;
+use crate::env::Env;
+use crate::error::RuntimeError;
+use crate::expr::{Expr, Op};
+use crate::lexer::{Token, TokenKind};
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::utils;
+use crate::val::Val;
+
+/// `name += val`, or the same with `-=`, `*=`, `/=` or `%=`, which changes an existing binding to
+/// the result of applying the operator to its value and `val`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Assign {
+    pub name: Symbol,
+    pub op: Op,
+    pub val: Expr,
+    pub span: Span,
+}
+
+impl Assign {
+    pub(crate) fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let (tokens, name) = utils::extract_ident(tokens)?;
+        let (op, tokens) = match tokens.split_first() {
+            Some((token, tokens)) => (compound_op(&token.kind).ok_or("expected +=")?, tokens),
+            None => return Err("expected +=".to_string()),
+        };
+
+        let (tokens, val) = Expr::new(tokens)?;
+
+        Ok((
+            tokens,
+            Self {
+                name: Symbol::intern(name),
+                op,
+                val,
+                span: utils::consumed_span(start, tokens),
+            },
+        ))
+    }
+
+    pub(crate) fn eval(&self, env: &mut Env) -> Result<(), RuntimeError> {
+        let val = self.val.eval(env)?;
+        apply(env, self.name, &self.op, val, &self.span)
+    }
+}
+
+/// The operator that a compound assignment token such as `+=` applies.
+pub(crate) fn compound_op(kind: &TokenKind) -> Option<Op> {
+    let op = match kind {
+        TokenKind::PlusEq => Op::Add,
+        TokenKind::MinusEq => Op::Sub,
+        TokenKind::StarEq => Op::Mul,
+        TokenKind::SlashEq => Op::Div,
+        TokenKind::PercentEq => Op::Rem,
+        _ => return None,
+    };
+
+    Some(op)
+}
+
+/// Changes the binding `name` to `op` applied to its value and `val`, which has already been
+/// evaluated, so that both backends read and write the binding the same way.
+pub(crate) fn apply(
+    env: &mut Env,
+    name: Symbol,
+    op: &Op,
+    val: Val,
+    span: &Span,
+) -> Result<(), RuntimeError> {
+    env.assign(name, |old| op.apply(old, val, span))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::Number;
+    use crate::interpreter::{Backend, Interpreter};
+
+    fn eval(s: &str) -> Vec<Result<Val, RuntimeError>> {
+        [Backend::TreeWalk, Backend::Vm]
+            .into_iter()
+            .map(|backend| Interpreter::with_backend(backend).eval(&crate::parse(s).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_assign() {
+        assert_eq!(
+            utils::parse_str(Assign::new, "total += 2"),
+            Ok((
+                "",
+                Assign {
+                    name: "total".into(),
+                    op: Op::Add,
+                    val: Expr::Number(Number(2.into()), Span::default()),
+                    span: Span::default(),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_every_compound_assignment() {
+        for (op, expected) in [("+=", 9), ("-=", 5), ("*=", 14), ("/=", 3), ("%=", 1)] {
+            let source = format!("let x = 7\nx {} 2\nx", op);
+
+            for result in eval(&source) {
+                assert_eq!(result, Ok(Val::Number(expected.into())), "{}", source);
+            }
+        }
+    }
+
+    #[test]
+    fn change_bindings_from_blocks_and_loops() {
+        let source = "let total = 0\nfor x in [1, 2, 3] { total += x }\n\
+                      { total *= 10\nlet total = 1 }\n\
+                      match total { 60 => { total -= 1 }, _ => () }\n\
+                      try { throw 1 } catch e { total += e }\n\
+                      total";
+
+        for result in eval(source) {
+            assert_eq!(result, Ok(Val::Number(60.into())));
+        }
+    }
+
+    #[test]
+    fn keep_changes_made_before_an_error() {
+        for result in eval("let n = 0\ntry { for x in [1, 2, 0] { n += 6 / x } } catch e { n }") {
+            assert_eq!(result, Ok(Val::Number(9.into())));
+        }
+    }
+
+    #[test]
+    fn missing_binding_cannot_be_assigned() {
+        for result in eval("x += 1") {
+            assert_eq!(
+                result,
+                Err(RuntimeError::Message(
+                    "binding with name 'x' does not exist".to_string()
+                )),
+            );
+        }
+    }
+
+    #[test]
+    fn constants_and_functions_cannot_be_assigned() {
+        for (source, message) in [
+            (
+                "const limit = 3\nlimit += 1",
+                "cannot assign to constant 'limit'",
+            ),
+            ("fn f => 1\nf += 1", "cannot assign to function 'f'"),
+        ] {
+            for result in eval(source) {
+                assert_eq!(result, Err(RuntimeError::Message(message.to_string())));
+            }
+        }
+    }
+
+    #[test]
+    fn functions_only_change_their_own_bindings() {
+        for result in eval("let count = 0\nfn bump => count += 1\nbump()") {
+            assert_eq!(
+                result,
+                Err(RuntimeError::Message(
+                    "cannot assign to 'count' outside of the function".to_string()
+                )),
+            );
+        }
+
+        for result in eval("fn sum xs => {\n    let total = 0\n    for x in xs { total += x }\n    total\n}\nsum([1, 2, 3])") {
+            assert_eq!(result, Ok(Val::Number(6.into())));
+        }
+    }
+
+    #[test]
+    fn child_environments_cannot_change_their_parent() {
+        let mut parent = Env::default();
+        parent.set_binding("count", Val::Number(1.into())).unwrap();
+
+        let mut child = parent.create_child();
+        assert_eq!(
+            crate::parse("count += 1").unwrap().eval(&mut child),
+            Err(RuntimeError::Message(
+                "cannot assign to 'count' of a parent environment".to_string()
+            )),
+        );
+    }
+}
@@ -1,36 +1,64 @@
+use crate::compat::prelude::*;
 use crate::env::Env;
+use crate::error::RuntimeError;
 use crate::expr::Expr;
+use crate::lexer::{Token, TokenKind};
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::ty::Type;
 use crate::utils;
 
+/// `let name = val` or `let name: type = val`, or the same with `const` for a binding that can't
+/// be redefined in its scope.
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct BindingDef {
-    pub(crate) name: String,
-    pub(crate) val: Expr,
+pub struct BindingDef {
+    pub name: Symbol,
+    pub constant: bool,
+    pub ty: Option<Type>,
+    pub val: Expr,
+    pub span: Span,
+    /// The text of the `##` comments right above the definition.
+    pub doc: Option<String>,
 }
 impl BindingDef {
-    pub(crate) fn new(s: &str) -> Result<(&str, Self), String> {
-        let s = utils::tag("let", s)?;
-        let (s, _) = utils::extract_whitespace1(s)?;
+    pub(crate) fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let doc = tokens.first().and_then(|token| token.doc.clone());
+        let (tokens, constant) = match utils::keyword("const", tokens) {
+            Ok(tokens) => (tokens, true),
+            Err(_) => (utils::keyword("let", tokens)?, false),
+        };
+        let (tokens, name) = utils::extract_ident(tokens)?;
+        let (tokens, ty) = Type::new_annotation(tokens)?;
+        let tokens = utils::expect(TokenKind::Eq, tokens)?;
 
-        let (s, name) = utils::extract_ident(s)?;
-        let (s, _) = utils::extract_whitespace(s);
-
-        let s = utils::tag("=", s)?;
-        let (s, _) = utils::extract_whitespace(s);
-
-        let (s, val) = Expr::new(s)?;
+        let (tokens, val) = Expr::new(tokens)?;
 
         Ok((
-            s,
+            tokens,
             Self {
-                name: name.to_string(),
+                name: Symbol::intern(name),
+                constant,
+                ty,
                 val,
+                span: utils::consumed_span(start, tokens),
+                doc,
             },
         ))
     }
 
-    pub(crate) fn eval(&self, env: &mut Env) -> Result<(), String> {
-        env.store_binding(self.name.clone(), self.val.eval(env)?);
+    pub(crate) fn eval(&self, env: &mut Env) -> Result<(), RuntimeError> {
+        let val = self.val.eval(env)?;
+
+        if let Some(ty) = self.ty {
+            ty.check(&val, format_args!("'{}'", self.name))?;
+        }
+
+        env.define_binding(self.name, val, self.constant)?;
+        if let Some(doc) = &self.doc {
+            env.set_doc(self.name, doc);
+        }
+
         Ok(())
     }
 }
@@ -38,24 +66,93 @@ impl BindingDef {
 #[cfg(test)]
 mod test {
     use crate::expr::Number;
-    use crate::expr::{BindingUsage, Block, Expr, Op};
+    use crate::expr::{Expr, Op};
+    use crate::val::Val;
     #[test]
     fn parse_binding_def() {
         assert_eq!(
-            BindingDef::new("let a = 10 / 2"),
+            utils::parse_str(BindingDef::new, "let a = 10 / 2"),
             Ok((
                 "",
                 BindingDef {
-                    name: "a".to_string(),
+                    name: "a".into(),
+                    constant: false,
+                    ty: None,
+                    span: Span::default(),
                     val: Expr::Operation {
-                        lhs: Box::new(Expr::Number(Number(10))),
-                        rhs: Box::new(Expr::Number(Number(2))),
+                        lhs: Box::new(Expr::Number(Number(10.into()), Span::default())),
+                        rhs: Box::new(Expr::Number(Number(2.into()), Span::default())),
                         op: Op::Div,
+                        span: Span::default(),
                     },
+                    doc: None,
                 },
             )),
         );
     }
 
+    #[test]
+    fn parse_annotated_binding_def() {
+        assert_eq!(
+            utils::parse_str(BindingDef::new, "let x: number = 5"),
+            Ok((
+                "",
+                BindingDef {
+                    name: "x".into(),
+                    constant: false,
+                    ty: Some(Type::Number),
+                    span: Span::default(),
+                    val: Expr::Number(Number(5.into()), Span::default()),
+                    doc: None,
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_doc_comment_above_binding_def() {
+        let (_, binding_def) = utils::parse_str(
+            BindingDef::new,
+            "## The answer.\n##\n## Really.\nlet a = 42",
+        )
+        .unwrap();
+
+        assert_eq!(binding_def.doc.as_deref(), Some("The answer.\n\nReally."));
+    }
+
+    #[test]
+    fn eval_binding_def_with_wrong_type() {
+        let mut env = Env::default();
+
+        assert_eq!(
+            utils::parse_str(BindingDef::new, "let s: string = 5")
+                .unwrap()
+                .1
+                .eval(&mut env),
+            Err(RuntimeError::Message(
+                "expected 's' to be string, got number".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn const_cannot_be_redefined_in_same_scope() {
+        let eval = |s| crate::parse(s).unwrap().eval(&mut Env::default());
+        let error = Err(RuntimeError::Message(
+            "cannot redefine constant 'limit'".to_string(),
+        ));
+
+        assert_eq!(eval("const limit = 3\nlet limit = 4"), error);
+        assert_eq!(eval("const limit = 3\nfn limit => 4"), error);
+        assert_eq!(
+            eval("const limit = 3\n{ let limit = 4\nlimit }"),
+            Ok(Val::Number(4.into())),
+        );
+        assert_eq!(
+            eval("let limit = 3\nlet limit = 4\nlimit"),
+            Ok(Val::Number(4.into())),
+        );
+    }
+
     use super::*;
 }
@@ -0,0 +1,387 @@
+//! Lets natives that return futures be awaited by [`Interpreter::eval_async`].
+//!
+//! Evaluation itself is synchronous, so `eval_async` runs it on a thread of its own. When an async
+//! native is called there, its future is handed across to the task awaiting `eval_async` and the
+//! evaluation thread waits for the result. The future is polled by that task, on whatever
+//! executor it runs on, so no executor thread is ever blocked. All of this needs the `os`
+//! feature, for the thread.
+//!
+//! [`Interpreter::eval_async`]: crate::Interpreter::eval_async
+
+use crate::compat::prelude::*;
+#[cfg(feature = "os")]
+use crate::env::Env;
+#[cfg(feature = "os")]
+use crate::error::RuntimeError;
+use crate::val::Val;
+use core::future::Future;
+use core::pin::Pin;
+#[cfg(feature = "os")]
+use std::{
+    any::Any,
+    fmt, mem, panic,
+    sync::atomic::{AtomicBool, Ordering},
+    sync::{Arc, Condvar, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+/// What an async native returns, as registered with
+/// [`Env::define_async_native`](crate::Env::define_async_native).
+pub type NativeFuture = Pin<Box<dyn Future<Output = Result<Val, String>> + Send>>;
+
+#[cfg(feature = "os")]
+type Finished = Result<(Result<Val, RuntimeError>, Env<'static>), Box<dyn Any + Send>>;
+
+/// What async natives and function calls fail with once the evaluation has been cancelled.
+#[cfg(feature = "os")]
+pub(crate) const CANCELLED: &str = "evaluation was cancelled";
+
+#[cfg(feature = "os")]
+#[derive(Default)]
+struct State {
+    /// The future of the async native that evaluation is waiting on.
+    pending: Option<NativeFuture>,
+    reply: Option<Result<Val, String>>,
+    /// Set once the evaluation thread is done, or panicked.
+    finished: Option<Finished>,
+    waker: Option<Waker>,
+}
+
+/// How the evaluation thread and the task awaiting it talk to each other.
+#[cfg(feature = "os")]
+#[derive(Default)]
+pub(crate) struct Bridge {
+    state: Mutex<State>,
+    replied: Condvar,
+    /// Notified once the evaluation thread is done.
+    done: Condvar,
+    /// Set if the task stops awaiting the evaluation before it is done.
+    cancelled: AtomicBool,
+}
+
+#[cfg(feature = "os")]
+impl fmt::Debug for Bridge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bridge").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "os")]
+impl Bridge {
+    /// Evaluates with `eval` on a new thread, in the environment taken out of `home`, resolving
+    /// once it's done. The environment is put back in `home` then, or if the future is dropped
+    /// before that, once the evaluation has stopped.
+    pub(crate) fn spawn<'a>(
+        home: &'a mut Env<'static>,
+        eval: impl FnOnce(&mut Env) -> Result<Val, RuntimeError> + Send + 'static,
+    ) -> impl Future<Output = Result<Val, RuntimeError>> + 'a {
+        let bridge = Arc::new(Self::default());
+        let mut env = mem::take(home);
+        env.set_bridge(Some(Arc::clone(&bridge)));
+
+        thread::spawn({
+            let bridge = Arc::clone(&bridge);
+
+            move || {
+                let finished = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                    let result = eval(&mut env);
+                    env.set_bridge(None);
+                    (result, env)
+                }));
+
+                let mut state = bridge.state.lock().unwrap();
+                state.finished = Some(finished);
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+                bridge.done.notify_one();
+            }
+        });
+
+        Evaluation {
+            bridge,
+            home,
+            resolved: false,
+        }
+    }
+
+    /// Waits on the evaluation thread for the task awaiting the evaluation to resolve `future`.
+    pub(crate) fn wait_for(&self, future: NativeFuture) -> Result<Val, String> {
+        let mut state = self.state.lock().unwrap();
+        state.pending = Some(future);
+        if let Some(waker) = &state.waker {
+            waker.wake_by_ref();
+        }
+
+        loop {
+            if let Some(reply) = state.reply.take() {
+                return reply;
+            }
+            if self.is_cancelled() {
+                state.pending = None;
+                return Err(CANCELLED.to_string());
+            }
+
+            state = self.replied.wait(state).unwrap();
+        }
+    }
+
+    /// Whether the task stopped awaiting the evaluation, which should then stop as soon as it
+    /// can.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// The future returned by [`Bridge::spawn`], which polls the futures of async natives for the
+/// evaluation thread.
+#[cfg(feature = "os")]
+struct Evaluation<'a> {
+    bridge: Arc<Bridge>,
+    home: &'a mut Env<'static>,
+    /// Whether the environment is back in `home`.
+    resolved: bool,
+}
+
+#[cfg(feature = "os")]
+impl Future for Evaluation<'_> {
+    type Output = Result<Val, RuntimeError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = this.bridge.state.lock().unwrap();
+
+        match state.finished.take() {
+            Some(Ok((result, env))) => {
+                *this.home = env;
+                this.resolved = true;
+                return Poll::Ready(result);
+            }
+            Some(Err(payload)) => {
+                this.resolved = true;
+                panic::resume_unwind(payload)
+            }
+            None => {}
+        }
+
+        state.waker = Some(cx.waker().clone());
+
+        if let Some(future) = &mut state.pending {
+            if let Poll::Ready(reply) = future.as_mut().poll(cx) {
+                state.pending = None;
+                state.reply = Some(reply);
+                this.bridge.replied.notify_one();
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Cancels the evaluation, which makes the native it's waiting on fail and so does every function
+/// it calls after that, and waits for it to stop so that the environment can be put back.
+/// Whatever the evaluation defined before then is kept.
+#[cfg(feature = "os")]
+impl Drop for Evaluation<'_> {
+    fn drop(&mut self) {
+        if self.resolved {
+            return;
+        }
+
+        self.bridge.cancelled.store(true, Ordering::SeqCst);
+        let mut state = self.bridge.state.lock().unwrap();
+        self.bridge.replied.notify_one();
+
+        let finished = loop {
+            match state.finished.take() {
+                Some(finished) => break finished,
+                None => state = self.bridge.done.wait(state).unwrap(),
+            }
+        };
+
+        // A panic can't be passed on while dropping, and it took the environment with it.
+        if let Ok((_, env)) = finished {
+            *self.home = env;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "os"))]
+mod tests {
+    use crate::interpreter::{Backend, Interpreter};
+    use crate::val::Val;
+    use crate::RuntimeError;
+    use std::future::{self, Future};
+    use std::pin::{pin, Pin};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread::{self, Thread, ThreadId};
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Runs `future` to completion on this thread, parking whenever it's pending.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    /// Resolves to its value after first saying it's pending once, noting which threads it was
+    /// polled on.
+    struct YieldOnce {
+        val: Option<Val>,
+        yielded: bool,
+        polled_on: Arc<Mutex<Vec<ThreadId>>>,
+    }
+
+    impl Future for YieldOnce {
+        type Output = Result<Val, String>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            self.polled_on.lock().unwrap().push(thread::current().id());
+
+            if self.yielded {
+                Poll::Ready(Ok(self.val.take().unwrap()))
+            } else {
+                self.yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    /// An interpreter with an async `fetch x`, which gives `x` in angle brackets.
+    fn interpreter(backend: Backend, polled_on: Arc<Mutex<Vec<ThreadId>>>) -> Interpreter {
+        let mut interpreter = Interpreter::with_backend(backend);
+        interpreter
+            .env_mut()
+            .define_async_native("fetch", 1, move |args| {
+                let inner = match &args[0] {
+                    Val::Str(s) => s.clone(),
+                    val => val.to_string(),
+                };
+
+                Box::pin(YieldOnce {
+                    val: Some(Val::Str(format!("<{}>", inner))),
+                    yielded: false,
+                    polled_on: Arc::clone(&polled_on),
+                })
+            });
+        interpreter
+    }
+
+    #[test]
+    fn await_async_natives_on_the_awaiting_thread() {
+        for backend in [Backend::TreeWalk, Backend::Vm] {
+            let polled_on = Arc::default();
+            let mut interpreter = interpreter(backend, Arc::clone(&polled_on));
+            let program =
+                crate::parse("fn twice x => fetch(fetch(x))\nlet a = twice(3)\na").unwrap();
+
+            fn assert_send<T: Send>(future: T) -> T {
+                future
+            }
+
+            assert_eq!(
+                block_on(assert_send(interpreter.eval_async(&program))),
+                Ok(Val::Str("<<3>>".to_string()))
+            );
+            assert_eq!(*polled_on.lock().unwrap(), [thread::current().id(); 4],);
+            assert_eq!(
+                interpreter.env().bindings(),
+                [("a", &Val::Str("<<3>>".to_string()))]
+            );
+        }
+    }
+
+    #[test]
+    fn keep_the_environment_when_eval_async_is_dropped() {
+        for backend in [Backend::TreeWalk, Backend::Vm] {
+            let waiting = Arc::new(AtomicBool::new(false));
+            let mut interpreter = Interpreter::with_backend(backend);
+            interpreter.env_mut().define_async_native("never", 1, {
+                let waiting = Arc::clone(&waiting);
+                move |_| {
+                    let waiting = Arc::clone(&waiting);
+                    Box::pin(future::poll_fn(move |_| {
+                        waiting.store(true, Ordering::SeqCst);
+                        Poll::Pending
+                    }))
+                }
+            });
+
+            let program = crate::parse(
+                "let a = 1\nlet b = try { never(a) } catch e { 2 }\nfn f => 3\nlet c = f()",
+            )
+            .unwrap();
+
+            {
+                let mut future = pin!(interpreter.eval_async(&program));
+                let waker = Waker::from(Arc::new(Unpark(thread::current())));
+                let mut cx = Context::from_waker(&waker);
+
+                // Polls until the evaluation is waiting on `never`, then drops the future.
+                loop {
+                    assert!(future.as_mut().poll(&mut cx).is_pending());
+                    if waiting.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    thread::park();
+                }
+            }
+
+            // Catching the failed native doesn't keep the evaluation going past the next call.
+            assert_eq!(
+                interpreter.env().bindings(),
+                [("a", &Val::Number(1.into())), ("b", &Val::Number(2.into()))]
+            );
+            assert_eq!(
+                interpreter.eval(&crate::parse("a + b + f()").unwrap()),
+                Ok(Val::Number(6.into()))
+            );
+        }
+    }
+
+    #[test]
+    fn natives_fail_without_eval_async() {
+        let mut interpreter = interpreter(Backend::TreeWalk, Arc::default());
+
+        assert_eq!(
+            interpreter.eval(&crate::parse("fetch(1)").unwrap()),
+            Err(RuntimeError::Message(
+                "'fetch' is async, so it can only be called through Interpreter::eval_async"
+                    .to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn errors_come_back_from_eval_async() {
+        let mut interpreter = interpreter(Backend::TreeWalk, Arc::default());
+
+        assert_eq!(
+            block_on(interpreter.eval_async(&crate::parse("fetch(1) + true").unwrap())),
+            Err(RuntimeError::InvalidOperands {
+                op: crate::expr::Op::Add,
+                lhs: "string",
+                rhs: "bool",
+                span: 0..15,
+            }),
+        );
+    }
+}
@@ -0,0 +1,753 @@
+use crate::compat::prelude::*;
+use crate::env::Env;
+use crate::error::RuntimeError;
+use crate::expr::call_partial;
+use crate::func_def::Partial;
+use crate::native::Native;
+use crate::num::Int;
+use crate::span::Span;
+use crate::val::Val;
+#[cfg(feature = "std")]
+use std::fs;
+
+pub(crate) fn install(env: &mut Env) {
+    env.store_native(Native::new("now_ms", 0, now_ms));
+    env.store_native(Native::new("random", 1, random));
+    env.store_native(Native::new("assert", 2, assert));
+    env.store_native(Native::new_raising("panic", 1, panic));
+    env.store_native(Native::new_raising("exit", 1, exit));
+    env.store_native(Native::new("len", 1, len));
+    env.store_native(Native::new("upper", 1, upper));
+    env.store_native(Native::new("to_number", 1, to_number));
+    env.store_native(Native::new("to_string", 1, to_string));
+    env.store_native(Native::new("contains", 2, contains));
+    env.store_native(Native::new("list", 1, list));
+    env.store_native(Native::new_raising("map", 2, map));
+    env.store_native(Native::new_raising("filter", 2, filter));
+    env.store_native(Native::new_raising("fold", 3, fold));
+}
+
+pub(crate) fn install_sleep(env: &mut Env) {
+    env.store_native(Native::new("sleep_ms", 1, sleep_ms));
+}
+
+/// The natives that use the host's files, which are only installed if it opts in.
+#[cfg(feature = "std")]
+pub(crate) fn install_files(env: &mut Env) {
+    env.store_native(Native::new("read_file", 1, read_file).nondeterministic());
+    env.store_native(Native::new("write_file", 2, write_file).nondeterministic());
+}
+
+fn now_ms(env: &Env, _: &[Val]) -> Result<Val, String> {
+    i64::try_from(env.clock().now_ms())
+        .map(|ms| Val::Number(ms.into()))
+        .map_err(|_| "clock value does not fit in a number".to_string())
+}
+
+fn sleep_ms(env: &Env, args: &[Val]) -> Result<Val, String> {
+    let ms = match args {
+        [Val::Number(ms)] => ms.to_u64(),
+        _ => None,
+    }
+    .ok_or_else(|| "sleep_ms expects a non-negative number of milliseconds".to_string())?;
+
+    env.clock().sleep_ms(ms);
+    Ok(Val::Unit)
+}
+
+/// A random whole number from 0 up to but not including the argument.
+fn random(env: &Env, args: &[Val]) -> Result<Val, String> {
+    let bound = match args {
+        [Val::Number(bound)] => bound
+            .to_u64()
+            .filter(|&bound| bound > 0 && bound <= i64::MAX as u64),
+        _ => None,
+    }
+    .ok_or_else(|| "random expects a positive number".to_string())?;
+
+    Ok(Val::Number((env.rng().below(bound) as i64).into()))
+}
+
+/// The natives that read the host's environment variables, which are only installed if it opts
+/// in.
+#[cfg(feature = "std")]
+pub(crate) fn install_env_vars(env: &mut Env) {
+    env.store_native(Native::new("env_var", 1, env_var).nondeterministic());
+}
+
+/// The value of an environment variable, or nil if it isn't set.
+#[cfg(feature = "std")]
+fn env_var(_: &Env, args: &[Val]) -> Result<Val, String> {
+    let name = match &args[0] {
+        Val::Str(name) => name,
+        val => return Err(format!("env_var expects a name, got {}", val.type_name())),
+    };
+
+    match std::env::var(name) {
+        Ok(val) => Ok(Val::Str(val)),
+        Err(std::env::VarError::NotPresent) => Ok(Val::Nil),
+        Err(std::env::VarError::NotUnicode(_)) => Err(format!(
+            "environment variable '{}' is not valid unicode",
+            name
+        )),
+    }
+}
+
+/// The contents of the text file at a path.
+#[cfg(feature = "std")]
+fn read_file(_: &Env, args: &[Val]) -> Result<Val, String> {
+    let path = match &args[0] {
+        Val::Str(path) => path,
+        val => return Err(format!("read_file expects a path, got {}", val.type_name())),
+    };
+
+    fs::read_to_string(path)
+        .map(Val::Str)
+        .map_err(|e| format!("cannot read '{}': {}", path, e))
+}
+
+/// Replaces the file at a path with a string, creating the file if it doesn't exist.
+#[cfg(feature = "std")]
+fn write_file(_: &Env, args: &[Val]) -> Result<Val, String> {
+    let (path, contents) = match args {
+        [Val::Str(path), Val::Str(contents)] => (path, contents),
+        _ => return Err("write_file expects a path and a string".to_string()),
+    };
+
+    fs::write(path, contents)
+        .map(|()| Val::Unit)
+        .map_err(|e| format!("cannot write '{}': {}", path, e))
+}
+
+fn assert(_: &Env, args: &[Val]) -> Result<Val, String> {
+    match args {
+        [Val::Bool(true), _] => Ok(Val::Unit),
+        [Val::Bool(false), Val::Str(msg)] => Err(format!("assertion failed: {}", msg)),
+        [Val::Bool(false), msg] => Err(format!("assertion failed: {}", msg)),
+        _ => Err("assert expects a boolean condition".to_string()),
+    }
+}
+
+/// Stops the program, asking the host to exit with a status from 0 to 255.
+fn exit(_: &Env, args: &[Val]) -> Result<Val, RuntimeError> {
+    let code = match &args[0] {
+        Val::Number(code) => code.to_u64().and_then(|code| u8::try_from(code).ok()),
+        _ => None,
+    }
+    .ok_or_else(|| "exit expects a code from 0 to 255".to_string())?;
+
+    Err(RuntimeError::Exit(code))
+}
+
+/// The number of items in a list, tuple or range, or of characters in a string.
+fn len(_: &Env, args: &[Val]) -> Result<Val, String> {
+    let len = match &args[0] {
+        Val::List(items) | Val::Tuple(items) => items.len(),
+        Val::Str(s) => s.chars().count(),
+        Val::Range {
+            start,
+            end,
+            inclusive,
+        } => {
+            let len = end
+                .checked_sub(start)
+                .and_then(|len| {
+                    if *inclusive {
+                        len.checked_add(&1.into())
+                    } else {
+                        Some(len)
+                    }
+                })
+                .ok_or_else(|| "length does not fit in a number".to_string())?;
+
+            return Ok(Val::Number(len.max(0.into())));
+        }
+        val => return Err(format!("cannot take the length of {}", val.type_name())),
+    };
+
+    i64::try_from(len)
+        .map(|len| Val::Number(len.into()))
+        .map_err(|_| "length does not fit in a number".to_string())
+}
+
+/// Whether a list, tuple or range has `val` as an item, or a string has it as a substring.
+fn contains(_: &Env, args: &[Val]) -> Result<Val, String> {
+    let found = match (&args[0], &args[1]) {
+        (Val::List(items) | Val::Tuple(items), val) => items.contains(val),
+        (Val::Str(s), Val::Str(sub)) => s.contains(sub.as_str()),
+        (Val::Str(_), val) => {
+            return Err(format!("cannot look for {} in a string", val.type_name()))
+        }
+        (
+            Val::Range {
+                start,
+                end,
+                inclusive,
+            },
+            val,
+        ) => match val {
+            Val::Number(n) => start <= n && (n < end || (*inclusive && n == end)),
+            _ => false,
+        },
+        (val, _) => return Err(format!("cannot look for items in {}", val.type_name())),
+    };
+
+    Ok(Val::Bool(found))
+}
+
+/// The items of something iterable, such as a range, as a list.
+fn list(_: &Env, args: &[Val]) -> Result<Val, String> {
+    Ok(Val::List(args[0].iter()?.collect()))
+}
+
+fn upper(_: &Env, args: &[Val]) -> Result<Val, String> {
+    match &args[0] {
+        Val::Str(s) => Ok(Val::Str(s.to_uppercase())),
+        val => Err(format!("cannot make {} upper case", val.type_name())),
+    }
+}
+
+/// The number a string spells out, written as a literal would be and with an optional `-` in
+/// front, or nil if it doesn't spell one out, so that `to_number(input) ?? 0` falls back on bad
+/// input. Whitespace around the number is ignored, and numbers are given back unchanged.
+fn to_number(_: &Env, args: &[Val]) -> Result<Val, String> {
+    match &args[0] {
+        Val::Str(s) => Ok(Int::parse_signed(s.trim()).map_or(Val::Nil, Val::Number)),
+        Val::Number(n) => Ok(Val::Number(n.clone())),
+        val => Err(format!("cannot turn {} into a number", val.type_name())),
+    }
+}
+
+/// A value as it would be printed, except that strings are given back without quotes, so that
+/// what a script reads can be handed on unchanged.
+fn to_string(_: &Env, args: &[Val]) -> Result<Val, String> {
+    match &args[0] {
+        Val::Str(s) => Ok(Val::Str(s.clone())),
+        val => Ok(Val::Str(val.to_string())),
+    }
+}
+
+/// Calls `f` on each item of something iterable, giving a list of the results.
+fn map(env: &Env, args: &[Val]) -> Result<Val, RuntimeError> {
+    let items = args[0].iter()?;
+    let f = func("map", &args[1])?;
+
+    items
+        .map(|item| apply(f, vec![item], env))
+        .collect::<Result<_, _>>()
+        .map(Val::List)
+}
+
+/// The items of something iterable that `f` returns `true` for.
+fn filter(env: &Env, args: &[Val]) -> Result<Val, RuntimeError> {
+    let items = args[0].iter()?;
+    let f = func("filter", &args[1])?;
+
+    let mut kept = Vec::new();
+    for item in items {
+        match apply(f, vec![item.clone()], env)? {
+            Val::Bool(true) => kept.push(item),
+            Val::Bool(false) => {}
+            val => {
+                return Err(format!(
+                    "filter expects a function that returns a bool, got {}",
+                    val.type_name()
+                )
+                .into())
+            }
+        }
+    }
+
+    Ok(Val::List(kept))
+}
+
+/// Combines the items of something iterable in order, starting from `init` and calling `f` with
+/// what has been combined so far and the next item.
+fn fold(env: &Env, args: &[Val]) -> Result<Val, RuntimeError> {
+    let mut items = args[0].iter()?;
+    let f = func("fold", &args[2])?;
+
+    items.try_fold(args[1].clone(), |acc, item| apply(f, vec![acc, item], env))
+}
+
+fn func<'a>(callee: &str, val: &'a Val) -> Result<&'a Partial, RuntimeError> {
+    match val {
+        Val::Func(partial) => Ok(partial),
+        val => Err(format!("{} expects a function, got {}", callee, val.type_name()).into()),
+    }
+}
+
+fn apply(f: &Partial, args: Vec<Val>, env: &Env) -> Result<Val, RuntimeError> {
+    call_partial(f, f.callee, args, &Span::default(), env)
+}
+
+fn panic(env: &Env, args: &[Val]) -> Result<Val, RuntimeError> {
+    let message = match &args[0] {
+        Val::Str(message) => message.clone(),
+        val => val.to_string(),
+    };
+
+    Err(RuntimeError::Panic {
+        message,
+        stack: env.call_stack(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::error::RuntimeError;
+    use crate::expr::{CallStyle, Expr, FuncCall, Number};
+    use crate::span::Span;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Debug, Default)]
+    struct FakeClock {
+        now: AtomicU64,
+    }
+
+    impl Clock for FakeClock {
+        fn now_ms(&self) -> u64 {
+            self.now.load(Ordering::Relaxed)
+        }
+
+        fn sleep_ms(&self, ms: u64) {
+            self.now.fetch_add(ms, Ordering::Relaxed);
+        }
+    }
+
+    fn call(callee: &str, params: Vec<Expr>, env: &Env) -> Result<Val, RuntimeError> {
+        Expr::FuncCall(FuncCall {
+            callee: callee.into(),
+            style: CallStyle::Direct,
+            named: Vec::new(),
+            span: Span::default(),
+            params,
+        })
+        .eval(&mut env.create_child())
+    }
+
+    #[test]
+    fn now_ms_reads_from_clock() {
+        let clock = Arc::new(FakeClock::default());
+        clock.now.store(1234, Ordering::Relaxed);
+        let env = Env::with_clock(clock);
+
+        assert_eq!(
+            call("now_ms", Vec::new(), &env),
+            Ok(Val::Number(1234.into()))
+        );
+    }
+
+    #[test]
+    fn sleep_ms_advances_clock() {
+        let clock = Arc::new(FakeClock::default());
+        let mut env = Env::with_clock(clock.clone());
+        env.allow_sleep();
+
+        assert_eq!(
+            call(
+                "sleep_ms",
+                vec![Expr::Number(Number(50.into()), Span::default())],
+                &env
+            ),
+            Ok(Val::Unit),
+        );
+        assert_eq!(call("now_ms", Vec::new(), &env), Ok(Val::Number(50.into())));
+    }
+
+    #[test]
+    fn sleep_ms_is_unavailable_without_opt_in() {
+        assert_eq!(
+            call(
+                "sleep_ms",
+                vec![Expr::Number(Number(10.into()), Span::default())],
+                &Env::default()
+            ),
+            Err(RuntimeError::Message(
+                "function with name 'sleep_ms' does not exist".to_string()
+            )),
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_and_read_files_once_allowed() {
+        let path = std::env::temp_dir().join(format!("eldiro-files-{}.txt", std::process::id()));
+        // Written as a string literal, quoted and escaped.
+        let path = Val::Str(path.to_str().unwrap().to_string()).to_string();
+        let source = format!(
+            "write_file({path}, \"a\\nb\")\nread_file({path})",
+            path = path
+        );
+
+        let mut env = Env::default();
+        assert_eq!(
+            crate::parse(&source).unwrap().eval(&mut env),
+            Err(RuntimeError::Message(
+                "function with name 'write_file' does not exist".to_string()
+            )),
+        );
+
+        env.allow_files();
+        assert_eq!(
+            crate::parse(&source).unwrap().eval(&mut env),
+            Ok(Val::Str("a\nb".to_string())),
+        );
+
+        env.make_deterministic(0);
+        assert!(crate::parse(&source).unwrap().eval(&mut env).is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn read_env_vars_once_allowed() {
+        let source = "(env_var(\"CARGO_PKG_NAME\"), env_var(\"ELDIRO_SURELY_UNSET\") ?? \"none\")";
+
+        let mut env = Env::default();
+        assert_eq!(
+            crate::parse(source).unwrap().eval(&mut env),
+            Err(RuntimeError::Message(
+                "function with name 'env_var' does not exist".to_string()
+            )),
+        );
+
+        env.allow_env_vars();
+        assert_eq!(
+            crate::parse(source).unwrap().eval(&mut env),
+            Ok(Val::Tuple(vec![
+                Val::Str("eldiro".to_string()),
+                Val::Str("none".to_string()),
+            ])),
+        );
+        assert_eq!(
+            crate::parse("env_var(1)").unwrap().eval(&mut env),
+            Err(RuntimeError::Message(
+                "env_var expects a name, got number".to_string()
+            )),
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn report_file_errors() {
+        let mut env = Env::default();
+        env.allow_files();
+        let mut eval = |s| crate::parse(s).unwrap().eval(&mut env);
+
+        assert!(matches!(
+            eval("read_file(\"/no/such/dir/file.txt\")"),
+            Err(RuntimeError::Message(msg)) if msg.starts_with("cannot read '/no/such/dir/file.txt': ")
+        ));
+        assert_eq!(
+            eval("write_file(\"out.txt\", 1)"),
+            Err(RuntimeError::Message(
+                "write_file expects a path and a string".to_string()
+            )),
+        );
+        assert_eq!(
+            eval("read_file(1)"),
+            Err(RuntimeError::Message(
+                "read_file expects a path, got number".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn random_stays_below_bound() {
+        let env = Env::default();
+
+        for _ in 0..100 {
+            match call(
+                "random",
+                vec![Expr::Number(Number(3.into()), Span::default())],
+                &env,
+            ) {
+                Ok(Val::Number(n)) => assert!((0..3).any(|i| n == i.into())),
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn random_with_non_positive_bound() {
+        assert_eq!(
+            call(
+                "random",
+                vec![Expr::Number(Number(0.into()), Span::default())],
+                &Env::default(),
+            ),
+            Err(RuntimeError::Message(
+                "random expects a positive number".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn passing_assert() {
+        assert_eq!(
+            call(
+                "assert",
+                vec![
+                    Expr::Bool(true, Span::default()),
+                    Expr::Str("unused".to_string(), Span::default())
+                ],
+                &Env::default(),
+            ),
+            Ok(Val::Unit),
+        );
+    }
+
+    #[test]
+    fn failing_assert() {
+        assert_eq!(
+            call(
+                "assert",
+                vec![
+                    Expr::Bool(false, Span::default()),
+                    Expr::Str("oh no".to_string(), Span::default())
+                ],
+                &Env::default(),
+            ),
+            Err(RuntimeError::Message("assertion failed: oh no".to_string())),
+        );
+    }
+
+    #[test]
+    fn assert_with_non_bool_condition() {
+        assert_eq!(
+            call(
+                "assert",
+                vec![
+                    Expr::Number(Number(1.into()), Span::default()),
+                    Expr::Str("oh no".to_string(), Span::default())
+                ],
+                &Env::default(),
+            ),
+            Err(RuntimeError::Message(
+                "assert expects a boolean condition".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn panic_records_call_stack() {
+        let program = crate::parse(
+            "fn check n => { panic(\"bad input\")\nn }\nfn outer n => check(n)\nouter(1)",
+        )
+        .unwrap();
+
+        assert_eq!(
+            program.eval(&mut Env::default()),
+            Err(RuntimeError::Panic {
+                message: "bad input".to_string(),
+                stack: vec!["check".to_string(), "outer".to_string()],
+            }),
+        );
+    }
+
+    #[test]
+    fn panic_at_top_level() {
+        assert_eq!(
+            call(
+                "panic",
+                vec![Expr::Number(Number(3.into()), Span::default())],
+                &Env::default()
+            ),
+            Err(RuntimeError::Panic {
+                message: "3".to_string(),
+                stack: Vec::new(),
+            }),
+        );
+    }
+
+    #[test]
+    fn len_and_upper() {
+        let eval = |s| crate::parse(s).unwrap().eval(&mut Env::default());
+
+        assert_eq!(
+            eval("(len([1, 2]), len(\"héllo\"), upper(\"héllo\"))"),
+            Ok(Val::Tuple(vec![
+                Val::Number(2.into()),
+                Val::Number(5.into()),
+                Val::Str("HÉLLO".to_string()),
+            ])),
+        );
+        assert_eq!(
+            eval("len(1)"),
+            Err(RuntimeError::Message(
+                "cannot take the length of number".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn convert_between_strings_and_numbers() {
+        let eval = |s| crate::parse(s).unwrap().eval(&mut Env::default());
+
+        assert_eq!(
+            eval("[to_number(\" -12 \"), to_number(\"0xff\"), to_number(\"1_000\"), to_number(7)]"),
+            Ok(Val::List(vec![
+                Val::Number((-12).into()),
+                Val::Number(255.into()),
+                Val::Number(1000.into()),
+                Val::Number(7.into()),
+            ])),
+        );
+        assert_eq!(
+            eval("[to_number(\"\"), to_number(\"1.5\"), to_number(\"- 1\"), to_number(\"abc\") ?? 0]"),
+            Ok(Val::List(vec![Val::Nil, Val::Nil, Val::Nil, Val::Number(0.into())])),
+        );
+        assert_eq!(
+            eval("to_number(true)"),
+            Err(RuntimeError::Message(
+                "cannot turn bool into a number".to_string()
+            )),
+        );
+        assert_eq!(
+            eval("(to_string(0 - 3), to_string(\"x\"), to_string([\"a\", nil]))"),
+            Ok(Val::Tuple(vec![
+                Val::Str("-3".to_string()),
+                Val::Str("x".to_string()),
+                Val::Str("[\"a\", nil]".to_string()),
+            ])),
+        );
+        assert_eq!(
+            eval("to_number(to_string(0 - 12345678)) == 0 - 12345678"),
+            Ok(Val::Bool(true)),
+        );
+        assert_eq!(
+            eval("assert(false, to_string(1..3))"),
+            Err(RuntimeError::Message("assertion failed: 1..3".to_string())),
+        );
+    }
+
+    #[test]
+    fn exit_ends_program_past_try() {
+        let eval = |s| crate::parse(s).unwrap().eval(&mut Env::default());
+
+        assert_eq!(
+            eval("fn quit => exit(3)\ntry { quit() } catch e { 0 }\n1"),
+            Err(RuntimeError::Exit(3)),
+        );
+        assert_eq!(
+            eval("exit(256)"),
+            Err(RuntimeError::Message(
+                "exit expects a code from 0 to 255".to_string()
+            )),
+        );
+        assert_eq!(
+            eval("try { exit(\"no\") } catch e { e }"),
+            Ok(Val::Str("exit expects a code from 0 to 255".to_string())),
+        );
+    }
+
+    #[test]
+    fn ranges_have_length_and_items() {
+        let eval = |s| {
+            crate::parse(s)
+                .unwrap()
+                .eval(&mut Env::default())
+                .map(|val| val.to_string())
+        };
+
+        assert_eq!(
+            eval("fn odd n => n & 1 == 1\n(list(1..4), 1..=4 |> filter(odd), list(3..1), len(0..=2), len(3..1))"),
+            Ok("([1, 2, 3], [1, 3], [], 3, 0)".to_string()),
+        );
+        assert_eq!(
+            eval("let r = 1..3\n(r.contains(1), r.contains(3), (1..=3).contains(3), r.contains(\"1\"))"),
+            Ok("(true, false, true, false)".to_string()),
+        );
+    }
+
+    #[test]
+    fn contains_items_and_substrings() {
+        let eval = |s| crate::parse(s).unwrap().eval(&mut Env::default());
+
+        assert_eq!(
+            eval("(contains([1, (2, 3)], (2, 3)), contains(\"hello\", \"ell\"), contains((1, 2), 3))"),
+            Ok(Val::Tuple(vec![Val::Bool(true), Val::Bool(true), Val::Bool(false)])),
+        );
+        assert_eq!(
+            eval("contains(1, 1)"),
+            Err(RuntimeError::Message(
+                "cannot look for items in number".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn map_filter_and_fold() {
+        let eval = |s| crate::parse(s).unwrap().eval(&mut Env::default());
+        let defs = "fn double x => x * 2\nfn big x => x > 2\nfn add a b => a + b\n";
+
+        assert_eq!(
+            eval(&format!(
+                "{}(map([1, 2, 3], double), filter([1, 2, 3, 4], big), fold([1, 2, 3], 10, add), map(\"ab\", upper))",
+                defs
+            )),
+            Ok(Val::Tuple(vec![
+                Val::List(vec![
+                    Val::Number(2.into()),
+                    Val::Number(4.into()),
+                    Val::Number(6.into()),
+                ]),
+                Val::List(vec![Val::Number(3.into()), Val::Number(4.into())]),
+                Val::Number(16.into()),
+                Val::List(vec![Val::Str("A".to_string()), Val::Str("B".to_string())]),
+            ])),
+        );
+    }
+
+    #[test]
+    fn map_filter_and_fold_check_their_arguments() {
+        let eval = |s| crate::parse(s).unwrap().eval(&mut Env::default());
+
+        assert_eq!(
+            eval("fn f x => x\nmap(1, f)"),
+            Err(RuntimeError::Message(
+                "cannot iterate over number".to_string()
+            )),
+        );
+        assert_eq!(
+            eval("fold([1], 0, 1)"),
+            Err(RuntimeError::Message(
+                "fold expects a function, got number".to_string()
+            )),
+        );
+        assert_eq!(
+            eval("fn f x => x\nfilter([1], f)"),
+            Err(RuntimeError::Message(
+                "filter expects a function that returns a bool, got number".to_string()
+            )),
+        );
+        assert_eq!(
+            eval("fn f x => panic(\"no\")\nmap([1], f)"),
+            Err(RuntimeError::Panic {
+                message: "no".to_string(),
+                stack: vec!["f".to_string()],
+            }),
+        );
+    }
+
+    #[test]
+    fn now_ms_rejects_parameters() {
+        assert_eq!(
+            call(
+                "now_ms",
+                vec![Expr::Number(Number(1.into()), Span::default())],
+                &Env::default()
+            ),
+            Err(RuntimeError::Arity {
+                callee: "now_ms".to_string(),
+                min: 0,
+                max: Some(0),
+                got: 1,
+                span: 0..0,
+            }),
+        );
+    }
+}
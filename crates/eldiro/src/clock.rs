@@ -0,0 +1,78 @@
+use crate::compat::Mutex;
+use core::fmt;
+#[cfg(feature = "os")]
+use std::thread;
+#[cfg(feature = "os")]
+use std::time::{Duration, Instant};
+
+/// The time source used by the `now_ms` and `sleep_ms` builtins.
+///
+/// Hosts can supply their own implementation through [`crate::Env::with_clock`], which is mostly
+/// useful for tests that need time to be predictable.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Milliseconds elapsed since some fixed starting point.
+    fn now_ms(&self) -> u64;
+
+    fn sleep_ms(&self, ms: u64);
+}
+
+/// A [`Clock`] backed by the operating system that counts from the moment it was created. This
+/// needs the `os` feature.
+#[cfg(feature = "os")]
+#[derive(Debug)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+#[cfg(feature = "os")]
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "os")]
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "os")]
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    fn sleep_ms(&self, ms: u64) {
+        thread::sleep(Duration::from_millis(ms));
+    }
+}
+
+/// A [`Clock`] that only moves when told to, starting at zero. Sleeping moves it forward instead of
+/// waiting, so it never blocks. This is the default without the `os` feature, when there's no
+/// system clock to read.
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    /// Behind a lock rather than in an atomic, since 32-bit microcontrollers don't have 64-bit
+    /// atomics.
+    now: Mutex<u64>,
+}
+
+impl ManualClock {
+    pub fn set_ms(&self, ms: u64) {
+        *self.now.lock() = ms;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_ms(&self) -> u64 {
+        *self.now.lock()
+    }
+
+    fn sleep_ms(&self, ms: u64) {
+        *self.now.lock() += ms;
+    }
+}
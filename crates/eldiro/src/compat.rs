@@ -0,0 +1,57 @@
+//! Stand-ins for the parts of the standard library that the parser and evaluator use, so that
+//! they build with only `alloc` when the `std` feature is off.
+
+/// What the standard prelude has on top of the `core` one.
+pub(crate) mod prelude {
+    pub(crate) use alloc::boxed::Box;
+    pub(crate) use alloc::string::{String, ToString};
+    pub(crate) use alloc::vec::Vec;
+    pub(crate) use alloc::{format, vec};
+}
+
+#[cfg(feature = "std")]
+pub(crate) use std::collections::{HashMap, HashSet};
+#[cfg(feature = "std")]
+pub(crate) use std::sync::{LazyLock, OnceLock};
+
+#[cfg(not(feature = "std"))]
+pub(crate) use hashbrown::{HashMap, HashSet};
+#[cfg(not(feature = "std"))]
+pub(crate) use spin::{LazyLock, Mutex};
+
+/// A lock that panics if a thread panicked while holding it, like the standard one, but whose
+/// `lock` returns the guard directly like the `spin` one used without `std`.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub(crate) struct Mutex<T>(std::sync::Mutex<T>);
+
+#[cfg(feature = "std")]
+impl<T> Mutex<T> {
+    pub(crate) fn new(val: T) -> Self {
+        Self(std::sync::Mutex::new(val))
+    }
+
+    pub(crate) fn lock(&self) -> std::sync::MutexGuard<'_, T> {
+        self.0.lock().unwrap()
+    }
+}
+
+/// A cell that is written once and then read without locking, with the methods of the standard
+/// one.
+#[cfg(not(feature = "std"))]
+pub(crate) struct OnceLock<T>(spin::Once<T>);
+
+#[cfg(not(feature = "std"))]
+impl<T> OnceLock<T> {
+    pub(crate) const fn new() -> Self {
+        Self(spin::Once::new())
+    }
+
+    pub(crate) fn get(&self) -> Option<&T> {
+        self.0.get()
+    }
+
+    pub(crate) fn get_or_init(&self, init: impl FnOnce() -> T) -> &T {
+        self.0.call_once(init)
+    }
+}
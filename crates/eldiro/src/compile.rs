@@ -0,0 +1,386 @@
+//! Lowers the syntax tree to bytecode for the [`vm`](crate::vm).
+
+use crate::binding_def::BindingDef;
+use crate::compat::prelude::*;
+use crate::enum_def::EnumDef;
+use crate::env::Func;
+use crate::expr::{Expr, Number, Op, Pattern, UnaryOp};
+use crate::import::Import;
+use crate::program::Program;
+use crate::span::Span;
+use crate::stmt::Stmt;
+use crate::symbol::Symbol;
+use crate::ty::Type;
+use crate::val::Val;
+use alloc::sync::Arc;
+
+/// A single VM instruction. Every statement leaves exactly one value on the stack.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Instr {
+    Push(Val),
+    /// Pushes the value of a binding, or the result of calling a function with no arguments if
+    /// there is no binding with that name.
+    Load(Symbol),
+    /// Pops a value and binds it to a name.
+    Store(Symbol),
+    /// Like [`Instr::Store`], but for a `const`.
+    StoreConst(Symbol),
+    /// Documents a binding that was just stored with what the `##` comments above it said.
+    Doc(Symbol, String),
+    /// Fails unless the value on top of the stack, which is about to be stored as `name`, has the
+    /// type it was annotated with.
+    Check {
+        name: Symbol,
+        ty: Type,
+    },
+    /// Defines a function, whose body is compiled along with the code that defines it rather than
+    /// each time it's called.
+    DefineFunc {
+        name: Symbol,
+        func: Func,
+        doc: Option<String>,
+    },
+    DefineEnum(EnumDef),
+    Import(Import),
+    /// Pops the right-hand side and then the left-hand side, and pushes the result.
+    BinOp(Op, Span),
+    /// Pops the given number of values and pushes them as a list, in the order they were pushed.
+    List(usize),
+    /// Like [`Instr::List`], but makes a tuple.
+    Tuple(usize),
+    /// Pops a value for each field, which were pushed in the same order, and pushes a record.
+    Record(Vec<Symbol>),
+    /// Pops a record and pushes one of its fields.
+    Field(Symbol),
+    /// Pops a value and pushes it back, unless it is nil, in which case the chunk is run and its
+    /// value pushed instead.
+    Coalesce(Chunk),
+    /// Pops a value and indexes into it with the value of the chunk, which is only run if needed.
+    Index {
+        index: Chunk,
+        safe: bool,
+        span: Span,
+    },
+    /// Pops the operand and pushes the result.
+    UnaryOp(UnaryOp),
+    /// Calls a function. Each argument is compiled separately, so that the arity can be checked
+    /// before any of them run. An argument that is `_` has no chunk, and makes the call push a
+    /// [`Val::Func`] instead.
+    Call {
+        callee: Symbol,
+        args: Vec<Option<Chunk>>,
+        named: Vec<(Symbol, Chunk)>,
+        span: Span,
+    },
+    /// Runs a chunk in a child scope and pushes its value.
+    Block(Chunk),
+    /// Runs `body` in a child scope, and if it fails runs `handler` in another with the error bound
+    /// to `name`, then pushes the value of whichever ran last.
+    Try {
+        body: Chunk,
+        name: Symbol,
+        handler: Chunk,
+    },
+    /// Pops a value and runs the chunk of the first arm whose pattern fits it, in a child scope
+    /// with what the pattern binds, and pushes its value.
+    Match(Vec<(Pattern, Chunk)>),
+    /// Pops a value and runs `body` once for each of its items, each time in a child scope with
+    /// the item bound to `name`, then pushes `()`.
+    For {
+        name: Symbol,
+        body: Chunk,
+    },
+    /// Pops a value and changes the binding `name` to `op` applied to its value and that.
+    Assign {
+        name: Symbol,
+        op: Op,
+        span: Span,
+    },
+    /// Pops a value and fails with it.
+    Throw,
+    Pop,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Chunk {
+    pub(crate) code: Vec<Instr>,
+}
+
+pub(crate) fn program(program: &Program) -> Chunk {
+    stmts(&program.stmts)
+}
+
+pub(crate) fn stmt(stmt: &Stmt) -> Chunk {
+    let mut chunk = Chunk::default();
+    compile_stmt(&mut chunk, stmt);
+    chunk
+}
+
+fn stmts(stmts: &[Stmt]) -> Chunk {
+    let mut chunk = Chunk::default();
+
+    if stmts.is_empty() {
+        chunk.code.push(Instr::Push(Val::Unit));
+    }
+
+    for (idx, stmt) in stmts.iter().enumerate() {
+        if idx > 0 {
+            chunk.code.push(Instr::Pop);
+        }
+
+        compile_stmt(&mut chunk, stmt);
+    }
+
+    chunk
+}
+
+fn compile_stmt(chunk: &mut Chunk, stmt: &Stmt) {
+    match stmt {
+        Stmt::BindingDef(BindingDef {
+            name,
+            constant,
+            ty,
+            val,
+            doc,
+            ..
+        }) => {
+            compile_expr(chunk, val);
+
+            if let Some(ty) = ty {
+                chunk.code.push(Instr::Check {
+                    name: *name,
+                    ty: *ty,
+                });
+            }
+
+            chunk.code.push(if *constant {
+                Instr::StoreConst(*name)
+            } else {
+                Instr::Store(*name)
+            });
+            if let Some(doc) = doc {
+                chunk.code.push(Instr::Doc(*name, doc.clone()));
+            }
+            chunk.code.push(Instr::Push(Val::Unit));
+        }
+        Stmt::Expr(expr) => compile_expr(chunk, expr),
+        Stmt::FuncDef(func_def) => {
+            let body = Arc::new(self::stmt(&func_def.body));
+            chunk.code.push(Instr::DefineFunc {
+                name: func_def.name,
+                func: Func::user(func_def, Some(body)),
+                doc: func_def.doc.clone(),
+            });
+            chunk.code.push(Instr::Push(Val::Unit));
+        }
+        Stmt::EnumDef(enum_def) => {
+            chunk.code.push(Instr::DefineEnum(enum_def.clone()));
+            chunk.code.push(Instr::Push(Val::Unit));
+        }
+        Stmt::Throw(val, _) => {
+            compile_expr(chunk, val);
+            chunk.code.push(Instr::Throw);
+        }
+        Stmt::Assign(assign) => {
+            compile_expr(chunk, &assign.val);
+            chunk.code.push(Instr::Assign {
+                name: assign.name,
+                op: assign.op.clone(),
+                span: assign.span.clone(),
+            });
+            chunk.code.push(Instr::Push(Val::Unit));
+        }
+        Stmt::Import(import) => {
+            chunk.code.push(Instr::Import(import.clone()));
+            chunk.code.push(Instr::Push(Val::Unit));
+        }
+    }
+}
+
+fn compile_expr(chunk: &mut Chunk, expr: &Expr) {
+    match expr {
+        Expr::Number(Number(n), _) => chunk.code.push(Instr::Push(Val::Number(n.clone()))),
+        Expr::Bool(b, _) => chunk.code.push(Instr::Push(Val::Bool(*b))),
+        Expr::Unit(_) => chunk.code.push(Instr::Push(Val::Unit)),
+        Expr::Nil(_) => chunk.code.push(Instr::Push(Val::Nil)),
+        Expr::Placeholder(_) => unreachable!("`_` is only parsed as an argument"),
+        Expr::Record(fields, _) => {
+            for (_, val) in fields {
+                compile_expr(chunk, val);
+            }
+
+            let names = fields.iter().map(|(name, _)| *name).collect();
+            chunk.code.push(Instr::Record(names));
+        }
+        Expr::Field { base, name, .. } => {
+            compile_expr(chunk, base);
+            chunk.code.push(Instr::Field(*name));
+        }
+        Expr::Operation {
+            lhs,
+            rhs,
+            op: Op::Coalesce,
+            ..
+        } => {
+            compile_expr(chunk, lhs);
+            chunk.code.push(Instr::Coalesce(expr_chunk(rhs)));
+        }
+        Expr::Str(s, _) => chunk.code.push(Instr::Push(Val::Str(s.clone()))),
+        Expr::Operation { lhs, rhs, op, span } => {
+            compile_expr(chunk, lhs);
+            compile_expr(chunk, rhs);
+            chunk.code.push(Instr::BinOp(op.clone(), span.clone()));
+        }
+        Expr::Unary { op, operand, .. } => {
+            compile_expr(chunk, operand);
+            chunk.code.push(Instr::UnaryOp(op.clone()));
+        }
+        Expr::BindingUsage(binding_usage) => {
+            chunk.code.push(Instr::Load(binding_usage.name));
+        }
+        Expr::Block(block) if block.stmts.is_empty() => chunk.code.push(Instr::Push(Val::Unit)),
+        Expr::Block(block) => chunk.code.push(Instr::Block(stmts(&block.stmts))),
+        Expr::TryCatch(try_catch) => chunk.code.push(Instr::Try {
+            body: stmts(&try_catch.body.stmts),
+            name: try_catch.name,
+            handler: stmts(&try_catch.handler.stmts),
+        }),
+        Expr::Match(m) => {
+            compile_expr(chunk, &m.val);
+            chunk.code.push(Instr::Match(
+                m.arms
+                    .iter()
+                    .map(|arm| (arm.pattern.clone(), expr_chunk(&arm.body)))
+                    .collect(),
+            ));
+        }
+        Expr::For(for_loop) => {
+            compile_expr(chunk, &for_loop.iterable);
+            chunk.code.push(Instr::For {
+                name: for_loop.name,
+                body: stmts(&for_loop.body.stmts),
+            });
+        }
+        Expr::Index {
+            base,
+            index,
+            safe,
+            span,
+        } => {
+            compile_expr(chunk, base);
+            chunk.code.push(Instr::Index {
+                index: expr_chunk(index),
+                safe: *safe,
+                span: span.clone(),
+            });
+        }
+        Expr::List(items, _) => {
+            for item in items {
+                compile_expr(chunk, item);
+            }
+
+            chunk.code.push(Instr::List(items.len()));
+        }
+        Expr::Tuple(items, _) => {
+            for item in items {
+                compile_expr(chunk, item);
+            }
+
+            chunk.code.push(Instr::Tuple(items.len()));
+        }
+        Expr::FuncCall(func_call) => chunk.code.push(Instr::Call {
+            callee: func_call.callee,
+            args: func_call
+                .params
+                .iter()
+                .map(|param| match param {
+                    Expr::Placeholder(_) => None,
+                    param => Some(expr_chunk(param)),
+                })
+                .collect(),
+            named: func_call
+                .named
+                .iter()
+                .map(|(name, val)| (*name, expr_chunk(val)))
+                .collect(),
+            span: func_call.span.clone(),
+        }),
+    }
+}
+
+pub(crate) fn expr_chunk(expr: &Expr) -> Chunk {
+    let mut chunk = Chunk::default();
+    compile_expr(&mut chunk, expr);
+    chunk
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_operation_in_postfix_order() {
+        assert_eq!(
+            program(&crate::parse("1 + 2 * 3").unwrap()).code,
+            vec![
+                Instr::Push(Val::Number(1.into())),
+                Instr::Push(Val::Number(2.into())),
+                Instr::Push(Val::Number(3.into())),
+                Instr::BinOp(Op::Mul, Span::default()),
+                Instr::BinOp(Op::Add, Span::default()),
+            ],
+        );
+    }
+
+    #[test]
+    fn compile_stmts_with_pops_between() {
+        assert_eq!(
+            program(&crate::parse("let a = 1\na").unwrap()).code,
+            vec![
+                Instr::Push(Val::Number(1.into())),
+                Instr::Store("a".into()),
+                Instr::Push(Val::Unit),
+                Instr::Pop,
+                Instr::Load("a".into()),
+            ],
+        );
+    }
+
+    #[test]
+    fn compile_call_args_separately() {
+        assert_eq!(
+            program(&crate::parse("f(1, x)").unwrap()).code,
+            vec![Instr::Call {
+                callee: "f".into(),
+                args: vec![
+                    Some(Chunk {
+                        code: vec![Instr::Push(Val::Number(1.into()))],
+                    }),
+                    Some(Chunk {
+                        code: vec![Instr::Load("x".into())],
+                    }),
+                ],
+                named: Vec::new(),
+                span: Span::default(),
+            }],
+        );
+    }
+
+    #[test]
+    fn compile_func_body_with_definition() {
+        let code = program(&crate::parse("fn f x => x").unwrap()).code;
+
+        match &code[0] {
+            Instr::DefineFunc {
+                func: Func::User { compiled, .. },
+                ..
+            } => assert_eq!(
+                compiled.as_deref(),
+                Some(&Chunk {
+                    code: vec![Instr::Load("x".into())],
+                }),
+            ),
+            instr => panic!("expected a user function to be defined, got {:?}", instr),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Hooks for stepping through a program one statement at a time.
+
+use crate::compat::prelude::*;
+use crate::compat::Mutex;
+use crate::env::Env;
+use crate::error::RuntimeError;
+use crate::expr::Expr;
+use crate::stmt::Stmt;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// What to do after evaluation pauses, as decided by a [`Debugger`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Runs the statement, pausing again before the next one, including those inside calls.
+    Step,
+    /// Runs the rest of the program without pausing again.
+    Continue,
+    /// Stops the program, which then fails with [`RuntimeError::Aborted`].
+    Abort,
+}
+
+/// A statement that evaluation paused before.
+#[derive(Debug)]
+pub struct Pause<'a> {
+    pub stmt: &'a Stmt,
+    /// The scope that the statement runs in, which methods such as [`Env::bindings`] can look
+    /// into.
+    pub env: &'a Env<'a>,
+}
+
+impl Pause<'_> {
+    /// The statement as canonical source text.
+    pub fn code(&self) -> String {
+        crate::fmt::stmt(self.stmt)
+    }
+
+    /// The names of the functions being called, innermost first.
+    pub fn call_stack(&self) -> Vec<String> {
+        self.env.call_stack()
+    }
+}
+
+/// Something that evaluation hands control to before each statement, such as an interactive
+/// stepper in a REPL. Attach one with [`Env::set_debugger`].
+///
+/// Only code that the tree-walking backend evaluates pauses, since compiled code has no
+/// statements left to pause before.
+pub trait Debugger: fmt::Debug + Send {
+    fn pause(&mut self, pause: &Pause) -> Action;
+}
+
+/// A debugger attached to an environment, which every scope made from it shares.
+#[derive(Debug)]
+pub(crate) struct Session {
+    debugger: Mutex<Box<dyn Debugger>>,
+    /// Turned off once the debugger says to continue.
+    stepping: AtomicBool,
+}
+
+impl Session {
+    pub(crate) fn new(debugger: Box<dyn Debugger>) -> Self {
+        Self {
+            debugger: Mutex::new(debugger),
+            stepping: AtomicBool::new(true),
+        }
+    }
+
+    /// Hands control to the debugger before `stmt` runs in `env`, unless it has said to continue.
+    ///
+    /// Blocks are skipped, since the debugger sees each of their statements instead.
+    pub(crate) fn before(&self, stmt: &Stmt, env: &Env) -> Result<(), RuntimeError> {
+        if !self.stepping.load(Ordering::Relaxed) || matches!(stmt, Stmt::Expr(Expr::Block(_))) {
+            return Ok(());
+        }
+
+        let action = self.debugger.lock().pause(&Pause { stmt, env });
+
+        match action {
+            Action::Step => Ok(()),
+            Action::Continue => {
+                self.stepping.store(false, Ordering::Relaxed);
+                Ok(())
+            }
+            Action::Abort => Err(RuntimeError::Aborted),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::val::Val;
+    use std::sync::{Arc, Mutex};
+
+    /// Records what it paused at, then takes the next of its actions, stepping once they run out.
+    #[derive(Debug)]
+    struct Script {
+        seen: Arc<Mutex<Vec<String>>>,
+        actions: Vec<Action>,
+    }
+
+    impl Debugger for Script {
+        fn pause(&mut self, pause: &Pause) -> Action {
+            let names = pause
+                .env
+                .bindings()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>();
+            let mut seen = format!("{} with [{}]", pause.code(), names.join(", "));
+            if let Some(callee) = pause.call_stack().first() {
+                seen = format!("{} in {}", seen, callee);
+            }
+
+            self.seen.lock().unwrap().push(seen);
+
+            if self.actions.is_empty() {
+                Action::Step
+            } else {
+                self.actions.remove(0)
+            }
+        }
+    }
+
+    fn debug(s: &str, actions: Vec<Action>) -> (Result<Val, RuntimeError>, Vec<String>) {
+        let seen = Arc::default();
+        let mut env = Env::default();
+        env.set_debugger(Script {
+            seen: Arc::clone(&seen),
+            actions,
+        });
+
+        let result = crate::parse(s).unwrap().eval(&mut env);
+        let seen = seen.lock().unwrap().clone();
+        (result, seen)
+    }
+
+    #[test]
+    fn pause_before_each_statement() {
+        assert_eq!(
+            debug("let a = 1\nfn f x => { let y = x\ny }\nf(a)", Vec::new()),
+            (
+                Ok(Val::Number(1.into())),
+                vec![
+                    "let a = 1 with []".to_string(),
+                    "fn f x => {\n    let y = x\n    y\n} with [a]".to_string(),
+                    "f(a) with [a]".to_string(),
+                    "let y = x with [a, x] in f".to_string(),
+                    "y with [a, x, y] in f".to_string(),
+                ],
+            ),
+        );
+    }
+
+    #[test]
+    fn continue_stops_pausing() {
+        let (result, seen) = debug("let a = 1\nlet b = 2\na + b", vec![Action::Continue]);
+
+        assert_eq!(result, Ok(Val::Number(3.into())));
+        assert_eq!(seen, ["let a = 1 with []"]);
+    }
+
+    #[test]
+    fn abort_stops_evaluation() {
+        let (result, seen) = debug("let a = 1\nthrow a", vec![Action::Step, Action::Abort]);
+
+        assert_eq!(result, Err(RuntimeError::Aborted));
+        assert_eq!(seen.len(), 2);
+    }
+
+    #[test]
+    fn try_does_not_catch_abort() {
+        let (result, _) = debug("try { 1 } catch e { e }", vec![Action::Step, Action::Abort]);
+
+        assert_eq!(result, Err(RuntimeError::Aborted));
+    }
+}
@@ -0,0 +1,261 @@
+//! Renders syntax trees as an indented outline, one node per line.
+
+use crate::binding_def::BindingDef;
+use crate::compat::prelude::*;
+use crate::expr::{Expr, Number};
+use crate::fmt;
+use crate::func_def::FuncDef;
+use crate::program::Program;
+use crate::stmt::Stmt;
+
+pub(crate) fn program(program: &Program) -> String {
+    let mut out = "Program\n".to_string();
+
+    for stmt in &program.stmts {
+        write_stmt(&mut out, stmt, 1);
+    }
+
+    out
+}
+
+fn line(out: &mut String, depth: usize, text: &str) {
+    *out += &"  ".repeat(depth);
+    *out += text;
+    out.push('\n');
+}
+
+fn write_stmt(out: &mut String, stmt: &Stmt, depth: usize) {
+    match stmt {
+        Stmt::BindingDef(BindingDef {
+            name,
+            constant,
+            ty,
+            val,
+            ..
+        }) => {
+            let keyword = if *constant { "const " } else { "" };
+            line(
+                out,
+                depth,
+                &format!("BindingDef {}{}{}", keyword, name, fmt::annotation(*ty)),
+            );
+            write_expr(out, val, depth + 1);
+        }
+        Stmt::Expr(expr) => write_expr(out, expr, depth),
+        Stmt::FuncDef(FuncDef {
+            name, params, body, ..
+        }) => {
+            line(
+                out,
+                depth,
+                &format!(
+                    "FuncDef {} ({})",
+                    name,
+                    params
+                        .iter()
+                        .map(|param| format!(
+                            "{}{}{}",
+                            if param.rest { "..." } else { "" },
+                            param.name,
+                            fmt::annotation(param.ty)
+                        ))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            );
+
+            for param in params {
+                if let Some(default) = &param.default {
+                    line(out, depth + 1, &format!("Default {}", param.name));
+                    write_expr(out, default, depth + 2);
+                }
+            }
+
+            write_stmt(out, body, depth + 1);
+        }
+        Stmt::EnumDef(enum_def) => {
+            line(out, depth, &format!("EnumDef {}", enum_def.name));
+
+            for variant in &enum_def.variants {
+                let fields: Vec<_> = variant.fields.iter().map(|field| field.as_str()).collect();
+                line(
+                    out,
+                    depth + 1,
+                    &format!("Variant {} ({})", variant.name, fields.join(", ")),
+                );
+            }
+        }
+        Stmt::Throw(val, _) => {
+            line(out, depth, "Throw");
+            write_expr(out, val, depth + 1);
+        }
+        Stmt::Assign(assign) => {
+            let op = fmt::op_symbol(&assign.op);
+            line(out, depth, &format!("Assign {} {}=", assign.name, op));
+            write_expr(out, &assign.val, depth + 1);
+        }
+        Stmt::Import(import) => {
+            let mut path = String::new();
+            fmt::write_string(&mut path, &import.path);
+            line(out, depth, &format!("Import {}", path));
+        }
+    }
+}
+
+fn write_expr(out: &mut String, expr: &Expr, depth: usize) {
+    match expr {
+        Expr::Number(Number(n), _) => line(out, depth, &format!("Number {}", n)),
+        Expr::Bool(b, _) => line(out, depth, &format!("Bool {}", b)),
+        Expr::Unit(_) => line(out, depth, "Unit"),
+        Expr::Nil(_) => line(out, depth, "Nil"),
+        Expr::Placeholder(_) => line(out, depth, "Placeholder"),
+        Expr::Index {
+            base, index, safe, ..
+        } => {
+            line(out, depth, if *safe { "SafeIndex" } else { "Index" });
+            write_expr(out, base, depth + 1);
+            write_expr(out, index, depth + 1);
+        }
+        Expr::Str(s, _) => {
+            let mut string = String::new();
+            fmt::write_string(&mut string, s);
+            line(out, depth, &format!("Str {}", string));
+        }
+        Expr::Operation { lhs, rhs, op, .. } => {
+            line(out, depth, &format!("Operation {}", fmt::op_symbol(op)));
+            write_expr(out, lhs, depth + 1);
+            write_expr(out, rhs, depth + 1);
+        }
+        Expr::Unary { op, operand, .. } => {
+            line(out, depth, &format!("Unary {}", fmt::unary_op_symbol(op)));
+            write_expr(out, operand, depth + 1);
+        }
+        Expr::BindingUsage(binding_usage) => {
+            line(out, depth, &format!("BindingUsage {}", binding_usage.name));
+        }
+        Expr::TryCatch(try_catch) => {
+            line(out, depth, &format!("TryCatch {}", try_catch.name));
+            write_expr(out, &Expr::Block(try_catch.body.clone()), depth + 1);
+            write_expr(out, &Expr::Block(try_catch.handler.clone()), depth + 1);
+        }
+        Expr::For(for_loop) => {
+            line(out, depth, &format!("For {}", for_loop.name));
+            write_expr(out, &for_loop.iterable, depth + 1);
+            write_expr(out, &Expr::Block(for_loop.body.clone()), depth + 1);
+        }
+        Expr::Match(m) => {
+            line(out, depth, "Match");
+            write_expr(out, &m.val, depth + 1);
+
+            for arm in &m.arms {
+                line(out, depth + 1, &format!("Arm {}", arm.pattern));
+                write_expr(out, &arm.body, depth + 2);
+            }
+        }
+        Expr::Block(block) => {
+            line(out, depth, "Block");
+
+            for stmt in &block.stmts {
+                write_stmt(out, stmt, depth + 1);
+            }
+        }
+        Expr::List(items, _) => {
+            line(out, depth, "List");
+
+            for item in items {
+                write_expr(out, item, depth + 1);
+            }
+        }
+        Expr::Tuple(items, _) => {
+            line(out, depth, "Tuple");
+
+            for item in items {
+                write_expr(out, item, depth + 1);
+            }
+        }
+        Expr::Record(fields, _) => {
+            line(out, depth, "Record");
+
+            for (name, val) in fields {
+                line(out, depth + 1, &format!("Field {}", name));
+                write_expr(out, val, depth + 2);
+            }
+        }
+        Expr::Field { base, name, .. } => {
+            line(out, depth, &format!("GetField {}", name));
+            write_expr(out, base, depth + 1);
+        }
+        Expr::FuncCall(func_call) => {
+            line(out, depth, &format!("FuncCall {}", func_call.callee));
+
+            for param in &func_call.params {
+                write_expr(out, param, depth + 1);
+            }
+
+            for (name, val) in &func_call.named {
+                line(out, depth + 1, &format!("Named {}", name));
+                write_expr(out, val, depth + 2);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_program() {
+        assert_eq!(
+            program(&crate::parse("let a = 1 + 2 * 3\nfn f x => { x }\nf(\"hi\", true)").unwrap()),
+            "\
+Program
+  BindingDef a
+    Operation +
+      Number 1
+      Operation *
+        Number 2
+        Number 3
+  FuncDef f (x)
+    Block
+      BindingUsage x
+  FuncCall f
+    Str \"hi\"
+    Bool true
+",
+        );
+    }
+
+    #[test]
+    fn dump_enum_and_match() {
+        assert_eq!(
+            program(
+                &crate::parse(
+                    "enum E { A(x, y), B }
+match B { A(x, _) => x, B => 0 }"
+                )
+                .unwrap()
+            ),
+            "\
+Program
+  EnumDef E
+    Variant A (x, y)
+    Variant B ()
+  Match
+    BindingUsage B
+    Arm A(x, _)
+      BindingUsage x
+    Arm B
+      Number 0
+",
+        );
+    }
+
+    #[test]
+    fn dump_import() {
+        assert_eq!(
+            program(&crate::parse(r#"import "a.eld""#).unwrap()),
+            "Program\n  Import \"a.eld\"\n",
+        );
+    }
+}
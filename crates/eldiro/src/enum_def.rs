@@ -0,0 +1,173 @@
+use crate::compat::prelude::*;
+use crate::env::Env;
+use crate::error::RuntimeError;
+use crate::lexer::{Token, TokenKind};
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::utils;
+use crate::val::Val;
+
+/// `enum Shape { Circle(r), Rect(w, h), Empty }`, which defines a constructor function for each
+/// variant with fields, and a binding for each variant without.
+#[derive(Debug, PartialEq, Clone)]
+pub struct EnumDef {
+    pub name: Symbol,
+    pub variants: Vec<Variant>,
+    pub span: Span,
+}
+
+/// A variant of an enum. Its fields are named like parameters, although the names only document
+/// what each field is for.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Variant {
+    pub name: Symbol,
+    pub fields: Vec<Symbol>,
+}
+
+impl Variant {
+    fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let (after_name, name) = utils::extract_ident(tokens)?;
+
+        let (tokens, fields) = match utils::expect(TokenKind::LParen, after_name) {
+            Ok(tokens) => utils::comma_separated(
+                |tokens| utils::extract_ident(tokens).map(|(tokens, field)| (tokens, field.into())),
+                TokenKind::RParen,
+                tokens,
+            )?,
+            Err(_) => (after_name, Vec::new()),
+        };
+
+        Ok((
+            tokens,
+            Self {
+                name: Symbol::intern(name),
+                fields,
+            },
+        ))
+    }
+}
+
+impl EnumDef {
+    pub(crate) fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let tokens = utils::keyword("enum", tokens)?;
+        let (tokens, name) = utils::extract_ident(tokens)?;
+        let tokens = utils::expect(TokenKind::LBrace, tokens)?;
+        let (tokens, variants) = utils::comma_separated(Variant::new, TokenKind::RBrace, tokens)?;
+
+        Ok((
+            tokens,
+            Self {
+                name: Symbol::intern(name),
+                variants,
+                span: utils::consumed_span(start, tokens),
+            },
+        ))
+    }
+
+    pub(crate) fn eval(&self, env: &mut Env) -> Result<(), RuntimeError> {
+        for (idx, variant) in self.variants.iter().enumerate() {
+            if self.variants[..idx]
+                .iter()
+                .any(|earlier| earlier.name == variant.name)
+            {
+                return Err(format!(
+                    "enum '{}' has two variants called '{}'",
+                    self.name, variant.name
+                )
+                .into());
+            }
+
+            env.check_redefinable(variant.name)?;
+        }
+
+        for variant in &self.variants {
+            if variant.fields.is_empty() {
+                let val = Val::Variant {
+                    ty: self.name,
+                    name: variant.name,
+                    fields: Vec::new(),
+                };
+                env.store_binding(variant.name, val);
+            } else {
+                env.store_constructor(self.name, variant.name, variant.fields.len());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_enum_def() {
+        assert_eq!(
+            utils::parse_str(EnumDef::new, "enum Shape { Circle(r), Rect(w, h), Empty }"),
+            Ok((
+                "",
+                EnumDef {
+                    name: "Shape".into(),
+                    variants: vec![
+                        Variant {
+                            name: "Circle".into(),
+                            fields: vec!["r".into()],
+                        },
+                        Variant {
+                            name: "Rect".into(),
+                            fields: vec!["w".into(), "h".into()],
+                        },
+                        Variant {
+                            name: "Empty".into(),
+                            fields: Vec::new(),
+                        },
+                    ],
+                    span: Span::default(),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_constructors() {
+        let eval = |s| {
+            crate::parse(s)
+                .unwrap()
+                .eval(&mut Env::default())
+                .map(|val| val.to_string())
+        };
+
+        assert_eq!(
+            eval("enum Shape { Circle(r), Rect(w, h), Empty }\n[Circle(1), Rect(2, 3), Empty]"),
+            Ok("[Circle(1), Rect(2, 3), Empty]".to_string()),
+        );
+        assert_eq!(
+            eval("enum Shape { Circle(r) }\nCircle(1) == Circle(1)"),
+            Ok("true".to_string()),
+        );
+        assert_eq!(
+            eval("enum Shape { Circle(r), Circle }"),
+            Err(RuntimeError::Message(
+                "enum 'Shape' has two variants called 'Circle'".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn check_constructor_arity() {
+        assert_eq!(
+            crate::parse("enum Shape { Rect(w, h) }\nRect(1)")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Err(RuntimeError::Arity {
+                callee: "Rect".to_string(),
+                min: 2,
+                max: Some(2),
+                got: 1,
+                span: 26..33,
+            }),
+        );
+    }
+}
@@ -1,11 +1,114 @@
+#[cfg(feature = "os")]
+use crate::bridge::Bridge;
+use crate::bridge::NativeFuture;
+use crate::builtins;
+use crate::clock::{Clock, ManualClock};
+use crate::compat::prelude::*;
+use crate::compat::{HashMap, HashSet};
+use crate::compile::Chunk;
+use crate::debug::{Debugger, Session};
+use crate::enum_def::{EnumDef, Variant};
+use crate::error::RuntimeError;
+use crate::func_def::{FuncDef, Param};
+#[cfg(feature = "std")]
+use crate::import::Module;
+use crate::native::Native;
+use crate::observe::EvalObserver;
+use crate::profile::{FuncProfile, Profiler};
+use crate::random::Rng;
+use crate::span::Span;
 use crate::stmt::Stmt;
+use crate::symbol::Symbol;
 use crate::val::Val;
-use std::collections::HashMap;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+#[cfg(feature = "std")]
+use core::mem;
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, PartialEq)]
 enum NamedInfo {
     Binding(Val),
-    Func { params: Vec<String>, body: Stmt },
+    Func(Func),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Func {
+    /// Shared so that looking a function up for a call doesn't copy its body.
+    User {
+        params: Arc<[Param]>,
+        body: Arc<Stmt>,
+        /// The body compiled to bytecode, if the function was defined by compiled code, so that
+        /// the VM doesn't compile it again for every call.
+        compiled: Option<Arc<Chunk>>,
+        /// Where the function was defined, if it came from source at all.
+        source: Option<Definition>,
+    },
+    Native(Native),
+    /// Makes a value of one of the variants of the enum `ty`.
+    Constructor {
+        ty: Symbol,
+        variant: Symbol,
+        arity: usize,
+    },
+}
+
+/// Where a user-defined function came from, so that errors in its body can be shown against the
+/// source they point into.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Definition {
+    /// The whole source text that the function was parsed from.
+    pub(crate) source: Arc<str>,
+    /// The span of the definition in `source`.
+    pub(crate) span: Span,
+    /// The file that `source` was read from, which is filled in when the function is defined.
+    pub(crate) path: Option<Arc<str>>,
+}
+
+impl Func {
+    /// The function that `func_def` defines, keeping track of the source it came from and its
+    /// `compiled` body if it has one.
+    pub(crate) fn user(func_def: &FuncDef, compiled: Option<Arc<Chunk>>) -> Self {
+        Self::User {
+            params: func_def.params.as_slice().into(),
+            body: Arc::new(func_def.body.as_ref().clone()),
+            compiled,
+            source: func_def.source.clone().map(|source| Definition {
+                source,
+                span: func_def.span.clone(),
+                path: None,
+            }),
+        }
+    }
+
+    /// Records that this function was defined by code in `module`.
+    #[cfg(feature = "std")]
+    fn defined_in(mut self, module: &Module) -> Self {
+        if let Self::User {
+            source: Some(definition),
+            ..
+        } = &mut self
+        {
+            definition.path = module.name().cloned();
+        }
+
+        self
+    }
+
+    /// Records that an error from calling this function came from its body, if its span could
+    /// point into source other than the caller's.
+    pub(crate) fn locate(&self, error: RuntimeError) -> RuntimeError {
+        match self {
+            Self::User {
+                source: Some(definition),
+                ..
+            } => error.in_source(&definition.source, definition.path.as_ref()),
+            _ => error,
+        }
+    }
 }
 
 impl NamedInfo {
@@ -17,52 +120,1109 @@ impl NamedInfo {
         }
     }
 
-    fn into_func(self) -> Option<(Vec<String>, Stmt)> {
-        if let Self::Func { params, body } = self {
-            Some((params, body))
+    fn into_func(self) -> Option<Func> {
+        if let Self::Func(func) = self {
+            Some(func)
         } else {
             None
         }
     }
 }
-#[derive(Debug, PartialEq, Default)]
+
+/// How many calls can be in progress at once unless the host sets its own limit with
+/// [`Env::set_max_call_depth`]. Each call takes up a few kilobytes of the native stack, and tens
+/// of kilobytes in debug builds, so this leaves room on the 8 MiB main thread of most platforms.
+pub(crate) const DEFAULT_MAX_CALL_DEPTH: usize = 200;
+
+/// A function call that is in progress, along with the chain of calls that led to it.
+#[derive(Debug)]
+struct Call {
+    callee: Symbol,
+    caller: Option<Arc<Call>>,
+    /// How many calls are in progress, counting this one.
+    depth: usize,
+}
+
+/// The names that code is evaluated with, in a chain of scopes.
+///
+/// Environments and values are `Send` and `Sync`, so one environment can serve as a read-only
+/// prelude for many threads: each evaluates in its own [`Env::create_child`], which sees the
+/// prelude's definitions without being able to change them. Debuggers, observers and profiles
+/// attached to the prelude are shared by every thread.
+#[derive(Debug)]
 pub struct Env<'parent> {
-    named: HashMap<String, NamedInfo>,
+    named: HashMap<Symbol, NamedInfo>,
+    /// The names in `named` that were defined with `const`, so can't be defined again here.
+    consts: HashSet<Symbol>,
+    /// The doc comments of the names in `named` that have them.
+    docs: HashMap<Symbol, String>,
     parent: Option<&'parent Self>,
+    context: Arc<Context>,
+    /// Kept apart from `parent`, since a call's scope is a child of where the function was
+    /// defined rather than of its caller.
+    call: Option<Arc<Call>>,
+    /// Whether this is the scope of a block, loop body, match arm or `catch`, whose changes to the
+    /// bindings above it are handed back once it's done. See [`Env::in_nested_scope`].
+    nested: bool,
+    /// The names in `named` that are copies of bindings above this scope, changed by `+=` and the
+    /// like.
+    assigned: HashSet<Symbol>,
+    /// Changes to bindings above this scope whose copies here were defined over since.
+    overwritten: Vec<(Symbol, Val)>,
+}
+
+/// What an environment evaluates with besides names. Child scopes share their parent's, so
+/// entering a block or a call costs the same however much is set up here; changing it from a
+/// child copies it first, leaving the parent's as it was.
+#[derive(Debug, Clone)]
+struct Context {
+    clock: Arc<dyn Clock>,
+    rng: Arc<Rng>,
+    /// Set by [`Env::make_deterministic`].
+    deterministic: bool,
+    /// Only imports need to know which file they're in.
+    #[cfg(feature = "std")]
+    module: Arc<Module>,
+    /// Set by [`Env::allow_imports`].
+    #[cfg(feature = "std")]
+    imports: bool,
+    /// Set by [`Env::set_max_call_depth`].
+    max_call_depth: usize,
+    debug: Option<Arc<Session>>,
+    observers: Arc<[Arc<dyn EvalObserver>]>,
+    profiler: Option<Arc<Profiler>>,
+    /// Set while [`crate::Interpreter::eval_async`] is evaluating in this environment.
+    #[cfg(feature = "os")]
+    bridge: Option<Arc<Bridge>>,
+}
+
+impl Default for Env<'_> {
+    fn default() -> Self {
+        #[cfg(feature = "os")]
+        let clock = crate::clock::SystemClock::new();
+        #[cfg(not(feature = "os"))]
+        let clock = crate::clock::ManualClock::default();
+
+        Self::with_clock(Arc::new(clock))
+    }
 }
 
 impl<'parent> Env<'parent> {
-    pub(crate) fn get_binding(&self, name: &str) -> Result<Val, String> {
+    /// Creates an environment with the standard builtins, reading time from `clock`.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        let mut env = Self {
+            named: HashMap::new(),
+            consts: HashSet::new(),
+            docs: HashMap::new(),
+            parent: None,
+            context: Arc::new(Context {
+                clock,
+                rng: Arc::new(Rng::unseeded()),
+                deterministic: false,
+                #[cfg(feature = "std")]
+                module: Arc::default(),
+                #[cfg(feature = "std")]
+                imports: false,
+                max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+                debug: None,
+                observers: Arc::new([]),
+                profiler: None,
+                #[cfg(feature = "os")]
+                bridge: None,
+            }),
+            call: None,
+            nested: false,
+            assigned: HashSet::new(),
+            overwritten: Vec::new(),
+        };
+        builtins::install(&mut env);
+        env
+    }
+
+    /// Makes the `sleep_ms` builtin available, which is off by default so that scripts can't
+    /// block the host unless it has opted in.
+    pub fn allow_sleep(&mut self) {
+        builtins::install_sleep(self);
+    }
+
+    /// Makes the `read_file` and `write_file` builtins available, which are off by default so
+    /// that scripts can't touch the host's files unless it has opted in. Relative paths are
+    /// taken from the host's working directory. This needs the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn allow_files(&mut self) {
+        builtins::install_files(self);
+    }
+
+    /// Lets `import` read and evaluate files, which it can't by default so that scripts can't
+    /// reach outside of what the host gave them. Imports are resolved relative to the file set
+    /// with [`Env::set_current_file`]. This needs the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn allow_imports(&mut self) {
+        self.context_mut().imports = true;
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn imports_allowed(&self) -> bool {
+        self.context.imports
+    }
+
+    /// Makes the `env_var` builtin available, which is off by default so that scripts can't read
+    /// the host's environment, which often holds secrets, unless it has opted in. This needs the
+    /// `std` feature.
+    #[cfg(feature = "std")]
+    pub fn allow_env_vars(&mut self) {
+        builtins::install_env_vars(self);
+    }
+
+    /// Makes evaluation reproducible, so that running the same programs gives the same results
+    /// every time, down to the last bit. The `random` builtin is seeded with `seed`, the clock is
+    /// replaced with a [`ManualClock`] that starts at zero and natives whose results can't be
+    /// reproduced, such as async ones, fail when called.
+    ///
+    /// Everything else already is reproducible: numbers are whole, and records and the listings
+    /// of an environment always go by name.
+    pub fn make_deterministic(&mut self, seed: u64) {
+        let context = self.context_mut();
+        context.clock = Arc::new(ManualClock::default());
+        context.rng = Arc::new(Rng::seeded(seed));
+        context.deterministic = true;
+    }
+
+    /// Limits how many calls can be in progress at once, past which a call fails with
+    /// [`RuntimeError::CallDepth`] instead of overflowing the native stack, which would abort the
+    /// host. The default of 200 suits the main thread; lower it when evaluating on threads with
+    /// small stacks, or raise it on threads spawned with large ones.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.context_mut().max_call_depth = depth;
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        self.context.deterministic
+    }
+
+    /// Pauses before each statement evaluated from now on, handing control to `debugger`, until
+    /// [`Env::remove_debugger`] is called.
+    pub fn set_debugger(&mut self, debugger: impl Debugger + 'static) {
+        self.context_mut().debug = Some(Arc::new(Session::new(Box::new(debugger))));
+    }
+
+    pub fn remove_debugger(&mut self) {
+        self.context_mut().debug = None;
+    }
+
+    /// Tells `observer` about everything evaluated in this environment from now on, along with
+    /// any observers added before it.
+    pub fn add_observer(&mut self, observer: Arc<dyn EvalObserver>) {
+        let context = self.context_mut();
+        context.observers = context
+            .observers
+            .iter()
+            .cloned()
+            .chain(Some(observer))
+            .collect();
+    }
+
+    /// Starts counting how often each function is called and how long the calls take, for
+    /// [`Env::profile`] to report.
+    pub fn enable_profiling(&mut self) {
+        self.context_mut().profiler = Some(Arc::default());
+    }
+
+    /// Every function called since profiling was enabled, those that took longest first. This
+    /// is empty if profiling was never enabled.
+    pub fn profile(&self) -> Vec<FuncProfile> {
+        self.context
+            .profiler
+            .as_ref()
+            .map_or_else(Vec::new, |profiler| profiler.report())
+    }
+
+    /// Makes `name` call `func`, a host function. Calling it with the wrong number of arguments
+    /// fails before `func` is reached, so it can rely on getting `arity` of them.
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        func: impl Fn(&[Val]) -> Result<Val, RuntimeError> + Send + Sync + 'static,
+    ) {
+        self.store_native(Native::new_raising(name, arity, move |_, args| func(args)));
+    }
+
+    /// Makes `name` call `func`, a host function returning a future, such as one that does HTTP
+    /// or database I/O. Async natives can only be called from code evaluated by
+    /// [`crate::Interpreter::eval_async`], which awaits their futures.
+    pub fn define_async_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        func: impl Fn(&[Val]) -> NativeFuture + Send + Sync + 'static,
+    ) {
+        let callee = name.to_string();
+
+        self.store_native(
+            Native::new(name, arity, move |env, args| {
+                #[cfg(feature = "os")]
+                if let Some(bridge) = &env.context.bridge {
+                    return bridge.wait_for(func(args));
+                }
+                // Without the `os` feature there's no `eval_async` to await them with.
+                #[cfg(not(feature = "os"))]
+                let _ = (env, args, &func);
+
+                Err(format!(
+                    "'{}' is async, so it can only be called through Interpreter::eval_async",
+                    callee
+                ))
+            })
+            .nondeterministic(),
+        );
+    }
+
+    /// Sets the file that this environment's code comes from, so that imports are resolved
+    /// relative to it. This needs the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn set_current_file(&mut self, path: impl Into<PathBuf>) {
+        self.context_mut().module = Arc::new(Module::new(path.into(), None));
+    }
+
+    /// Evaluates the file at `path` directly in this environment, as if it had been typed in.
+    /// Imports inside the file are resolved relative to it. This needs the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<Val, RuntimeError> {
+        let path = path.as_ref();
+
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("cannot load '{}': {}", path.display(), e))?;
+        let program = crate::parse(&source).map_err(|e| {
+            let (line, _) = e.line_col(&source);
+            format!("in '{}' at line {}: {}", path.display(), line, e)
+        })?;
+
+        let loaded = Arc::new(Module::new(path.to_path_buf(), Some(self.module())));
+        let name = loaded.name().cloned();
+        let previous = mem::replace(&mut self.context_mut().module, loaded);
+        let result = program.eval(self);
+        self.context_mut().module = previous;
+
+        result.map_err(|e| e.in_source(program.shared_source(), name.as_ref()))
+    }
+
+    /// Binds `name` to `val` as a `let` would, such as to give a program its inputs before
+    /// evaluating it. Fails if `name` is a constant.
+    pub fn set_binding(&mut self, name: &str, val: Val) -> Result<(), RuntimeError> {
+        self.define_binding(Symbol::intern(name), val, false)
+    }
+
+    /// The bindings visible from this environment, sorted by name.
+    pub fn bindings(&self) -> Vec<(&str, &Val)> {
+        self.visible()
+            .into_iter()
+            .filter_map(|(name, info)| match info {
+                NamedInfo::Binding(val) => Some((name, val)),
+                NamedInfo::Func(_) => None,
+            })
+            .collect()
+    }
+
+    /// The user-defined functions visible from this environment along with their parameter
+    /// names, sorted by name.
+    pub fn funcs(&self) -> Vec<(&str, Vec<&str>)> {
+        self.visible()
+            .into_iter()
+            .filter_map(|(name, info)| match info {
+                NamedInfo::Func(Func::User { params, .. }) => Some((
+                    name,
+                    params.iter().map(|param| param.name.as_str()).collect(),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The `##` doc comment written above the definition of `name`, as it is visible from this
+    /// environment.
+    pub fn doc(&self, name: &str) -> Option<&str> {
+        let name = Symbol::intern(name);
+        let mut scope = self;
+
+        while !scope.named.contains_key(&name) {
+            scope = scope.parent?;
+        }
+
+        scope.docs.get(&name).map(String::as_str)
+    }
+
+    /// The source text of the `fn` definition that `name` refers to as it is visible from this
+    /// environment, exactly as it was written. Builtins and functions that weren't parsed from
+    /// source have none.
+    pub fn func_source(&self, name: &str) -> Option<&str> {
+        let name = Symbol::intern(name);
+        let mut scope = self;
+
+        loop {
+            match scope.named.get(&name) {
+                Some(NamedInfo::Func(Func::User {
+                    source: Some(definition),
+                    ..
+                })) => return Some(&definition.source[definition.span.0.clone()]),
+                Some(_) => return None,
+                None => scope = scope.parent?,
+            }
+        }
+    }
+
+    /// Every name visible from this environment, including builtins, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        self.visible().into_keys().collect()
+    }
+
+    /// Writes out everything visible from this environment as source text, which
+    /// [`Env::restore`] evaluates to define it all again: enums first, then functions, then
+    /// bindings, each with its doc comment. This is how a long REPL session or a precomputed
+    /// prelude is saved to a file.
+    ///
+    /// Natives aren't written out, since they come from the host. Enums don't keep the names of
+    /// their variants' fields or the order of their variants, so the fields are written as
+    /// `field1`, `field2` and so on, and the variants in order of name. Functions don't capture
+    /// the scope they were defined in, so there are no captured values to save along with them.
+    pub fn snapshot(&self) -> String {
+        let mut defined = BTreeMap::new();
+        let mut scope = Some(self);
+
+        while let Some(current) = scope {
+            for (name, info) in &current.named {
+                defined
+                    .entry(name.as_str())
+                    .or_insert((*name, info, current));
+            }
+
+            scope = current.parent;
+        }
+
+        let mut enums: BTreeMap<&str, EnumDef> = BTreeMap::new();
+        let mut funcs = Vec::new();
+        let mut bindings = Vec::new();
+
+        for (name, info, scope) in defined.into_values() {
+            let doc = scope.docs.get(&name).cloned();
+
+            let variant = match info {
+                NamedInfo::Func(Func::Constructor { ty, arity, .. }) => Some((
+                    *ty,
+                    (1..=*arity)
+                        .map(|idx| Symbol::intern(&format!("field{}", idx)))
+                        .collect(),
+                )),
+                NamedInfo::Binding(Val::Variant {
+                    ty,
+                    name: variant,
+                    fields,
+                }) if *variant == name && fields.is_empty() => Some((*ty, Vec::new())),
+                _ => None,
+            };
+
+            if let Some((ty, fields)) = variant {
+                enums
+                    .entry(ty.as_str())
+                    .or_insert_with(|| EnumDef {
+                        name: ty,
+                        variants: Vec::new(),
+                        span: Span::default(),
+                    })
+                    .variants
+                    .push(Variant { name, fields });
+                continue;
+            }
+
+            match info {
+                NamedInfo::Func(Func::User { params, body, .. }) => {
+                    let func_def = Stmt::FuncDef(FuncDef {
+                        name,
+                        params: params.to_vec(),
+                        body: Box::new(body.as_ref().clone()),
+                        span: Span::default(),
+                        doc: None,
+                        source: None,
+                    });
+                    funcs.push((doc, crate::fmt::stmt(&func_def)));
+                }
+                NamedInfo::Func(_) => {}
+                NamedInfo::Binding(val) => {
+                    let keyword = if scope.consts.contains(&name) {
+                        "const"
+                    } else {
+                        "let"
+                    };
+                    bindings.push((doc, format!("{} {} = {}", keyword, name, val)));
+                }
+            }
+        }
+
+        let enums = enums
+            .into_values()
+            .map(|enum_def| (None, crate::fmt::stmt(&Stmt::EnumDef(enum_def))));
+
+        let mut out = String::new();
+        for (doc, stmt) in enums.chain(funcs).chain(bindings) {
+            for line in doc.iter().flat_map(|doc| doc.lines()) {
+                out += &format!("## {}\n", line);
+            }
+            out += &stmt;
+            out.push('\n');
+        }
+
+        out
+    }
+
+    /// Evaluates a snapshot written by [`Env::snapshot`] in this environment, defining everything
+    /// in it again.
+    pub fn restore(&mut self, snapshot: &str) -> Result<(), RuntimeError> {
+        let program = crate::parse(snapshot).map_err(|e| {
+            let (line, _) = e.line_col(snapshot);
+            format!("in snapshot at line {}: {}", line, e)
+        })?;
+
+        program.eval(self).map(|_| ())
+    }
+
+    fn visible(&self) -> BTreeMap<&str, &NamedInfo> {
+        let mut visible = BTreeMap::new();
+        let mut scope = Some(self);
+
+        while let Some(current) = scope {
+            for (name, info) in &current.named {
+                visible.entry(name.as_str()).or_insert(info);
+            }
+
+            scope = current.parent;
+        }
+
+        visible
+    }
+
+    pub(crate) fn get_binding(&self, name: Symbol) -> Result<Val, RuntimeError> {
         self.get_named_info(name)
             .and_then(NamedInfo::into_binding)
-            .ok_or_else(|| format!("binding with name '{}' does not exist", name))
+            .ok_or_else(|| format!("binding with name '{}' does not exist", name).into())
     }
 
-    pub(crate) fn get_func(&self, name: &str) -> Result<(Vec<String>, Stmt), String> {
+    pub(crate) fn get_func(&self, name: Symbol) -> Result<Func, RuntimeError> {
         self.get_named_info(name)
             .and_then(NamedInfo::into_func)
-            .ok_or_else(|| format!("function with name '{}' does not exist", name))
+            .ok_or_else(|| format!("function with name '{}' does not exist", name).into())
+    }
+
+    /// Looks up a function along with the scope that defines it, which is the scope that calls to
+    /// it should be evaluated in.
+    ///
+    /// Call scopes are children of the defining scope rather than the caller's, so the chain stays
+    /// as deep as the source is nested, no matter how deep the calls go.
+    pub(crate) fn get_func_and_scope(&self, name: Symbol) -> Result<(Func, &Self), RuntimeError> {
+        self.find_func_and_scope(name)
+            .map(|(func, scope)| (func.clone(), scope))
     }
 
-    fn get_named_info(&self, name: &str) -> Option<NamedInfo> {
+    /// Like [`Env::get_func_and_scope`], but without copying the function.
+    pub(crate) fn find_func_and_scope(&self, name: Symbol) -> Result<(&Func, &Self), RuntimeError> {
+        let mut scope = self;
+
+        loop {
+            match scope.named.get(&name) {
+                Some(NamedInfo::Func(func)) => return Ok((func, scope)),
+                Some(NamedInfo::Binding(_)) => break,
+                None => match scope.parent {
+                    Some(parent) => scope = parent,
+                    None => break,
+                },
+            }
+        }
+
+        Err(format!("function with name '{}' does not exist", name).into())
+    }
+
+    /// Like [`Env::get_binding`], but without copying the value or making an error when there is
+    /// no such binding, which is the usual case when checking whether a callee is a binding.
+    pub(crate) fn find_binding(&self, name: Symbol) -> Option<&Val> {
+        let mut scope = self;
+
+        loop {
+            match scope.named.get(&name) {
+                Some(NamedInfo::Binding(val)) => return Some(val),
+                Some(NamedInfo::Func(_)) => return None,
+                None => scope = scope.parent?,
+            }
+        }
+    }
+
+    /// The top-level scope that this one is inside, or this one if it is at the top level.
+    pub(crate) fn root(&self) -> &Self {
+        self.parent.map_or(self, Env::root)
+    }
+
+    fn get_named_info(&self, name: Symbol) -> Option<NamedInfo> {
         self.named
-            .get(name)
+            .get(&name)
             .cloned()
             .or_else(|| self.parent.and_then(|parent| parent.get_named_info(name)))
     }
 
-    pub(crate) fn create_child(&'parent self) -> Self {
+    pub(crate) fn clock(&self) -> &dyn Clock {
+        self.context.clock.as_ref()
+    }
+
+    pub(crate) fn rng(&self) -> &Rng {
+        &self.context.rng
+    }
+
+    pub(crate) fn debug_session(&self) -> Option<&Session> {
+        self.context.debug.as_deref()
+    }
+
+    /// Calls `event` with each observer, in the order they were added.
+    pub(crate) fn notify(&self, event: impl Fn(&dyn EvalObserver)) {
+        for observer in self.context.observers.iter() {
+            event(observer.as_ref());
+        }
+    }
+
+    #[cfg(feature = "os")]
+    pub(crate) fn set_bridge(&mut self, bridge: Option<Arc<Bridge>>) {
+        self.context_mut().bridge = bridge;
+    }
+
+    pub(crate) fn profiler(&self) -> Option<&Arc<Profiler>> {
+        self.context.profiler.as_ref()
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn module(&self) -> Arc<Module> {
+        Arc::clone(&self.context.module)
+    }
+
+    /// The context to change, copied first if any other scope shares it.
+    fn context_mut(&mut self) -> &mut Context {
+        Arc::make_mut(&mut self.context)
+    }
+
+    /// The names of the functions being called, innermost first.
+    pub(crate) fn call_stack(&self) -> Vec<String> {
+        let mut stack = Vec::new();
+        let mut call = self.call.as_deref();
+
+        while let Some(current) = call {
+            stack.push(current.callee.to_string());
+            call = current.caller.as_deref();
+        }
+
+        stack
+    }
+
+    /// Marks this environment as the scope of a call to `callee` made from `caller` at `span`,
+    /// failing if that would be more calls at once than [`Env::set_max_call_depth`] allows, or if
+    /// the evaluation has been cancelled.
+    pub(crate) fn enter_call(
+        &mut self,
+        callee: Symbol,
+        caller: &Env,
+        span: &Span,
+    ) -> Result<(), RuntimeError> {
+        #[cfg(feature = "os")]
+        if caller
+            .context
+            .bridge
+            .as_ref()
+            .is_some_and(|bridge| bridge.is_cancelled())
+        {
+            return Err(RuntimeError::Message(crate::bridge::CANCELLED.to_string()));
+        }
+
+        let depth = caller.call.as_ref().map_or(0, |call| call.depth) + 1;
+        if depth > caller.context.max_call_depth {
+            return Err(RuntimeError::CallDepth {
+                span: span.0.clone(),
+            });
+        }
+
+        self.call = Some(Arc::new(Call {
+            callee,
+            caller: caller.call.clone(),
+            depth,
+        }));
+        Ok(())
+    }
+
+    /// Creates an empty scope that can see everything defined here. What is defined in the child
+    /// stays there. This takes constant time: nothing is copied until the child changes its
+    /// debugger, observers or the like.
+    pub fn create_child(&'parent self) -> Self {
         Self {
             named: HashMap::new(),
+            consts: HashSet::new(),
+            docs: HashMap::new(),
             parent: Some(self),
+            context: Arc::clone(&self.context),
+            call: self.call.clone(),
+            nested: false,
+            assigned: HashSet::new(),
+            overwritten: Vec::new(),
+        }
+    }
+
+    /// Creates an environment for evaluating an imported module, which shares nothing with this
+    /// one except for natives.
+    #[cfg(feature = "std")]
+    pub(crate) fn create_module(&self, module: Module) -> Env<'static> {
+        let mut env = Env {
+            named: HashMap::new(),
+            consts: HashSet::new(),
+            docs: HashMap::new(),
+            parent: None,
+            context: Arc::new(Context {
+                module: Arc::new(module),
+                ..Context::clone(&self.context)
+            }),
+            call: self.call.clone(),
+            nested: false,
+            assigned: HashSet::new(),
+            overwritten: Vec::new(),
+        };
+
+        for (name, info) in self.visible() {
+            if let NamedInfo::Func(Func::Native(_)) = info {
+                env.named.insert(Symbol::intern(name), info.clone());
+            }
+        }
+
+        env
+    }
+
+    /// Moves everything a module defined into this environment.
+    #[cfg(feature = "std")]
+    pub(crate) fn expose(&mut self, module_env: Env) {
+        for (name, info) in module_env.named {
+            if !matches!(info, NamedInfo::Func(Func::Native(_))) {
+                self.keep_assigned(name);
+                self.forget_doc(name);
+                self.named.insert(name, info);
+            }
+        }
+
+        self.consts.extend(module_env.consts);
+        self.docs.extend(module_env.docs);
+    }
+
+    /// Fails if this scope has a constant called `name`. Scopes below it can still define their
+    /// own `name`.
+    pub(crate) fn check_redefinable(&self, name: Symbol) -> Result<(), RuntimeError> {
+        if self.consts.contains(&name) {
+            Err(format!("cannot redefine constant '{}'", name).into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Binds `name` as a `let` or `const` would, failing if that would redefine a constant.
+    pub(crate) fn define_binding(
+        &mut self,
+        name: Symbol,
+        val: Val,
+        constant: bool,
+    ) -> Result<(), RuntimeError> {
+        self.check_redefinable(name)?;
+
+        if constant {
+            self.consts.insert(name);
+        }
+
+        self.store_binding(name, val);
+        Ok(())
+    }
+
+    pub(crate) fn store_binding(&mut self, name: Symbol, val: Val) {
+        self.notify(|observer| observer.on_binding_created(name.as_str(), &val));
+        self.keep_assigned(name);
+        self.forget_doc(name);
+        self.named.insert(name, NamedInfo::Binding(val));
+    }
+
+    #[cfg(test)]
+    pub(crate) fn store_func(
+        &mut self,
+        name: Symbol,
+        params: &[Param],
+        body: impl Into<Arc<Stmt>>,
+    ) {
+        let func = Func::User {
+            params: params.into(),
+            body: body.into(),
+            compiled: None,
+            source: None,
+        };
+        self.insert_func(name, func);
+    }
+
+    /// Defines `func` as `name`, documented with `doc` if there is one, failing if that would
+    /// redefine a constant.
+    pub(crate) fn define_func(
+        &mut self,
+        name: Symbol,
+        func: Func,
+        doc: Option<&str>,
+    ) -> Result<(), RuntimeError> {
+        self.check_redefinable(name)?;
+        #[cfg(feature = "std")]
+        let func = func.defined_in(&self.context.module);
+        self.insert_func(name, func);
+        if let Some(doc) = doc {
+            self.set_doc(name, doc);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn insert_func(&mut self, name: Symbol, func: Func) {
+        self.keep_assigned(name);
+        self.forget_doc(name);
+        self.named.insert(name, NamedInfo::Func(func));
+    }
+
+    /// Changes the binding `name` to `update` of its value, as `+=` and the like do, failing if it
+    /// is a constant or there is no such binding.
+    ///
+    /// Only the bindings of this scope and of the scopes it is nested in with
+    /// [`Env::in_nested_scope`] can be changed. A function can't change anything outside of its
+    /// own scope, and neither can a [`Env::create_child`] change its parent, which may be shared.
+    pub(crate) fn assign(
+        &mut self,
+        name: Symbol,
+        update: impl FnOnce(Val) -> Result<Val, RuntimeError>,
+    ) -> Result<(), RuntimeError> {
+        let mut scope: &Self = self;
+
+        let val = loop {
+            match scope.named.get(&name) {
+                Some(NamedInfo::Binding(_)) if scope.consts.contains(&name) => {
+                    return Err(format!("cannot assign to constant '{}'", name).into())
+                }
+                Some(NamedInfo::Binding(val)) => break val.clone(),
+                Some(NamedInfo::Func(_)) => {
+                    return Err(format!("cannot assign to function '{}'", name).into())
+                }
+                None => match scope.parent {
+                    Some(parent) if scope.nested => scope = parent,
+                    Some(parent) if parent.find_binding(name).is_some() => {
+                        let message = if scope.call.is_some() {
+                            format!("cannot assign to '{}' outside of the function", name)
+                        } else {
+                            format!("cannot assign to '{}' of a parent environment", name)
+                        };
+                        return Err(message.into());
+                    }
+                    _ => return Err(format!("binding with name '{}' does not exist", name).into()),
+                },
+            }
+        };
+
+        let val = update(val)?;
+        self.store_assigned(name, val);
+        Ok(())
+    }
+
+    /// Evaluates `eval` in a new scope for a block, loop body, match arm or `catch`, in which `+=`
+    /// and the like can change the bindings of this scope. Scopes below only have a shared
+    /// reference to this one, so they change copies, which are handed back here once `eval` is
+    /// done, whether or not it failed.
+    pub(crate) fn in_nested_scope<T>(&mut self, eval: impl FnOnce(&mut Env) -> T) -> T {
+        let mut scope = self.create_child();
+        scope.nested = true;
+        let result = eval(&mut scope);
+
+        let mut named = scope.named;
+        let changes = scope
+            .overwritten
+            .into_iter()
+            .chain(
+                scope
+                    .assigned
+                    .into_iter()
+                    .filter_map(|name| match named.remove(&name) {
+                        Some(NamedInfo::Binding(val)) => Some((name, val)),
+                        _ => None,
+                    }),
+            );
+        for (name, val) in changes.collect::<Vec<_>>() {
+            self.store_assigned(name, val);
         }
+
+        result
     }
 
-    pub(crate) fn store_binding(&mut self, name: String, val: Val) {
+    /// Stores a new value for a binding that [`Env::assign`] found here or above.
+    fn store_assigned(&mut self, name: Symbol, val: Val) {
+        if !self.named.contains_key(&name) {
+            self.assigned.insert(name);
+        }
         self.named.insert(name, NamedInfo::Binding(val));
     }
 
-    pub(crate) fn store_func(&mut self, name: String, params: Vec<String>, body: Stmt) {
-        self.named.insert(name, NamedInfo::Func { params, body });
+    /// Keeps the change to a binding above this scope when `name` is being defined over it here.
+    fn keep_assigned(&mut self, name: Symbol) {
+        if self.assigned.remove(&name) {
+            if let Some(NamedInfo::Binding(val)) = self.named.remove(&name) {
+                self.overwritten.push((name, val));
+            }
+        }
+    }
+
+    /// Documents `name`, which this scope has just defined.
+    pub(crate) fn set_doc(&mut self, name: Symbol, doc: &str) {
+        self.docs.insert(name, doc.to_string());
+    }
+
+    /// Drops the doc comment of `name` when it's being defined again.
+    fn forget_doc(&mut self, name: Symbol) {
+        if !self.docs.is_empty() {
+            self.docs.remove(&name);
+        }
+    }
+
+    /// Makes every name this scope has defined so far a constant, so that code evaluated in it
+    /// can't define them again.
+    pub(crate) fn make_read_only(&mut self) {
+        self.consts.extend(self.named.keys().copied());
+    }
+
+    pub(crate) fn store_constructor(&mut self, ty: Symbol, variant: Symbol, arity: usize) {
+        let func = Func::Constructor { ty, variant, arity };
+        self.named.insert(variant, NamedInfo::Func(func));
+    }
+
+    pub(crate) fn store_native(&mut self, native: Native) {
+        self.named.insert(
+            Symbol::intern(native.name()),
+            NamedInfo::Func(Func::Native(native)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_bindings_and_funcs_through_scopes() {
+        let mut parent = Env::default();
+        parent.store_binding("b".into(), Val::Number(1.into()));
+        parent.store_binding("a".into(), Val::Number(2.into()));
+        parent.store_func(
+            "f".into(),
+            &["x".into()],
+            Stmt::Expr(crate::expr::Expr::Number(
+                crate::expr::Number(1.into()),
+                Span::default(),
+            )),
+        );
+
+        let mut child = parent.create_child();
+        child.store_binding("a".into(), Val::Number(3.into()));
+
+        assert_eq!(
+            child.bindings(),
+            vec![("a", &Val::Number(3.into())), ("b", &Val::Number(1.into()))],
+        );
+        assert_eq!(child.funcs(), vec![("f", vec!["x"])]);
+        assert!(child.names().contains(&"now_ms"));
+    }
+
+    #[test]
+    fn find_scope_that_defines_func() {
+        let mut parent = Env::default();
+        parent.store_func(
+            "f".into(),
+            &[],
+            Stmt::Expr(crate::expr::Expr::Number(
+                crate::expr::Number(1.into()),
+                Span::default(),
+            )),
+        );
+
+        let mut child = parent.create_child();
+        child.store_binding("a".into(), Val::Number(1.into()));
+        let grandchild = child.create_child();
+
+        let (_, scope) = grandchild.get_func_and_scope("f".into()).unwrap();
+        assert!(std::ptr::eq(scope, &parent));
+    }
+
+    #[test]
+    fn child_shares_context_until_changing_it() {
+        let parent = Env::default();
+        let mut child = parent.create_child();
+        assert!(Arc::ptr_eq(&parent.context, &child.context));
+
+        child.make_deterministic(1);
+        assert!(child.is_deterministic());
+        assert!(!parent.is_deterministic());
+        assert!(child.create_child().is_deterministic());
+    }
+
+    #[test]
+    fn share_values_and_environments_across_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Env>();
+        assert_send_sync::<Val>();
+        assert_send_sync::<crate::Program>();
+        assert_send_sync::<crate::CompiledProgram>();
+        assert_send_sync::<crate::Interpreter>();
+
+        let mut prelude = Env::default();
+        crate::parse("let rate = 3\nfn scale x => x * rate")
+            .unwrap()
+            .eval(&mut prelude)
+            .unwrap();
+        let program = crate::parse("let rate = 100\nscale(input)").unwrap();
+
+        let results = std::thread::scope(|scope| {
+            let threads = (0..4)
+                .map(|input| {
+                    let (prelude, program) = (&prelude, &program);
+
+                    scope.spawn(move || {
+                        let mut env = prelude.create_child();
+                        env.set_binding("input", Val::Number(input.into())).unwrap();
+                        program.eval(&mut env)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        assert_eq!(
+            results,
+            (0..4)
+                .map(|input| Ok(Val::Number((input * 3).into())))
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(
+            prelude.get_binding("rate".into()),
+            Ok(Val::Number(3.into()))
+        );
+    }
+
+    #[test]
+    fn looking_up_func_shares_body() {
+        let mut env = Env::default();
+        env.store_func(
+            "f".into(),
+            &["x".into()],
+            Stmt::Expr(crate::expr::Expr::Number(
+                crate::expr::Number(1.into()),
+                Span::default(),
+            )),
+        );
+
+        match (env.get_func("f".into()), env.get_func("f".into())) {
+            (Ok(Func::User { body: a, .. }), Ok(Func::User { body: b, .. })) => {
+                assert!(Arc::ptr_eq(&a, &b));
+            }
+            funcs => panic!("expected two user functions, got {:?}", funcs),
+        }
+    }
+
+    #[test]
+    fn restore_snapshot_in_new_environment() {
+        let mut env = Env::default();
+        crate::parse(
+            "## Doubles x.
+## Works on any number.
+fn double x => x * 2
+enum Shape { Rect(w, h), Empty }
+const rate = 3
+let shapes = [Rect(1, 2), Empty]
+let twice = double(_)",
+        )
+        .unwrap()
+        .eval(&mut env)
+        .unwrap();
+
+        let snapshot = env.snapshot();
+        assert_eq!(
+            snapshot,
+            "enum Shape { Empty, Rect(field1, field2) }
+## Doubles x.
+## Works on any number.
+fn double x => x * 2
+const rate = 3
+let shapes = [Rect(1, 2), Empty]
+let twice = double(_)
+",
+        );
+
+        let mut restored = Env::default();
+        restored.restore(&snapshot).unwrap();
+
+        assert_eq!(restored.snapshot(), snapshot);
+        assert_eq!(
+            restored.doc("double"),
+            Some("Doubles x.\nWorks on any number.")
+        );
+        assert_eq!(
+            crate::parse("twice(rate)").unwrap().eval(&mut restored),
+            Ok(Val::Number(6.into())),
+        );
+        assert!(restored.restore("const rate = 4").is_err());
+    }
+
+    #[test]
+    fn restore_negative_and_nested_values() {
+        let mut env = Env::default();
+        crate::parse(
+            "fn add a b => a + b
+enum Shape { Rect(w, h) }
+let n = 0 - 5
+let nested = [-1, (2, [0 - 3]), {a: -4, b: Rect(-6, {c: -7})}]
+let range = -2..=-1
+let offset = add(-8, _)",
+        )
+        .unwrap()
+        .eval(&mut env)
+        .unwrap();
+
+        let snapshot = env.snapshot();
+        assert!(
+            snapshot.contains("let nested = [-1, (2, [-3]), {a: -4, b: Rect(-6, {c: -7})}]\n"),
+            "{}",
+            snapshot,
+        );
+
+        let mut restored = Env::default();
+        restored.restore(&snapshot).unwrap();
+
+        assert_eq!(restored.snapshot(), snapshot);
+        for name in ["n", "nested", "range"] {
+            assert_eq!(
+                restored.get_binding(Symbol::intern(name)),
+                env.get_binding(Symbol::intern(name)),
+            );
+        }
+        assert_eq!(
+            crate::parse("offset(n)").unwrap().eval(&mut restored),
+            Ok(Val::Number((-13).into())),
+        );
+    }
+
+    #[test]
+    fn report_invalid_snapshot() {
+        assert_eq!(
+            Env::default()
+                .restore("let a = 1\nlet = 2")
+                .unwrap_err()
+                .to_string(),
+            "in snapshot at line 2: expected statement",
+        );
     }
 }
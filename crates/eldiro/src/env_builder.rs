@@ -0,0 +1,169 @@
+//! Setting up the environment that an embedder hands to user scripts.
+
+use crate::compat::prelude::*;
+use crate::env::Env;
+use crate::error::RuntimeError;
+use crate::val::Val;
+
+/// Builds an [`Env`] that starts out with whatever the host provides: bindings, natives and
+/// preludes written in eldiro, on top of the standard builtins.
+///
+/// ```
+/// use eldiro::{EnvBuilder, Interpreter, Val};
+///
+/// let env = EnvBuilder::new()
+///     .binding("limit", Val::Number(10.into()))
+///     .native("pair", 2, |args| Ok(Val::Tuple(args.to_vec())))
+///     .prelude("fn clamp x => match x > limit { true => limit, _ => x }")
+///     .unwrap()
+///     .read_only()
+///     .build();
+///
+/// let mut interpreter = Interpreter::new().with_env(env);
+/// let program = eldiro::parse("pair(clamp(12), clamp(3))").unwrap();
+/// assert_eq!(interpreter.eval(&program).unwrap().to_string(), "(10, 3)");
+///
+/// let program = eldiro::parse("let limit = 100").unwrap();
+/// assert!(interpreter.eval(&program).is_err());
+/// ```
+#[derive(Debug, Default)]
+pub struct EnvBuilder {
+    env: Env<'static>,
+    read_only: bool,
+}
+
+impl EnvBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from `env` instead of a default environment, such as one with its own clock.
+    pub fn with_env(mut self, env: Env<'static>) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Binds `name` to `val`, replacing anything called `name` so far.
+    pub fn binding(mut self, name: &str, val: Val) -> Self {
+        self.env.store_binding(name.into(), val);
+        self
+    }
+
+    /// Makes `name` call `func`, as [`Env::define_native`] does.
+    pub fn native(
+        mut self,
+        name: &str,
+        arity: usize,
+        func: impl Fn(&[Val]) -> Result<Val, RuntimeError> + Send + Sync + 'static,
+    ) -> Self {
+        self.env.define_native(name, arity, func);
+        self
+    }
+
+    /// Evaluates `source`, keeping everything it defines. It can use what has been added before
+    /// it, but fails if it redefines any of it after [`EnvBuilder::read_only`].
+    pub fn prelude(mut self, source: &str) -> Result<Self, RuntimeError> {
+        let program = crate::parse(source).map_err(|e| {
+            let (line, _) = e.line_col(source);
+            format!("in prelude at line {}: {}", line, e)
+        })?;
+
+        if self.read_only {
+            self.env.make_read_only();
+        }
+        program.eval(&mut self.env)?;
+
+        Ok(self)
+    }
+
+    /// Makes every name in the environment a constant once it's built, builtins included, so that
+    /// scripts can't define them again and clobber what the host provides. Scripts still can in
+    /// their own blocks and functions, which only shadow the names there.
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    pub fn build(mut self) -> Env<'static> {
+        if self.read_only {
+            self.env.make_read_only();
+        }
+        self.env
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(env: &mut Env, s: &str) -> Result<Val, RuntimeError> {
+        crate::parse(s).unwrap().eval(env)
+    }
+
+    #[test]
+    fn build_with_bindings_natives_and_preludes() {
+        let mut env = EnvBuilder::new()
+            .binding("base", Val::Number(100.into()))
+            .native("offset", 1, |args| match &args[0] {
+                Val::Number(n) => Ok(Val::Number((n.to_i64().unwrap() + 1).into())),
+                _ => Err("offset expects a number".to_string().into()),
+            })
+            .prelude("## Adds the base.\nfn add_base x => offset(x) + base")
+            .unwrap()
+            .build();
+
+        assert_eq!(eval(&mut env, "add_base(1)"), Ok(Val::Number(102.into())));
+        assert_eq!(env.doc("add_base"), Some("Adds the base."));
+        assert_eq!(
+            eval(&mut env, "offset(true)"),
+            Err("offset expects a number".to_string().into()),
+        );
+
+        // Without `read_only`, scripts can replace what the host provided.
+        assert_eq!(
+            eval(&mut env, "let base = 1\nbase"),
+            Ok(Val::Number(1.into()))
+        );
+    }
+
+    #[test]
+    fn keep_scripts_from_redefining_read_only_names() {
+        let mut env = EnvBuilder::new()
+            .binding("base", Val::Number(100.into()))
+            .prelude("fn f x => x")
+            .unwrap()
+            .read_only()
+            .build();
+
+        for script in ["let base = 1", "fn f => 2", "fn len x => 0", "enum E { f }"] {
+            assert!(eval(&mut env, script).is_err(), "{}", script);
+        }
+        assert_eq!(
+            eval(&mut env, "{ let base = 1\nbase }"),
+            Ok(Val::Number(1.into()))
+        );
+        assert_eq!(eval(&mut env, "base"), Ok(Val::Number(100.into())));
+    }
+
+    #[test]
+    fn keep_later_preludes_from_redefining_read_only_names() {
+        let builder = EnvBuilder::new()
+            .binding("base", Val::Number(100.into()))
+            .read_only();
+
+        assert_eq!(
+            builder.prelude("let base = 1").unwrap_err(),
+            "cannot redefine constant 'base'".to_string().into(),
+        );
+    }
+
+    #[test]
+    fn report_parse_errors_in_preludes() {
+        assert_eq!(
+            EnvBuilder::new().prelude("let a = 1\nlet = 2").unwrap_err(),
+            "in prelude at line 2: expected statement"
+                .to_string()
+                .into(),
+        );
+    }
+}
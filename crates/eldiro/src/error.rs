@@ -0,0 +1,418 @@
+use crate::compat::prelude::*;
+use crate::expr::Op;
+use crate::num::Int;
+use crate::val::Val;
+use alloc::sync::Arc;
+use core::fmt;
+use core::ops::Range;
+
+/// An error encountered while parsing, pointing at the part of the source it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// The byte range of the offending text in the source that was parsed.
+    pub span: Range<usize>,
+}
+
+impl ParseError {
+    /// The 1-based line and column that this error starts at within `source`.
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.span.start];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+        let column = before[line_start..].chars().count() + 1;
+
+        (line, column)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// An error found by [`Program::check`](crate::Program::check) before a program runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeError {
+    pub message: String,
+    /// The byte range of the offending code, for the kinds of code that keep track of it.
+    pub span: Option<Range<usize>>,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// An error encountered while evaluating a program.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum RuntimeError {
+    /// An arithmetic operation whose result doesn't fit in a number.
+    Overflow {
+        op: Op,
+    },
+    DivisionByZero {
+        /// The byte range of the division in the source that was evaluated.
+        span: Range<usize>,
+    },
+    /// Raising to a negative power with `**`, whose result wouldn't be a whole number.
+    NegativeExponent {
+        /// The byte range of the operation in the source that was evaluated.
+        span: Range<usize>,
+    },
+    /// An ordering comparison such as `<` between values that have no order, like a number and a
+    /// string. Both sides are named by their type.
+    Incomparable {
+        lhs: &'static str,
+        rhs: &'static str,
+        /// The byte range of the comparison in the source that was evaluated.
+        span: Range<usize>,
+    },
+    /// `==` or `!=` with a function on either side, even one inside a list or record. Partial
+    /// applications made from the same function with the same arguments could be told apart or
+    /// not depending on how they were made, so functions don't have an equality at all.
+    FuncComparison {
+        /// The byte range of the comparison in the source that was evaluated.
+        span: Range<usize>,
+    },
+    /// Indexing with `[]` past the end of a list, tuple or string. `?[]` gives `nil` instead.
+    IndexOutOfRange {
+        index: Int,
+        len: usize,
+        /// The byte range of the indexing in the source that was evaluated.
+        span: Range<usize>,
+    },
+    /// Slicing a list or string with a range that isn't all there, such as `s[2..9]` on a
+    /// string of three characters or `s[2..1]`. `?[]` gives `nil` instead.
+    SliceOutOfRange {
+        start: Int,
+        end: Int,
+        inclusive: bool,
+        len: usize,
+        /// The byte range of the slicing in the source that was evaluated.
+        span: Range<usize>,
+    },
+    /// An arithmetic or bitwise operation on something other than two numbers, such as adding
+    /// the unit value to a number. Both sides are named by their type.
+    InvalidOperands {
+        op: Op,
+        lhs: &'static str,
+        rhs: &'static str,
+        /// The byte range of the operation in the source that was evaluated.
+        span: Range<usize>,
+    },
+    /// A call with more or fewer arguments than the function takes.
+    Arity {
+        callee: String,
+        /// The fewest arguments the function takes.
+        min: usize,
+        /// The most arguments the function takes, which is unlimited with a rest parameter.
+        max: Option<usize>,
+        got: usize,
+        /// The byte range of the call in the source that was evaluated.
+        span: Range<usize>,
+    },
+    /// A call made while too many calls were already in progress, such as by recursion that
+    /// goes too deep or never stops.
+    CallDepth {
+        /// The byte range of the call in the source that was evaluated.
+        span: Range<usize>,
+    },
+    /// A call to the `panic` builtin.
+    Panic {
+        message: String,
+        /// The names of the functions that were being called, innermost first.
+        stack: Vec<String>,
+    },
+    /// A value raised by `throw` that no `try` caught.
+    Thrown(Val),
+    /// Evaluation stopped by a [`Debugger`](crate::Debugger), which `try` doesn't catch.
+    Aborted,
+    /// A call to the `exit` builtin with the status the program asked to exit with. `try` doesn't
+    /// catch it, so it ends the whole program, and it's up to the host what exiting means.
+    Exit(u8),
+    Message(String),
+    /// An error whose span points into source other than what was being evaluated, such as the
+    /// body of a function that an earlier REPL input or an imported file defined.
+    Elsewhere {
+        error: Box<RuntimeError>,
+        /// The source text that the span of `error` points into.
+        source: Arc<str>,
+        /// The file that `source` was read from, if it was.
+        path: Option<Arc<str>>,
+    },
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Overflow { op } => {
+                let operation = match op {
+                    Op::Add => "addition",
+                    Op::Sub => "subtraction",
+                    Op::Mul => "multiplication",
+                    Op::Div => "division",
+                    Op::Rem => "remainder",
+                    Op::Pow => "exponentiation",
+                    Op::Shl => "left shift",
+                    Op::Shr => "right shift",
+                    _ => "operation",
+                };
+                write!(f, "integer overflow in {}", operation)
+            }
+            Self::DivisionByZero { .. } => write!(f, "division by zero"),
+            Self::NegativeExponent { .. } => write!(f, "cannot raise to a negative power"),
+            Self::Incomparable { lhs, rhs, .. } => write!(f, "cannot compare {} with {}", lhs, rhs),
+            Self::FuncComparison { .. } => write!(f, "cannot compare functions"),
+            Self::IndexOutOfRange { index, len, .. } => {
+                write!(f, "index {} is out of range for length {}", index, len)
+            }
+            Self::SliceOutOfRange {
+                start,
+                end,
+                inclusive,
+                len,
+                ..
+            } => write!(
+                f,
+                "slice {}{}{} is out of range for length {}",
+                start,
+                if *inclusive { "..=" } else { ".." },
+                end,
+                len
+            ),
+            Self::InvalidOperands { op, lhs, rhs, .. } => write!(
+                f,
+                "cannot apply {} to {} and {}",
+                crate::fmt::op_symbol(op),
+                lhs,
+                rhs
+            ),
+            Self::Arity {
+                callee,
+                min,
+                max,
+                got,
+                ..
+            } => {
+                let arguments = |n| if n == 1 { "argument" } else { "arguments" };
+
+                write!(f, "function `{}` expects ", callee)?;
+                match max {
+                    Some(max) if max == min => write!(f, "{} {}", max, arguments(*max))?,
+                    Some(max) => write!(f, "{} to {} {}", min, max, arguments(*max))?,
+                    None => write!(f, "at least {} {}", min, arguments(*min))?,
+                }
+                write!(f, " but got {}", got)
+            }
+            Self::CallDepth { .. } => write!(f, "maximum call depth exceeded"),
+            Self::Panic { message, stack } => {
+                write!(f, "panic: {}", message)?;
+
+                for callee in stack {
+                    write!(f, "\n    in {}", callee)?;
+                }
+
+                Ok(())
+            }
+            Self::Thrown(Val::Str(message)) => write!(f, "uncaught error: {}", message),
+            Self::Thrown(val) => write!(f, "uncaught error: {}", val),
+            Self::Aborted => write!(f, "aborted by debugger"),
+            Self::Exit(code) => write!(f, "exited with code {}", code),
+            Self::Message(message) => write!(f, "{}", message),
+            Self::Elsewhere { error, .. } => write!(f, "{}", error),
+        }
+    }
+}
+
+impl RuntimeError {
+    /// The byte range of the source that caused this error, if known.
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            Self::DivisionByZero { span }
+            | Self::NegativeExponent { span }
+            | Self::Incomparable { span, .. }
+            | Self::FuncComparison { span }
+            | Self::IndexOutOfRange { span, .. }
+            | Self::SliceOutOfRange { span, .. }
+            | Self::InvalidOperands { span, .. }
+            | Self::Arity { span, .. }
+            | Self::CallDepth { span } => Some(span.clone()),
+            Self::Elsewhere { error, .. } => error.span(),
+            _ => None,
+        }
+    }
+
+    /// The source text that [`RuntimeError::span`] points into, when it isn't the source that
+    /// was evaluated, along with the file it was read from if there was one.
+    pub fn source(&self) -> Option<(&str, Option<&str>)> {
+        match self {
+            Self::Elsewhere { source, path, .. } => Some((source, path.as_deref())),
+            _ => None,
+        }
+    }
+
+    /// Records that the span of this error points into `source`, unless it has no span or
+    /// already knows where its span points.
+    pub(crate) fn in_source(self, source: &Arc<str>, path: Option<&Arc<str>>) -> Self {
+        match self {
+            Self::Elsewhere { .. } => self,
+            error if error.span().is_some() => Self::Elsewhere {
+                error: Box::new(error),
+                source: Arc::clone(source),
+                path: path.cloned(),
+            },
+            error => error,
+        }
+    }
+
+    /// Forgets the source that the span of this error points into if it's `source`, since
+    /// whoever evaluated `source` already knows it.
+    pub(crate) fn relative_to(self, source: &Arc<str>) -> Self {
+        match self {
+            Self::Elsewhere {
+                error,
+                source: elsewhere,
+                ..
+            } if Arc::ptr_eq(&elsewhere, source) => *error,
+            error => error,
+        }
+    }
+}
+
+impl From<String> for RuntimeError {
+    fn from(message: String) -> Self {
+        Self::Message(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_col_of_span() {
+        let error = ParseError {
+            message: "oops".to_string(),
+            span: 10..11,
+        };
+
+        assert_eq!(error.line_col("let a = 1\nlet = 2"), (2, 1));
+    }
+
+    #[test]
+    fn display_overflow() {
+        assert_eq!(
+            RuntimeError::Overflow { op: Op::Mul }.to_string(),
+            "integer overflow in multiplication",
+        );
+    }
+
+    #[test]
+    fn display_invalid_operands() {
+        assert_eq!(
+            RuntimeError::InvalidOperands {
+                op: Op::Add,
+                lhs: "unit",
+                rhs: "number",
+                span: 0..1,
+            }
+            .to_string(),
+            "cannot apply + to unit and number",
+        );
+    }
+
+    #[test]
+    fn display_panic() {
+        assert_eq!(
+            RuntimeError::Panic {
+                message: "bad input".to_string(),
+                stack: vec!["check".to_string(), "main".to_string()],
+            }
+            .to_string(),
+            "panic: bad input\n    in check\n    in main",
+        );
+    }
+
+    #[test]
+    fn display_thrown() {
+        assert_eq!(
+            RuntimeError::Thrown(Val::Str("bad".to_string())).to_string(),
+            "uncaught error: bad",
+        );
+        assert_eq!(
+            RuntimeError::Thrown(Val::List(vec![Val::Bool(true)])).to_string(),
+            "uncaught error: [true]",
+        );
+    }
+
+    #[test]
+    fn display_arity() {
+        let arity = |min, max, got| {
+            RuntimeError::Arity {
+                callee: "f".to_string(),
+                min,
+                max,
+                got,
+                span: 0..0,
+            }
+            .to_string()
+        };
+
+        assert_eq!(
+            arity(2, Some(2), 1),
+            "function `f` expects 2 arguments but got 1"
+        );
+        assert_eq!(
+            arity(1, Some(1), 0),
+            "function `f` expects 1 argument but got 0"
+        );
+        assert_eq!(
+            arity(0, Some(2), 3),
+            "function `f` expects 0 to 2 arguments but got 3"
+        );
+        assert_eq!(
+            arity(1, None, 0),
+            "function `f` expects at least 1 argument but got 0"
+        );
+    }
+
+    #[test]
+    fn display_slice_out_of_range() {
+        assert_eq!(
+            RuntimeError::SliceOutOfRange {
+                start: 1.into(),
+                end: 4.into(),
+                inclusive: false,
+                len: 3,
+                span: 0..1,
+            }
+            .to_string(),
+            "slice 1..4 is out of range for length 3",
+        );
+    }
+
+    #[test]
+    fn display_incomparable() {
+        assert_eq!(
+            RuntimeError::Incomparable {
+                lhs: "list",
+                rhs: "bool",
+                span: 0..1,
+            }
+            .to_string(),
+            "cannot compare list with bool",
+        );
+    }
+
+    #[test]
+    fn display_func_comparison() {
+        assert_eq!(
+            RuntimeError::FuncComparison { span: 0..1 }.to_string(),
+            "cannot compare functions",
+        );
+    }
+}
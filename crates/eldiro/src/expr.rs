@@ -1,125 +1,827 @@
 mod binding_usage;
 mod block;
+mod for_loop;
 mod func_call;
+mod match_expr;
+mod try_catch;
 
+use crate::compat::prelude::*;
 use crate::env::Env;
+use crate::error::RuntimeError;
+use crate::lexer::{Token, TokenKind};
+use crate::num::Int;
+use crate::span::Span;
+use crate::symbol::Symbol;
 use crate::utils;
 use crate::val::Val;
-pub(crate) use binding_usage::BindingUsage;
-pub(crate) use block::Block;
-pub(crate) use func_call::FuncCall;
+pub use binding_usage::BindingUsage;
+pub use block::Block;
+use core::cmp::Ordering;
+use core::ops::Range;
+pub use for_loop::For;
+pub(crate) use func_call::call_partial;
+pub use func_call::{CallStyle, FuncCall};
+pub(crate) use match_expr::no_match;
+pub use match_expr::{Arm, Match, Pattern};
+pub(crate) use try_catch::caught;
+pub use try_catch::TryCatch;
+/// A number literal.
 #[derive(PartialEq, Eq, Debug, Clone)]
-pub(crate) struct Number(pub(crate) i32);
+pub struct Number(pub Int);
 
 impl Number {
-    fn new(s: &str) -> Result<(&str, Self), String> {
-        let (s, number) = utils::extract_digits(s)?;
-        Ok((s, Self(number.parse().unwrap())))
+    pub(crate) fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        match tokens.split_first() {
+            Some((
+                Token {
+                    kind: TokenKind::Number(digits),
+                    ..
+                },
+                tokens,
+            )) => Int::parse(digits).map(|n| (tokens, Self(n))),
+            _ => Err("expected digits".to_string()),
+        }
     }
 }
 
+/// A binary operator.
 #[derive(PartialEq, Debug, Eq, Clone)]
-pub(crate) enum Op {
+#[non_exhaustive]
+pub enum Op {
     Add,
     Sub,
     Mul,
     Div,
+    /// `a % b`, the remainder of dividing `a` by `b`, which has the sign of `a`.
+    Rem,
+    /// `a ** b`, `a` raised to the power `b`, which can't be negative.
+    Pow,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    /// `a ?? b`, which is `b` if `a` is nil and `a` otherwise. `b` is only evaluated if needed.
+    Coalesce,
+    /// `a..b`, the numbers from `a` up to but not including `b`.
+    Range,
+    /// `a..=b`, the numbers from `a` up to and including `b`.
+    RangeInclusive,
 }
 
 impl Op {
-    fn new(s: &str) -> Result<(&str, Self), String> {
-        utils::tag("+", s)
-            .map(|s| (s, Self::Add))
-            .or_else(|_| utils::tag("-", s).map(|s| (s, Self::Sub)))
-            .or_else(|_| utils::tag("*", s).map(|s| (s, Self::Mul)))
-            .or_else(|_| utils::tag("/", s).map(|s| (s, Self::Div)))
+    fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        match tokens.split_first() {
+            Some((token, tokens)) => Self::from_token(&token.kind)
+                .map(|op| (tokens, op))
+                .ok_or_else(|| "expected operator".to_string()),
+            None => Err("expected operator".to_string()),
+        }
+    }
+
+    pub(crate) fn from_token(kind: &TokenKind) -> Option<Self> {
+        let op = match kind {
+            TokenKind::Plus => Self::Add,
+            TokenKind::Minus => Self::Sub,
+            TokenKind::Star => Self::Mul,
+            TokenKind::Slash => Self::Div,
+            TokenKind::Percent => Self::Rem,
+            TokenKind::StarStar => Self::Pow,
+            TokenKind::EqEq => Self::Eq,
+            TokenKind::BangEq => Self::Ne,
+            TokenKind::Lt => Self::Lt,
+            TokenKind::Le => Self::Le,
+            TokenKind::Gt => Self::Gt,
+            TokenKind::Ge => Self::Ge,
+            TokenKind::Amp => Self::BitAnd,
+            TokenKind::Pipe => Self::BitOr,
+            TokenKind::Caret => Self::BitXor,
+            TokenKind::Shl => Self::Shl,
+            TokenKind::Shr => Self::Shr,
+            TokenKind::QuestionQuestion => Self::Coalesce,
+            TokenKind::DotDot => Self::Range,
+            TokenKind::DotDotEq => Self::RangeInclusive,
+            _ => return None,
+        };
+
+        Some(op)
+    }
+
+    /// Applies the operator to its operands, with `span` covering the whole operation for error
+    /// reporting.
+    ///
+    /// `==` and `!=` work on any two values and compare them structurally, so lists and tuples are
+    /// equal when their items are. Values of different types are never equal, so `1 == "1"` and
+    /// `[1, 2] == (1, 2)` are both false. The exception is functions, which can't be compared at
+    /// all, even inside other values.
+    ///
+    /// `<`, `<=`, `>` and `>=` order numbers by value and strings lexicographically by Unicode code
+    /// point, so `"Z" < "a"`. Anything else has no order.
+    pub(crate) fn apply(&self, lhs: Val, rhs: Val, span: &Span) -> Result<Val, RuntimeError> {
+        match self {
+            Self::Coalesce if lhs == Val::Nil => return Ok(rhs),
+            Self::Coalesce => return Ok(lhs),
+            Self::Eq | Self::Ne if lhs.has_func() || rhs.has_func() => {
+                return Err(RuntimeError::FuncComparison {
+                    span: span.0.clone(),
+                })
+            }
+            Self::Eq => return Ok(Val::Bool(lhs == rhs)),
+            Self::Ne => return Ok(Val::Bool(lhs != rhs)),
+            Self::Lt | Self::Le | Self::Gt | Self::Ge => {
+                let ordering = Self::order(&lhs, &rhs, span)?;
+
+                return Ok(Val::Bool(match self {
+                    Self::Lt => ordering.is_lt(),
+                    Self::Le => ordering.is_le(),
+                    Self::Gt => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                }));
+            }
+            _ => {}
+        }
+
+        let (lhs, rhs) = match (lhs, rhs) {
+            (Val::Number(lhs), Val::Number(rhs)) => (lhs, rhs),
+            (lhs, rhs) => {
+                return Err(RuntimeError::InvalidOperands {
+                    op: self.clone(),
+                    lhs: lhs.type_name(),
+                    rhs: rhs.type_name(),
+                    span: span.0.clone(),
+                })
+            }
+        };
+
+        let checked = |result: Option<Int>| {
+            result
+                .map(Val::Number)
+                .ok_or_else(|| RuntimeError::Overflow { op: self.clone() })
+        };
+
+        let result = match self {
+            Self::Add => checked(lhs.checked_add(&rhs))?,
+            Self::Sub => checked(lhs.checked_sub(&rhs))?,
+            Self::Mul => checked(lhs.checked_mul(&rhs))?,
+            Self::Div | Self::Rem if rhs.is_zero() => {
+                return Err(RuntimeError::DivisionByZero {
+                    span: span.0.clone(),
+                })
+            }
+            Self::Div => checked(lhs.checked_div(&rhs))?,
+            Self::Rem => checked(lhs.checked_rem(&rhs))?,
+            Self::Pow if rhs < Int::from(0) => {
+                return Err(RuntimeError::NegativeExponent {
+                    span: span.0.clone(),
+                })
+            }
+            Self::Pow => checked(lhs.checked_pow(&rhs))?,
+            Self::BitAnd => Val::Number(lhs.bitand(&rhs)),
+            Self::BitOr => Val::Number(lhs.bitor(&rhs)),
+            Self::BitXor => Val::Number(lhs.bitxor(&rhs)),
+            Self::Shl => checked(lhs.checked_shl(&rhs))?,
+            Self::Shr => checked(lhs.checked_shr(&rhs))?,
+            Self::Range | Self::RangeInclusive => Val::Range {
+                start: lhs,
+                end: rhs,
+                inclusive: *self == Self::RangeInclusive,
+            },
+            Self::Eq | Self::Ne | Self::Lt | Self::Le | Self::Gt | Self::Ge | Self::Coalesce => {
+                unreachable!()
+            }
+        };
+
+        Ok(result)
+    }
+
+    fn order(lhs: &Val, rhs: &Val, span: &Span) -> Result<Ordering, RuntimeError> {
+        match (lhs, rhs) {
+            (Val::Number(lhs), Val::Number(rhs)) => Ok(lhs.cmp(rhs)),
+            (Val::Str(lhs), Val::Str(rhs)) => Ok(lhs.cmp(rhs)),
+            _ => Err(RuntimeError::Incomparable {
+                lhs: lhs.type_name(),
+                rhs: rhs.type_name(),
+                span: span.0.clone(),
+            }),
+        }
+    }
+
+    /// The left and right binding powers of this operator; higher binds tighter.
+    pub(crate) fn binding_power(&self) -> (u8, u8) {
+        match self {
+            Self::Coalesce => (1, 2),
+            Self::Eq | Self::Ne | Self::Lt | Self::Le | Self::Gt | Self::Ge => (3, 4),
+            // Between `|>` and the bitwise operators, so that `0..n + 1 |> list` is
+            // `list(0..(n + 1))`.
+            Self::Range | Self::RangeInclusive => (7, 8),
+            Self::BitOr => (9, 10),
+            Self::BitXor => (11, 12),
+            Self::BitAnd => (13, 14),
+            Self::Shl | Self::Shr => (15, 16),
+            Self::Add | Self::Sub => (17, 18),
+            Self::Mul | Self::Div | Self::Rem => (19, 20),
+            // The only right-associative operator, so that `2 ** 3 ** 2` is `2 ** 9`.
+            Self::Pow => (22, 21),
+        }
+    }
+}
+
+/// The binding powers of `|>`, which sits between comparisons and arithmetic, so that
+/// `a + b |> f == c` is `f(a + b) == c`.
+pub(crate) const PIPELINE_BINDING_POWER: (u8, u8) = (5, 6);
+
+/// How tightly `-` binds its operand, which is more than `*` but less than `**`, so that `-a * b`
+/// is `(-a) * b` and `-a ** b` is `-(a ** b)`.
+pub(crate) const NEG_BINDING_POWER: u8 = 21;
+
+/// A prefix operator.
+#[derive(PartialEq, Debug, Eq, Clone)]
+#[non_exhaustive]
+pub enum UnaryOp {
+    BitNot,
+    Neg,
+}
+
+impl UnaryOp {
+    fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        match tokens.split_first() {
+            Some((
+                Token {
+                    kind: TokenKind::Tilde,
+                    ..
+                },
+                tokens,
+            )) => Ok((tokens, Self::BitNot)),
+            Some((
+                Token {
+                    kind: TokenKind::Minus,
+                    ..
+                },
+                tokens,
+            )) => Ok((tokens, Self::Neg)),
+            _ => Err("expected prefix operator".to_string()),
+        }
+    }
+
+    pub(crate) fn apply(&self, operand: Val) -> Result<Val, RuntimeError> {
+        match (self, operand) {
+            (Self::BitNot, Val::Number(n)) => Ok(Val::Number(n.not())),
+            (Self::Neg, Val::Number(n)) => Int::from(0)
+                .checked_sub(&n)
+                .map(Val::Number)
+                .ok_or(RuntimeError::Overflow { op: Op::Sub }),
+            (Self::BitNot, _) => Err("cannot apply ~ to something that isn't a number"
+                .to_string()
+                .into()),
+            (Self::Neg, _) => Err("cannot apply - to something that isn't a number"
+                .to_string()
+                .into()),
+        }
     }
 }
 
+/// Anything that evaluates to a value.
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) enum Expr {
-    Number(Number),
+#[non_exhaustive]
+pub enum Expr {
+    Number(Number, Span),
+    Bool(bool, Span),
+    Str(String, Span),
     Operation {
         lhs: Box<Self>,
         rhs: Box<Self>,
         op: Op,
+        span: Span,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Self>,
+        span: Span,
     },
     BindingUsage(BindingUsage),
     Block(Block),
     FuncCall(FuncCall),
+    TryCatch(TryCatch),
+    Match(Match),
+    For(For),
+    /// `[a, b, c]`
+    List(Vec<Self>, Span),
+    /// `(a, b)`, which always has at least two items since `(a)` only groups `a`.
+    Tuple(Vec<Self>, Span),
+    /// `()`, the value of things that don't produce anything, like an empty block.
+    Unit(Span),
+    Nil(Span),
+    /// `{x: 1, y: 2}`, with the fields in the order they were written.
+    Record(Vec<(Symbol, Self)>, Span),
+    /// `base.name`, which gets a field of a record.
+    Field {
+        base: Box<Self>,
+        name: Symbol,
+        span: Span,
+    },
+    /// `_` as an argument of a call, which leaves the argument out to make a [`Val::Func`].
+    Placeholder(Span),
+    /// `base[index]`, or `base?[index]` if `safe`, which gives nil instead of failing when `base`
+    /// is nil or has nothing at `index`.
+    Index {
+        base: Box<Self>,
+        index: Box<Self>,
+        safe: bool,
+        span: Span,
+    },
 }
 
 impl Expr {
-    pub(crate) fn new(s: &str) -> Result<(&str, Self), String> {
-        Self::new_operation(s).or_else(|_| Self::new_non_operation(s))
+    pub(crate) fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        Self::new_operation(tokens, 0)
+    }
+
+    /// The byte range of the source that the expression was parsed from. An expression in
+    /// parentheses doesn't include them, and one that was desugared or folded covers whatever
+    /// it replaced.
+    pub fn span(&self) -> Range<usize> {
+        let span = match self {
+            Self::Number(_, span)
+            | Self::Bool(_, span)
+            | Self::Str(_, span)
+            | Self::List(_, span)
+            | Self::Tuple(_, span)
+            | Self::Record(_, span)
+            | Self::Unit(span)
+            | Self::Nil(span)
+            | Self::Placeholder(span)
+            | Self::Operation { span, .. }
+            | Self::Unary { span, .. }
+            | Self::Field { span, .. }
+            | Self::Index { span, .. } => span,
+            Self::BindingUsage(binding_usage) => &binding_usage.span,
+            Self::Block(block) => &block.span,
+            Self::FuncCall(func_call) => &func_call.span,
+            Self::TryCatch(try_catch) => &try_catch.span,
+            Self::Match(m) => &m.span,
+            Self::For(for_loop) => &for_loop.span,
+        };
+
+        span.0.clone()
+    }
+
+    pub(crate) fn span_mut(&mut self) -> &mut Span {
+        match self {
+            Self::Number(_, span)
+            | Self::Bool(_, span)
+            | Self::Str(_, span)
+            | Self::List(_, span)
+            | Self::Tuple(_, span)
+            | Self::Record(_, span)
+            | Self::Unit(span)
+            | Self::Nil(span)
+            | Self::Placeholder(span)
+            | Self::Operation { span, .. }
+            | Self::Unary { span, .. }
+            | Self::Field { span, .. }
+            | Self::Index { span, .. } => span,
+            Self::BindingUsage(binding_usage) => &mut binding_usage.span,
+            Self::Block(block) => &mut block.span,
+            Self::FuncCall(func_call) => &mut func_call.span,
+            Self::TryCatch(try_catch) => &mut try_catch.span,
+            Self::Match(m) => &mut m.span,
+            Self::For(for_loop) => &mut for_loop.span,
+        }
     }
 
-    fn new_non_operation(s: &str) -> Result<(&str, Self), String> {
-        Self::new_number(s)
-            .or_else(|_| FuncCall::new(s).map(|(s, func_call)| (s, Self::FuncCall(func_call))))
+    fn new_non_operation(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        Self::new_literal(tokens)
+            .or_else(|_| {
+                TryCatch::new(tokens).map(|(tokens, try_catch)| (tokens, Self::TryCatch(try_catch)))
+            })
+            .or_else(|_| Match::new(tokens).map(|(tokens, m)| (tokens, Self::Match(m))))
+            .or_else(|_| For::new(tokens).map(|(tokens, for_loop)| (tokens, Self::For(for_loop))))
             .or_else(|_| {
-                BindingUsage::new(s)
-                    .map(|(s, binding_usage)| (s, Self::BindingUsage(binding_usage)))
+                FuncCall::new(tokens).map(|(tokens, func_call)| (tokens, Self::FuncCall(func_call)))
             })
-            .or_else(|_| Block::new(s).map(|(s, block)| (s, Self::Block(block))))
+            .or_else(|_| {
+                BindingUsage::new(tokens)
+                    .map(|(tokens, binding_usage)| (tokens, Self::BindingUsage(binding_usage)))
+            })
+            .or_else(|_| Self::new_record(tokens))
+            .or_else(|_| Block::new(tokens).map(|(tokens, block)| (tokens, Self::Block(block))))
+            .or_else(|_| Self::new_unary(tokens))
+            .or_else(|_| Self::new_list(tokens))
+            .or_else(|_| Self::new_parenthesized(tokens))
+    }
+
+    /// Parses an expression followed by any number of indexes and method calls. The `[` or `.`
+    /// has to follow directly, since `f [1]` is a call with a list argument.
+    fn new_postfix(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let (mut tokens, mut base) = Self::new_non_operation(tokens)?;
+        let mut depth = utils::Depth::chain();
+
+        loop {
+            let safe = match tokens.first() {
+                Some(token) if token.span.start != utils::consumed_span(start, tokens).0.end => {
+                    break
+                }
+                Some(Token {
+                    kind: TokenKind::Dot,
+                    ..
+                }) => {
+                    depth.link(tokens)?;
+                    (tokens, base) = Self::new_member(base, start, tokens)?;
+                    continue;
+                }
+                Some(Token {
+                    kind: TokenKind::LBracket,
+                    ..
+                }) => false,
+                Some(Token {
+                    kind: TokenKind::QuestionLBracket,
+                    ..
+                }) => true,
+                _ => break,
+            };
+
+            depth.link(tokens)?;
+            let (new_tokens, index) = Self::new(&tokens[1..])?;
+            tokens = utils::expect(TokenKind::RBracket, new_tokens)?;
+
+            base = Self::Index {
+                base: Box::new(base),
+                index: Box::new(index),
+                safe,
+                span: utils::consumed_span(start, tokens),
+            };
+        }
+
+        Ok((tokens, base))
+    }
+
+    /// Parses the `.name` of `base.name`, which gets a field unless arguments follow directly,
+    /// in which case it calls `name` with `base` as the first argument. `start` is where `base`
+    /// began.
+    fn new_member<'t>(
+        base: Self,
+        start: &'t [Token],
+        tokens: &'t [Token],
+    ) -> Result<(&'t [Token], Self), String> {
+        let after_dot = utils::expect(TokenKind::Dot, tokens)?;
+        let (after_name, name) = utils::extract_ident(after_dot)
+            .map_err(|_| "expected field or method name after .".to_string())?;
+
+        match after_name.first() {
+            Some(Token {
+                kind: TokenKind::LParen,
+                span,
+                ..
+            }) if span.start == after_dot[0].span.end => {
+                let (tokens, func_call) = FuncCall::new_method(base, start, tokens)?;
+                Ok((tokens, Self::FuncCall(func_call)))
+            }
+            _ => Ok((
+                after_name,
+                Self::Field {
+                    base: Box::new(base),
+                    name: Symbol::intern(name),
+                    span: utils::consumed_span(start, after_name),
+                },
+            )),
+        }
     }
 
-    fn new_operation(s: &str) -> Result<(&str, Self), String> {
-        let (s, lhs) = Self::new_non_operation(s)?;
-        let (s, _) = utils::extract_whitespace(s);
+    /// Parses `{x: 1, y: 2}`, which is told apart from a block by the `name:` at the start.
+    fn new_record(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let tokens = utils::expect(TokenKind::LBrace, tokens)?;
 
-        let (s, op) = Op::new(s)?;
-        let (s, _) = utils::extract_whitespace(s);
+        if !matches!(
+            tokens,
+            [
+                Token {
+                    kind: TokenKind::Ident(_),
+                    ..
+                },
+                Token {
+                    kind: TokenKind::Colon,
+                    ..
+                },
+                ..
+            ]
+        ) {
+            return Err("expected record".to_string());
+        }
 
-        let (s, rhs) = Self::new_non_operation(s)?;
+        let (tokens, fields) = utils::comma_separated(
+            |tokens| {
+                let (tokens, name) = utils::extract_ident(tokens)?;
+                let tokens = utils::expect(TokenKind::Colon, tokens)?;
+                let (tokens, val) = Self::new(tokens)?;
+                Ok((tokens, (Symbol::intern(name), val)))
+            },
+            TokenKind::RBrace,
+            tokens,
+        )?;
 
         Ok((
-            s,
-            Self::Operation {
+            tokens,
+            Self::Record(fields, utils::consumed_span(start, tokens)),
+        ))
+    }
+
+    fn new_unary(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let (tokens, op) = UnaryOp::new(tokens)?;
+        let _depth = utils::Depth::enter(tokens)?;
+        let (tokens, operand) = match op {
+            UnaryOp::BitNot => Self::new_postfix(tokens)?,
+            UnaryOp::Neg => Self::new_operation(tokens, NEG_BINDING_POWER)?,
+        };
+
+        Ok((
+            tokens,
+            Self::Unary {
+                op,
+                operand: Box::new(operand),
+                span: utils::consumed_span(start, tokens),
+            },
+        ))
+    }
+
+    fn new_operation(tokens: &[Token], min_binding_power: u8) -> Result<(&[Token], Self), String> {
+        let mut depth = utils::Depth::enter(tokens)?;
+        let start = tokens;
+        let (mut tokens, mut lhs) = Self::new_postfix(tokens)?;
+
+        // A line break is allowed after an operator but not before one, so that a line starting
+        // with an operator isn't taken as continuing the line above.
+        loop {
+            if let Ok(after_pipe) =
+                utils::same_line(tokens).and_then(|tokens| utils::expect(TokenKind::PipeGt, tokens))
+            {
+                let (left_binding_power, right_binding_power) = PIPELINE_BINDING_POWER;
+                if left_binding_power < min_binding_power {
+                    break;
+                }
+
+                depth.link(tokens)?;
+                let (new_tokens, rhs) = Self::new_operation(after_pipe, right_binding_power)?;
+                tokens = new_tokens;
+
+                lhs = Self::pipe(lhs, rhs, utils::consumed_span(start, tokens))?;
+                continue;
+            }
+
+            let (after_op, op) = match utils::same_line(tokens).and_then(Op::new) {
+                Ok(op) => op,
+                Err(_) => break,
+            };
+
+            let (left_binding_power, right_binding_power) = op.binding_power();
+            if left_binding_power < min_binding_power {
+                break;
+            }
+
+            depth.link(tokens)?;
+            let (new_tokens, rhs) = Self::new_operation(after_op, right_binding_power)?;
+            tokens = new_tokens;
+
+            lhs = Self::Operation {
                 lhs: Box::new(lhs),
                 rhs: Box::new(rhs),
                 op,
+                span: utils::consumed_span(start, tokens),
+            };
+        }
+
+        Ok((tokens, lhs))
+    }
+
+    /// Desugars `arg |> rhs`, where `rhs` is a function name or a call, into a call with `arg` in
+    /// place of the first `_` of `rhs`, or as the first argument if it has none.
+    fn pipe(arg: Self, rhs: Self, span: Span) -> Result<Self, String> {
+        let mut func_call = match rhs {
+            Self::BindingUsage(binding_usage) => FuncCall {
+                callee: binding_usage.name,
+                named: Vec::new(),
+                params: Vec::new(),
+                style: CallStyle::Piped(0),
+                span,
+            },
+            Self::FuncCall(func_call) => FuncCall {
+                style: CallStyle::Piped(0),
+                span,
+                ..func_call
             },
+            _ => return Err("expected a function to pipe into".to_string()),
+        };
+
+        match func_call
+            .params
+            .iter()
+            .position(|param| matches!(param, Self::Placeholder(_)))
+        {
+            Some(idx) => {
+                func_call.params[idx] = arg;
+                func_call.style = CallStyle::Piped(idx);
+            }
+            None => func_call.params.insert(0, arg),
+        }
+
+        Ok(Self::FuncCall(func_call))
+    }
+
+    fn new_list(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        utils::once(utils::Memo::List, Self::new_list_uncached, tokens)
+    }
+
+    fn new_list_uncached(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let tokens = utils::expect(TokenKind::LBracket, tokens)?;
+        let (tokens, items) = utils::comma_separated(Self::new, TokenKind::RBracket, tokens)?;
+
+        Ok((
+            tokens,
+            Self::List(items, utils::consumed_span(start, tokens)),
         ))
     }
-    fn new_number(s: &str) -> Result<(&str, Self), String> {
-        Number::new(s).map(|(s, number)| (s, Self::Number(number)))
+
+    /// Parses `()`, `(a, b)` or `(a)`. The last only groups `a`, so it keeps the span of `a`
+    /// without the parentheses.
+    fn new_parenthesized(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        utils::once(
+            utils::Memo::Parenthesized,
+            Self::new_parenthesized_uncached,
+            tokens,
+        )
+    }
+
+    fn new_parenthesized_uncached(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let tokens = utils::expect(TokenKind::LParen, tokens)?;
+        let (tokens, mut items) = utils::comma_separated(Self::new, TokenKind::RParen, tokens)?;
+        let span = utils::consumed_span(start, tokens);
+
+        match items.len() {
+            0 => Ok((tokens, Self::Unit(span))),
+            1 => Ok((tokens, items.pop().unwrap())),
+            _ => Ok((tokens, Self::Tuple(items, span))),
+        }
+    }
+
+    /// Parses a number, bool, `nil` or string, which are all a single token.
+    fn new_literal(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let span = match tokens.first() {
+            Some(token) => Span(token.span.clone()),
+            None => return Err("expected literal".to_string()),
+        };
+
+        Self::new_number(tokens, span.clone())
+            .or_else(|_| Self::new_bool(tokens, span.clone()))
+            .or_else(|_| Self::new_nil(tokens, span.clone()))
+            .or_else(|_| Self::new_str(tokens, span))
+    }
+
+    fn new_number(tokens: &[Token], span: Span) -> Result<(&[Token], Self), String> {
+        Number::new(tokens).map(|(tokens, number)| (tokens, Self::Number(number, span)))
+    }
+
+    fn new_bool(tokens: &[Token], span: Span) -> Result<(&[Token], Self), String> {
+        let (tokens, b) = utils::keyword("true", tokens)
+            .map(|tokens| (tokens, true))
+            .or_else(|_| utils::keyword("false", tokens).map(|tokens| (tokens, false)))
+            .map_err(|_| "expected boolean".to_string())?;
+
+        Ok((tokens, Self::Bool(b, span)))
+    }
+
+    fn new_nil(tokens: &[Token], span: Span) -> Result<(&[Token], Self), String> {
+        utils::keyword("nil", tokens).map(|tokens| (tokens, Self::Nil(span)))
+    }
+
+    fn new_str(tokens: &[Token], span: Span) -> Result<(&[Token], Self), String> {
+        match tokens.split_first() {
+            Some((
+                Token {
+                    kind: TokenKind::Str(string),
+                    ..
+                },
+                tokens,
+            )) => Ok((tokens, Self::Str(string.clone(), span))),
+            _ => Err("expected string".to_string()),
+        }
     }
 
-    pub(crate) fn eval(&self, env: &Env) -> Result<Val, String> {
+    pub(crate) fn eval(&self, env: &mut Env) -> Result<Val, RuntimeError> {
         match self {
-            Self::Number(Number(n)) => Ok(Val::Number(*n)),
-            Self::Operation { lhs, rhs, op } => {
-                let lhs = lhs.eval(env)?;
-                let rhs = rhs.eval(env)?;
-
-                let (lhs, rhs) = match (lhs,rhs) {
-                    (Val::Number(lhs), Val::Number(rhs)) => (lhs, rhs),
-                    _ => return Err("cannot evaluate operation whose left-hand side and right-hand side are not both numbers".to_string()),
-                };
-
-                let result = match op {
-                    Op::Add => lhs + rhs,
-                    Op::Sub => lhs - rhs,
-                    Op::Mul => lhs * rhs,
-                    Op::Div => lhs / rhs,
-                };
-
-                Ok(Val::Number(result))
+            Self::Number(Number(n), _) => Ok(Val::Number(n.clone())),
+            Self::Bool(b, _) => Ok(Val::Bool(*b)),
+            Self::Str(s, _) => Ok(Val::Str(s.clone())),
+            Self::Unit(_) => Ok(Val::Unit),
+            Self::Operation {
+                lhs,
+                rhs,
+                op: Op::Coalesce,
+                ..
+            } => match lhs.eval(env)? {
+                Val::Nil => rhs.eval(env),
+                lhs => Ok(lhs),
+            },
+            Self::Operation { lhs, rhs, op, span } => {
+                op.apply(lhs.eval(env)?, rhs.eval(env)?, span)
             }
+            Self::Nil(_) => Ok(Val::Nil),
+            Self::Placeholder(_) => Err("`_` can only be used as an argument".to_string().into()),
+            Self::Record(fields, _) => Ok(Val::record(
+                fields
+                    .iter()
+                    .map(|(name, val)| Ok((*name, val.eval(env)?)))
+                    .collect::<Result<_, RuntimeError>>()?,
+            )?),
+            Self::Field { base, name, .. } => Ok(base.eval(env)?.field(*name)?),
+            Self::Index {
+                base,
+                index,
+                safe,
+                span,
+            } => index_into(base.eval(env)?, || index.eval(env), *safe, span),
+            Self::Unary { op, operand, .. } => op.apply(operand.eval(env)?),
             Self::BindingUsage(binding_usage) => binding_usage.eval(env),
             Self::Block(block) => block.eval(env),
             Self::FuncCall(func_call) => func_call.eval(env),
+            Self::TryCatch(try_catch) => try_catch.eval(env),
+            Self::Match(m) => m.eval(env),
+            Self::For(for_loop) => for_loop.eval(env),
+            Self::List(items, _) => items
+                .iter()
+                .map(|item| item.eval(env))
+                .collect::<Result<_, _>>()
+                .map(Val::List),
+            Self::Tuple(items, _) => items
+                .iter()
+                .map(|item| item.eval(env))
+                .collect::<Result<_, _>>()
+                .map(Val::Tuple),
         }
     }
 }
 
+/// Indexes into `base`, only evaluating the index if `base` isn't nil in a safe index.
+pub(crate) fn index_into(
+    base: Val,
+    index: impl FnOnce() -> Result<Val, RuntimeError>,
+    safe: bool,
+    span: &Span,
+) -> Result<Val, RuntimeError> {
+    if safe && base == Val::Nil {
+        return Ok(Val::Nil);
+    }
+
+    let index = index()?;
+
+    let item = base.index(&index)?;
+    let len = || {
+        base.indexable_len()
+            .expect("only lists, tuples and strings can be indexed")
+    };
+
+    match (item, index) {
+        (Some(item), _) => Ok(item),
+        (None, _) if safe => Ok(Val::Nil),
+        (None, Val::Number(index)) => Err(RuntimeError::IndexOutOfRange {
+            index,
+            len: len(),
+            span: span.0.clone(),
+        }),
+        (
+            None,
+            Val::Range {
+                start,
+                end,
+                inclusive,
+            },
+        ) => Err(RuntimeError::SliceOutOfRange {
+            start,
+            end,
+            inclusive,
+            len: len(),
+            span: span.0.clone(),
+        }),
+        (None, _) => unreachable!("only numbers and ranges can index"),
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
     use crate::env::Env;
     use crate::func_def::FuncDef;
+    use crate::interpreter::{Backend, Interpreter};
     use crate::stmt::Stmt;
     use crate::val::Val;
 
@@ -128,26 +830,35 @@ mod tests {
         let mut env = Env::default();
 
         env.store_func(
-            "add".to_string(),
-            vec!["x".to_string(), "y".to_string()],
+            "add".into(),
+            &["x".into(), "y".into()],
             Stmt::Expr(Expr::Operation {
                 lhs: Box::new(Expr::BindingUsage(BindingUsage {
-                    name: "x".to_string(),
+                    name: "x".into(),
+                    span: Span::default(),
                 })),
                 rhs: Box::new(Expr::BindingUsage(BindingUsage {
-                    name: "y".to_string(),
+                    name: "y".into(),
+                    span: Span::default(),
                 })),
                 op: Op::Add,
+                span: Span::default(),
             }),
         );
 
         assert_eq!(
             Expr::FuncCall(FuncCall {
-                callee: "add".to_string(),
-                params: vec![Expr::Number(Number(2)), Expr::Number(Number(2))],
+                callee: "add".into(),
+                style: CallStyle::Direct,
+                named: Vec::new(),
+                span: Span::default(),
+                params: vec![
+                    Expr::Number(Number(2.into()), Span::default()),
+                    Expr::Number(Number(2.into()), Span::default())
+                ],
             })
-            .eval(&env),
-            Ok(Val::Number(4)),
+            .eval(&mut env),
+            Ok(Val::Number(4.into())),
         );
     }
 
@@ -155,9 +866,12 @@ mod tests {
     fn eval_func_def() {
         assert_eq!(
             Stmt::FuncDef(FuncDef {
-                name: "always_return_one".to_string(),
+                name: "always_return_one".into(),
+                span: Span::default(),
                 params: Vec::new(),
-                body: Box::new(Stmt::Expr(Expr::Number(Number(1)))),
+                body: Box::new(Stmt::Expr(Expr::Number(Number(1.into()), Span::default()))),
+                doc: None,
+                source: None,
             })
             .eval(&mut Env::default()),
             Ok(Val::Unit),
@@ -168,24 +882,34 @@ mod tests {
     fn eval_non_number_operation() {
         assert_eq!(
             Expr::Operation {
-                lhs: Box::new(Expr::Number(Number(10))),
-                rhs: Box::new(Expr::Block(Block { stmts: Vec::new() })),
+                lhs: Box::new(Expr::Number(Number(10.into()), Span::default())),
+                rhs: Box::new(Expr::Block(Block {
+                    stmts: Vec::new(),
+                    span: Span::default(),
+                })),
                 op: Op::Add,
+                span: Span::default(),
             }
-            .eval(&Env::default()),
-            Err("cannot evaluate operation whose left-hand side and right-hand side are not both numbers".to_string()),
+            .eval(&mut Env::default()),
+            Err(RuntimeError::InvalidOperands {
+                op: Op::Add,
+                lhs: "number",
+                rhs: "unit",
+                span: 0..0,
+            }),
         );
     }
     #[test]
     fn parse_one_plus_two() {
         assert_eq!(
-            Expr::new("1+2"),
+            utils::parse_str(Expr::new, "1+2"),
             Ok((
                 "",
                 Expr::Operation {
-                    lhs: Box::new(Expr::Number(Number(1))),
-                    rhs: Box::new(Expr::Number(Number(2))),
+                    lhs: Box::new(Expr::Number(Number(1.into()), Span::default())),
+                    rhs: Box::new(Expr::Number(Number(2.into()), Span::default())),
                     op: Op::Add,
+                    span: Span::default(),
                 },
             )),
         );
@@ -194,30 +918,154 @@ mod tests {
     #[test]
     fn parse_expr_with_whitespace() {
         assert_eq!(
-            Expr::new("2 * 2"),
+            utils::parse_str(Expr::new, "2 * 2"),
             Ok((
                 "",
                 Expr::Operation {
-                    lhs: Box::new(Expr::Number(Number(2))),
-                    rhs: Box::new(Expr::Number(Number(2))),
+                    lhs: Box::new(Expr::Number(Number(2.into()), Span::default())),
+                    rhs: Box::new(Expr::Number(Number(2.into()), Span::default())),
                     op: Op::Mul,
+                    span: Span::default(),
                 },
             )),
         );
     }
 
-    // snip
+    #[test]
+    fn parse_mul_before_add() {
+        assert_eq!(
+            utils::parse_str(Expr::new, "1 + 2 * 3"),
+            Ok((
+                "",
+                Expr::Operation {
+                    lhs: Box::new(Expr::Number(Number(1.into()), Span::default())),
+                    rhs: Box::new(Expr::Operation {
+                        lhs: Box::new(Expr::Number(Number(2.into()), Span::default())),
+                        rhs: Box::new(Expr::Number(Number(3.into()), Span::default())),
+                        op: Op::Mul,
+                        span: Span::default(),
+                    }),
+                    op: Op::Add,
+                    span: Span::default(),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_left_associative_sub() {
+        assert_eq!(
+            utils::parse_str(Expr::new, "5 - 2 - 1"),
+            Ok((
+                "",
+                Expr::Operation {
+                    lhs: Box::new(Expr::Operation {
+                        lhs: Box::new(Expr::Number(Number(5.into()), Span::default())),
+                        rhs: Box::new(Expr::Number(Number(2.into()), Span::default())),
+                        op: Op::Sub,
+                        span: Span::default(),
+                    }),
+                    rhs: Box::new(Expr::Number(Number(1.into()), Span::default())),
+                    op: Op::Sub,
+                    span: Span::default(),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_comparison_below_arithmetic() {
+        assert_eq!(
+            utils::parse_str(Expr::new, "1 + 1 == 2"),
+            Ok((
+                "",
+                Expr::Operation {
+                    lhs: Box::new(Expr::Operation {
+                        lhs: Box::new(Expr::Number(Number(1.into()), Span::default())),
+                        rhs: Box::new(Expr::Number(Number(1.into()), Span::default())),
+                        op: Op::Add,
+                        span: Span::default(),
+                    }),
+                    rhs: Box::new(Expr::Number(Number(2.into()), Span::default())),
+                    op: Op::Eq,
+                    span: Span::default(),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_bool() {
+        assert_eq!(
+            utils::parse_str(Expr::new, "true"),
+            Ok(("", Expr::Bool(true, Span::default())))
+        );
+    }
+
+    #[test]
+    fn parse_ident_starting_with_bool_keyword() {
+        assert_eq!(
+            utils::parse_str(Expr::new, "falsey"),
+            Ok((
+                "",
+                Expr::BindingUsage(BindingUsage {
+                    name: "falsey".into(),
+                    span: Span::default(),
+                }),
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_string() {
+        assert_eq!(
+            utils::parse_str(Expr::new, r#""hello \"world\"""#),
+            Ok((
+                "",
+                Expr::Str(r#"hello "world""#.to_string(), Span::default())
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_eq() {
+        assert_eq!(
+            Expr::Operation {
+                lhs: Box::new(Expr::Str("a".to_string(), Span::default())),
+                rhs: Box::new(Expr::Str("a".to_string(), Span::default())),
+                op: Op::Eq,
+                span: Span::default(),
+            }
+            .eval(&mut Env::default()),
+            Ok(Val::Bool(true)),
+        );
+    }
+
+    #[test]
+    fn eval_lt() {
+        assert_eq!(
+            Expr::Operation {
+                lhs: Box::new(Expr::Number(Number(3.into()), Span::default())),
+                rhs: Box::new(Expr::Number(Number(2.into()), Span::default())),
+                op: Op::Lt,
+                span: Span::default(),
+            }
+            .eval(&mut Env::default()),
+            Ok(Val::Bool(false)),
+        );
+    }
 
     #[test]
     fn eval_add() {
         assert_eq!(
             Expr::Operation {
-                lhs: Box::new(Expr::Number(Number(10))),
-                rhs: Box::new(Expr::Number(Number(10))),
+                lhs: Box::new(Expr::Number(Number(10.into()), Span::default())),
+                rhs: Box::new(Expr::Number(Number(10.into()), Span::default())),
                 op: Op::Add,
+                span: Span::default(),
             }
-            .eval(&Env::default()),
-            Ok(Val::Number(20)),
+            .eval(&mut Env::default()),
+            Ok(Val::Number(20.into())),
         );
     }
 
@@ -225,12 +1073,13 @@ mod tests {
     fn eval_sub() {
         assert_eq!(
             Expr::Operation {
-                lhs: Box::new(Expr::Number(Number(1))),
-                rhs: Box::new(Expr::Number(Number(5))),
+                lhs: Box::new(Expr::Number(Number(1.into()), Span::default())),
+                rhs: Box::new(Expr::Number(Number(5.into()), Span::default())),
                 op: Op::Sub,
+                span: Span::default(),
             }
-            .eval(&Env::default()),
-            Ok(Val::Number(-4)),
+            .eval(&mut Env::default()),
+            Ok(Val::Number((-4).into())),
         );
     }
 
@@ -238,12 +1087,13 @@ mod tests {
     fn eval_mul() {
         assert_eq!(
             Expr::Operation {
-                lhs: Box::new(Expr::Number(Number(5))),
-                rhs: Box::new(Expr::Number(Number(6))),
+                lhs: Box::new(Expr::Number(Number(5.into()), Span::default())),
+                rhs: Box::new(Expr::Number(Number(6.into()), Span::default())),
                 op: Op::Mul,
+                span: Span::default(),
             }
-            .eval(&Env::default()),
-            Ok(Val::Number(30)),
+            .eval(&mut Env::default()),
+            Ok(Val::Number(30.into())),
         );
     }
 
@@ -251,12 +1101,796 @@ mod tests {
     fn eval_div() {
         assert_eq!(
             Expr::Operation {
-                lhs: Box::new(Expr::Number(Number(200))),
-                rhs: Box::new(Expr::Number(Number(20))),
+                lhs: Box::new(Expr::Number(Number(200.into()), Span::default())),
+                rhs: Box::new(Expr::Number(Number(20.into()), Span::default())),
                 op: Op::Div,
+                span: Span::default(),
+            }
+            .eval(&mut Env::default()),
+            Ok(Val::Number(10.into())),
+        );
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn eval_overflowing_mul() {
+        assert_eq!(
+            Expr::Operation {
+                lhs: Box::new(Expr::Number(Number(i64::MAX.into()), Span::default())),
+                rhs: Box::new(Expr::Number(Number(2.into()), Span::default())),
+                op: Op::Mul,
+                span: Span::default(),
             }
-            .eval(&Env::default()),
-            Ok(Val::Number(10)),
+            .eval(&mut Env::default()),
+            Err(RuntimeError::Overflow { op: Op::Mul }),
+        );
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn eval_overflowing_sub() {
+        assert_eq!(
+            crate::parse("0 - 9223372036854775807 - 2")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Err(RuntimeError::Overflow { op: Op::Sub }),
+        );
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn parse_oversized_number() {
+        assert_eq!(
+            utils::parse_str(Number::new, "9223372036854775808"),
+            Err("number literal out of range for i64".to_string()),
+        );
+        assert_eq!(
+            utils::parse_str(Number::new, "9223372036854775807"),
+            Ok(("", Number(i64::MAX.into()))),
+        );
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn eval_past_i64() {
+        assert_eq!(
+            crate::parse("9223372036854775807 * 9223372036854775807")
+                .unwrap()
+                .eval(&mut Env::default())
+                .map(|val| val.to_string()),
+            Ok("85070591730234615847396907784232501249".to_string()),
+        );
+    }
+
+    #[test]
+    fn eval_division_by_zero() {
+        assert_eq!(
+            crate::parse("let a = 1 + 200 / 0")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Err(RuntimeError::DivisionByZero { span: 12..19 }),
+        );
+    }
+
+    #[test]
+    fn eval_rem_with_the_sign_of_the_dividend() {
+        let eval = |s| crate::parse(s).unwrap().eval(&mut Env::default());
+
+        assert_eq!(
+            eval("(7 % 3, -7 % 3, 7 % -3)"),
+            Ok(Val::Tuple(vec![
+                Val::Number(1.into()),
+                Val::Number((-1).into()),
+                Val::Number(1.into()),
+            ]))
+        );
+        assert_eq!(eval("1 + 2 * 7 % 4"), Ok(Val::Number(3.into())));
+        assert_eq!(
+            eval("let a = 1 + 200 % 0"),
+            Err(RuntimeError::DivisionByZero { span: 12..19 }),
+        );
+    }
+
+    #[test]
+    fn parse_operation_span() {
+        match utils::parse_str(Expr::new, " 1 + 2 * x") {
+            Ok((_, Expr::Operation { span, .. })) => assert_eq!(span.0, 1..10),
+            result => panic!("expected an operation, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn parse_shift_below_add_and_and_below_shift() {
+        assert_eq!(
+            utils::parse_str(Expr::new, "1 << 2 + 3 & 4"),
+            Ok((
+                "",
+                Expr::Operation {
+                    lhs: Box::new(Expr::Operation {
+                        lhs: Box::new(Expr::Number(Number(1.into()), Span::default())),
+                        rhs: Box::new(Expr::Operation {
+                            lhs: Box::new(Expr::Number(Number(2.into()), Span::default())),
+                            rhs: Box::new(Expr::Number(Number(3.into()), Span::default())),
+                            op: Op::Add,
+                            span: Span::default(),
+                        }),
+                        op: Op::Shl,
+                        span: Span::default(),
+                    }),
+                    rhs: Box::new(Expr::Number(Number(4.into()), Span::default())),
+                    op: Op::BitAnd,
+                    span: Span::default(),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_bitwise_precedence() {
+        let eval = |s| {
+            crate::parse(s)
+                .unwrap()
+                .eval(&mut Env::default())
+                .map(|val| val.to_string())
+        };
+
+        assert_eq!(eval("1 | 2 ^ 3 & 6"), Ok("1".to_string()));
+        assert_eq!(eval("6 & 3 == 2"), Ok("true".to_string()));
+    }
+
+    #[test]
+    fn parse_range_between_pipeline_and_bitwise() {
+        let eval = |s| {
+            crate::parse(s)
+                .unwrap()
+                .eval(&mut Env::default())
+                .map(|val| val.to_string())
+        };
+
+        assert_eq!(eval("1 | 2..2 + 1"), Ok("3..3".to_string()));
+        assert_eq!(eval("0..=2 |> list"), Ok("[0, 1, 2]".to_string()));
+        assert_eq!(eval("0..2 == 0..2"), Ok("true".to_string()));
+        assert_eq!(
+            eval("0..\"a\""),
+            Err(RuntimeError::InvalidOperands {
+                op: Op::Range,
+                lhs: "number",
+                rhs: "string",
+                span: 0..6,
+            }),
+        );
+    }
+
+    #[test]
+    fn parse_bit_not() {
+        assert_eq!(
+            utils::parse_str(Expr::new, "~x & 1"),
+            Ok((
+                "",
+                Expr::Operation {
+                    lhs: Box::new(Expr::Unary {
+                        op: UnaryOp::BitNot,
+                        operand: Box::new(Expr::BindingUsage(BindingUsage {
+                            name: "x".into(),
+                            span: Span::default(),
+                        })),
+                        span: Span::default(),
+                    }),
+                    rhs: Box::new(Expr::Number(Number(1.into()), Span::default())),
+                    op: Op::BitAnd,
+                    span: Span::default(),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_bitwise_ops() {
+        let eval = |s| crate::parse(s).unwrap().eval(&mut Env::default());
+
+        assert_eq!(eval("12 & 10"), Ok(Val::Number(8.into())));
+        assert_eq!(eval("12 | 10"), Ok(Val::Number(14.into())));
+        assert_eq!(eval("12 ^ 10"), Ok(Val::Number(6.into())));
+        assert_eq!(eval("1 << 4"), Ok(Val::Number(16.into())));
+        assert_eq!(eval("{ 0 - 16 } >> 2"), Ok(Val::Number((-4).into())));
+        assert_eq!(eval("~0"), Ok(Val::Number((-1).into())));
+    }
+
+    #[test]
+    fn eval_negative_shift() {
+        assert_eq!(
+            crate::parse("1 << { 0 - 1 }")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Err(RuntimeError::Overflow { op: Op::Shl }),
+        );
+    }
+
+    #[test]
+    fn parse_pow_right_associative_above_mul() {
+        let eval = |s| crate::parse(s).unwrap().eval(&mut Env::default());
+
+        assert_eq!(eval("2 ** 3 ** 2"), Ok(Val::Number(512.into())));
+        assert_eq!(eval("(2 ** 3) ** 2"), Ok(Val::Number(64.into())));
+        assert_eq!(eval("3 * 2 ** 2"), Ok(Val::Number(12.into())));
+        assert_eq!(eval("2 ** 2 * 3"), Ok(Val::Number(12.into())));
+        assert_eq!(eval("2 ** 0"), Ok(Val::Number(1.into())));
+        assert_eq!(
+            crate::format("(2 ** 3) ** 2 ** 1"),
+            Ok("(2 ** 3) ** 2 ** 1\n".to_string())
+        );
+    }
+
+    #[test]
+    fn functions_cannot_be_compared() {
+        let eval = |s: &str| {
+            let program = crate::parse(s).unwrap();
+            let walked = program.eval(&mut Env::default());
+            assert_eq!(program.compile().eval(&mut Env::default()), walked);
+            walked
+        };
+        let funcs = "fn add a b => a + b\nlet inc = add(1, _)\n";
+
+        for (comparison, span) in [
+            ("inc == inc", 0..10),
+            ("add != 1", 0..8),
+            ("[1, {f: inc}] == [1, {f: 2}]", 0..28),
+            ("(1 == 1) == (inc == 2)", 13..21),
+        ] {
+            let start = funcs.len();
+            assert_eq!(
+                eval(&format!("{}{}", funcs, comparison)),
+                Err(RuntimeError::FuncComparison {
+                    span: start + span.start..start + span.end,
+                }),
+                "{}",
+                comparison,
+            );
+        }
+        assert_eq!(eval(&format!("{}inc(1) == 2", funcs)), Ok(Val::Bool(true)));
+    }
+
+    #[test]
+    fn parse_neg_between_mul_and_pow() {
+        let eval = |s| crate::parse(s).unwrap().eval(&mut Env::default());
+
+        assert_eq!(eval("-5"), Ok(Val::Number((-5).into())));
+        assert_eq!(eval("let x = -1\nx"), Ok(Val::Number((-1).into())));
+        assert_eq!(eval("-2 ** 2"), Ok(Val::Number((-4).into())));
+        assert_eq!(eval("-2 * 3 - -1"), Ok(Val::Number((-5).into())));
+        assert_eq!(eval("-[1, 2][1]"), Ok(Val::Number((-2).into())));
+        assert_eq!(
+            eval("2 ** -1"),
+            Err(RuntimeError::NegativeExponent { span: 0..7 }),
+        );
+        assert_eq!(
+            eval("-true"),
+            Err(RuntimeError::Message(
+                "cannot apply - to something that isn't a number".to_string()
+            )),
+        );
+        assert_eq!(
+            utils::parse_str(Expr::new, "-x * 2"),
+            Ok((
+                "",
+                Expr::Operation {
+                    lhs: Box::new(Expr::Unary {
+                        op: UnaryOp::Neg,
+                        operand: Box::new(Expr::BindingUsage(BindingUsage {
+                            name: "x".into(),
+                            span: Span::default(),
+                        })),
+                        span: Span::default(),
+                    }),
+                    rhs: Box::new(Expr::Number(Number(2.into()), Span::default())),
+                    op: Op::Mul,
+                    span: Span::default(),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn juxtaposed_args_cannot_start_with_neg() {
+        let eval = |s| crate::parse(s).unwrap().eval(&mut Env::default());
+
+        assert_eq!(eval("let n = 3\nn -1"), Ok(Val::Number(2.into())));
+        assert_eq!(eval("fn f x => x\nf (-1)"), Ok(Val::Number((-1).into())));
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn eval_overflowing_neg() {
+        assert_eq!(
+            crate::parse("-(-9223372036854775807 - 1)")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Err(RuntimeError::Overflow { op: Op::Sub }),
+        );
+    }
+
+    #[test]
+    fn eval_negative_exponent() {
+        assert_eq!(
+            crate::parse("let a = 2 ** { 0 - 1 }")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Err(RuntimeError::NegativeExponent { span: 8..22 }),
+        );
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn eval_overflowing_pow() {
+        assert_eq!(
+            crate::parse("2 ** 63").unwrap().eval(&mut Env::default()),
+            Err(RuntimeError::Overflow { op: Op::Pow }),
+        );
+        assert_eq!(
+            crate::parse("{ 0 - 2 } ** 63")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Ok(Val::Number(i64::MIN.into())),
+        );
+    }
+
+    #[test]
+    fn eval_bit_not_of_bool() {
+        assert_eq!(
+            crate::parse("~true").unwrap().eval(&mut Env::default()),
+            Err(RuntimeError::Message(
+                "cannot apply ~ to something that isn't a number".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_list() {
+        assert_eq!(
+            utils::parse_str(Expr::new, "[1, x, []]"),
+            Ok((
+                "",
+                Expr::List(
+                    vec![
+                        Expr::Number(Number(1.into()), Span::default()),
+                        Expr::BindingUsage(BindingUsage {
+                            name: "x".into(),
+                            span: Span::default(),
+                        }),
+                        Expr::List(Vec::new(), Span::default()),
+                    ],
+                    Span::default()
+                ),
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_list() {
+        assert_eq!(
+            crate::parse("[1 + 1, \"a\", true]")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Ok(Val::List(vec![
+                Val::Number(2.into()),
+                Val::Str("a".to_string()),
+                Val::Bool(true),
+            ])),
+        );
+    }
+
+    #[test]
+    fn parse_parenthesized_and_tuple() {
+        assert_eq!(
+            utils::parse_str(Expr::new, "(1 + 2) * 3"),
+            Ok((
+                "",
+                Expr::Operation {
+                    lhs: Box::new(Expr::Operation {
+                        lhs: Box::new(Expr::Number(Number(1.into()), Span::default())),
+                        rhs: Box::new(Expr::Number(Number(2.into()), Span::default())),
+                        op: Op::Add,
+                        span: Span::default(),
+                    }),
+                    rhs: Box::new(Expr::Number(Number(3.into()), Span::default())),
+                    op: Op::Mul,
+                    span: Span::default(),
+                },
+            )),
+        );
+        assert_eq!(
+            utils::parse_str(Expr::new, "(1, x)"),
+            Ok((
+                "",
+                Expr::Tuple(
+                    vec![
+                        Expr::Number(Number(1.into()), Span::default()),
+                        Expr::BindingUsage(BindingUsage {
+                            name: "x".into(),
+                            span: Span::default(),
+                        }),
+                    ],
+                    Span::default()
+                ),
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_structural_equality() {
+        let eval = |s| {
+            crate::parse(s)
+                .unwrap()
+                .eval(&mut Env::default())
+                .map(|val| val.to_string())
+        };
+
+        assert_eq!(
+            eval("[1, (2, \"a\")] == [1, (2, \"a\")]"),
+            Ok("true".to_string())
+        );
+        assert_eq!(eval("[1, [2]] != [1, [3]]"), Ok("true".to_string()));
+        assert_eq!(eval("[1, 2] == (1, 2)"), Ok("false".to_string()));
+        assert_eq!(eval("1 == \"1\""), Ok("false".to_string()));
+        assert_eq!(eval("{} == {}"), Ok("true".to_string()));
+    }
+
+    #[test]
+    fn eval_string_ordering() {
+        let eval = |s| {
+            crate::parse(s)
+                .unwrap()
+                .eval(&mut Env::default())
+                .map(|val| val.to_string())
+        };
+
+        assert_eq!(eval(r#""apple" < "banana""#), Ok("true".to_string()));
+        assert_eq!(eval(r#""app" <= "apple""#), Ok("true".to_string()));
+        assert_eq!(eval(r#""Z" > "a""#), Ok("false".to_string()));
+        assert_eq!(eval(r#""b" >= "b""#), Ok("true".to_string()));
+    }
+
+    #[test]
+    fn eval_incomparable_ordering() {
+        assert_eq!(
+            crate::parse("let a = 1 < \"2\"")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Err(RuntimeError::Incomparable {
+                lhs: "number",
+                rhs: "string",
+                span: 8..15,
+            }),
+        );
+    }
+
+    #[test]
+    fn eval_unit() {
+        assert_eq!(
+            crate::parse("let a = ()\nlet b = {}\n[a == b, a == ()]")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Ok(Val::List(vec![Val::Bool(true), Val::Bool(true)])),
+        );
+    }
+
+    #[test]
+    fn eval_arithmetic_on_unit() {
+        assert_eq!(
+            crate::parse("() * 2").unwrap().eval(&mut Env::default()),
+            Err(RuntimeError::InvalidOperands {
+                op: Op::Mul,
+                lhs: "unit",
+                rhs: "number",
+                span: 0..6,
+            }),
+        );
+    }
+
+    #[test]
+    fn parse_index_binds_tighter_than_operators() {
+        assert_eq!(
+            utils::parse_str(Expr::new, "~xs[0] ?? ys?[1]"),
+            Ok((
+                "",
+                Expr::Operation {
+                    lhs: Box::new(Expr::Unary {
+                        op: UnaryOp::BitNot,
+                        operand: Box::new(Expr::Index {
+                            base: Box::new(Expr::BindingUsage(BindingUsage {
+                                name: "xs".into(),
+                                span: Span::default(),
+                            })),
+                            index: Box::new(Expr::Number(Number(0.into()), Span::default())),
+                            safe: false,
+                            span: Span::default(),
+                        }),
+                        span: Span::default(),
+                    }),
+                    rhs: Box::new(Expr::Index {
+                        base: Box::new(Expr::BindingUsage(BindingUsage {
+                            name: "ys".into(),
+                            span: Span::default(),
+                        })),
+                        index: Box::new(Expr::Number(Number(1.into()), Span::default())),
+                        safe: true,
+                        span: Span::default(),
+                    }),
+                    op: Op::Coalesce,
+                    span: Span::default(),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_pipeline_as_nested_calls() {
+        assert_eq!(
+            utils::parse_str(Expr::new, "x |> f |> g(2)"),
+            Ok((
+                "",
+                Expr::FuncCall(FuncCall {
+                    callee: "g".into(),
+                    named: Vec::new(),
+                    params: vec![
+                        Expr::FuncCall(FuncCall {
+                            callee: "f".into(),
+                            named: Vec::new(),
+                            params: vec![Expr::BindingUsage(BindingUsage {
+                                name: "x".into(),
+                                span: Span::default(),
+                            })],
+                            style: CallStyle::Piped(0),
+                            span: Span::default(),
+                        }),
+                        Expr::Number(Number(2.into()), Span::default()),
+                    ],
+                    style: CallStyle::Piped(0),
+                    span: Span::default(),
+                }),
+            )),
+        );
+        assert_eq!(
+            utils::parse_str(Expr::new, "x |> 1"),
+            Err("expected a function to pipe into".to_string()),
+        );
+    }
+
+    #[test]
+    fn eval_pipeline_between_comparison_and_arithmetic() {
+        let eval = |s: &str| {
+            crate::parse(&format!("fn double x => x * 2\nfn sub x y => x - y\n{}", s))
+                .unwrap()
+                .eval(&mut Env::default())
+                .map(|val| val.to_string())
+        };
+
+        assert_eq!(eval("1 + 2 |> double |> sub(1)"), Ok("5".to_string()));
+        assert_eq!(eval("3 |> double == 6"), Ok("true".to_string()));
+        assert_eq!(eval("(3 |> double) + 1"), Ok("7".to_string()));
+        assert_eq!(
+            eval("let from_ten = sub(10, _)\n2 |> from_ten |> double"),
+            Ok("16".to_string())
+        );
+        assert_eq!(eval("nil ?? 4 |> double"), Ok("8".to_string()));
+    }
+
+    #[test]
+    fn eval_pipeline_into_placeholder() {
+        let program = crate::parse(
+            "fn sub x y => x - y\n\
+             let a = 10 |> sub(_, 2)\n\
+             let b = 10 |> sub(2, _)\n\
+             let c = 10 |> sub(2)\n\
+             let d = 10 |> sub(_, _)\n\
+             [a, b, c, d(1)]",
+        )
+        .unwrap();
+
+        for backend in [Backend::TreeWalk, Backend::Vm] {
+            assert_eq!(
+                Interpreter::with_backend(backend)
+                    .eval(&program)
+                    .map(|val| val.to_string()),
+                Ok("[8, -8, 8, 9]".to_string()),
+            );
+        }
+    }
+
+    #[test]
+    fn parse_method_call_as_call() {
+        assert_eq!(
+            utils::parse_str(Expr::new, "xs[0].f(1)"),
+            Ok((
+                "",
+                Expr::FuncCall(FuncCall {
+                    callee: "f".into(),
+                    named: Vec::new(),
+                    params: vec![
+                        Expr::Index {
+                            base: Box::new(Expr::BindingUsage(BindingUsage {
+                                name: "xs".into(),
+                                span: Span::default(),
+                            })),
+                            index: Box::new(Expr::Number(Number(0.into()), Span::default())),
+                            safe: false,
+                            span: Span::default(),
+                        },
+                        Expr::Number(Number(1.into()), Span::default()),
+                    ],
+                    style: CallStyle::Method,
+                    span: Span::default(),
+                }),
+            )),
+        );
+        assert_eq!(
+            utils::parse_str(Expr::new, "xs.1"),
+            Err("expected field or method name after .".to_string()),
+        );
+    }
+
+    #[test]
+    fn eval_method_calls() {
+        let eval = |s| {
+            crate::parse(s)
+                .unwrap()
+                .eval(&mut Env::default())
+                .map(|val| val.to_string())
+        };
+
+        assert_eq!(eval("\"abc\".upper()"), Ok("\"ABC\"".to_string()));
+        assert_eq!(
+            eval("fn sub x y => x - y\n[1, 2].len().sub(5) + 1"),
+            Ok("-2".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_record_and_field() {
+        assert_eq!(
+            utils::parse_str(Expr::new, "{y: 1, x: p}.x"),
+            Ok((
+                "",
+                Expr::Field {
+                    base: Box::new(Expr::Record(
+                        vec![
+                            ("y".into(), Expr::Number(Number(1.into()), Span::default())),
+                            (
+                                "x".into(),
+                                Expr::BindingUsage(BindingUsage {
+                                    name: "p".into(),
+                                    span: Span::default(),
+                                })
+                            ),
+                        ],
+                        Span::default()
+                    )),
+                    name: "x".into(),
+                    span: Span::default(),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_records() {
+        let eval = |s| {
+            crate::parse(s)
+                .unwrap()
+                .eval(&mut Env::default())
+                .map(|val| val.to_string())
+        };
+
+        assert_eq!(
+            eval(
+                "let p = {y: 2, x: 1}
+p"
+            ),
+            Ok("{x: 1, y: 2}".to_string())
+        );
+        assert_eq!(
+            eval(
+                "let p = {x: {y: [3]}}
+p.x.y[0] + p.x.y.len()"
+            ),
+            Ok("4".to_string())
+        );
+        assert_eq!(eval("{x: 1, y: 2} == {y: 2, x: 1}"), Ok("true".to_string()));
+        assert_eq!(eval("{x: 1} == {x: 1, y: 2}"), Ok("false".to_string()));
+        assert_eq!(
+            eval("{x: 1}.y"),
+            Err(RuntimeError::Message("record has no field 'y'".to_string())),
+        );
+        assert_eq!(
+            eval("{x: 1, x: 2}"),
+            Err(RuntimeError::Message(
+                "field 'x' was given twice".to_string()
+            )),
+        );
+        assert_eq!(
+            eval("[1].x"),
+            Err(RuntimeError::Message(
+                "cannot access field 'x' of list".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_index_and_coalesce() {
+        let eval = |s| {
+            crate::parse(s)
+                .unwrap()
+                .eval(&mut Env::default())
+                .map(|val| val.to_string())
+        };
+
+        assert_eq!(eval("[[1, 2], (3, 4)][1][0]"), Ok("3".to_string()));
+        assert_eq!(eval("[1]?[5]"), Ok("nil".to_string()));
+        assert_eq!(eval("nil?[0]"), Ok("nil".to_string()));
+        assert_eq!(eval("[1]?[5] ?? 0"), Ok("0".to_string()));
+        assert_eq!(eval("1 ?? 2 / 0"), Ok("1".to_string()));
+        assert_eq!(eval("nil ?? nil"), Ok("nil".to_string()));
+    }
+
+    #[test]
+    fn eval_index_out_of_range() {
+        assert_eq!(
+            crate::parse("let xs = [1, 2]\nxs[2]")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Err(RuntimeError::IndexOutOfRange {
+                index: 2.into(),
+                len: 2,
+                span: 16..21,
+            }),
+        );
+        assert_eq!(
+            crate::parse("nil[0]").unwrap().eval(&mut Env::default()),
+            Err(RuntimeError::Message("cannot index into nil".to_string())),
+        );
+    }
+
+    #[test]
+    fn eval_string_index_and_slices() {
+        let eval = |s| {
+            crate::parse(s)
+                .unwrap()
+                .eval(&mut Env::default())
+                .map(|val| val.to_string())
+        };
+
+        assert_eq!(
+            eval("let s = \"héllo\"\n(s[1], s[1..4], s[0..=0], s[5..5], [1, 2, 3][1..3])"),
+            Ok("(\"é\", \"éll\", \"h\", \"\", [2, 3])".to_string()),
+        );
+        assert_eq!(eval("\"ab\"?[2] ?? \"ab\"?[1..3]"), Ok("nil".to_string()));
+    }
+
+    #[test]
+    fn eval_string_index_out_of_range() {
+        let eval = |s| crate::parse(s).unwrap().eval(&mut Env::default());
+
+        assert_eq!(
+            eval("\"héllo\"[5]"),
+            Err(RuntimeError::IndexOutOfRange {
+                index: 5.into(),
+                len: 5,
+                span: 0..11,
+            }),
+        );
+        assert_eq!(
+            eval("\"ab\"[1..=2]").map_err(|error| error.to_string()),
+            Err("slice 1..=2 is out of range for length 2".to_string()),
+        );
+        assert_eq!(
+            eval("\"abc\"[2..1]").map_err(|error| error.to_string()),
+            Err("slice 2..1 is out of range for length 3".to_string()),
+        );
+        assert_eq!(
+            eval("(1, 2)[0..1]"),
+            Err(RuntimeError::Message(
+                "cannot index tuple with range".to_string()
+            )),
         );
     }
 
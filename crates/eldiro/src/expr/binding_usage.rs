@@ -1,34 +1,47 @@
-use crate::expr::func_call::FuncCall;
-use crate::{env::Env, utils, val::Val};
+use crate::compat::prelude::*;
+use crate::error::RuntimeError;
+use crate::expr::func_call::{CallStyle, FuncCall};
+use crate::func_def::Partial;
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::{env::Env, lexer::Token, utils, val::Val};
 
+/// A reference to a binding by name.
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct BindingUsage {
-    pub(crate) name: String,
+pub struct BindingUsage {
+    pub name: Symbol,
+    pub span: Span,
 }
 
 impl BindingUsage {
-    pub(super) fn new(s: &str) -> Result<(&str, Self), String> {
-        let (s, name) = utils::extract_ident(s)?;
+    pub(super) fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let (tokens, name) = utils::extract_ident(tokens)?;
 
         Ok((
-            s,
+            tokens,
             Self {
-                name: name.to_string(),
+                name: Symbol::intern(name),
+                span: utils::consumed_span(start, tokens),
             },
         ))
     }
-    pub(super) fn eval(&self, env: &Env) -> Result<Val, String> {
-        env.get_binding(&self.name).or_else(|error_msg| {
-            if env.get_func(&self.name).is_ok() {
-                FuncCall {
-                    callee: self.name.clone(),
-                    params: Vec::new(),
-                }
-                .eval(env)
-            } else {
-                Err(error_msg)
-            }
-        })
+    pub(super) fn eval(&self, env: &mut Env) -> Result<Val, RuntimeError> {
+        env.get_binding(self.name)
+            .or_else(|error_msg| match env.get_func(self.name) {
+                Ok(func) => match Partial::of(self.name, &func) {
+                    Some(partial) => Ok(Val::Func(partial)),
+                    None => FuncCall {
+                        callee: self.name,
+                        style: CallStyle::Direct,
+                        named: Vec::new(),
+                        span: self.span.clone(),
+                        params: Vec::new(),
+                    }
+                    .eval(env),
+                },
+                Err(_) => Err(error_msg),
+            })
     }
 }
 #[cfg(test)]
@@ -38,11 +51,12 @@ mod tests {
     #[test]
     fn parse_binding_usage() {
         assert_eq!(
-            BindingUsage::new("abc"),
+            utils::parse_str(BindingUsage::new, "abc"),
             Ok((
                 "",
                 BindingUsage {
-                    name: "abc".to_string(),
+                    name: "abc".into(),
+                    span: Span::default(),
                 },
             )),
         );
@@ -50,14 +64,15 @@ mod tests {
     #[test]
     fn eval_existing_binding_usage() {
         let mut env = Env::default();
-        env.store_binding("foo".to_string(), Val::Number(10));
+        env.store_binding("foo".into(), Val::Number(10.into()));
 
         assert_eq!(
             BindingUsage {
-                name: "foo".to_string(),
+                name: "foo".into(),
+                span: Span::default(),
             }
-            .eval(&env),
-            Ok(Val::Number(10)),
+            .eval(&mut env),
+            Ok(Val::Number(10.into())),
         );
     }
 }
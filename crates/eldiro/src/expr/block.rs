@@ -1,39 +1,55 @@
+use crate::compat::prelude::*;
 use crate::env::Env;
-use crate::stmt::{self, Stmt};
+use crate::error::RuntimeError;
+use crate::lexer::{Token, TokenKind};
+use crate::span::Span;
+use crate::stmt::Stmt;
 use crate::utils;
 use crate::val::Val;
 
-use super::binding_usage;
+/// Statements in braces, which get a scope of their own.
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct Block {
-    pub(crate) stmts: Vec<Stmt>,
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    pub span: Span,
 }
 
 impl Block {
-    pub(super) fn new(s: &str) -> Result<(&str, Self), String> {
-        let s = utils::tag("{", s)?;
-        let (s, _) = utils::extract_whitespace(s);
+    pub(super) fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        utils::once(utils::Memo::Block, Self::new_uncached, tokens)
+    }
+
+    fn new_uncached(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let tokens = utils::expect(TokenKind::LBrace, tokens)?;
 
-        let (s, stmts) = utils::sequence(Stmt::new, utils::extract_whitespace, s)?;
+        let (tokens, stmts) = utils::lines(Stmt::new, tokens)?;
 
-        let (s, _) = utils::extract_whitespace(s);
-        let s = utils::tag("}", s)?;
-        Ok((s, Self { stmts }))
+        let tokens = utils::expect(TokenKind::RBrace, tokens)?;
+        Ok((
+            tokens,
+            Self {
+                stmts,
+                span: utils::consumed_span(start, tokens),
+            },
+        ))
     }
-    pub(super) fn eval(&self, env: &Env) -> Result<Val, String> {
+
+    pub(super) fn eval(&self, env: &mut Env) -> Result<Val, RuntimeError> {
         if self.stmts.is_empty() {
             return Ok(Val::Unit);
         }
 
-        let mut child_env = env.create_child();
-
-        let stmts_except_last = &self.stmts[..self.stmts.len() - 1];
-        for stmt in stmts_except_last {
-            stmt.eval(&mut child_env)?;
-        }
+        env.in_nested_scope(|child_env| {
+            let stmts_except_last = &self.stmts[..self.stmts.len() - 1];
+            for stmt in stmts_except_last {
+                stmt.eval(child_env)?;
+            }
 
-        // We can unwrap safely here because we have already checked whether self.stmts is empty.
-        self.stmts.last().unwrap().eval(&mut child_env)
+            // We can unwrap safely here because we have already checked whether self.stmts is
+            // empty.
+            self.stmts.last().unwrap().eval(child_env)
+        })
     }
 }
 
@@ -46,24 +62,31 @@ mod tests {
     #[test]
     fn eval_block_using_bindings_from_parent_env() {
         let mut env = Env::default();
-        env.store_binding("foo".to_string(), Val::Number(2));
+        env.store_binding("foo".into(), Val::Number(2.into()));
 
         assert_eq!(
             Block {
                 stmts: vec![
                     Stmt::BindingDef(BindingDef {
-                        name: "baz".to_string(),
+                        name: "baz".into(),
+                        constant: false,
+                        ty: None,
+                        span: Span::default(),
                         val: Expr::BindingUsage(BindingUsage {
-                            name: "foo".to_string(),
+                            name: "foo".into(),
+                            span: Span::default(),
                         }),
+                        doc: None,
                     }),
                     Stmt::Expr(Expr::BindingUsage(BindingUsage {
-                        name: "baz".to_string(),
+                        name: "baz".into(),
+                        span: Span::default(),
                     })),
                 ],
+                span: Span::default(),
             }
-            .eval(&env),
-            Ok(Val::Number(2)),
+            .eval(&mut env),
+            Ok(Val::Number(2.into())),
         );
     }
     #[test]
@@ -72,20 +95,33 @@ mod tests {
             Block {
                 stmts: vec![
                     Stmt::BindingDef(BindingDef {
-                        name: "foo".to_string(),
-                        val: Expr::Number(Number(5)),
+                        name: "foo".into(),
+                        constant: false,
+                        ty: None,
+                        span: Span::default(),
+                        val: Expr::Number(Number(5.into()), Span::default()),
+                        doc: None,
                     }),
                     Stmt::BindingDef(BindingDef {
-                        name: "bar".to_string(),
-                        val: Expr::Number(Number(4)),
+                        name: "bar".into(),
+                        constant: false,
+                        ty: None,
+                        span: Span::default(),
+                        val: Expr::Number(Number(4.into()), Span::default()),
+                        doc: None,
                     }),
                     Stmt::BindingDef(BindingDef {
-                        name: "baz".to_string(),
-                        val: Expr::Number(Number(3)),
+                        name: "baz".into(),
+                        constant: false,
+                        ty: None,
+                        span: Span::default(),
+                        val: Expr::Number(Number(3.into()), Span::default()),
+                        doc: None,
                     }),
                 ],
+                span: Span::default(),
             }
-            .eval(&Env::default()),
+            .eval(&mut Env::default()),
             Ok(Val::Unit),
         );
     }
@@ -95,17 +131,19 @@ mod tests {
         assert_eq!(
             Block {
                 stmts: vec![
-                    Stmt::Expr(Expr::Number(Number(100))),
-                    Stmt::Expr(Expr::Number(Number(30))),
+                    Stmt::Expr(Expr::Number(Number(100.into()), Span::default())),
+                    Stmt::Expr(Expr::Number(Number(30.into()), Span::default())),
                     Stmt::Expr(Expr::Operation {
-                        lhs: Box::new(Expr::Number(Number(10))),
-                        rhs: Box::new(Expr::Number(Number(7))),
+                        lhs: Box::new(Expr::Number(Number(10.into()), Span::default())),
+                        rhs: Box::new(Expr::Number(Number(7.into()), Span::default())),
                         op: Op::Sub,
+                        span: Span::default(),
                     }),
                 ],
+                span: Span::default(),
             }
-            .eval(&Env::default()),
-            Ok(Val::Number(3)),
+            .eval(&mut Env::default()),
+            Ok(Val::Number(3.into())),
         );
     }
 
@@ -115,16 +153,22 @@ mod tests {
             Block {
                 stmts: vec![
                     Stmt::BindingDef(BindingDef {
-                        name: "one".to_string(),
-                        val: Expr::Number(Number(1)),
+                        name: "one".into(),
+                        constant: false,
+                        ty: None,
+                        span: Span::default(),
+                        val: Expr::Number(Number(1.into()), Span::default()),
+                        doc: None,
                     }),
                     Stmt::Expr(Expr::BindingUsage(BindingUsage {
-                        name: "one".to_string(),
+                        name: "one".into(),
+                        span: Span::default(),
                     })),
                 ],
+                span: Span::default(),
             }
-            .eval(&Env::default()),
-            Ok(Val::Number(1)),
+            .eval(&mut Env::default()),
+            Ok(Val::Number(1.into())),
         );
     }
 
@@ -132,37 +176,52 @@ mod tests {
     fn eval_block_with_one_expr() {
         assert_eq!(
             Block {
-                stmts: vec![Stmt::Expr(Expr::Number(Number(25)))],
+                stmts: vec![Stmt::Expr(Expr::Number(Number(25.into()), Span::default()))],
+                span: Span::default(),
             }
-            .eval(&Env::default()),
-            Ok(Val::Number(25)),
+            .eval(&mut Env::default()),
+            Ok(Val::Number(25.into())),
         );
     }
 
     #[test]
     fn eval_empty_block() {
         assert_eq!(
-            Block { stmts: Vec::new() }.eval(&Env::default()),
+            Block {
+                stmts: Vec::new(),
+                span: Span::default(),
+            }
+            .eval(&mut Env::default()),
             Ok(Val::Unit),
         );
     }
     #[test]
     fn parse_bolck() {
-        assert_eq!(Block::new("{}"), Ok(("", Block { stmts: Vec::new() })))
+        assert_eq!(
+            utils::parse_str(Block::new, "{}"),
+            Ok((
+                "",
+                Block {
+                    stmts: Vec::new(),
+                    span: Span::default(),
+                }
+            ))
+        )
     }
 
     #[test]
     fn cannot_parse_binding_def_without_space_after_let() {
         assert_eq!(
-            BindingDef::new("letaaa=1+2"),
-            Err("expected whitespace".to_string()),
+            utils::parse_str(BindingDef::new, "letaaa=1+2"),
+            Err("expected let".to_string()),
         );
     }
 
     #[test]
     fn parse_block_with_multiple_stmts() {
         assert_eq!(
-            Block::new(
+            utils::parse_str(
+                Block::new,
                 "{
     let a = 10
     let b = a
@@ -174,21 +233,90 @@ mod tests {
                 Block {
                     stmts: vec![
                         Stmt::BindingDef(BindingDef {
-                            name: "a".to_string(),
-                            val: Expr::Number(Number(10)),
+                            name: "a".into(),
+                            constant: false,
+                            ty: None,
+                            span: Span::default(),
+                            val: Expr::Number(Number(10.into()), Span::default()),
+                            doc: None,
                         }),
                         Stmt::BindingDef(BindingDef {
-                            name: "b".to_string(),
+                            name: "b".into(),
+                            constant: false,
+                            ty: None,
+                            span: Span::default(),
                             val: Expr::BindingUsage(BindingUsage {
-                                name: "a".to_string(),
+                                name: "a".into(),
+                                span: Span::default(),
                             }),
+                            doc: None,
                         }),
                         Stmt::Expr(Expr::BindingUsage(BindingUsage {
-                            name: "b".to_string(),
+                            name: "b".into(),
+                            span: Span::default(),
                         })),
                     ],
+                    span: Span::default(),
                 },
             )),
         );
     }
+
+    /// Evaluates `source` by walking the tree and on the VM, which have to agree.
+    fn eval_both(source: &str) -> Result<Val, RuntimeError> {
+        let program = crate::parse(source).unwrap();
+        let walked = program.eval(&mut Env::default());
+        assert_eq!(program.compile().eval(&mut Env::default()), walked);
+        walked
+    }
+
+    #[test]
+    fn bindings_made_in_block_disappear_on_exit() {
+        assert_eq!(
+            eval_both("let a = { let inner = 2\ninner * 3 }\ninner"),
+            Err(RuntimeError::Message(
+                "binding with name 'inner' does not exist".to_string()
+            )),
+        );
+        assert_eq!(
+            eval_both("{ fn local x => x }\nlocal(1)"),
+            Err(RuntimeError::Message(
+                "function with name 'local' does not exist".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn shadowing_in_block_leaves_outer_binding() {
+        assert_eq!(
+            eval_both("let x = 1\nlet y = { let x = x + 10\nx }\n[x, y]"),
+            Ok(Val::List(vec![
+                Val::Number(1.into()),
+                Val::Number(11.into())
+            ])),
+        );
+        assert_eq!(
+            eval_both("const rate = 2\nlet y = { const rate = 5\nrate }\n[rate, y]"),
+            Ok(Val::List(vec![
+                Val::Number(2.into()),
+                Val::Number(5.into())
+            ])),
+        );
+    }
+
+    #[test]
+    fn nested_blocks_see_every_outer_scope() {
+        assert_eq!(
+            eval_both("let a = 1\n{ let b = 2\n{ let c = 3\na + b + c } }"),
+            Ok(Val::Number(6.into())),
+        );
+    }
+
+    #[test]
+    fn funcs_defined_in_block_see_its_bindings() {
+        assert_eq!(
+            eval_both("let a = 1\n{ let b = 10\nfn f x => x + a + b\nf(100) }"),
+            Ok(Val::Number(111.into())),
+        );
+    }
 }
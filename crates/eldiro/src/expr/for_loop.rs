@@ -0,0 +1,161 @@
+use super::{Block, Expr};
+use crate::compat::prelude::*;
+use crate::env::Env;
+use crate::error::RuntimeError;
+use crate::lexer::{Token, TokenKind};
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::utils;
+use crate::val::Val;
+
+/// `for x in xs { ... }`, which runs the body once for each item of `xs` with the item bound to
+/// `x`. The loop itself evaluates to `()`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct For {
+    pub name: Symbol,
+    pub iterable: Box<Expr>,
+    pub body: Block,
+    pub span: Span,
+}
+
+impl For {
+    pub(super) fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let tokens = utils::keyword("for", tokens)?;
+        let (tokens, name) = utils::extract_ident(tokens)?;
+        let tokens = utils::keyword("in", tokens)?;
+
+        let head = head(tokens);
+        let (rest, iterable) = Expr::new(head)?;
+        if !rest.is_empty() {
+            return Err("expected {".to_string());
+        }
+        let (tokens, body) = Block::new(&tokens[head.len()..])?;
+
+        Ok((
+            tokens,
+            Self {
+                name: Symbol::intern(name),
+                iterable: Box::new(iterable),
+                body,
+                span: utils::consumed_span(start, tokens),
+            },
+        ))
+    }
+
+    pub(super) fn eval(&self, env: &mut Env) -> Result<Val, RuntimeError> {
+        let iterable = self.iterable.eval(env)?;
+
+        for item in iterable.iter()? {
+            env.in_nested_scope(|body_env| {
+                body_env.store_binding(self.name, item);
+                self.body.eval(body_env)
+            })?;
+        }
+
+        Ok(Val::Unit)
+    }
+}
+
+/// The tokens before the `{` that starts the body, so that `for x in xs { ... }` doesn't pass
+/// the body to `xs` as an argument. A `{` right at the start begins a record or block instead.
+fn head(tokens: &[Token]) -> &[Token] {
+    let mut depth = 0_usize;
+
+    let end = tokens.iter().enumerate().position(|(idx, token)| {
+        match token.kind {
+            TokenKind::LBrace if depth == 0 && idx > 0 => return true,
+            TokenKind::LParen
+            | TokenKind::LBracket
+            | TokenKind::QuestionLBracket
+            | TokenKind::LBrace => depth += 1,
+            TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => {
+                depth = depth.saturating_sub(1)
+            }
+            _ => {}
+        }
+
+        false
+    });
+
+    &tokens[..end.unwrap_or(tokens.len())]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::BindingUsage;
+    use crate::stmt::Stmt;
+
+    fn eval(s: &str) -> Result<Val, RuntimeError> {
+        crate::parse(s).unwrap().eval(&mut Env::default())
+    }
+
+    #[test]
+    fn parse_for() {
+        assert_eq!(
+            utils::parse_str(For::new, "for x in xs { x }"),
+            Ok((
+                "",
+                For {
+                    name: "x".into(),
+                    iterable: Box::new(Expr::BindingUsage(BindingUsage {
+                        name: "xs".into(),
+                        span: Span::default(),
+                    })),
+                    body: Block {
+                        stmts: vec![Stmt::Expr(Expr::BindingUsage(BindingUsage {
+                            name: "x".into(),
+                            span: Span::default(),
+                        }))],
+                        span: Span::default(),
+                    },
+                    span: Span::default(),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn run_body_for_each_item() {
+        assert_eq!(
+            eval("fn check x => assert(x != \"b\", x)\nfor c in \"abc\" { check(c) }"),
+            Err(RuntimeError::Message("assertion failed: b".to_string())),
+        );
+        assert_eq!(eval("for x in [1, 2] { x }"), Ok(Val::Unit));
+        assert_eq!(
+            eval("for f in {a: 1} { throw f }"),
+            Err(RuntimeError::Thrown(Val::Tuple(vec![
+                Val::Str("a".to_string()),
+                Val::Number(1.into()),
+            ]))),
+        );
+    }
+
+    #[test]
+    fn loop_over_range() {
+        assert_eq!(
+            eval("fn check n => assert(n < 3, n)\nfor n in 1..=3 { check(n) }"),
+            Err(RuntimeError::Message("assertion failed: 3".to_string())),
+        );
+        assert_eq!(eval("for n in 3..3 { throw n }"), Ok(Val::Unit));
+    }
+
+    #[test]
+    fn item_stays_in_the_loop() {
+        assert_eq!(
+            eval("let x = 0\nfor x in [1] { x }\nx"),
+            Ok(Val::Number(0.into())),
+        );
+    }
+
+    #[test]
+    fn fail_on_non_iterable() {
+        assert_eq!(
+            eval("for x in 1 { x }"),
+            Err(RuntimeError::Message(
+                "cannot iterate over number".to_string()
+            )),
+        );
+    }
+}
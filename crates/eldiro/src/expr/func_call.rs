@@ -1,52 +1,326 @@
 use super::utils;
 use super::Expr;
+use crate::compat::prelude::*;
+use crate::env::Func;
+use crate::error::RuntimeError;
+use crate::func_def::{self, Partial};
+use crate::lexer::{Token, TokenKind};
+use crate::span::Span;
+use crate::symbol::Symbol;
 use crate::Env;
 use crate::Val;
 
+/// A call of a named function, written either `f(a, b)` or `f a b`.
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) struct FuncCall {
-    pub(crate) callee: String,
-    pub(crate) params: Vec<Expr>,
+pub struct FuncCall {
+    pub callee: Symbol,
+    pub named: Vec<(Symbol, Expr)>,
+    pub params: Vec<Expr>,
+    pub style: CallStyle,
+    pub span: Span,
+}
+
+/// How a call was written, which only matters for formatting it back out. Desugared calls have
+/// one of their arguments written before the callee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallStyle {
+    /// `f(a, b)` or `f a b`.
+    Direct,
+    /// `a |> f(b)`, or `a |> f(b, _)` with `a` in place of the `_`. Holds the index of the
+    /// argument that `a` became.
+    Piped(usize),
+    /// `a.f(b)`.
+    Method,
+}
+
+/// An argument inside the parentheses of a call, which is either positional or `name: val`. A
+/// positional argument can be `_` to leave it out.
+enum Arg {
+    Positional(Expr),
+    Named(Symbol, Expr),
+}
+
+impl Arg {
+    fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        if let Ok((after_name, name)) = utils::extract_ident(tokens) {
+            if let Ok(tokens) = utils::expect(TokenKind::Colon, after_name) {
+                let (tokens, val) = Expr::new(tokens)?;
+                return Ok((tokens, Self::Named(Symbol::intern(name), val)));
+            }
+
+            if name == "_"
+                && matches!(
+                    after_name.first(),
+                    Some(Token {
+                        kind: TokenKind::Comma | TokenKind::RParen,
+                        ..
+                    })
+                )
+            {
+                return Ok((
+                    after_name,
+                    Self::Positional(Expr::Placeholder(utils::consumed_span(tokens, after_name))),
+                ));
+            }
+        }
+
+        let (tokens, val) = Expr::new(tokens)?;
+        Ok((tokens, Self::Positional(val)))
+    }
 }
 
 impl FuncCall {
-    pub(crate) fn new(s: &str) -> Result<(&str, Self), String> {
-        let (s, callee) = utils::extract_ident(s)?;
-        let (s, _) = utils::take_while(|c| c == ' ', s);
+    pub(crate) fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        // With the bracket right after the name this can only be a parenthesized call. Trying it
+        // as `f (…)` as well would parse everything inside the brackets twice, and so on for
+        // every call nested inside, taking exponential time on input like `f(f(f(`.
+        match tokens {
+            [callee, paren, ..]
+                if matches!(callee.kind, TokenKind::Ident(_))
+                    && paren.kind == TokenKind::LParen
+                    && paren.span.start == callee.span.end =>
+            {
+                Self::new_parenthesized(tokens)
+            }
+            _ => Self::new_juxtaposed(tokens),
+        }
+    }
 
-        let (s, params) = utils::sequence1(Expr::new, |s| utils::take_while(|c| c == ' ', s), s)?;
+    fn new_parenthesized(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        utils::once(
+            utils::Memo::CallArgs,
+            Self::new_parenthesized_uncached,
+            tokens,
+        )
+    }
+
+    fn new_parenthesized_uncached(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let (after_callee, callee) = utils::extract_ident(tokens)?;
+
+        // `f (x)` isn't a parenthesized call; the bracket has to follow the name directly.
+        if after_callee.first().map(|token| token.span.start) != Some(tokens[0].span.end) {
+            return Err("expected (".to_string());
+        }
+
+        let start = tokens;
+        let tokens = utils::expect(TokenKind::LParen, after_callee)?;
+        let (tokens, args) = utils::comma_separated(Arg::new, TokenKind::RParen, tokens)?;
+
+        let mut params = Vec::new();
+        let mut named = Vec::new();
+        for arg in args {
+            match arg {
+                Arg::Positional(_) if !named.is_empty() => {
+                    return Err("positional arguments have to come before named ones".to_string())
+                }
+                Arg::Positional(val) => params.push(val),
+                Arg::Named(name, val) => named.push((name, val)),
+            }
+        }
 
         Ok((
-            s,
+            tokens,
             Self {
-                callee: callee.to_string(),
+                callee: Symbol::intern(callee),
+                style: CallStyle::Direct,
+                named,
                 params,
+                span: utils::consumed_span(start, tokens),
             },
         ))
     }
 
-    pub(super) fn eval(&self, env: &Env) -> Result<Val, String> {
-        let mut child_env = env.create_child();
+    /// Parses the `.f(b)` of `a.f(b)`, which calls `f` with `receiver` as its first argument.
+    /// `start` is where the receiver began.
+    pub(super) fn new_method<'t>(
+        receiver: Expr,
+        start: &'t [Token],
+        tokens: &'t [Token],
+    ) -> Result<(&'t [Token], Self), String> {
+        let tokens = utils::expect(TokenKind::Dot, tokens)?;
+        let (tokens, mut func_call) = Self::new_parenthesized(tokens)?;
+        func_call.params.insert(0, receiver);
+        func_call.style = CallStyle::Method;
+        func_call.span = utils::consumed_span(start, tokens);
+
+        Ok((tokens, func_call))
+    }
+
+    fn new_juxtaposed(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let (after_callee, callee) = utils::extract_ident(tokens)?;
 
-        let (param_names, body) = env.get_func(&self.callee)?;
+        // `f[0]` indexes into `f` rather than passing it a list.
+        if let Some(Token {
+            kind: TokenKind::LBracket,
+            span,
+            ..
+        }) = after_callee.first()
+        {
+            if span.start == tokens[0].span.end {
+                return Err("expected argument".to_string());
+            }
+        }
 
-        let num_expected_params = param_names.len();
-        let num_actual_params = self.params.len();
+        let start = tokens;
+        let tokens = after_callee;
+        // `f -1` subtracts from `f`, so a negative argument needs brackets.
+        let (tokens, params) = utils::sequence1(
+            |tokens| match utils::same_line(tokens)?.first() {
+                Some(Token {
+                    kind: TokenKind::Minus,
+                    ..
+                }) => Err("expected argument".to_string()),
+                _ => Expr::new(tokens),
+            },
+            tokens,
+        )?;
+
+        Ok((
+            tokens,
+            Self {
+                callee: Symbol::intern(callee),
+                style: CallStyle::Direct,
+                named: Vec::new(),
+                params,
+                span: utils::consumed_span(start, tokens),
+            },
+        ))
+    }
 
-        if num_expected_params != num_actual_params {
-            return Err(format!(
-                "expected {} parameters, got {}",
-                num_expected_params, num_actual_params,
-            ));
+    pub(super) fn eval(&self, env: &mut Env) -> Result<Val, RuntimeError> {
+        if let Ok(Val::Func(partial)) = env.get_binding(self.callee) {
+            return self.eval_partial(&partial, env);
         }
 
-        for (param_name, param_expr) in param_names.into_iter().zip(&self.params) {
-            let param_val = param_expr.eval(&child_env)?;
-            child_env.store_binding(param_name, param_val);
+        let func = env.get_func(self.callee)?;
+        func_def::check_args(
+            self.callee,
+            &func,
+            self.params.len(),
+            self.named.len(),
+            &self.span,
+        )?;
+
+        let args = self
+            .params
+            .iter()
+            .map(|param| match param {
+                Expr::Placeholder(_) => Ok(None),
+                param => param.eval(env).map(Some),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let named = self
+            .named
+            .iter()
+            .map(|(name, val)| Ok((*name, val.eval(env)?)))
+            .collect::<Result<Vec<_>, RuntimeError>>()?;
+
+        if args.iter().any(Option::is_none) {
+            return Ok(Val::Func(Partial::new(self.callee, func, args, named)?));
         }
 
-        body.eval(&mut child_env)
+        call(
+            self.callee,
+            args.into_iter().flatten().collect(),
+            named,
+            &self.span,
+            env,
+        )
     }
+
+    /// Calls a binding that holds a [`Partial`], filling in its `_`s with the arguments.
+    fn eval_partial(&self, partial: &Partial, env: &mut Env) -> Result<Val, RuntimeError> {
+        // Checked before evaluating the arguments, since `call_partial` can't see named ones.
+        partial.check_args(self.callee, self.params.len(), self.named.len(), &self.span)?;
+
+        let args = self
+            .params
+            .iter()
+            .map(|param| param.eval(env))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        call_partial(partial, self.callee, args, &self.span, env)
+    }
+}
+
+/// Calls the function value `partial`, which was reached through the name `name`, filling in its
+/// `_`s with `args`. Natives such as `map` use this to call the functions they are given.
+pub(crate) fn call_partial(
+    partial: &Partial,
+    name: Symbol,
+    args: Vec<Val>,
+    span: &Span,
+    env: &Env,
+) -> Result<Val, RuntimeError> {
+    partial.check_args(name, args.len(), 0, span)?;
+    let (args, named) = partial.fill(args);
+    func_def::check_args(partial.callee, &partial.func, args.len(), named.len(), span)?;
+
+    let scope = func_def::captured_scope(partial, env);
+    call_in(
+        partial.callee,
+        Func::clone(&partial.func),
+        &scope,
+        args,
+        named,
+        span,
+        env,
+    )
+}
+
+fn call(
+    callee: Symbol,
+    args: Vec<Val>,
+    named: Vec<(Symbol, Val)>,
+    span: &Span,
+    env: &Env,
+) -> Result<Val, RuntimeError> {
+    let (func, scope) = env.get_func_and_scope(callee)?;
+    call_in(callee, func, scope, args, named, span, env)
+}
+
+/// Calls `func`, which goes by the name `callee`, from `env` at `span`, evaluating its body in a
+/// child of `scope`.
+fn call_in(
+    callee: Symbol,
+    func: Func,
+    scope: &Env,
+    args: Vec<Val>,
+    named: Vec<(Symbol, Val)>,
+    span: &Span,
+    env: &Env,
+) -> Result<Val, RuntimeError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("call", callee = %callee).entered();
+
+    env.notify(|observer| observer.on_call(callee.as_str(), &args));
+    let _timer = env.profiler().map(|profiler| profiler.start(callee));
+
+    let (params, body) = match &func {
+        Func::User { params, body, .. } => (params.clone(), body.clone()),
+        Func::Native(native) => return native.call(env, &args),
+        Func::Constructor { ty, variant, .. } => {
+            return Ok(Val::Variant {
+                ty: *ty,
+                name: *variant,
+                fields: args,
+            })
+        }
+    };
+
+    let mut child_env = scope.create_child();
+    child_env.enter_call(callee, env, span)?;
+
+    func_def::bind_args(
+        callee,
+        &params,
+        args,
+        named,
+        &mut child_env,
+        |default, env| default.eval(env),
+    )
+    .and_then(|()| body.eval(&mut child_env))
+    .map_err(|error| func.locate(error))
 }
 
 #[cfg(test)]
@@ -60,26 +334,38 @@ mod tests {
         let mut env = Env::default();
 
         env.store_func(
-            "mul".to_string(),
-            vec!["a".to_string(), "b".to_string()],
+            "mul".into(),
+            &["a".into(), "b".into()],
             Stmt::Expr(Expr::Operation {
                 lhs: Box::new(Expr::BindingUsage(BindingUsage {
-                    name: "a".to_string(),
+                    name: "a".into(),
+                    span: Span::default(),
                 })),
                 rhs: Box::new(Expr::BindingUsage(BindingUsage {
-                    name: "b".to_string(),
+                    name: "b".into(),
+                    span: Span::default(),
                 })),
                 op: Op::Mul,
+                span: Span::default(),
             }),
         );
 
         assert_eq!(
             FuncCall {
-                callee: "mul".to_string(),
-                params: vec![Expr::Number(Number(100))],
+                callee: "mul".into(),
+                style: CallStyle::Direct,
+                named: Vec::new(),
+                span: Span::default(),
+                params: vec![Expr::Number(Number(100.into()), Span::default())],
             }
-            .eval(&env),
-            Err("expected 2 parameters, got 1".to_string()),
+            .eval(&mut env),
+            Err(RuntimeError::Arity {
+                callee: "mul".to_string(),
+                min: 2,
+                max: Some(2),
+                got: 1,
+                span: 0..0,
+            }),
         );
     }
 
@@ -88,39 +374,59 @@ mod tests {
         let mut env = Env::default();
 
         env.store_func(
-            "square".to_string(),
-            vec!["n".to_string()],
+            "square".into(),
+            &["n".into()],
             Stmt::Expr(Expr::Operation {
                 lhs: Box::new(Expr::BindingUsage(BindingUsage {
-                    name: "n".to_string(),
+                    name: "n".into(),
+                    span: Span::default(),
                 })),
                 rhs: Box::new(Expr::BindingUsage(BindingUsage {
-                    name: "n".to_string(),
+                    name: "n".into(),
+                    span: Span::default(),
                 })),
                 op: Op::Mul,
+                span: Span::default(),
             }),
         );
 
         assert_eq!(
             FuncCall {
-                callee: "square".to_string(),
-                params: vec![Expr::Number(Number(5)), Expr::Number(Number(42))],
+                callee: "square".into(),
+                style: CallStyle::Direct,
+                named: Vec::new(),
+                span: Span::default(),
+                params: vec![
+                    Expr::Number(Number(5.into()), Span::default()),
+                    Expr::Number(Number(42.into()), Span::default())
+                ],
             }
-            .eval(&env),
-            Err("expected 1 parameters, got 2".to_string()),
+            .eval(&mut env),
+            Err(RuntimeError::Arity {
+                callee: "square".to_string(),
+                min: 1,
+                max: Some(1),
+                got: 2,
+                span: 0..0,
+            }),
         );
     }
     #[test]
     fn eval_non_existent_func_call() {
-        let env = Env::default();
+        let mut env = Env::default();
 
         assert_eq!(
             FuncCall {
-                callee: "i_dont_exist".to_string(),
-                params: vec![Expr::Number(Number(1))],
+                callee: "i_dont_exist".into(),
+                style: CallStyle::Direct,
+                named: Vec::new(),
+                span: Span::default(),
+                params: vec![Expr::Number(Number(1.into()), Span::default())],
             }
-            .eval(&env),
-            Err("function with name 'i_dont_exist' does not exist".to_string()),
+            .eval(&mut env),
+            Err(RuntimeError::Message(
+                "function with name 'i_dont_exist' does not exist".to_string()
+            )),
         );
     }
     #[test]
@@ -128,26 +434,30 @@ mod tests {
         let mut env = Env::default();
 
         env.store_func(
-            "id".to_string(),
-            vec!["x".to_string()],
+            "id".into(),
+            &["x".into()],
             Stmt::Expr(Expr::BindingUsage(BindingUsage {
-                name: "x".to_string(),
+                name: "x".into(),
+                span: Span::default(),
             })),
         );
 
         assert_eq!(
             FuncCall {
-                callee: "id".to_string(),
-                params: vec![Expr::Number(Number(10))],
+                callee: "id".into(),
+                style: CallStyle::Direct,
+                named: Vec::new(),
+                span: Span::default(),
+                params: vec![Expr::Number(Number(10.into()), Span::default())],
             }
-            .eval(&env),
-            Ok(Val::Number(10)),
+            .eval(&mut env),
+            Ok(Val::Number(10.into())),
         );
     }
     // #[test]
     // fn parse_func_call_without_params() {
     //     assert_eq!(
-    //         FuncCall::new("foo"),
+    //         utils::parse_str(FuncCall::new, "foo"),
     //         Ok((
     //             "",
     //             FuncCall {
@@ -157,17 +467,331 @@ mod tests {
     //         ))
     //     );
     // }
+    #[test]
+    fn parse_parenthesized_func_call() {
+        assert_eq!(
+            utils::parse_str(FuncCall::new, "add(1, x)"),
+            Ok((
+                "",
+                FuncCall {
+                    callee: "add".into(),
+                    style: CallStyle::Direct,
+                    named: Vec::new(),
+                    span: Span::default(),
+                    params: vec![
+                        Expr::Number(Number(1.into()), Span::default()),
+                        Expr::BindingUsage(BindingUsage {
+                            name: "x".into(),
+                            span: Span::default(),
+                        }),
+                    ],
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_parenthesized_func_call_without_params() {
+        assert_eq!(
+            utils::parse_str(FuncCall::new, "now_ms()"),
+            Ok((
+                "",
+                FuncCall {
+                    callee: "now_ms".into(),
+                    style: CallStyle::Direct,
+                    named: Vec::new(),
+                    span: Span::default(),
+                    params: Vec::new(),
+                },
+            )),
+        );
+    }
+
     #[test]
     fn parse_func_call_with_num_params() {
         assert_eq!(
-            FuncCall::new("foo 1 2 "),
+            utils::parse_str(FuncCall::new, "foo 1 2 "),
             Ok((
                 "",
                 FuncCall {
                     callee: "foo".into(),
-                    params: vec![Expr::Number(Number(1)), Expr::Number(Number(2))],
+                    style: CallStyle::Direct,
+                    named: Vec::new(),
+                    span: Span::default(),
+                    params: vec![
+                        Expr::Number(Number(1.into()), Span::default()),
+                        Expr::Number(Number(2.into()), Span::default())
+                    ],
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_named_args() {
+        assert_eq!(
+            utils::parse_str(FuncCall::new, "area(1, height: 4)"),
+            Ok((
+                "",
+                FuncCall {
+                    callee: "area".into(),
+                    style: CallStyle::Direct,
+                    named: vec![(
+                        "height".into(),
+                        Expr::Number(Number(4.into()), Span::default())
+                    )],
+                    span: Span::default(),
+                    params: vec![Expr::Number(Number(1.into()), Span::default())],
+                }
+            ))
+        );
+        assert_eq!(
+            utils::parse_str(FuncCall::new_parenthesized, "area(width: 3, 4)"),
+            Err("positional arguments have to come before named ones".to_string()),
+        );
+    }
+
+    #[test]
+    fn match_named_args_to_params() {
+        let eval = |s: &str| {
+            crate::parse(&format!("fn area width height => width - height\n{}", s))
+                .unwrap()
+                .eval(&mut Env::default())
+        };
+
+        assert_eq!(
+            eval("area(height: 4, width: 3)"),
+            Ok(Val::Number((-1).into()))
+        );
+        assert_eq!(eval("area(5, height: 1)"), Ok(Val::Number(4.into())));
+        assert_eq!(
+            eval("area(5, width: 1)"),
+            Err(RuntimeError::Message(
+                "parameter 'width' of 'area' was given twice".to_string()
+            )),
+        );
+        assert_eq!(
+            eval("area(5, depth: 1)"),
+            Err(RuntimeError::Message(
+                "'area' has no parameter 'depth'".to_string()
+            )),
+        );
+        assert_eq!(
+            eval("area(height: 1)"),
+            Err(RuntimeError::Message(
+                "missing argument for parameter 'width' of 'area'".to_string()
+            )),
+        );
+        assert_eq!(
+            eval("now_ms(x: 1)"),
+            Err(RuntimeError::Message(
+                "'now_ms' doesn't take named arguments".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_placeholder_args() {
+        assert_eq!(
+            utils::parse_str(FuncCall::new, "add(_, _x)"),
+            Ok((
+                "",
+                FuncCall {
+                    callee: "add".into(),
+                    style: CallStyle::Direct,
+                    named: Vec::new(),
+                    span: Span::default(),
+                    params: vec![
+                        Expr::Placeholder(Span::default()),
+                        Expr::BindingUsage(BindingUsage {
+                            name: "_x".into(),
+                            span: Span::default(),
+                        }),
+                    ],
                 }
             ))
         );
     }
+
+    #[test]
+    fn apply_partially() {
+        let eval = |s: &str| {
+            crate::parse(&format!("fn sub x y, z = 0 => x - y - z\n{}", s))
+                .unwrap()
+                .eval(&mut Env::default())
+        };
+
+        assert_eq!(
+            eval("let from_ten = sub(10, _)\nfrom_ten(3)"),
+            Ok(Val::Number(7.into()))
+        );
+        assert_eq!(
+            eval("let f = sub(_, 1, z: 2)\nfn apply g x => g(x)\napply(f, 10)"),
+            Ok(Val::Number(7.into()))
+        );
+        assert_eq!(
+            eval("sub(_, 1, z: 2)").map(|val| val.to_string()),
+            Ok("sub(_, 1, z: 2)".to_string())
+        );
+        assert_eq!(
+            eval("let f = sub(_, x: 10)\nf(3)"),
+            Ok(Val::Number(7.into()))
+        );
+        assert_eq!(
+            eval("sub(_, 1, x: 2)").map(|val| val.to_string()),
+            Ok("sub(2, _, 1)".to_string())
+        );
+        assert_eq!(
+            eval("sub(_, w: 1)"),
+            Err(RuntimeError::Message(
+                "'sub' has no parameter 'w'".to_string()
+            )),
+        );
+        assert_eq!(
+            eval("let f = sub(_, _)\nf(1)"),
+            Err(RuntimeError::Arity {
+                callee: "f".to_string(),
+                min: 2,
+                max: Some(2),
+                got: 1,
+                span: 49..53,
+            }),
+        );
+        assert_eq!(
+            eval("sub(_, _, _, _)"),
+            Err(RuntimeError::Arity {
+                callee: "sub".to_string(),
+                min: 2,
+                max: Some(3),
+                got: 4,
+                span: 31..46,
+            }),
+        );
+    }
+
+    #[test]
+    fn partial_keeps_the_function_it_was_made_from() {
+        let eval = |s: &str| crate::parse(s).unwrap().eval(&mut Env::default());
+
+        assert_eq!(
+            eval("fn add x => x + 1\nlet h = add(_)\n{ fn add x => 0\nh(5) }"),
+            Ok(Val::Number(6.into()))
+        );
+        assert_eq!(
+            eval("fn f x => x + 1\nlet g = f(_)\nfn f x => x * 10\ng(2)"),
+            Ok(Val::Number(3.into()))
+        );
+        assert_eq!(
+            eval("fn adder n => {\nfn inner a b => a + b\ninner(n, _)\n}\nlet add = adder(10)\n(add(1), map([1, 2], add))"),
+            Ok(Val::Tuple(vec![
+                Val::Number(11.into()),
+                Val::List(vec![Val::Number(11.into()), Val::Number(12.into())]),
+            ]))
+        );
+        assert_eq!(
+            eval("fn counter => {\nfn down n acc => match n { 0 => acc, _ => down(n - 1, acc + 1) }\ndown(_, 0)\n}\nlet count = counter()\ncount(3)"),
+            Ok(Val::Number(3.into()))
+        );
+        assert_eq!(
+            eval("fn f x => x + y\nlet g = f(_)\n{ let y = 1\ng(1) }"),
+            Err(RuntimeError::Message(
+                "binding with name 'y' does not exist".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn eval_args_in_caller_scope() {
+        let mut env = Env::default();
+        env.store_binding("x".into(), Val::Number(5.into()));
+        env.store_func(
+            "second".into(),
+            &["x".into(), "y".into()],
+            Stmt::Expr(Expr::BindingUsage(BindingUsage {
+                name: "y".into(),
+                span: Span::default(),
+            })),
+        );
+
+        assert_eq!(
+            FuncCall {
+                callee: "second".into(),
+                style: CallStyle::Direct,
+                named: Vec::new(),
+                span: Span::default(),
+                params: vec![
+                    Expr::Number(Number(1.into()), Span::default()),
+                    Expr::BindingUsage(BindingUsage {
+                        name: "x".into(),
+                        span: Span::default(),
+                    }),
+                ],
+            }
+            .eval(&mut env),
+            Ok(Val::Number(5.into())),
+        );
+    }
+
+    #[test]
+    fn body_cannot_see_caller_bindings() {
+        assert_eq!(
+            crate::parse("fn f => y\n{ let y = 1\nf() }")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Err(RuntimeError::Message(
+                "binding with name 'y' does not exist".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn runaway_recursion_fails_instead_of_overflowing() {
+        let source = "fn f n => match n { 0 => 0, _ => 1 + f(n - 1) }\nf(10000)";
+        let inner = source.find("f(n - 1)").unwrap();
+        let program = crate::parse(source).unwrap();
+
+        // Debug builds use far more stack per call than the default limit allows for on a test
+        // thread, so reaching the limit needs a thread with room to spare.
+        let results = std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(move || {
+                (
+                    program.eval(&mut Env::default()),
+                    program.compile().eval(&mut Env::default()),
+                )
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        let expected = Err(RuntimeError::CallDepth {
+            span: inner..inner + "f(n - 1)".len(),
+        });
+        assert_eq!(results, (expected.clone(), expected));
+    }
+
+    #[test]
+    fn call_depth_limit_can_be_lowered() {
+        let count_down = |n: u32| {
+            let program = crate::parse(&format!(
+                "fn f n => match n {{ 0 => 0, _ => 1 + f(n - 1) }}\nf({})",
+                n
+            ))
+            .unwrap();
+            let env = || {
+                let mut env = Env::default();
+                env.set_max_call_depth(10);
+                env
+            };
+            let walked = program.eval(&mut env());
+            assert_eq!(program.compile().eval(&mut env()), walked);
+            walked.map_err(|error| error.to_string())
+        };
+
+        assert_eq!(count_down(9), Ok(Val::Number(9.into())));
+        assert_eq!(
+            count_down(10),
+            Err("maximum call depth exceeded".to_string())
+        );
+    }
 }
@@ -0,0 +1,332 @@
+use super::Expr;
+use crate::compat::prelude::*;
+use crate::env::Env;
+use crate::error::RuntimeError;
+use crate::lexer::{Token, TokenKind};
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::utils;
+use crate::val::Val;
+use core::fmt;
+
+/// `match shape { Circle(r) => 3 * r * r, Rect(w, h) => w * h, _ => 0 }`, which evaluates the
+/// first arm whose pattern fits the value. Arms are separated by commas or new lines.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Match {
+    pub val: Box<Expr>,
+    pub arms: Vec<Arm>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub body: Expr,
+}
+
+/// What a `match` arm accepts. Names starting with an uppercase letter are variants, and any
+/// other name binds whatever is there.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    /// `_`, which accepts anything.
+    Wildcard,
+    Binding(Symbol),
+    /// A number, string, bool or `nil`, which accepts values equal to it.
+    Literal(Val),
+    /// `Rect(w, h)`, or just `Empty` for a variant without fields.
+    Variant {
+        name: Symbol,
+        fields: Vec<Pattern>,
+    },
+}
+
+impl Match {
+    pub(super) fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let tokens = utils::keyword("match", tokens)?;
+        let (tokens, val) = Expr::new(tokens)?;
+        let mut tokens = utils::expect(TokenKind::LBrace, tokens)?;
+
+        let mut arms = Vec::new();
+        loop {
+            if let Ok(tokens) = utils::expect(TokenKind::RBrace, tokens) {
+                return Ok((
+                    tokens,
+                    Self {
+                        val: Box::new(val),
+                        arms,
+                        span: utils::consumed_span(start, tokens),
+                    },
+                ));
+            }
+
+            let (new_tokens, arm) = Arm::new(tokens)?;
+            tokens = utils::expect(TokenKind::Comma, new_tokens).unwrap_or(new_tokens);
+            arms.push(arm);
+        }
+    }
+
+    pub(super) fn eval(&self, env: &mut Env) -> Result<Val, RuntimeError> {
+        let val = self.val.eval(env)?;
+
+        for arm in &self.arms {
+            let mut bindings = Vec::new();
+
+            if arm.pattern.matches(&val, &mut bindings)? {
+                return env.in_nested_scope(|arm_env| {
+                    for (name, val) in bindings {
+                        arm_env.store_binding(name, val);
+                    }
+
+                    arm.body.eval(arm_env)
+                });
+            }
+        }
+
+        Err(no_match(&val))
+    }
+}
+
+impl Arm {
+    fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let (tokens, pattern) = Pattern::new(tokens)?;
+        let tokens = utils::expect(TokenKind::FatArrow, tokens)?;
+        let (tokens, body) = Expr::new(tokens)?;
+
+        Ok((tokens, Self { pattern, body }))
+    }
+}
+
+impl Pattern {
+    fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        if let Ok((tokens, expr)) = Expr::new_literal(tokens) {
+            let val = match expr {
+                Expr::Number(n, _) => Val::Number(n.0),
+                Expr::Str(s, _) => Val::Str(s),
+                Expr::Bool(b, _) => Val::Bool(b),
+                Expr::Nil(_) => Val::Nil,
+                _ => unreachable!("literals are numbers, strings, bools or nil"),
+            };
+            return Ok((tokens, Self::Literal(val)));
+        }
+
+        let (after_name, name) = match tokens.split_first() {
+            Some((
+                Token {
+                    kind: TokenKind::Ident(name),
+                    ..
+                },
+                after_name,
+            )) if name == "_" => return Ok((after_name, Self::Wildcard)),
+            _ => utils::extract_ident(tokens).map_err(|_| "expected pattern".to_string())?,
+        };
+
+        if !name.starts_with(char::is_uppercase) {
+            return Ok((after_name, Self::Binding(Symbol::intern(name))));
+        }
+
+        let (tokens, fields) = match utils::expect(TokenKind::LParen, after_name) {
+            Ok(tokens) => {
+                let _depth = utils::Depth::enter(tokens)?;
+                utils::comma_separated(Self::new, TokenKind::RParen, tokens)?
+            }
+            Err(_) => (after_name, Vec::new()),
+        };
+
+        Ok((
+            tokens,
+            Self::Variant {
+                name: Symbol::intern(name),
+                fields,
+            },
+        ))
+    }
+
+    /// Whether `val` fits this pattern, adding what the pattern binds to `bindings` if it does.
+    ///
+    /// A variant pattern that doesn't give every field is an error rather than a mismatch, since
+    /// it can never match.
+    pub(crate) fn matches(
+        &self,
+        val: &Val,
+        bindings: &mut Vec<(Symbol, Val)>,
+    ) -> Result<bool, RuntimeError> {
+        match (self, val) {
+            (Self::Wildcard, _) => Ok(true),
+            (Self::Binding(name), _) => {
+                bindings.push((*name, val.clone()));
+                Ok(true)
+            }
+            (Self::Literal(expected), _) => Ok(expected == val),
+            (
+                Self::Variant { name, fields },
+                Val::Variant {
+                    name: variant,
+                    fields: vals,
+                    ..
+                },
+            ) if name == variant => {
+                if fields.len() != vals.len() {
+                    return Err(format!(
+                        "pattern gives {} fields of '{}', which has {}",
+                        fields.len(),
+                        name,
+                        vals.len()
+                    )
+                    .into());
+                }
+
+                for (field, val) in fields.iter().zip(vals) {
+                    if !field.matches(val, bindings)? {
+                        return Ok(false);
+                    }
+                }
+
+                Ok(true)
+            }
+            (Self::Variant { .. }, _) => Ok(false),
+        }
+    }
+}
+
+/// The error for a `match` that has no arm for `val`.
+pub(crate) fn no_match(val: &Val) -> RuntimeError {
+    format!("no pattern matches {}", val).into()
+}
+
+/// Shows patterns the way they are written in source.
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wildcard => write!(f, "_"),
+            Self::Binding(name) => write!(f, "{}", name),
+            Self::Literal(val) => write!(f, "{}", val),
+            Self::Variant { name, fields } if fields.is_empty() => write!(f, "{}", name),
+            Self::Variant { name, fields } => {
+                write!(f, "{}(", name)?;
+
+                for (idx, field) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}", field)?;
+                }
+
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::BindingUsage;
+
+    fn eval(s: &str) -> Result<Val, RuntimeError> {
+        crate::parse(s).unwrap().eval(&mut Env::default())
+    }
+
+    #[test]
+    fn parse_match() {
+        assert_eq!(
+            utils::parse_str(
+                Match::new,
+                "match s {\n  Rect(w, _) => w\n  \"a\" => 1, n => n\n}"
+            ),
+            Ok((
+                "",
+                Match {
+                    val: Box::new(Expr::BindingUsage(BindingUsage {
+                        name: "s".into(),
+                        span: Span::default(),
+                    })),
+                    arms: vec![
+                        Arm {
+                            pattern: Pattern::Variant {
+                                name: "Rect".into(),
+                                fields: vec![Pattern::Binding("w".into()), Pattern::Wildcard],
+                            },
+                            body: Expr::BindingUsage(BindingUsage {
+                                name: "w".into(),
+                                span: Span::default(),
+                            }),
+                        },
+                        Arm {
+                            pattern: Pattern::Literal(Val::Str("a".to_string())),
+                            body: Expr::Number(crate::expr::Number(1.into()), Span::default()),
+                        },
+                        Arm {
+                            pattern: Pattern::Binding("n".into()),
+                            body: Expr::BindingUsage(BindingUsage {
+                                name: "n".into(),
+                                span: Span::default(),
+                            }),
+                        },
+                    ],
+                    span: Span::default(),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn match_variants() {
+        let area = "enum Shape { Circle(r), Rect(w, h), Empty }
+fn area s => match s {
+    Circle(r) => 3 * r * r
+    Rect(w, h) => w * h
+    Empty => 0
+}
+";
+
+        assert_eq!(
+            eval(&format!(
+                "{}[area(Circle(2)), area(Rect(2, 5)), area(Empty)]",
+                area
+            )),
+            Ok(Val::List(vec![
+                Val::Number(12.into()),
+                Val::Number(10.into()),
+                Val::Number(0.into()),
+            ])),
+        );
+    }
+
+    #[test]
+    fn match_nested_patterns_and_literals() {
+        assert_eq!(
+            eval("enum Opt { Some(x), None }\nmatch Some(Some(3)) { Some(None) => 0, Some(Some(n)) => n }"),
+            Ok(Val::Number(3.into())),
+        );
+        assert_eq!(
+            eval("match \"b\" { \"a\" => 1, nil => 2, _ => 3 }"),
+            Ok(Val::Number(3.into())),
+        );
+    }
+
+    #[test]
+    fn bindings_stay_in_their_arm() {
+        assert_eq!(
+            eval("let n = 1\nlet m = match 2 { n => n * 10 }\nn + m"),
+            Ok(Val::Number(21.into())),
+        );
+    }
+
+    #[test]
+    fn fail_without_matching_arm() {
+        assert_eq!(
+            eval("enum Opt { Some(x), None }\nmatch Some(1) { None => 0 }"),
+            Err(RuntimeError::Message(
+                "no pattern matches Some(1)".to_string()
+            )),
+        );
+        assert_eq!(
+            eval("enum Opt { Some(x) }\nmatch Some(1) { Some(a, b) => 0 }"),
+            Err(RuntimeError::Message(
+                "pattern gives 2 fields of 'Some', which has 1".to_string()
+            )),
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use super::Block;
+use crate::compat::prelude::*;
+use crate::env::Env;
+use crate::error::RuntimeError;
+use crate::lexer::Token;
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::utils;
+use crate::val::Val;
+
+/// `try { ... } catch e { ... }`, which runs the handler with the error bound to `e` if the body
+/// fails.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TryCatch {
+    pub body: Block,
+    pub name: Symbol,
+    pub handler: Block,
+    pub span: Span,
+}
+
+impl TryCatch {
+    pub(super) fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let tokens = utils::keyword("try", tokens)?;
+        let (tokens, body) = Block::new(tokens)?;
+
+        let tokens = utils::keyword("catch", tokens)?;
+        let (tokens, name) = utils::extract_ident(tokens)?;
+        let (tokens, handler) = Block::new(tokens)?;
+
+        Ok((
+            tokens,
+            Self {
+                body,
+                name: Symbol::intern(name),
+                handler,
+                span: utils::consumed_span(start, tokens),
+            },
+        ))
+    }
+
+    pub(super) fn eval(&self, env: &mut Env) -> Result<Val, RuntimeError> {
+        self.body
+            .eval(env)
+            .or_else(|error| caught(env, self.name, error, |env| self.handler.eval(env)))
+    }
+}
+
+/// Runs a handler in a child of `env` with `error` bound to `name`. A thrown value is bound as
+/// it is, while any other error is bound as its message. A debugger aborting and a call to
+/// `exit` can't be caught.
+pub(crate) fn caught(
+    env: &mut Env,
+    name: Symbol,
+    error: RuntimeError,
+    handler: impl FnOnce(&mut Env) -> Result<Val, RuntimeError>,
+) -> Result<Val, RuntimeError> {
+    let val = match error {
+        RuntimeError::Thrown(val) => val,
+        RuntimeError::Panic { message, .. } => Val::Str(message),
+        RuntimeError::Aborted | RuntimeError::Exit(_) => return Err(error),
+        error => Val::Str(error.to_string()),
+    };
+
+    env.in_nested_scope(|handler_env| {
+        handler_env.store_binding(name, val);
+        handler(handler_env)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(s: &str) -> Result<Val, RuntimeError> {
+        crate::parse(s).unwrap().eval(&mut Env::default())
+    }
+
+    #[test]
+    fn catch_thrown_value() {
+        assert_eq!(
+            eval("try { throw [1, 2]\n3 } catch e { e[1] }"),
+            Ok(Val::Number(2.into())),
+        );
+    }
+
+    #[test]
+    fn skip_handler_without_error() {
+        assert_eq!(
+            eval("try { 1 } catch e { throw e }"),
+            Ok(Val::Number(1.into())),
+        );
+    }
+
+    #[test]
+    fn catch_runtime_error_as_message() {
+        assert_eq!(
+            eval("try { 1 / 0 } catch e { e }"),
+            Ok(Val::Str("division by zero".to_string())),
+        );
+    }
+
+    #[test]
+    fn throw_out_of_function() {
+        assert_eq!(
+            eval("fn check x => { throw \"bad\"\nx }\ntry { check(1) } catch e { e }"),
+            Ok(Val::Str("bad".to_string())),
+        );
+        assert_eq!(
+            eval("throw \"bad\""),
+            Err(RuntimeError::Thrown(Val::Str("bad".to_string()))),
+        );
+    }
+
+    #[test]
+    fn catch_panic_as_its_message() {
+        assert_eq!(
+            eval("fn f => panic(\"oops\")\ntry { f() } catch e { e }"),
+            Ok(Val::Str("oops".to_string())),
+        );
+    }
+
+    #[test]
+    fn rethrow_from_handler() {
+        assert_eq!(
+            eval("try { try { throw 1 } catch e { throw e + 1 } } catch e { e }"),
+            Ok(Val::Number(2.into())),
+        );
+    }
+}
@@ -0,0 +1,753 @@
+//! Renders syntax trees back to canonical source text.
+
+use crate::binding_def::BindingDef;
+use crate::compat::prelude::*;
+use crate::expr::{
+    Block, CallStyle, Expr, FuncCall, Number, Op, UnaryOp, NEG_BINDING_POWER,
+    PIPELINE_BINDING_POWER,
+};
+use crate::func_def::FuncDef;
+use crate::num::Int;
+use crate::program::Program;
+use crate::span::Span;
+use crate::stmt::Stmt;
+use crate::symbol::Symbol;
+use crate::syntax::{self, SyntaxElement, SyntaxKind};
+use crate::ty::Type;
+
+const INDENT: &str = "    ";
+
+/// The text written so far, along with the comments from the source that are yet to be put back
+/// into it.
+#[derive(Default)]
+struct Out<'s> {
+    text: String,
+    /// By where they start in the source, with the last one first.
+    comments: Vec<(usize, String)>,
+    /// What the syntax tree was parsed from, if it's known, so that number literals can be
+    /// written the way they were, such as `0xFF` or `1_000`.
+    source: &'s str,
+}
+
+impl Out<'_> {
+    /// Writes the comments that come before `pos` in the source on lines of their own, at the
+    /// start of a line that is indented `depth` levels and stays that way.
+    fn comments_before(&mut self, pos: usize, depth: usize) {
+        while let Some((_, comment)) = self.comments.pop_if(|(start, _)| *start < pos) {
+            self.text += &comment;
+            self.text.push('\n');
+            self.text += &INDENT.repeat(depth);
+        }
+    }
+
+    /// Writes the comments that come before `pos` in the source on new lines indented `depth`
+    /// levels, such as those after the last statement of a block.
+    fn comments_after(&mut self, pos: usize, depth: usize) {
+        while let Some((_, comment)) = self.comments.pop_if(|(start, _)| *start < pos) {
+            self.text.push('\n');
+            self.text += &INDENT.repeat(depth);
+            self.text += &comment;
+        }
+    }
+}
+
+impl core::ops::Deref for Out<'_> {
+    type Target = String;
+
+    fn deref(&self) -> &String {
+        &self.text
+    }
+}
+
+impl core::ops::DerefMut for Out<'_> {
+    fn deref_mut(&mut self) -> &mut String {
+        &mut self.text
+    }
+}
+
+impl core::ops::AddAssign<&str> for Out<'_> {
+    fn add_assign(&mut self, s: &str) {
+        self.text += s;
+    }
+}
+
+/// Formats a whole program, with one statement per line and a trailing newline.
+pub(crate) fn program(program: &Program) -> String {
+    let mut out = Out {
+        source: program.source(),
+        ..Out::default()
+    };
+
+    if let Some(shebang) = &program.shebang {
+        out += shebang;
+        out.push('\n');
+    }
+
+    for stmt in &program.stmts {
+        write_stmt(&mut out, stmt, 0);
+        out.push('\n');
+    }
+
+    out.text
+}
+
+/// Formats `program`, which was parsed from `source`, keeping the comments in `source`.
+///
+/// Comments between top-level statements stay where they are, and those at the end of a line stay
+/// at the end of it. Comments inside a statement go before the statement or `match` arm nested in
+/// it that they come before, or at the end of the block or `match` they are in, on lines of their
+/// own. Anywhere else in a statement there is nowhere for them to go, so they are moved to the
+/// line above it.
+pub(crate) fn source(source: &str, program: &Program) -> String {
+    let mut out = String::new();
+    let mut stmts = program.stmts.iter();
+    let mut newline_since_stmt = true;
+
+    for element in syntax::parse(source).syntax().children_with_tokens() {
+        match element {
+            SyntaxElement::Token(token) => match token.kind() {
+                SyntaxKind::Shebang => {
+                    out += token.text();
+                    out.push('\n');
+                }
+                SyntaxKind::Whitespace => newline_since_stmt |= token.text().contains('\n'),
+                SyntaxKind::Comment if !newline_since_stmt && !out.is_empty() => {
+                    out.pop();
+                    out += &format!(" {}\n", token.text());
+                }
+                SyntaxKind::Comment => {
+                    out += token.text();
+                    out.push('\n');
+                }
+                _ => {}
+            },
+            SyntaxElement::Node(node) => {
+                let mut comments: Vec<_> = node
+                    .tokens()
+                    .into_iter()
+                    .filter(|token| token.kind() == SyntaxKind::Comment)
+                    .map(|token| (token.text_range().start, token.text().to_string()))
+                    .collect();
+                comments.reverse();
+
+                // The statement is written first to find out which comments have a place in it.
+                let mut stmt_out = Out {
+                    text: String::new(),
+                    comments,
+                    source,
+                };
+                if let Some(stmt) = stmts.next() {
+                    write_stmt(&mut stmt_out, stmt, 0);
+                    stmt_out.push('\n');
+                }
+
+                for (_, comment) in stmt_out.comments.iter().rev() {
+                    out += comment;
+                    out.push('\n');
+                }
+                out += &stmt_out.text;
+
+                newline_since_stmt = false;
+            }
+        }
+    }
+
+    for stmt in stmts {
+        let mut stmt_out = Out {
+            source,
+            ..Out::default()
+        };
+        write_stmt(&mut stmt_out, stmt, 0);
+        out += &stmt_out.text;
+        out.push('\n');
+    }
+
+    out
+}
+
+/// A single statement in canonical style, without a trailing new line.
+pub(crate) fn stmt(stmt: &Stmt) -> String {
+    let mut out = Out::default();
+    write_stmt(&mut out, stmt, 0);
+    out.text
+}
+
+fn write_stmt(out: &mut Out, stmt: &Stmt, depth: usize) {
+    match stmt {
+        Stmt::BindingDef(BindingDef {
+            name,
+            constant,
+            ty,
+            val,
+            ..
+        }) => {
+            let keyword = if *constant { "const" } else { "let" };
+            *out += &format!("{} {}{} = ", keyword, name, annotation(*ty));
+            write_expr(out, val, depth);
+        }
+        Stmt::Expr(expr) => write_expr(out, expr, depth),
+        Stmt::FuncDef(FuncDef {
+            name, params, body, ..
+        }) => {
+            *out += "fn ";
+            *out += name.as_str();
+
+            // Annotated parameters and ones with defaults are separated with commas so that each
+            // type or default stays next to its name.
+            let separator = if params
+                .iter()
+                .any(|param| param.ty.is_some() || param.default.is_some())
+            {
+                ", "
+            } else {
+                " "
+            };
+
+            for (idx, param) in params.iter().enumerate() {
+                *out += if idx == 0 { " " } else { separator };
+                if param.rest {
+                    *out += "...";
+                }
+                *out += &format!("{}{}", param.name, annotation(param.ty));
+
+                if let Some(default) = &param.default {
+                    *out += " = ";
+                    write_expr(out, default, depth);
+                }
+            }
+
+            *out += " => ";
+            write_stmt(out, body, depth);
+        }
+        Stmt::EnumDef(enum_def) => {
+            *out += &format!("enum {} {{ ", enum_def.name);
+
+            for (idx, variant) in enum_def.variants.iter().enumerate() {
+                if idx > 0 {
+                    *out += ", ";
+                }
+
+                *out += variant.name.as_str();
+
+                if !variant.fields.is_empty() {
+                    let fields: Vec<_> =
+                        variant.fields.iter().map(|field| field.as_str()).collect();
+                    *out += &format!("({})", fields.join(", "));
+                }
+            }
+
+            *out += " }";
+        }
+        Stmt::Throw(val, _) => {
+            *out += "throw ";
+            write_expr(out, val, depth);
+        }
+        Stmt::Assign(assign) => {
+            *out += assign.name.as_str();
+            *out += &format!(" {}= ", op_symbol(&assign.op));
+            write_expr(out, &assign.val, depth);
+        }
+        Stmt::Import(import) => {
+            *out += "import ";
+            write_string(out, &import.path);
+        }
+    }
+}
+
+fn write_expr(out: &mut Out, expr: &Expr, depth: usize) {
+    match expr {
+        Expr::Number(number, span) => write_number(out, number, span),
+        Expr::Bool(b, _) => *out += &b.to_string(),
+        Expr::Unit(_) => *out += "()",
+        Expr::Nil(_) => *out += "nil",
+        Expr::Placeholder(_) => *out += "_",
+        Expr::Index {
+            base, index, safe, ..
+        } => {
+            let brackets =
+                binding_power(base).is_some() || matches!(base.as_ref(), Expr::Unary { .. });
+            write_operand(out, base, brackets, depth);
+            *out += if *safe { "?[" } else { "[" };
+            write_expr(out, index, depth);
+            out.push(']');
+        }
+        Expr::Str(s, _) => write_string(out, s),
+        Expr::Record(fields, _) => {
+            out.push('{');
+
+            for (idx, (name, val)) in fields.iter().enumerate() {
+                if idx > 0 {
+                    *out += ", ";
+                }
+
+                *out += &format!("{}: ", name);
+                write_expr(out, val, depth);
+            }
+
+            out.push('}');
+        }
+        Expr::Field { base, name, .. } => {
+            let brackets =
+                binding_power(base).is_some() || matches!(base.as_ref(), Expr::Unary { .. });
+            write_operand(out, base, brackets, depth);
+            out.push('.');
+            *out += name.as_str();
+        }
+        Expr::Operation { lhs, rhs, op, .. } => {
+            let (left_binding_power, right_binding_power) = op.binding_power();
+
+            // The lhs needs brackets if this operator would otherwise be parsed as part of the
+            // lhs's right-hand side, and the rhs needs them if it binds less tightly than this.
+            let lhs_brackets =
+                binding_power(lhs).is_some_and(|(_, lhs_right)| left_binding_power >= lhs_right);
+            let rhs_brackets =
+                binding_power(rhs).is_some_and(|(rhs_left, _)| rhs_left < right_binding_power);
+
+            write_operand(out, lhs, lhs_brackets, depth);
+            match op {
+                // Ranges are written like `1..4`, the way they are everywhere else.
+                Op::Range | Op::RangeInclusive => *out += op_symbol(op),
+                _ => *out += &format!(" {} ", op_symbol(op)),
+            }
+            write_operand(out, rhs, rhs_brackets, depth);
+        }
+        Expr::Unary { op, operand, .. } => {
+            *out += unary_op_symbol(op);
+            let brackets = unary_operand_brackets(op, operand);
+            write_operand(out, operand, brackets, depth);
+        }
+        Expr::BindingUsage(binding_usage) => *out += binding_usage.name.as_str(),
+        Expr::Block(block) => write_block(out, block, depth),
+        Expr::TryCatch(try_catch) => {
+            *out += "try ";
+            write_block(out, &try_catch.body, depth);
+            *out += &format!(" catch {} ", try_catch.name);
+            write_block(out, &try_catch.handler, depth);
+        }
+        Expr::For(for_loop) => {
+            *out += &format!("for {} in ", for_loop.name);
+            write_expr(out, &for_loop.iterable, depth);
+            out.push(' ');
+            write_block(out, &for_loop.body, depth);
+        }
+        Expr::Match(m) => {
+            *out += "match ";
+            write_expr(out, &m.val, depth);
+
+            if m.arms.is_empty() {
+                *out += " {}";
+                return;
+            }
+
+            out.push_str(" {");
+
+            for arm in &m.arms {
+                out.push('\n');
+                *out += &INDENT.repeat(depth + 1);
+                out.comments_before(arm.body.span().start, depth + 1);
+                *out += &format!("{} => ", arm.pattern);
+                write_expr(out, &arm.body, depth + 1);
+            }
+
+            out.comments_after(m.span.0.end, depth + 1);
+            out.push('\n');
+            *out += &INDENT.repeat(depth);
+            out.push('}');
+        }
+        Expr::List(items, _) => {
+            out.push('[');
+
+            for (idx, item) in items.iter().enumerate() {
+                if idx > 0 {
+                    *out += ", ";
+                }
+
+                write_expr(out, item, depth);
+            }
+
+            out.push(']');
+        }
+        Expr::Tuple(items, _) => {
+            out.push('(');
+
+            for (idx, item) in items.iter().enumerate() {
+                if idx > 0 {
+                    *out += ", ";
+                }
+
+                write_expr(out, item, depth);
+            }
+
+            out.push(')');
+        }
+        Expr::FuncCall(func_call) => match (func_call.style, func_call.params.split_first()) {
+            (CallStyle::Piped(idx), Some(_)) => {
+                let arg = &func_call.params[idx];
+                let brackets = binding_power(arg)
+                    .is_some_and(|(_, arg_right)| PIPELINE_BINDING_POWER.0 >= arg_right);
+                write_operand(out, arg, brackets, depth);
+                *out += " |> ";
+                *out += func_call.callee.as_str();
+                write_piped_args(out, func_call, idx, depth);
+            }
+            (CallStyle::Method, Some((receiver, params))) => {
+                let brackets =
+                    binding_power(receiver).is_some() || matches!(receiver, Expr::Unary { .. });
+                write_operand(out, receiver, brackets, depth);
+                out.push('.');
+                *out += func_call.callee.as_str();
+                write_args(out, params, &func_call.named, depth);
+            }
+            // Calls always get parentheses, since juxtaposed arguments are ambiguous to read.
+            _ => {
+                *out += func_call.callee.as_str();
+                write_args(out, &func_call.params, &func_call.named, depth);
+            }
+        },
+    }
+}
+
+/// The binding powers of `expr` if it is written with an infix operator, so may need brackets
+/// around it.
+fn binding_power(expr: &Expr) -> Option<(u8, u8)> {
+    match expr {
+        Expr::Operation { op, .. } => Some(op.binding_power()),
+        Expr::FuncCall(FuncCall {
+            style: CallStyle::Piped(_),
+            params,
+            ..
+        }) if !params.is_empty() => Some(PIPELINE_BINDING_POWER),
+        // Nothing to its left can take part of a `-a`, but a `**` to its right would.
+        Expr::Unary {
+            op: UnaryOp::Neg, ..
+        } => Some((u8::MAX, NEG_BINDING_POWER)),
+        _ => None,
+    }
+}
+
+/// Stands in for the argument of a piped call that was written before the callee.
+const PLACEHOLDER: &Expr = &Expr::Placeholder(Span(0..0));
+
+/// Writes the arguments of a piped call after the callee, putting a `_` back where the argument
+/// at `idx` went unless it went first.
+fn write_piped_args(out: &mut Out, func_call: &FuncCall, idx: usize, depth: usize) {
+    if func_call.params.len() == 1 && idx == 0 && func_call.named.is_empty() {
+        return;
+    }
+
+    let params = func_call
+        .params
+        .iter()
+        .enumerate()
+        .filter_map(|(param_idx, param)| {
+            if param_idx != idx {
+                Some(param)
+            } else {
+                (idx > 0).then_some(PLACEHOLDER)
+            }
+        });
+    write_args(out, params, &func_call.named, depth);
+}
+
+fn write_args<'a>(
+    out: &mut Out,
+    params: impl IntoIterator<Item = &'a Expr>,
+    named: &[(Symbol, Expr)],
+    depth: usize,
+) {
+    out.push('(');
+
+    let mut written = 0;
+    for param in params {
+        if written > 0 {
+            *out += ", ";
+        }
+
+        write_expr(out, param, depth);
+        written += 1;
+    }
+
+    for (name, val) in named {
+        if written > 0 {
+            *out += ", ";
+        }
+
+        *out += &format!("{}: ", name);
+        write_expr(out, val, depth);
+        written += 1;
+    }
+
+    out.push(')');
+}
+
+fn write_block(out: &mut Out, block: &Block, depth: usize) {
+    if block.stmts.is_empty() {
+        *out += "{}";
+        return;
+    }
+
+    out.push('{');
+
+    for stmt in &block.stmts {
+        out.push('\n');
+        *out += &INDENT.repeat(depth + 1);
+        out.comments_before(stmt.span().start, depth + 1);
+        write_stmt(out, stmt, depth + 1);
+    }
+
+    out.comments_after(block.span.0.end, depth + 1);
+    out.push('\n');
+    *out += &INDENT.repeat(depth);
+    out.push('}');
+}
+
+/// Whether the operand of a prefix operator needs brackets. Kept out of [`write_expr`], which
+/// recurses once per level of nesting, so that its frame stays small.
+fn unary_operand_brackets(op: &UnaryOp, operand: &Expr) -> bool {
+    match op {
+        UnaryOp::BitNot => binding_power(operand).is_some(),
+        // Brackets also keep `-(-a)` from reading like a decrement.
+        UnaryOp::Neg => {
+            binding_power(operand).is_some_and(|(operand_left, _)| operand_left < NEG_BINDING_POWER)
+                || matches!(
+                    operand,
+                    Expr::Unary {
+                        op: UnaryOp::Neg,
+                        ..
+                    }
+                )
+        }
+    }
+}
+
+/// Writes a number literal the way it was written in the source, or in decimal if it wasn't, such
+/// as when it was folded from other literals.
+fn write_number(out: &mut Out, Number(n): &Number, span: &Span) {
+    match out.source.get(span.0.clone()) {
+        Some(text) if Int::parse(text).as_ref() == Ok(n) => *out += text,
+        _ => *out += &n.to_string(),
+    }
+}
+
+fn write_operand(out: &mut Out, operand: &Expr, brackets: bool, depth: usize) {
+    if brackets {
+        out.push('(');
+        write_expr(out, operand, depth);
+        out.push(')');
+    } else {
+        write_expr(out, operand, depth);
+    }
+}
+
+pub(crate) fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '\n' => *out += "\\n",
+            '\t' => *out += "\\t",
+            '"' => *out += "\\\"",
+            '\\' => *out += "\\\\",
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+}
+
+pub(crate) fn op_symbol(op: &Op) -> &'static str {
+    match op {
+        Op::Add => "+",
+        Op::Sub => "-",
+        Op::Mul => "*",
+        Op::Div => "/",
+        Op::Rem => "%",
+        Op::Pow => "**",
+        Op::Eq => "==",
+        Op::Ne => "!=",
+        Op::Lt => "<",
+        Op::Le => "<=",
+        Op::Gt => ">",
+        Op::Ge => ">=",
+        Op::BitAnd => "&",
+        Op::BitOr => "|",
+        Op::BitXor => "^",
+        Op::Shl => "<<",
+        Op::Shr => ">>",
+        Op::Coalesce => "??",
+        Op::Range => "..",
+        Op::RangeInclusive => "..=",
+    }
+}
+
+pub(crate) fn unary_op_symbol(op: &UnaryOp) -> &'static str {
+    match op {
+        UnaryOp::BitNot => "~",
+        UnaryOp::Neg => "-",
+    }
+}
+
+/// The `: type` that follows an annotated name, or nothing.
+pub(crate) fn annotation(ty: Option<Type>) -> String {
+    ty.map(|ty| format!(": {}", ty)).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::Span;
+
+    fn assert_round_trip(source: &str) {
+        let parsed = crate::parse(source).unwrap();
+        let formatted = program(&parsed);
+
+        // Comments are matched up with statements through the syntax tree, so both parsers have
+        // to agree on where statements are.
+        let syntax = syntax::parse(source);
+        assert!(syntax.errors().is_empty(), "{:?}", syntax.errors());
+        assert_eq!(syntax.root().stmts().count(), parsed.stmts.len());
+
+        assert_eq!(crate::parse(&formatted), Ok(parsed), "{}", formatted);
+        assert_eq!(
+            program(&crate::parse(&formatted).unwrap()),
+            formatted,
+            "formatting is not idempotent",
+        );
+    }
+
+    #[test]
+    fn format_canonical_spacing() {
+        assert_eq!(
+            program(&crate::parse("let  a=1+2*3\nfn add x y=>x+y\n\n\nadd(a, 1)").unwrap()),
+            "let a = 1 + 2 * 3\nfn add x y => x + y\nadd(a, 1)\n",
+        );
+    }
+
+    #[test]
+    fn format_nested_blocks() {
+        assert_eq!(
+            program(&crate::parse("fn f x => { let y = { x }\ny }").unwrap()),
+            "fn f x => {\n    let y = {\n        x\n    }\n    y\n}\n",
+        );
+    }
+
+    #[test]
+    fn format_keeps_number_spelling() {
+        let source = "let mask = 0xFF & 0b1010_1010 | 0o77\nlet big = 1_000_000 // a million\n";
+        assert_eq!(crate::format(source), Ok(source.to_string()));
+        assert_eq!(
+            crate::parse(source).unwrap().format(),
+            source.replace(" // a million", "")
+        );
+
+        // A literal that isn't in the source, like one folded from others, is written in decimal.
+        let mut program = crate::parse("0x10 + 0x01").unwrap();
+        crate::optimize::program(&mut program);
+        assert_eq!(program.format(), "17\n");
+    }
+
+    #[test]
+    fn format_ranges_without_spaces() {
+        let source = "let r = 1 .. 4\nlet s = 0 ..= n + 1 |> list\n(1 .. 2, -1 .. -x, a.b .. f(c))";
+        let formatted = "let r = 1..4\nlet s = 0..=n + 1 |> list\n(1..2, -1..-x, a.b..f(c))\n";
+
+        assert_eq!(program(&crate::parse(source).unwrap()), formatted);
+        assert_round_trip(formatted);
+    }
+
+    #[test]
+    fn format_string_escapes() {
+        let mut out = Out::default();
+        write_expr(
+            &mut out,
+            &Expr::Str("say \"hi\"\n\\".to_string(), Span::default()),
+            0,
+        );
+
+        assert_eq!(out.text, r#""say \"hi\"\n\\""#);
+    }
+
+    #[test]
+    fn keep_shebang() {
+        assert_eq!(
+            program(&crate::parse("#!/usr/bin/env eldiro\n1").unwrap()),
+            "#!/usr/bin/env eldiro\n1\n",
+        );
+    }
+
+    fn assert_keeps_comments(source: &str, formatted: &str) {
+        assert_eq!(
+            super::source(source, &crate::parse(source).unwrap()),
+            formatted
+        );
+        assert_eq!(
+            super::source(formatted, &crate::parse(formatted).unwrap()),
+            formatted,
+            "formatting is not idempotent",
+        );
+    }
+
+    #[test]
+    fn keep_comments() {
+        assert_keeps_comments(
+            "// header\nlet a = 1  // one\n\n// before f\nfn f x => {\n    // inside\n    x\n}\n// trailer",
+            "// header\nlet a = 1 // one\n// before f\nfn f x => {\n    // inside\n    x\n}\n// trailer\n",
+        );
+    }
+
+    #[test]
+    fn keep_comments_in_nested_blocks_and_arms() {
+        assert_keeps_comments(
+            "fn f x => {\n  // first\n  let y = { // why\n    x }\n  match y {\n    // zero\n    0 => 1\n    // any\n    _ => y // same\n  }\n  // end\n}",
+            "fn f x => {\n    // first\n    let y = {\n        // why\n        x\n    }\n    match y {\n        // zero\n        0 => 1\n        // any\n        _ => y\n        // same\n    }\n    // end\n}\n",
+        );
+    }
+
+    #[test]
+    fn move_comments_with_nowhere_to_go_above_statement() {
+        assert_keeps_comments("let a = f(1, // one\n  2)", "// one\nlet a = f(1, 2)\n");
+    }
+
+    #[test]
+    fn round_trip() {
+        for source in [
+            "",
+            "1",
+            "let a = 10 / 2 - 3",
+            "5 - 2 - 1 == 2",
+            "fn nothing => {}",
+            "fn add x y => x + y\nadd(1, add 2 3)",
+            r#"import "lib/math.eld""#,
+            "let s = \"tab\\there\"\nlet t = s == \"x\"",
+            "fn test_f => assert(true, \"ok\")\nnow_ms() >= 0",
+            "{ let a = { 1 }\nfn g => a\ng }",
+            "e(f(g(1), 2 * 3), {})",
+            "let m = ~x & 255 | 1 << 4 ^ y >> 2",
+            "let h = 0xff_ff + 0b1 - 0o17 * 1_000",
+            "let n = -x * -2 ** -y - -(a + 1) + -(-1)\n-f(1) |> g\n(-a)[0] + (-2) ** 2",
+            "[[], [1, \"two\"], f(x)]",
+            "(1 + 2) * (3 - (4 - 5)) == ~(a & b)",
+            "let t = (1, (x, [y]))",
+            "let u = ()\nconst LIMIT = 10",
+            "fn greet name = \"world\", times: number = 1 + 1 => name",
+            "fn sum first ...rest => first",
+            "area(width: 3, height: 4 + 1)\narea(3, height: 4)",
+            "xs |> f |> g(2, y: 3) == (a |> h) + 1\n(1 ?? 2) |> f\n~(x |> f)[0]",
+            "x |> f(1, _, 3) |> g(2)\n(a |> b) |> c(d, _)",
+            "name.upper().len() + (1 + 2).f(x: 1) + (~a).g()[0]",
+            "let p = {x: 1, y: {z: [2]}}\np.y.z[0] + (~p).x + {a: 1}.a.f()",
+            "let inc = add(1, _)\nsub(_, 2, z: _x)",
+            "let n: number = 1\nfn add x: number, y: number => x + y\nfn both a, b: nil => b",
+            "let r = try {\n    throw \"x\"\n} catch e {\n    e\n}",
+            "enum Shape { Circle(r), Rect(w, h), Empty }",
+            "fn area s => match s {\n    Circle(r) => 3 * r * r\n    Rect(w, _) => match w {\n        1 => \"one\"\n        n => n\n    }\n    nil => ()\n}",
+            "for c in upper(\"ab\") {\n    assert(c != \"\", c)\n}",
+            "let r = (0..n + 1) | 1 |> list\nlet s = 0..=(2 |> f)",
+            "let v = (~a)[0] ?? xs?[i + 1][0] ?? nil",
+            "#!/usr/bin/env eldiro\nlet x = true != false",
+            "let n = 7 % 2\nn += 1\nn -= n * 2 % 3\nfor i in xs {\n    n *= i\n}\nn /= 2\nn %= 3",
+        ] {
+            assert_round_trip(source);
+        }
+    }
+}
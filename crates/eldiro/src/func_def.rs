@@ -1,43 +1,457 @@
-use crate::env::Env;
+use crate::compat::prelude::*;
+use crate::env::{Env, Func};
+use crate::error::RuntimeError;
+use crate::expr::Expr;
+use crate::lexer::{Token, TokenKind};
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::ty::Type;
+use crate::val::Val;
 use crate::{stmt::Stmt, utils};
+use alloc::sync::Arc;
+use core::fmt;
 
+/// `fn name params => body`
+#[derive(Debug, Clone)]
+pub struct FuncDef {
+    pub name: Symbol,
+    pub params: Vec<Param>,
+    pub body: Box<Stmt>,
+    pub span: Span,
+    /// The text of the `##` comments right above the definition.
+    pub doc: Option<String>,
+    /// The whole source text that the definition was parsed from, which `span` and the spans
+    /// inside `body` point into. [`crate::parse`] fills it in.
+    pub source: Option<Arc<str>>,
+}
+
+/// The source is ignored, so that the same definition compares equal wherever it's written.
+impl PartialEq for FuncDef {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.params == other.params
+            && self.body == other.body
+            && self.doc == other.doc
+    }
+}
+
+/// A function parameter, written `x` or `x: number`, optionally followed by `= default` for when
+/// a call leaves it out. The last parameter can instead be `...xs`, which collects any remaining
+/// arguments into a list.
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct FuncDef {
-    pub(crate) name: String,
-    pub(crate) params: Vec<String>,
-    pub(crate) body: Box<Stmt>,
+pub struct Param {
+    pub name: Symbol,
+    pub ty: Option<Type>,
+    pub default: Option<Expr>,
+    pub rest: bool,
 }
 
-impl FuncDef {
-    pub(crate) fn eval(&self, env: &mut Env) -> Result<(), String> {
-        env.store_func(self.name.clone(), self.params.clone(), *self.body.clone());
+impl Param {
+    fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let (tokens, rest) = match utils::expect(TokenKind::Ellipsis, tokens) {
+            Ok(tokens) => (tokens, true),
+            Err(_) => (tokens, false),
+        };
+        let (tokens, name) = utils::extract_ident(tokens)?;
+        let (tokens, ty) = Type::new_annotation(tokens)?;
+
+        let (tokens, default) = match utils::expect(TokenKind::Eq, tokens) {
+            Ok(tokens) if !rest => {
+                let (tokens, default) = Expr::new(tokens)?;
+                (tokens, Some(default))
+            }
+            _ => (tokens, None),
+        };
+
+        // Commas are optional, but read better once parameters have annotations.
+        let tokens = utils::expect(TokenKind::Comma, tokens).unwrap_or(tokens);
+
+        Ok((
+            tokens,
+            Self {
+                name: Symbol::intern(name),
+                ty,
+                default,
+                rest,
+            },
+        ))
+    }
+
+    /// Binds the argument for this parameter in the scope of a call to `callee`, after checking
+    /// it against the annotation.
+    pub(crate) fn bind(&self, callee: Symbol, arg: Val, env: &mut Env) -> Result<(), RuntimeError> {
+        if let Some(ty) = self.ty {
+            ty.check(
+                &arg,
+                format_args!("parameter '{}' of '{}'", self.name, callee),
+            )?;
+        }
+
+        env.store_binding(self.name, arg);
         Ok(())
     }
+}
+
+impl From<&str> for Param {
+    fn from(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            ty: None,
+            default: None,
+            rest: false,
+        }
+    }
+}
+
+/// How many arguments a call has to pass, which leaves out the parameters at the end that have
+/// defaults or collect the rest.
+pub(crate) fn required_params(params: &[Param]) -> usize {
+    params
+        .iter()
+        .rposition(|param| param.default.is_none() && !param.rest)
+        .map_or(0, |idx| idx + 1)
+}
+
+/// How many arguments a call can pass, which is unlimited with a rest parameter.
+pub(crate) fn max_params(params: &[Param]) -> Option<usize> {
+    match params.last() {
+        Some(param) if param.rest => None,
+        _ => Some(params.len()),
+    }
+}
+
+/// Checks the number of arguments that the call to `callee` at `span` passes, before any of them
+/// are evaluated.
+///
+/// Named arguments can fill in any parameter, so only having too many of them is caught here.
+/// Missing ones are found once the arguments are matched up with the parameters.
+pub(crate) fn check_args(
+    callee: Symbol,
+    func: &Func,
+    positional: usize,
+    named: usize,
+    span: &Span,
+) -> Result<(), RuntimeError> {
+    let (min, max) = match func {
+        Func::User { params, .. } => (required_params(params), max_params(params)),
+        Func::Native(_) | Func::Constructor { .. } if named > 0 => {
+            return Err(format!("'{}' doesn't take named arguments", callee).into());
+        }
+        Func::Native(native) => (native.arity(), Some(native.arity())),
+        Func::Constructor { arity, .. } => (*arity, Some(*arity)),
+    };
+
+    let got = positional + named;
+    if named == 0 || max.is_some_and(|max| got > max) {
+        check_arity(callee, min, max, got, span)?;
+    }
+
+    Ok(())
+}
+
+/// Fails unless `got` arguments are at least `min` and at most `max`, if there is one.
+pub(crate) fn check_arity(
+    callee: Symbol,
+    min: usize,
+    max: Option<usize>,
+    got: usize,
+    span: &Span,
+) -> Result<(), RuntimeError> {
+    if got >= min && max.is_none_or(|max| got <= max) {
+        Ok(())
+    } else {
+        Err(RuntimeError::Arity {
+            callee: callee.to_string(),
+            min,
+            max,
+            got,
+            span: span.0.clone(),
+        })
+    }
+}
+
+/// Binds the arguments of a call to `callee` in the scope of the call, with `args` being the
+/// positional ones and `named` the ones given as `name: val`.
+///
+/// Parameters that weren't given an argument are bound to their default, which `eval` evaluates
+/// in that scope so that defaults can refer to earlier parameters, and a rest parameter is bound
+/// to a list of whatever positional arguments are left.
+pub(crate) fn bind_args(
+    callee: Symbol,
+    params: &[Param],
+    args: Vec<Val>,
+    named: Vec<(Symbol, Val)>,
+    env: &mut Env,
+    eval: impl Fn(&Expr, &mut Env) -> Result<Val, RuntimeError>,
+) -> Result<(), RuntimeError> {
+    let (fixed, rest) = match params.split_last() {
+        Some((rest, fixed)) if rest.rest => (fixed, Some(rest)),
+        _ => (params, None),
+    };
+
+    let mut args = args.into_iter();
+    let mut slots: Vec<_> = fixed.iter().map(|_| args.next()).collect();
+
+    for (name, val) in named {
+        let idx = fixed
+            .iter()
+            .position(|param| param.name == name)
+            .ok_or_else(|| format!("'{}' has no parameter '{}'", callee, name))?;
+
+        if slots[idx].is_some() {
+            return Err(format!("parameter '{}' of '{}' was given twice", name, callee).into());
+        }
+
+        slots[idx] = Some(val);
+    }
+
+    for (param, slot) in fixed.iter().zip(slots) {
+        let arg = match (slot, &param.default) {
+            (Some(arg), _) => arg,
+            (None, Some(default)) => eval(default, env)?,
+            (None, None) => {
+                return Err(format!(
+                    "missing argument for parameter '{}' of '{}'",
+                    param.name, callee
+                )
+                .into())
+            }
+        };
+
+        param.bind(callee, arg, env)?;
+    }
 
-    pub(crate) fn new(s: &str) -> Result<(&str, Self), String> {
-        let s = utils::tag("fn", s)?;
-        let (s, _) = utils::extract_whitespace1(s)?;
+    if let Some(rest) = rest {
+        rest.bind(callee, Val::List(args.collect()), env)?;
+    }
 
-        let (s, name) = utils::extract_ident(s)?;
-        let (s, _) = utils::extract_whitespace(s);
+    Ok(())
+}
 
-        let (s, params) = utils::sequence(
-            |s| utils::extract_ident(s).map(|(s, ident)| (s, ident.to_string())),
-            utils::extract_whitespace,
-            s,
-        )?;
+/// A call with `_` in place of some of its positional arguments, as in `add(2, _)`, which is a
+/// function taking the missing arguments in order.
+///
+/// The callee is looked up when this is made, so that what it calls stays the same wherever it
+/// ends up being called and whatever is defined with the same name in the meantime.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Partial {
+    pub(crate) callee: Symbol,
+    pub(crate) func: Arc<Func>,
+    /// The positional arguments, with `None` for each `_`.
+    pub(crate) args: Vec<Option<Val>>,
+    pub(crate) named: Vec<(Symbol, Val)>,
+}
 
-        let s = utils::tag("=>", s)?;
-        let (s, _) = utils::extract_whitespace(s);
+impl Partial {
+    /// What the bare name of `func` evaluates to when `func` needs arguments, which is a `_` for
+    /// each of them. That way `map(xs, double)` passes `double` along instead of calling it.
+    pub(crate) fn of(callee: Symbol, func: &Func) -> Option<Self> {
+        let required = match func {
+            Func::User { params, .. } => required_params(params),
+            Func::Native(native) => native.arity(),
+            Func::Constructor { arity, .. } => *arity,
+        };
 
-        let (s, body) = Stmt::new(s)?;
+        (required > 0).then(|| Self {
+            callee,
+            func: Arc::new(func.clone()),
+            args: vec![None; required],
+            named: Vec::new(),
+        })
+    }
+
+    /// The partial that a call of `func` makes when some of its positional `args` are `_`.
+    /// Named arguments are matched up with the parameters of a user function now, so that the `_`s
+    /// stand for the parameters that are left in order, as in `sub(_, x: 10)` taking `y`.
+    pub(crate) fn new(
+        callee: Symbol,
+        func: Func,
+        args: Vec<Option<Val>>,
+        named: Vec<(Symbol, Val)>,
+    ) -> Result<Self, RuntimeError> {
+        let (args, named) = match &func {
+            Func::User { params, .. } if !named.is_empty() => {
+                resolve_named(callee, params, args, named)?
+            }
+            _ => (args, named),
+        };
+
+        Ok(Self {
+            callee,
+            func: Arc::new(func),
+            args,
+            named,
+        })
+    }
+
+    fn holes(&self) -> usize {
+        self.args.iter().filter(|arg| arg.is_none()).count()
+    }
+
+    /// Checks the arguments that a call of this through the name `name` passes, which have to be
+    /// exactly one positional argument for each `_`.
+    pub(crate) fn check_args(
+        &self,
+        name: Symbol,
+        positional: usize,
+        named: usize,
+        span: &Span,
+    ) -> Result<(), RuntimeError> {
+        if named > 0 {
+            return Err(format!("'{}' doesn't take named arguments", name).into());
+        }
+
+        let holes = self.holes();
+        check_arity(name, holes, Some(holes), positional, span)
+    }
+
+    /// Fills in the `_`s with `args`, giving the positional and named arguments to call the
+    /// callee with.
+    pub(crate) fn fill(&self, args: Vec<Val>) -> (Vec<Val>, Vec<(Symbol, Val)>) {
+        let mut args = args.into_iter();
+        let filled = self
+            .args
+            .iter()
+            .map(|arg| arg.clone().or_else(|| args.next()))
+            .collect::<Option<_>>()
+            .expect("there is an argument for every hole");
+
+        (filled, self.named.clone())
+    }
+}
+
+/// The positional arguments of a partial call, with `None` for each `_`, and its named ones.
+type PartialArgs = (Vec<Option<Val>>, Vec<(Symbol, Val)>);
+
+/// Puts the named arguments of a partial call of `callee` in the slots of their parameters, and
+/// its positional ones, `_`s included, in the slots that are left. Named arguments up to the last
+/// positional one are then passed positionally with it, and the rest stay named.
+fn resolve_named(
+    callee: Symbol,
+    params: &[Param],
+    args: Vec<Option<Val>>,
+    named: Vec<(Symbol, Val)>,
+) -> Result<PartialArgs, RuntimeError> {
+    let fixed = match params.split_last() {
+        Some((rest, fixed)) if rest.rest => fixed,
+        _ => params,
+    };
+
+    let mut slots: Vec<Option<Option<Val>>> = vec![None; fixed.len()];
+    for (name, val) in named {
+        let idx = fixed
+            .iter()
+            .position(|param| param.name == name)
+            .ok_or_else(|| format!("'{}' has no parameter '{}'", callee, name))?;
+
+        if slots[idx].is_some() {
+            return Err(format!("parameter '{}' of '{}' was given twice", name, callee).into());
+        }
+
+        slots[idx] = Some(Some(val));
+    }
+
+    let mut args = args.into_iter();
+    let mut end = 0;
+    for (idx, slot) in slots.iter_mut().enumerate() {
+        if slot.is_some() {
+            continue;
+        }
+
+        match args.next() {
+            Some(arg) => {
+                *slot = Some(arg);
+                end = idx + 1;
+            }
+            None => break,
+        }
+    }
+
+    // Whatever is left over goes to the rest parameter, after all the others.
+    let rest: Vec<_> = args.collect();
+    if !rest.is_empty() {
+        end = fixed.len();
+    }
+
+    let named = fixed[end..]
+        .iter()
+        .zip(slots.split_off(end))
+        .filter_map(|(param, slot)| Some((param.name, slot??)))
+        .collect();
+    let positional = slots.into_iter().flatten().chain(rest).collect();
+
+    Ok((positional, named))
+}
+
+/// The scope that calls of `partial` from `env` are evaluated in. The scope that defined the
+/// callee may be gone by then, so it is the top-level scope with the callee added under its own
+/// name, which lets the callee still call itself but not see the caller's bindings.
+pub(crate) fn captured_scope<'a>(partial: &Partial, env: &'a Env) -> Env<'a> {
+    let mut scope = env.root().create_child();
+    scope.insert_func(partial.callee, Func::clone(&partial.func));
+    scope
+}
+
+impl fmt::Display for Partial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(", self.callee)?;
+
+        let args = self.args.iter().map(|arg| match arg {
+            Some(arg) => arg.to_string(),
+            None => "_".to_string(),
+        });
+        let named = self
+            .named
+            .iter()
+            .map(|(name, val)| format!("{}: {}", name, val));
+
+        for (idx, arg) in args.chain(named).enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+
+            write!(f, "{}", arg)?;
+        }
+
+        write!(f, ")")
+    }
+}
+
+impl FuncDef {
+    pub(crate) fn eval(&self, env: &mut Env) -> Result<(), RuntimeError> {
+        env.define_func(self.name, Func::user(self, None), self.doc.as_deref())
+    }
+
+    pub(crate) fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let doc = tokens.first().and_then(|token| token.doc.clone());
+        let tokens = utils::keyword("fn", tokens)?;
+        let (tokens, name) = utils::extract_ident(tokens)?;
+
+        let (tokens, params) = utils::sequence(Param::new, tokens)?;
+
+        if let Some(param) = params.iter().rev().skip(1).find(|param| param.rest) {
+            return Err(format!(
+                "rest parameter '{}' has to be the last parameter",
+                param.name
+            ));
+        }
+
+        let tokens = utils::expect(TokenKind::FatArrow, tokens)?;
+
+        // The body can be another definition, so functions nest without anything else between.
+        let _depth = utils::Depth::enter(tokens)?;
+        let (tokens, body) = Stmt::new(tokens)?;
 
         Ok((
-            s,
+            tokens,
             Self {
-                name: name.to_string(),
+                name: Symbol::intern(name),
                 params,
                 body: Box::new(body),
+                span: utils::consumed_span(start, tokens),
+                doc,
+                source: None,
             },
         ))
     }
@@ -51,50 +465,202 @@ mod tests {
     #[test]
     fn parse_func_def_with_multiple_params() {
         assert_eq!(
-            FuncDef::new("fn add x y => x + y"),
+            utils::parse_str(FuncDef::new, "fn add x y => x + y"),
             Ok((
                 "",
                 FuncDef {
-                    name: "add".to_string(),
-                    params: vec!["x".to_string(), "y".to_string()],
+                    name: "add".into(),
+                    span: Span::default(),
+                    params: vec!["x".into(), "y".into()],
                     body: Box::new(Stmt::Expr(Expr::Operation {
                         lhs: Box::new(Expr::BindingUsage(BindingUsage {
-                            name: "x".to_string(),
+                            name: "x".into(),
+                            span: Span::default(),
                         })),
                         rhs: Box::new(Expr::BindingUsage(BindingUsage {
-                            name: "y".to_string(),
+                            name: "y".into(),
+                            span: Span::default(),
                         })),
                         op: Op::Add,
+                        span: Span::default(),
                     })),
+                    doc: None,
+                    source: None,
                 },
             )),
         );
     }
+
+    #[test]
+    fn parse_doc_comment_above_func_def() {
+        let (_, func_def) =
+            utils::parse_str(FuncDef::new, "## Doubles x.\nfn double x => x * 2").unwrap();
+
+        assert_eq!(func_def.doc.as_deref(), Some("Doubles x."));
+    }
+
     #[test]
     fn parse_func_def_with_no_params_and_empty_body() {
         assert_eq!(
-            FuncDef::new("fn nothing => {}"),
+            utils::parse_str(FuncDef::new, "fn nothing => {}"),
             Ok((
                 "",
                 FuncDef {
-                    name: "nothing".to_string(),
+                    name: "nothing".into(),
+                    span: Span::default(),
                     params: Vec::new(),
-                    body: Box::new(Stmt::Expr(Expr::Block(Block { stmts: Vec::new() }))),
+                    body: Box::new(Stmt::Expr(Expr::Block(Block {
+                        stmts: Vec::new(),
+                        span: Span::default(),
+                    }))),
+                    doc: None,
+                    source: None,
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_func_def_with_annotated_params() {
+        assert_eq!(
+            utils::parse_str(FuncDef::new, "fn add x: number, y => {}"),
+            Ok((
+                "",
+                FuncDef {
+                    name: "add".into(),
+                    span: Span::default(),
+                    params: vec![
+                        Param {
+                            name: "x".into(),
+                            ty: Some(Type::Number),
+                            default: None,
+                            rest: false,
+                        },
+                        "y".into(),
+                    ],
+                    body: Box::new(Stmt::Expr(Expr::Block(Block {
+                        stmts: Vec::new(),
+                        span: Span::default(),
+                    }))),
+                    doc: None,
+                    source: None,
                 },
             )),
         );
     }
 
+    #[test]
+    fn check_annotated_params_at_call() {
+        let program = crate::parse("fn add x: number, y: number => x + y\nadd(1, \"2\")").unwrap();
+
+        assert_eq!(
+            program.eval(&mut Env::default()),
+            Err(RuntimeError::Message(
+                "expected parameter 'y' of 'add' to be number, got string".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn parse_func_def_with_default() {
+        assert_eq!(
+            utils::parse_str(FuncDef::new, "fn greet name = \"world\" => {}"),
+            Ok((
+                "",
+                FuncDef {
+                    name: "greet".into(),
+                    span: Span::default(),
+                    params: vec![Param {
+                        name: "name".into(),
+                        ty: None,
+                        default: Some(Expr::Str("world".to_string(), Span::default())),
+                        rest: false,
+                    }],
+                    body: Box::new(Stmt::Expr(Expr::Block(Block {
+                        stmts: Vec::new(),
+                        span: Span::default(),
+                    }))),
+                    doc: None,
+                    source: None,
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn fill_in_defaults_for_missing_args() {
+        let eval = |s| crate::parse(s).unwrap().eval(&mut Env::default());
+
+        assert_eq!(
+            eval("fn add x, y = x + 1 => x + y\n[add(1), add(1, 5)]"),
+            Ok(Val::List(vec![
+                Val::Number(3.into()),
+                Val::Number(6.into())
+            ])),
+        );
+        assert_eq!(
+            eval("fn greet name = \"world\" => name\ngreet"),
+            Ok(Val::Str("world".to_string())),
+        );
+        assert_eq!(
+            eval("fn add x, y = 1 => x + y\nadd()"),
+            Err(RuntimeError::Arity {
+                callee: "add".to_string(),
+                min: 1,
+                max: Some(2),
+                got: 0,
+                span: 25..30,
+            }),
+        );
+    }
+
+    #[test]
+    fn collect_rest_args_into_list() {
+        let eval = |s| crate::parse(s).unwrap().eval(&mut Env::default());
+
+        assert_eq!(
+            eval("fn tail first ...rest => rest\n[tail(1), tail(1, 2, 3)]"),
+            Ok(Val::List(vec![
+                Val::List(Vec::new()),
+                Val::List(vec![Val::Number(2.into()), Val::Number(3.into())]),
+            ])),
+        );
+        assert_eq!(
+            eval("fn tail first ...rest => rest\ntail()"),
+            Err(RuntimeError::Arity {
+                callee: "tail".to_string(),
+                min: 1,
+                max: None,
+                got: 0,
+                span: 30..36,
+            }),
+        );
+    }
+
+    #[test]
+    fn rest_param_has_to_be_last() {
+        assert_eq!(
+            utils::parse_str(FuncDef::new, "fn f ...xs y => y"),
+            Err("rest parameter 'xs' has to be the last parameter".to_string()),
+        );
+    }
+
     #[test]
     fn parse_func_def_with_params_and_empty_body() {
         assert_eq!(
-            FuncDef::new("fn add x y => {}"),
+            utils::parse_str(FuncDef::new, "fn add x y => {}"),
             Ok((
                 "",
                 FuncDef {
-                    name: "add".to_string(),
+                    name: "add".into(),
+                    span: Span::default(),
                     params: vec!["x".into(), "y".into()],
-                    body: Box::new(Stmt::Expr(Expr::Block(Block { stmts: Vec::new() }))),
+                    body: Box::new(Stmt::Expr(Expr::Block(Block {
+                        stmts: Vec::new(),
+                        span: Span::default(),
+                    }))),
+                    doc: None,
+                    source: None,
                 },
             )),
         );
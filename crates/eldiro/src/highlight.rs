@@ -0,0 +1,157 @@
+//! Classifies the pieces of source text for syntax highlighting.
+
+use crate::compat::prelude::*;
+use crate::lexer::{self, TokenKind};
+use core::ops::Range;
+
+/// A piece of source that [`highlight`] classified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Highlight {
+    pub kind: HighlightKind,
+    /// The byte range of the piece in the source.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum HighlightKind {
+    /// One of [`KEYWORDS`](crate::KEYWORDS), which includes `true`, `false` and `nil`.
+    Keyword,
+    Number,
+    String,
+    Identifier,
+    /// Symbols that combine or compare values, along with `=`, `=>` and the like.
+    Operator,
+    /// Brackets, commas and dots.
+    Punctuation,
+    /// A comment or a shebang line.
+    Comment,
+    /// Something that can't start a token, such as `@` or a string with a bad escape.
+    Error,
+}
+
+impl HighlightKind {
+    /// A lowercase name for the kind, such as `keyword`, for use as a CSS class.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Keyword => "keyword",
+            Self::Number => "number",
+            Self::String => "string",
+            Self::Identifier => "identifier",
+            Self::Operator => "operator",
+            Self::Punctuation => "punctuation",
+            Self::Comment => "comment",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// Splits `s` into classified pieces, in order and leaving out whitespace. This never fails, so
+/// code that doesn't parse can still be highlighted.
+pub fn highlight(s: &str) -> Vec<Highlight> {
+    lexer::lex_lossless(s)
+        .into_iter()
+        .filter_map(|token| {
+            let kind = match token.kind {
+                TokenKind::Ident(ident) if crate::KEYWORDS.contains(&ident.as_str()) => {
+                    HighlightKind::Keyword
+                }
+                TokenKind::Ident(_) => HighlightKind::Identifier,
+                TokenKind::Number(_) => HighlightKind::Number,
+                TokenKind::Str(_) => HighlightKind::String,
+                TokenKind::Plus
+                | TokenKind::Minus
+                | TokenKind::Star
+                | TokenKind::StarStar
+                | TokenKind::Slash
+                | TokenKind::Percent
+                | TokenKind::EqEq
+                | TokenKind::BangEq
+                | TokenKind::Lt
+                | TokenKind::Le
+                | TokenKind::Gt
+                | TokenKind::Ge
+                | TokenKind::Amp
+                | TokenKind::Pipe
+                | TokenKind::PipeGt
+                | TokenKind::Caret
+                | TokenKind::Shl
+                | TokenKind::Shr
+                | TokenKind::Tilde
+                | TokenKind::QuestionQuestion
+                | TokenKind::Eq
+                | TokenKind::PlusEq
+                | TokenKind::MinusEq
+                | TokenKind::StarEq
+                | TokenKind::SlashEq
+                | TokenKind::PercentEq
+                | TokenKind::FatArrow
+                | TokenKind::Colon
+                | TokenKind::Ellipsis
+                | TokenKind::DotDot
+                | TokenKind::DotDotEq => HighlightKind::Operator,
+                TokenKind::QuestionLBracket
+                | TokenKind::LParen
+                | TokenKind::RParen
+                | TokenKind::LBrace
+                | TokenKind::RBrace
+                | TokenKind::LBracket
+                | TokenKind::RBracket
+                | TokenKind::Comma
+                | TokenKind::Dot => HighlightKind::Punctuation,
+                TokenKind::Comment | TokenKind::Shebang => HighlightKind::Comment,
+                TokenKind::Unknown => HighlightKind::Error,
+                TokenKind::Whitespace => return None,
+            };
+
+            Some(Highlight {
+                kind,
+                span: token.span,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classes(s: &str) -> Vec<(&str, &'static str)> {
+        highlight(s)
+            .into_iter()
+            .map(|highlight| (&s[highlight.span], highlight.kind.name()))
+            .collect()
+    }
+
+    #[test]
+    fn classify_each_token() {
+        assert_eq!(
+            classes("let a = [1, \"b\"] // c"),
+            [
+                ("let", "keyword"),
+                ("a", "identifier"),
+                ("=", "operator"),
+                ("[", "punctuation"),
+                ("1", "number"),
+                (",", "punctuation"),
+                ("\"b\"", "string"),
+                ("]", "punctuation"),
+                ("// c", "comment"),
+            ],
+        );
+    }
+
+    #[test]
+    fn highlight_code_that_does_not_parse() {
+        assert_eq!(
+            classes("#!/bin/eldiro\nfn => @ true"),
+            [
+                ("#!/bin/eldiro", "comment"),
+                ("fn", "keyword"),
+                ("=>", "operator"),
+                ("@", "error"),
+                ("true", "keyword"),
+            ],
+        );
+    }
+}
@@ -0,0 +1,403 @@
+use crate::compat::prelude::*;
+use crate::env::Env;
+use crate::error::RuntimeError;
+use crate::lexer::{Token, TokenKind};
+use crate::span::Span;
+#[cfg(feature = "std")]
+use crate::stmt::Stmt;
+#[cfg(feature = "std")]
+use crate::symbol::Symbol;
+use crate::utils;
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+
+/// `import "path"`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Import {
+    pub path: String,
+    pub span: Span,
+}
+
+/// The file that code in an [`Env`] came from, along with the chain of files that imported it.
+/// This needs the `std` feature, since without it there are no files.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub(crate) struct Module {
+    path: Option<PathBuf>,
+    /// `path` as it's shown in errors, kept so that every function the module defines can share
+    /// it.
+    name: Option<Arc<str>>,
+    importer: Option<Arc<Module>>,
+}
+
+#[cfg(feature = "std")]
+impl Module {
+    pub(crate) fn new(path: PathBuf, importer: Option<Arc<Module>>) -> Self {
+        Self {
+            name: Some(path.display().to_string().into()),
+            path: Some(path),
+            importer,
+        }
+    }
+
+    pub(crate) fn name(&self) -> Option<&Arc<str>> {
+        self.name.as_ref()
+    }
+
+    /// Resolves an import path relative to the directory of this module's file, or the current
+    /// directory if there isn't one.
+    fn resolve(&self, path: &str) -> PathBuf {
+        match self.path.as_deref().and_then(Path::parent) {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        }
+    }
+
+    /// Returns the chain of imports that leads back to `path`, if it is already being imported.
+    fn cycle_to(&self, path: &Path) -> Option<Vec<&Path>> {
+        let mut chain = Vec::new();
+        let mut module = Some(self);
+
+        while let Some(current) = module {
+            if let Some(current_path) = &current.path {
+                chain.push(current_path.as_path());
+
+                if current_path == path {
+                    chain.reverse();
+                    return Some(chain);
+                }
+            }
+
+            module = current.importer.as_deref();
+        }
+
+        None
+    }
+}
+
+impl Import {
+    pub(crate) fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let tokens = utils::keyword("import", tokens)?;
+
+        match tokens.split_first() {
+            Some((
+                Token {
+                    kind: TokenKind::Str(path),
+                    ..
+                },
+                tokens,
+            )) => Ok((
+                tokens,
+                Self {
+                    path: path.clone(),
+                    span: utils::consumed_span(start, tokens),
+                },
+            )),
+            _ => Err("expected string".to_string()),
+        }
+    }
+
+    /// Evaluates the imported file in a fresh environment of its own, then exposes everything it
+    /// defined in `env`. This fails without touching the file unless the host called
+    /// [`Env::allow_imports`], and in deterministic mode, since the file can change between runs.
+    #[cfg(feature = "std")]
+    pub(crate) fn eval(&self, env: &mut Env) -> Result<(), RuntimeError> {
+        if !env.imports_allowed() {
+            return Err(format!("cannot import '{}': imports are turned off", self.path).into());
+        }
+        if env.is_deterministic() {
+            return Err(format!(
+                "cannot import '{}' in deterministic mode, since files can change between runs",
+                self.path
+            )
+            .into());
+        }
+
+        let module = env.module();
+
+        let path = fs::canonicalize(module.resolve(&self.path))
+            .map_err(|e| format!("cannot import '{}': {}", self.path, e))?;
+
+        if let Some(chain) = module.cycle_to(&path) {
+            let chain: Vec<_> = chain
+                .iter()
+                .chain([&path.as_path()])
+                .map(|path| path.display().to_string())
+                .collect();
+
+            return Err(format!("import cycle detected: {}", chain.join(" -> ")).into());
+        }
+
+        let source = fs::read_to_string(&path)
+            .map_err(|e| format!("cannot import '{}': {}", self.path, e))?;
+
+        let program = crate::parse(&source).map_err(|e| {
+            let (line, _) = e.line_col(&source);
+            format!("in '{}' at line {}: {}", self.path, line, e)
+        })?;
+
+        let module = Module::new(path, Some(module));
+        let name = module.name.clone();
+        let mut module_env = env.create_module(module);
+        program
+            .eval(&mut module_env)
+            .map_err(|e| e.in_source(program.shared_source(), name.as_ref()))?;
+        env.expose(module_env);
+
+        Ok(())
+    }
+
+    /// The names evaluating this from `module` would expose, found by reading the imported file and
+    /// the files it imports without running any of them. This is `None` if one of them can't be
+    /// read or parsed, or they import each other in a cycle, since the import then fails anyway.
+    #[cfg(feature = "std")]
+    pub(crate) fn defined_names(&self, module: &Arc<Module>) -> Option<Vec<Symbol>> {
+        let path = fs::canonicalize(module.resolve(&self.path)).ok()?;
+        if module.cycle_to(&path).is_some() {
+            return None;
+        }
+
+        let program = crate::parse(&fs::read_to_string(&path).ok()?).ok()?;
+        let module = Arc::new(Module::new(path, Some(Arc::clone(module))));
+        let mut names = Vec::new();
+
+        for stmt in program.stmts() {
+            match stmt {
+                Stmt::BindingDef(binding_def) => names.push(binding_def.name),
+                Stmt::FuncDef(func_def) => names.push(func_def.name),
+                Stmt::EnumDef(enum_def) => {
+                    names.extend(enum_def.variants.iter().map(|variant| variant.name));
+                }
+                Stmt::Import(import) => names.extend(import.defined_names(&module)?),
+                Stmt::Throw(..) | Stmt::Assign(_) | Stmt::Expr(_) => {}
+            }
+        }
+
+        Some(names)
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub(crate) fn eval(&self, _: &mut Env) -> Result<(), RuntimeError> {
+        Err(format!(
+            "cannot import '{}': imports need the `std` feature",
+            self.path
+        )
+        .into())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::expr::{BindingUsage, Expr};
+    use crate::val::Val;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("eldiro-import-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn env_in(dir: &Path) -> Env<'static> {
+        let mut env = Env::default();
+        env.set_current_file(dir.join("main.eld"));
+        env.allow_imports();
+        env
+    }
+
+    #[test]
+    fn parse_import() {
+        assert_eq!(
+            utils::parse_str(Import::new, r#"import "lib/math.eld""#),
+            Ok((
+                "",
+                Import {
+                    path: "lib/math.eld".to_string(),
+                    span: Span::default(),
+                },
+            )),
+        );
+    }
+
+    #[test]
+    fn import_exposes_bindings_and_funcs() {
+        let dir = temp_dir("exposes");
+        fs::write(dir.join("math.eld"), "let ten = 10\nfn square x => x * x\n").unwrap();
+
+        let mut env = env_in(&dir);
+        Import {
+            path: "math.eld".to_string(),
+            span: Span::default(),
+        }
+        .eval(&mut env)
+        .unwrap();
+
+        assert_eq!(
+            crate::parse("square(ten)").unwrap().eval(&mut env),
+            Ok(Val::Number(100.into())),
+        );
+    }
+
+    #[test]
+    fn imports_are_off_unless_allowed() {
+        let dir = temp_dir("off");
+        fs::write(dir.join("secret.eld"), "let secret = 1\n").unwrap();
+
+        for file in ["secret.eld", "missing.eld"] {
+            let import = Import {
+                path: file.to_string(),
+                span: Span::default(),
+            };
+
+            let mut env = Env::default();
+            env.set_current_file(dir.join("main.eld"));
+            assert_eq!(
+                import.eval(&mut env),
+                Err(RuntimeError::Message(format!(
+                    "cannot import '{}': imports are turned off",
+                    file
+                ))),
+            );
+
+            let mut env = env_in(&dir);
+            env.make_deterministic(0);
+            assert_eq!(
+                import.eval(&mut env),
+                Err(RuntimeError::Message(format!(
+                    "cannot import '{}' in deterministic mode, since files can change between runs",
+                    file
+                ))),
+            );
+        }
+    }
+
+    #[test]
+    fn imported_file_cannot_see_importer_bindings() {
+        let dir = temp_dir("isolated");
+        fs::write(dir.join("uses_outer.eld"), "let copy = outer\n").unwrap();
+
+        let mut env = env_in(&dir);
+        env.store_binding("outer".into(), Val::Number(1.into()));
+
+        assert_eq!(
+            Import {
+                path: "uses_outer.eld".to_string(),
+                span: Span::default(),
+            }
+            .eval(&mut env),
+            Err(RuntimeError::Message(
+                "binding with name 'outer' does not exist".to_string()
+            )),
+        );
+        assert_eq!(
+            Expr::BindingUsage(BindingUsage {
+                name: "copy".into(),
+                span: Span::default(),
+            })
+            .eval(&mut env),
+            Err(RuntimeError::Message(
+                "binding with name 'copy' does not exist".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn imports_resolve_relative_to_importing_file() {
+        let dir = temp_dir("relative");
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(dir.join("lib/outer.eld"), r#"import "inner.eld""#).unwrap();
+        fs::write(dir.join("lib/inner.eld"), "let inner = 5").unwrap();
+
+        let mut env = env_in(&dir);
+        Import {
+            path: "lib/outer.eld".to_string(),
+            span: Span::default(),
+        }
+        .eval(&mut env)
+        .unwrap();
+
+        assert_eq!(env.get_binding("inner".into()), Ok(Val::Number(5.into())));
+    }
+
+    #[test]
+    fn detect_import_cycle() {
+        let dir = temp_dir("cycle");
+        fs::write(dir.join("a.eld"), r#"import "b.eld""#).unwrap();
+        fs::write(dir.join("b.eld"), r#"import "a.eld""#).unwrap();
+
+        let result = Import {
+            path: "a.eld".to_string(),
+            span: Span::default(),
+        }
+        .eval(&mut env_in(&dir));
+
+        let a = fs::canonicalize(dir.join("a.eld")).unwrap();
+        let b = fs::canonicalize(dir.join("b.eld")).unwrap();
+
+        assert_eq!(
+            result,
+            Err(RuntimeError::Message(format!(
+                "import cycle detected: {} -> {} -> {}",
+                a.display(),
+                b.display(),
+                a.display(),
+            ))),
+        );
+    }
+
+    #[test]
+    fn import_missing_file() {
+        let dir = temp_dir("missing");
+
+        assert!(Import {
+            path: "nope.eld".to_string(),
+            span: Span::default(),
+        }
+        .eval(&mut env_in(&dir))
+        .unwrap_err()
+        .to_string()
+        .starts_with("cannot import 'nope.eld'"));
+    }
+
+    #[test]
+    fn errors_point_into_the_imported_file() {
+        let dir = temp_dir("errors");
+        let source = "fn bad x => x / 0\n";
+        fs::write(dir.join("bad.eld"), source).unwrap();
+        fs::write(dir.join("top.eld"), "let a = [][1]\n").unwrap();
+        let path = fs::canonicalize(dir.join("bad.eld")).unwrap();
+
+        let main = "import \"bad.eld\"\nbad(1)";
+        let mut env = env_in(&dir);
+        let tree_walked = crate::parse(main).unwrap().eval(&mut env).unwrap_err();
+        let mut env = env_in(&dir);
+        let compiled = crate::parse(main)
+            .unwrap()
+            .compile()
+            .eval(&mut env)
+            .unwrap_err();
+
+        for error in [tree_walked, compiled] {
+            assert_eq!(error.span(), Some(12..17));
+            assert_eq!(
+                error.source(),
+                Some((source, Some(path.display().to_string().as_str()))),
+            );
+        }
+
+        let error = crate::parse("import \"top.eld\"")
+            .unwrap()
+            .eval(&mut env_in(&dir))
+            .unwrap_err();
+        assert_eq!(
+            error.source().map(|(source, _)| source),
+            Some("let a = [][1]\n")
+        );
+    }
+}
@@ -0,0 +1,241 @@
+#[cfg(feature = "os")]
+use crate::bridge::Bridge;
+use crate::env::Env;
+use crate::error::RuntimeError;
+use crate::observe::EvalObserver;
+use crate::program::Program;
+use crate::val::Val;
+use crate::{compile, vm};
+use alloc::sync::Arc;
+
+/// How an [`Interpreter`] runs programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// Evaluates the syntax tree directly.
+    #[default]
+    TreeWalk,
+    /// Compiles programs to bytecode and runs them on a stack machine. Imported files are still
+    /// evaluated by the tree-walker.
+    Vm,
+}
+
+/// Runs programs in an environment that persists between them, using the chosen [`Backend`].
+#[derive(Debug, Default)]
+pub struct Interpreter {
+    env: Env<'static>,
+    backend: Backend,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_backend(backend: Backend) -> Self {
+        Self {
+            env: Env::default(),
+            backend,
+        }
+    }
+
+    /// Uses `env` instead of a default environment.
+    pub fn with_env(mut self, env: Env<'static>) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Makes every run reproducible, as described in [`Env::make_deterministic`]. Call this after
+    /// [`Interpreter::with_env`], which would replace the environment it applies to.
+    pub fn deterministic(mut self, seed: u64) -> Self {
+        self.env.make_deterministic(seed);
+        self
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    pub fn env(&self) -> &Env<'static> {
+        &self.env
+    }
+
+    pub fn env_mut(&mut self) -> &mut Env<'static> {
+        &mut self.env
+    }
+
+    /// Tells `observer` about everything evaluated from now on, including any errors that
+    /// programs fail with.
+    pub fn add_observer(&mut self, observer: Arc<dyn EvalObserver>) {
+        self.env.add_observer(observer);
+    }
+
+    pub fn eval(&mut self, program: &Program) -> Result<Val, RuntimeError> {
+        let result = eval_with(self.backend, program, &mut self.env);
+        self.report(&result);
+        result
+    }
+
+    /// Like [`Interpreter::eval`], but awaits the futures of natives registered with
+    /// [`Env::define_async_native`], so that an async host isn't blocked while they run.
+    ///
+    /// The program is evaluated on a thread started for each call. If the returned future is
+    /// dropped before it resolves, the evaluation is cancelled: the async native being awaited
+    /// fails, and so does every function called after that. Dropping waits for the evaluation to
+    /// stop, so the environment is kept with whatever the program defined until then. This needs
+    /// the `os` feature.
+    #[cfg(feature = "os")]
+    pub async fn eval_async(&mut self, program: &Program) -> Result<Val, RuntimeError> {
+        let backend = self.backend;
+        let program = program.clone();
+
+        let result =
+            Bridge::spawn(&mut self.env, move |env| eval_with(backend, &program, env)).await;
+
+        self.report(&result);
+        result
+    }
+
+    fn report(&self, result: &Result<Val, RuntimeError>) {
+        if let Err(error) = result {
+            self.env.notify(|observer| observer.on_error(error));
+        }
+    }
+}
+
+fn eval_with(backend: Backend, program: &Program, env: &mut Env) -> Result<Val, RuntimeError> {
+    match backend {
+        Backend::TreeWalk => program.eval(env),
+        Backend::Vm => vm::run(&compile::program(program), env),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_doc_comments_of_definitions() {
+        for backend in [Backend::TreeWalk, Backend::Vm] {
+            let mut interpreter = Interpreter::with_backend(backend);
+            interpreter
+                .eval(
+                    &crate::parse(
+                        "## The rate.\n## In percent.\nconst rate = 3\n## Scales x.\nfn scale x => x * rate\n## Gone.\nlet a = 1\nlet a = 2",
+                    )
+                    .unwrap(),
+                )
+                .unwrap();
+
+            let env = interpreter.env();
+            assert_eq!(env.doc("rate"), Some("The rate.\nIn percent."));
+            assert_eq!(env.doc("scale"), Some("Scales x."));
+            assert_eq!(env.doc("a"), None);
+            assert_eq!(env.doc("missing"), None);
+
+            let mut child = env.create_child();
+            child.set_binding("rate", Val::Unit).unwrap();
+            assert_eq!(child.doc("rate"), None);
+            assert_eq!(child.doc("scale"), Some("Scales x."));
+        }
+    }
+
+    #[test]
+    fn keep_the_source_of_functions() {
+        for backend in [Backend::TreeWalk, Backend::Vm] {
+            let mut interpreter = Interpreter::with_backend(backend);
+            let program =
+                crate::parse("let a = 1\nfn double x => {\n    x * 2\n}\n\ndouble(a)").unwrap();
+            interpreter.eval(&program).unwrap();
+            drop(program);
+
+            let env = interpreter.env();
+            assert_eq!(
+                env.func_source("double"),
+                Some("fn double x => {\n    x * 2\n}")
+            );
+            assert_eq!(env.func_source("a"), None);
+            assert_eq!(env.func_source("len"), None);
+        }
+    }
+
+    #[test]
+    fn backends_agree() {
+        for source in [
+            "",
+            "1 + 2 * 3 == 7",
+            r#"let s = "a"
+s != "b""#,
+            "fn sq x => x * x\nlet a = sq 3\n{ let a = 1\na } + a",
+            "fn add x y => x + y\nadd(1, add 2 3)",
+            "fn one => 1\none + one",
+            "fn f x => { fn g => x\ng() }\nf(4)",
+            "assert(1 > 2, \"nope\")",
+            "missing",
+            "1 + true",
+            "-2 ** 2 * -3",
+            "let n = 10\nfor i in 0..n { n -= i % 3 }\nn",
+            "let a = 1\n{ a += true }",
+        ] {
+            let program = crate::parse(source).unwrap();
+
+            assert_eq!(
+                Interpreter::with_backend(Backend::Vm).eval(&program),
+                Interpreter::with_backend(Backend::TreeWalk).eval(&program),
+                "{}",
+                source,
+            );
+        }
+    }
+
+    #[test]
+    fn deterministic_runs_repeat_exactly() {
+        let program =
+            crate::parse("([random(1000), random(1000), random(1000)], now_ms())").unwrap();
+        let run = |backend, seed| {
+            Interpreter::with_backend(backend)
+                .deterministic(seed)
+                .eval(&program)
+                .unwrap()
+        };
+
+        for backend in [Backend::TreeWalk, Backend::Vm] {
+            assert_eq!(run(backend, 42), run(backend, 42));
+            assert_ne!(run(backend, 42), run(backend, 43));
+
+            match run(backend, 42) {
+                Val::Tuple(items) => assert_eq!(items[1], Val::Number(0.into())),
+                other => panic!("{:?}", other),
+            }
+        }
+        assert_eq!(run(Backend::TreeWalk, 7), run(Backend::Vm, 7));
+    }
+
+    #[test]
+    fn deterministic_mode_rejects_async_natives() {
+        let mut interpreter = Interpreter::new().deterministic(0);
+        interpreter
+            .env_mut()
+            .define_async_native("fetch", 0, |_| Box::pin(async { Ok(Val::Unit) }));
+
+        assert_eq!(
+            interpreter.eval(&crate::parse("fetch()").unwrap()),
+            Err(RuntimeError::Message(
+                "'fetch' can't be called in deterministic mode, since its results can't be reproduced"
+                    .to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn env_persists_between_programs() {
+        let mut interpreter = Interpreter::with_backend(Backend::Vm);
+        interpreter
+            .eval(&crate::parse("let x = 10").unwrap())
+            .unwrap();
+
+        assert_eq!(
+            interpreter.eval(&crate::parse("x * 2").unwrap()),
+            Ok(Val::Number(20.into())),
+        );
+    }
+}
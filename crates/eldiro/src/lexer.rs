@@ -0,0 +1,718 @@
+//! Splits source text into tokens, so that parsers don't have to deal with whitespace.
+
+use crate::compat::prelude::*;
+use core::fmt;
+use core::mem;
+use core::ops::Range;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TokenKind {
+    Ident(String),
+    Number(String),
+    Str(String),
+    Plus,
+    Minus,
+    Star,
+    /// `**`, which raises to a power.
+    StarStar,
+    Slash,
+    Percent,
+    EqEq,
+    BangEq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Amp,
+    Pipe,
+    PipeGt,
+    Caret,
+    Shl,
+    Shr,
+    Tilde,
+    QuestionQuestion,
+    /// `?[`, which starts a nil-safe index.
+    QuestionLBracket,
+    Eq,
+    /// `+=`, which adds to an existing binding. The other compound assignments follow it.
+    PlusEq,
+    MinusEq,
+    StarEq,
+    SlashEq,
+    PercentEq,
+    FatArrow,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Comma,
+    /// `:`, which starts a type annotation.
+    Colon,
+    /// `...`, which marks a rest parameter.
+    Ellipsis,
+    DotDot,
+    DotDotEq,
+    Dot,
+    Whitespace,
+    /// A `//` comment or a `##` doc comment, which runs to the end of the line.
+    Comment,
+    /// A `#!` line at the very start of the input.
+    Shebang,
+    /// Anything that can't start a token, such as `@` or a string with a bad escape. Parsers
+    /// never accept it, so parsing stops there.
+    Unknown,
+}
+
+impl TokenKind {
+    /// Whether this is whitespace or a comment, which only matter to tools that care about the
+    /// exact source text.
+    pub(crate) fn is_trivia(&self) -> bool {
+        matches!(self, Self::Whitespace | Self::Comment | Self::Shebang)
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Self::Ident(_) => "identifier",
+            Self::Number(_) => "number",
+            Self::Str(_) => "string",
+            Self::Plus => "+",
+            Self::Minus => "-",
+            Self::Star => "*",
+            Self::StarStar => "**",
+            Self::Slash => "/",
+            Self::Percent => "%",
+            Self::EqEq => "==",
+            Self::BangEq => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Amp => "&",
+            Self::Pipe => "|",
+            Self::PipeGt => "|>",
+            Self::Caret => "^",
+            Self::Shl => "<<",
+            Self::Shr => ">>",
+            Self::Tilde => "~",
+            Self::QuestionQuestion => "??",
+            Self::QuestionLBracket => "?[",
+            Self::Eq => "=",
+            Self::PlusEq => "+=",
+            Self::MinusEq => "-=",
+            Self::StarEq => "*=",
+            Self::SlashEq => "/=",
+            Self::PercentEq => "%=",
+            Self::FatArrow => "=>",
+            Self::LParen => "(",
+            Self::RParen => ")",
+            Self::LBrace => "{",
+            Self::RBrace => "}",
+            Self::LBracket => "[",
+            Self::RBracket => "]",
+            Self::Comma => ",",
+            Self::Colon => ":",
+            Self::Ellipsis => "...",
+            Self::DotDot => "..",
+            Self::DotDotEq => "..=",
+            Self::Dot => ".",
+            Self::Whitespace => "whitespace",
+            Self::Comment => "comment",
+            Self::Shebang => "shebang",
+            Self::Unknown => "unknown token",
+        };
+
+        write!(f, "{}", text)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Token {
+    pub(crate) kind: TokenKind,
+    pub(crate) span: Range<usize>,
+    /// Whether there is a line break between this token and the previous one that ends whatever
+    /// came before. Line breaks inside `()` or `[]` don't count, since the closing bracket already
+    /// shows where things end. Always false for trivia.
+    pub(crate) newline_before: bool,
+    /// The text of the `##` comments on the lines right before this token, without the `##`s.
+    /// Always `None` for trivia.
+    pub(crate) doc: Option<String>,
+}
+
+const SYMBOLS: &[(&str, TokenKind)] = &[
+    // Longer symbols come first so that `==` isn't lexed as two `=`s.
+    ("...", TokenKind::Ellipsis),
+    ("..=", TokenKind::DotDotEq),
+    ("==", TokenKind::EqEq),
+    ("!=", TokenKind::BangEq),
+    ("<=", TokenKind::Le),
+    (">=", TokenKind::Ge),
+    ("=>", TokenKind::FatArrow),
+    ("+=", TokenKind::PlusEq),
+    ("-=", TokenKind::MinusEq),
+    ("*=", TokenKind::StarEq),
+    ("/=", TokenKind::SlashEq),
+    ("%=", TokenKind::PercentEq),
+    ("<<", TokenKind::Shl),
+    (">>", TokenKind::Shr),
+    ("??", TokenKind::QuestionQuestion),
+    ("?[", TokenKind::QuestionLBracket),
+    ("|>", TokenKind::PipeGt),
+    ("**", TokenKind::StarStar),
+    ("..", TokenKind::DotDot),
+    ("+", TokenKind::Plus),
+    ("-", TokenKind::Minus),
+    ("*", TokenKind::Star),
+    ("/", TokenKind::Slash),
+    ("%", TokenKind::Percent),
+    ("<", TokenKind::Lt),
+    (">", TokenKind::Gt),
+    ("=", TokenKind::Eq),
+    ("(", TokenKind::LParen),
+    (")", TokenKind::RParen),
+    ("{", TokenKind::LBrace),
+    ("}", TokenKind::RBrace),
+    ("[", TokenKind::LBracket),
+    ("]", TokenKind::RBracket),
+    (",", TokenKind::Comma),
+    (":", TokenKind::Colon),
+    (".", TokenKind::Dot),
+    ("&", TokenKind::Amp),
+    ("|", TokenKind::Pipe),
+    ("^", TokenKind::Caret),
+    ("~", TokenKind::Tilde),
+];
+
+/// Splits `s` into tokens, skipping whitespace, comments and a shebang line at the very start.
+pub(crate) fn lex(s: &str) -> Vec<Token> {
+    lex_lossless(s)
+        .into_iter()
+        .filter(|token| !token.kind.is_trivia())
+        .collect()
+}
+
+/// Splits `s` into tokens, keeping whitespace and comments so that the tokens cover all of `s`.
+pub(crate) fn lex_lossless(s: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut idx = 0;
+
+    if s.starts_with("#!") {
+        idx = s.find('\n').unwrap_or(s.len());
+        tokens.push(Token {
+            kind: TokenKind::Shebang,
+            span: 0..idx,
+            newline_before: false,
+            doc: None,
+        });
+    }
+
+    while idx < s.len() {
+        let (len, kind) = lex_token(&s[idx..]);
+
+        tokens.push(Token {
+            kind,
+            span: idx..idx + len,
+            newline_before: false,
+            doc: None,
+        });
+
+        idx += len;
+    }
+
+    mark_line_breaks(s, &mut tokens);
+    attach_docs(s, &mut tokens);
+
+    tokens
+}
+
+fn mark_line_breaks(s: &str, tokens: &mut [Token]) {
+    let mut brackets = Vec::new();
+    let mut newline_before = false;
+
+    for token in tokens {
+        if token.kind.is_trivia() {
+            newline_before |= s[token.span.clone()].contains('\n');
+            continue;
+        }
+
+        token.newline_before = newline_before
+            && !matches!(
+                brackets.last(),
+                Some(TokenKind::LParen | TokenKind::LBracket)
+            );
+        newline_before = false;
+
+        match token.kind {
+            TokenKind::LParen | TokenKind::LBrace | TokenKind::LBracket => {
+                brackets.push(token.kind.clone())
+            }
+            TokenKind::QuestionLBracket => brackets.push(TokenKind::LBracket),
+            TokenKind::RParen | TokenKind::RBrace | TokenKind::RBracket => {
+                brackets.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Gives each token the doc comment above it. A blank line or an ordinary comment in between
+/// means the doc comment isn't about that token.
+fn attach_docs(s: &str, tokens: &mut [Token]) {
+    let mut lines = Vec::new();
+
+    for token in tokens {
+        let text = &s[token.span.clone()];
+
+        match token.kind {
+            TokenKind::Comment => match text.strip_prefix("##") {
+                Some(line) => lines.push(line.strip_prefix(' ').unwrap_or(line)),
+                None => lines.clear(),
+            },
+            TokenKind::Whitespace if text.matches('\n').count() > 1 => lines.clear(),
+            TokenKind::Whitespace | TokenKind::Shebang => {}
+            _ if lines.is_empty() => {}
+            _ => token.doc = Some(mem::take(&mut lines).join("\n")),
+        }
+    }
+}
+
+/// Lexes the token at the start of `s`, returning its length in bytes.
+fn lex_token(s: &str) -> (usize, TokenKind) {
+    let first = s.chars().next().unwrap();
+
+    if is_whitespace(first) {
+        let whitespace = take_while(is_whitespace, s);
+        return (whitespace.len(), TokenKind::Whitespace);
+    }
+
+    if s.starts_with("//") || s.starts_with("##") {
+        // The `\r` of a Windows line ending belongs with the line break, not the comment.
+        let line = &s[..s.find('\n').unwrap_or(s.len())];
+        return (line.trim_end_matches('\r').len(), TokenKind::Comment);
+    }
+
+    if first.is_alphabetic() || first == '_' {
+        let ident = take_while(|c| c.is_alphanumeric() || c == '_', s);
+        return (ident.len(), TokenKind::Ident(ident.to_string()));
+    }
+
+    if first.is_ascii_digit() {
+        // Letters are included so that `0xFF` is one token, and so that `12ab` is reported as a bad
+        // number rather than a number followed by an identifier.
+        let digits = take_while(|c| c.is_ascii_alphanumeric() || c == '_', s);
+        return (digits.len(), TokenKind::Number(digits.to_string()));
+    }
+
+    if first == '"' {
+        return match extract_string(s) {
+            Ok((remainder, string)) => (s.len() - remainder.len(), TokenKind::Str(string)),
+            Err(_) => (1, TokenKind::Unknown),
+        };
+    }
+
+    SYMBOLS
+        .iter()
+        .find(|(symbol, _)| s.starts_with(symbol))
+        .map(|(symbol, kind)| (symbol.len(), kind.clone()))
+        .unwrap_or((first.len_utf8(), TokenKind::Unknown))
+}
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+fn take_while(accept: impl Fn(char) -> bool, s: &str) -> &str {
+    let end = s
+        .char_indices()
+        .find_map(|(idx, c)| if accept(c) { None } else { Some(idx) })
+        .unwrap_or(s.len());
+
+    &s[..end]
+}
+
+/// Extracts the string literal at the start of `s`, or why it isn't one along with the part of `s`
+/// to blame.
+pub(crate) fn extract_string(s: &str) -> Result<(&str, String), (Range<usize>, String)> {
+    let mut string = String::new();
+    let mut chars = s.char_indices().skip(1);
+
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => return Ok((&s[idx + 1..], string)),
+            // Strings spanning lines in a file with Windows line endings still only hold `\n`.
+            '\r' if s[idx + 1..].starts_with('\n') => {}
+            '\\' => match chars.next() {
+                Some((_, 'n')) => string.push('\n'),
+                Some((_, 't')) => string.push('\t'),
+                Some((_, '"')) => string.push('"'),
+                Some((_, '\\')) => string.push('\\'),
+                Some((end, c)) => {
+                    return Err((
+                        idx..end + c.len_utf8(),
+                        format!("unknown escape sequence \\{}", c),
+                    ))
+                }
+                None => break,
+            },
+            c => string.push(c),
+        }
+    }
+
+    Err((0..1, "unterminated string literal".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(s: &str) -> Vec<TokenKind> {
+        lex(s).into_iter().map(|token| token.kind).collect()
+    }
+
+    #[test]
+    fn lex_alphabetic_ident() {
+        assert_eq!(
+            kinds("abcdEFG stop"),
+            vec![
+                TokenKind::Ident("abcdEFG".to_string()),
+                TokenKind::Ident("stop".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn lex_alphanumeric_ident() {
+        assert_eq!(
+            kinds("foobar1()"),
+            vec![
+                TokenKind::Ident("foobar1".to_string()),
+                TokenKind::LParen,
+                TokenKind::RParen,
+            ],
+        );
+    }
+
+    #[test]
+    fn lex_ident_with_underscore() {
+        assert_eq!(
+            kinds("now_ms"),
+            vec![TokenKind::Ident("now_ms".to_string())]
+        );
+    }
+
+    #[test]
+    fn lex_ident_with_leading_and_trailing_underscores() {
+        assert_eq!(
+            kinds("_tmp count_ x2"),
+            vec![
+                TokenKind::Ident("_tmp".to_string()),
+                TokenKind::Ident("count_".to_string()),
+                TokenKind::Ident("x2".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn lex_unicode_ident() {
+        assert_eq!(
+            kinds("größe+π"),
+            vec![
+                TokenKind::Ident("größe".to_string()),
+                TokenKind::Plus,
+                TokenKind::Ident("π".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn lex_multiple_digits() {
+        assert_eq!(
+            kinds("10-20"),
+            vec![
+                TokenKind::Number("10".to_string()),
+                TokenKind::Minus,
+                TokenKind::Number("20".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn lex_prefixed_number_with_separators() {
+        assert_eq!(
+            kinds("0xFF_FF+1_000"),
+            vec![
+                TokenKind::Number("0xFF_FF".to_string()),
+                TokenKind::Plus,
+                TokenKind::Number("1_000".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn lex_power_before_star() {
+        assert_eq!(
+            kinds("a ** b * c"),
+            vec![
+                TokenKind::Ident("a".to_string()),
+                TokenKind::StarStar,
+                TokenKind::Ident("b".to_string()),
+                TokenKind::Star,
+                TokenKind::Ident("c".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn lex_compound_assignment_before_operator() {
+        assert_eq!(
+            kinds("a += b % c -= d\n*= /= %= **="),
+            vec![
+                TokenKind::Ident("a".to_string()),
+                TokenKind::PlusEq,
+                TokenKind::Ident("b".to_string()),
+                TokenKind::Percent,
+                TokenKind::Ident("c".to_string()),
+                TokenKind::MinusEq,
+                TokenKind::Ident("d".to_string()),
+                TokenKind::StarEq,
+                TokenKind::SlashEq,
+                TokenKind::PercentEq,
+                TokenKind::StarStar,
+                TokenKind::Eq,
+            ],
+        );
+    }
+
+    #[test]
+    fn lex_bitwise_symbols() {
+        assert_eq!(
+            kinds("~a & b | c ^ d << e >> f <= g"),
+            vec![
+                TokenKind::Tilde,
+                TokenKind::Ident("a".to_string()),
+                TokenKind::Amp,
+                TokenKind::Ident("b".to_string()),
+                TokenKind::Pipe,
+                TokenKind::Ident("c".to_string()),
+                TokenKind::Caret,
+                TokenKind::Ident("d".to_string()),
+                TokenKind::Shl,
+                TokenKind::Ident("e".to_string()),
+                TokenKind::Shr,
+                TokenKind::Ident("f".to_string()),
+                TokenKind::Le,
+                TokenKind::Ident("g".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn lex_two_character_symbols() {
+        assert_eq!(
+            kinds("== != <= >= => = <"),
+            vec![
+                TokenKind::EqEq,
+                TokenKind::BangEq,
+                TokenKind::Le,
+                TokenKind::Ge,
+                TokenKind::FatArrow,
+                TokenKind::Eq,
+                TokenKind::Lt,
+            ],
+        );
+    }
+
+    #[test]
+    fn lex_ellipsis() {
+        assert_eq!(
+            kinds("...xs"),
+            vec![TokenKind::Ellipsis, TokenKind::Ident("xs".to_string())],
+        );
+    }
+
+    #[test]
+    fn lex_ranges() {
+        assert_eq!(
+            kinds("1..n..=2"),
+            vec![
+                TokenKind::Number("1".to_string()),
+                TokenKind::DotDot,
+                TokenKind::Ident("n".to_string()),
+                TokenKind::DotDotEq,
+                TokenKind::Number("2".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn lex_string_with_escapes() {
+        assert_eq!(
+            kinds(r#""a\tb\\" rest"#),
+            vec![
+                TokenKind::Str("a\tb\\".to_string()),
+                TokenKind::Ident("rest".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn unterminated_string_is_unknown() {
+        assert_eq!(
+            extract_string(r#""abc"#),
+            Err((0..1, "unterminated string literal".to_string()))
+        );
+        assert_eq!(
+            extract_string(r#""a\qb""#),
+            Err((2..4, "unknown escape sequence \\q".to_string()))
+        );
+        assert_eq!(lex(r#""abc"#)[0].kind, TokenKind::Unknown);
+    }
+
+    #[test]
+    fn record_spans_and_line_breaks() {
+        assert_eq!(
+            lex("a\n  @"),
+            vec![
+                Token {
+                    kind: TokenKind::Ident("a".to_string()),
+                    span: 0..1,
+                    newline_before: false,
+                    doc: None,
+                },
+                Token {
+                    kind: TokenKind::Unknown,
+                    span: 4..5,
+                    newline_before: true,
+                    doc: None,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn ignore_line_breaks_inside_parentheses() {
+        assert_eq!(
+            lex("f(\n1\n)\n{\n2\n}")
+                .iter()
+                .map(|token| token.newline_before)
+                .collect::<Vec<_>>(),
+            vec![false, false, false, false, true, true, true],
+        );
+    }
+
+    #[test]
+    fn lex_tabs_and_windows_line_endings() {
+        assert_eq!(
+            lex("let\ta = 1 // one\r\n\tb"),
+            vec![
+                Token {
+                    kind: TokenKind::Ident("let".to_string()),
+                    span: 0..3,
+                    newline_before: false,
+                    doc: None,
+                },
+                Token {
+                    kind: TokenKind::Ident("a".to_string()),
+                    span: 4..5,
+                    newline_before: false,
+                    doc: None,
+                },
+                Token {
+                    kind: TokenKind::Eq,
+                    span: 6..7,
+                    newline_before: false,
+                    doc: None,
+                },
+                Token {
+                    kind: TokenKind::Number("1".to_string()),
+                    span: 8..9,
+                    newline_before: false,
+                    doc: None,
+                },
+                Token {
+                    kind: TokenKind::Ident("b".to_string()),
+                    span: 19..20,
+                    newline_before: true,
+                    doc: None,
+                },
+            ],
+        );
+        assert_eq!(lex_lossless("// one\r\n")[0].span, 0..6);
+    }
+
+    #[test]
+    fn normalize_windows_line_endings_in_strings() {
+        assert_eq!(extract_string("\"a\r\nb\""), Ok(("", "a\nb".to_string())));
+    }
+
+    #[test]
+    fn skip_comments() {
+        assert_eq!(
+            lex("1 // one\n// two\n2"),
+            vec![
+                Token {
+                    kind: TokenKind::Number("1".to_string()),
+                    span: 0..1,
+                    newline_before: false,
+                    doc: None,
+                },
+                Token {
+                    kind: TokenKind::Number("2".to_string()),
+                    span: 16..17,
+                    newline_before: true,
+                    doc: None,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn attach_doc_comments_to_the_next_token() {
+        let docs = |s: &str| -> Vec<Option<String>> {
+            lex(s).into_iter().map(|token| token.doc).collect()
+        };
+
+        assert_eq!(
+            docs("## Adds one.\n##\n##  Really.\nfn inc x => x + 1"),
+            [
+                Some("Adds one.\n\n Really.".to_string()),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None
+            ],
+        );
+        assert_eq!(docs("## Not about a.\n\nlet a = 1")[0], None);
+        assert_eq!(docs("## Not about a.\n// note\nlet a = 1")[0], None);
+        assert_eq!(kinds("##doc\n1"), [TokenKind::Number("1".to_string())],);
+    }
+
+    #[test]
+    fn lossless_tokens_cover_input() {
+        let s = "#!/bin/x\nlet a = 1 // one\n  @\"";
+        let tokens = lex_lossless(s);
+
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| &s[token.span.clone()])
+                .collect::<String>(),
+            s,
+        );
+        assert_eq!(tokens[0].kind, TokenKind::Shebang);
+    }
+
+    #[test]
+    fn skip_shebang_only_at_start() {
+        assert_eq!(
+            kinds("#!/bin/x\n1"),
+            vec![TokenKind::Number("1".to_string())]
+        );
+        assert_eq!(kinds("1 #!")[1], TokenKind::Unknown);
+    }
+}
@@ -1,28 +1,548 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+compile_error!("eldiro needs either the `std` or the `alloc` feature");
+
+extern crate alloc;
+
+mod assign;
 mod binding_def;
+mod bridge;
+mod builtins;
+mod clock;
+mod compat;
+mod compile;
+mod debug;
+mod dump;
+mod enum_def;
 mod env;
+mod env_builder;
+mod error;
 mod expr;
+mod fmt;
 mod func_def;
+mod highlight;
+mod import;
+mod interpreter;
+mod lexer;
+mod lint;
+mod native;
+mod num;
+mod observe;
+mod optimize;
+mod profile;
+mod program;
+mod random;
+mod reparse;
+mod span;
 mod stmt;
+mod symbol;
+pub mod syntax;
+mod ty;
+mod typecheck;
 mod utils;
 mod val;
+pub mod visit;
+mod vm;
 
+use crate::compat::prelude::*;
+
+pub use bridge::NativeFuture;
+#[cfg(feature = "os")]
+pub use clock::SystemClock;
+pub use clock::{Clock, ManualClock};
+pub use debug::{Action, Debugger, Pause};
 pub use env::Env;
+pub use env_builder::EnvBuilder;
+pub use error::{ParseError, RuntimeError, TypeError};
+pub use highlight::{highlight, Highlight, HighlightKind};
+pub use interpreter::{Backend, Interpreter};
+pub use lint::{lint, lint_in, lint_with, Diagnostic, LintKind, LintOptions, Severity};
+pub use num::Int;
+pub use observe::EvalObserver;
+pub use profile::FuncProfile;
+pub use program::{CompiledProgram, Program, TestOutcome};
+pub use reparse::TextEdit;
+pub use span::Span;
+pub use symbol::Symbol;
+pub use ty::Type;
 pub use val::Val;
 
-#[derive(Debug)]
-pub struct Parse(stmt::Stmt);
+/// The syntax tree that programs are evaluated from, as produced by [`parse`].
+pub mod ast {
+    pub use crate::assign::Assign;
+    pub use crate::binding_def::BindingDef;
+    pub use crate::enum_def::{EnumDef, Variant};
+    pub use crate::expr::{
+        Arm, BindingUsage, Block, CallStyle, Expr, For, FuncCall, Match, Number, Op, Pattern,
+        TryCatch, UnaryOp,
+    };
+    pub use crate::func_def::{FuncDef, Param};
+    pub use crate::import::Import;
+    pub use crate::stmt::Stmt;
+}
+
+/// The words with special meaning in eldiro source.
+pub const KEYWORDS: &[&str] = &[
+    "catch", "const", "enum", "false", "fn", "for", "import", "in", "let", "match", "nil", "throw",
+    "true", "try",
+];
+
+/// Parses `s` into a program. It gives an error for anything that isn't one, without panicking
+/// and in time linear in the length of `s`, whatever `s` is, so it's safe to use on untrusted
+/// input. Nesting more than 64 levels deep is an error too, as is chaining more than 1024
+/// operators, pipes, indexes and method calls together, so that the tree stays shallow enough
+/// to walk.
+pub fn parse(s: &str) -> Result<Program, ParseError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("parse").entered();
+
+    check_tokens(s, &lexer::lex(s))?;
+
+    // Forgets where any earlier parse went too deep.
+    utils::too_deep();
+    let (remainder, program) = Program::new(s).map_err(|message| ParseError {
+        message,
+        span: 0..s.len(),
+    })?;
+
+    if remainder.is_empty() {
+        Ok(program)
+    } else {
+        Err(stopped_at(s, s.len() - remainder.len()))
+    }
+}
+
+/// The error for when parsing `s` stopped short at `start`, which is the most specific one that
+/// can be found: going too deep, or else the first error the syntax tree parser finds from there,
+/// which points at the token that it couldn't make sense of.
+fn stopped_at(s: &str, start: usize) -> ParseError {
+    too_deep(start)
+        .or_else(|| {
+            syntax::parse(s)
+                .errors()
+                .iter()
+                .find(|error| error.span.start >= start)
+                .cloned()
+        })
+        .unwrap_or_else(|| not_consumed(s, &s[start..]))
+}
 
-impl Parse {
-    pub fn eval(&self, env: &mut Env) -> Result<Val, String> {
-        self.0.eval(env)
+/// The error for when parsing stopped at `start` because the statement there went past
+/// [`utils::MAX_DEPTH`] or [`utils::MAX_CHAIN`], which would otherwise show up as input that
+/// wasn't consumed.
+fn too_deep(start: usize) -> Option<ParseError> {
+    utils::too_deep()
+        .filter(|(span, _)| span.start >= start)
+        .map(|(span, message)| ParseError { message, span })
+}
+
+/// The error for when parsing `s` stopped short at `remainder` for a reason that nothing more
+/// specific is known about, pointing at the rest of the line where it stopped.
+fn not_consumed(s: &str, remainder: &str) -> ParseError {
+    let start = s.len() - remainder.len();
+    let end = start + remainder.find('\n').unwrap_or(remainder.len()).max(1);
+
+    ParseError {
+        message: "input was not consumed fully by parser".to_string(),
+        span: start..end.min(s.len()),
     }
 }
 
-pub fn parse(s: &str) -> Result<Parse, String> {
-    let (s, stmt) = stmt::Stmt::new(s)?;
-    if s.is_empty() {
-        Ok(Parse(stmt))
-    } else {
-        Err("input was not consumed fully by parser".into())
+/// Catches mistakes in single tokens up front. The parser backtracks past anything it can't
+/// parse, so a bad literal or a keyword used as a name would otherwise only show up as input that
+/// wasn't consumed.
+fn check_tokens(s: &str, tokens: &[lexer::Token]) -> Result<(), ParseError> {
+    let check = |result: Result<(), String>, token: &lexer::Token| {
+        result.map_err(|message| ParseError {
+            message,
+            span: token.span.clone(),
+        })
+    };
+
+    for (idx, token) in tokens.iter().enumerate() {
+        match &token.kind {
+            // A string that doesn't lex leaves an unknown `"` behind.
+            lexer::TokenKind::Unknown if s[token.span.clone()] == *"\"" => {
+                if let Err((span, message)) = lexer::extract_string(&s[token.span.start..]) {
+                    let start = token.span.start;
+                    return Err(ParseError {
+                        message,
+                        span: start + span.start..start + span.end,
+                    });
+                }
+            }
+            lexer::TokenKind::Number(_) => {
+                check(
+                    expr::Number::new(core::slice::from_ref(token)).map(|_| ()),
+                    token,
+                )?;
+            }
+            // `let`, `const` and `catch` define one name, and `fn` defines a function name
+            // followed by its parameters.
+            lexer::TokenKind::Ident(word)
+                if matches!(word.as_str(), "let" | "const" | "catch" | "fn") =>
+            {
+                let defined = if word == "fn" { usize::MAX } else { 1 };
+
+                for name in tokens[idx + 1..]
+                    .iter()
+                    .take_while(|token| {
+                        matches!(
+                            token.kind,
+                            lexer::TokenKind::Ident(_) | lexer::TokenKind::Ellipsis
+                        )
+                    })
+                    .filter(|token| token.kind != lexer::TokenKind::Ellipsis)
+                    .take(defined)
+                {
+                    check(
+                        utils::extract_ident(core::slice::from_ref(name)).map(|_| ()),
+                        name,
+                    )?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses `s` and renders it as canonical source text, keeping its comments.
+pub fn format(s: &str) -> Result<String, ParseError> {
+    let program = parse(s)?;
+    Ok(fmt::source(s, &program))
+}
+
+/// Whether `s` needs more lines before it can be parsed, such as a block that hasn't been closed
+/// yet. Useful for REPLs that accept multi-line input.
+pub fn is_incomplete(s: &str) -> bool {
+    utils::is_incomplete(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_error_points_at_unparsed_line() {
+        assert_eq!(
+            parse("let a = 1\n@ 2\nlet b = 3").unwrap_err(),
+            ParseError {
+                message: "expected statement".to_string(),
+                span: 10..11,
+            },
+        );
+    }
+
+    #[test]
+    fn parse_error_names_what_failed() {
+        for (source, message, span) in [
+            (
+                "let a = 1\nlet s = \"abc",
+                "unterminated string literal",
+                18..19,
+            ),
+            ("let a = \"x\\qy\"", "unknown escape sequence \\q", 10..12),
+            ("let a = try { 1 }\na", "expected catch", 18..19),
+            ("let a = 1\nthrow", "expected expression", 15..15),
+        ] {
+            assert_eq!(
+                parse(source).unwrap_err(),
+                ParseError {
+                    message: message.to_string(),
+                    span,
+                },
+                "{}",
+                source,
+            );
+        }
+    }
+
+    #[test]
+    fn keyword_as_binding_name_is_parse_error() {
+        assert_eq!(
+            parse("let let = 5").unwrap_err(),
+            ParseError {
+                message: "`let` is a reserved keyword".to_string(),
+                span: 4..7,
+            },
+        );
+    }
+
+    #[test]
+    fn keyword_as_param_is_parse_error() {
+        assert_eq!(
+            parse("fn f x fn => x").unwrap_err(),
+            ParseError {
+                message: "`fn` is a reserved keyword".to_string(),
+                span: 7..9,
+            },
+        );
+    }
+
+    #[test]
+    fn eval_snake_case_and_unicode_names() {
+        assert_eq!(
+            parse("let größe = 2\nlet _my_var2 = größe * 3\n_my_var2")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Ok(Val::Number(6.into())),
+        );
+    }
+
+    #[test]
+    fn expression_continues_after_trailing_operator() {
+        assert_eq!(
+            parse("let a = 1 +\n    2 *\n    3\na")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Ok(Val::Number(7.into())),
+        );
+    }
+
+    #[test]
+    fn leading_operator_does_not_continue_line() {
+        assert_eq!(
+            parse("let a = 1\n+ 2").unwrap_err(),
+            ParseError {
+                message: "expected statement".to_string(),
+                span: 10..11,
+            },
+        );
+    }
+
+    #[test]
+    fn line_breaks_inside_call_parentheses() {
+        assert_eq!(
+            parse("fn add x y => x + y\nadd(\n    1\n    + 2,\n    3\n)")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Ok(Val::Number(6.into())),
+        );
+    }
+
+    #[test]
+    fn parse_windows_line_endings_and_tabs() {
+        assert_eq!(
+            parse("fn double x => {\r\n\tx * 2\r\n}\r\ndouble 21\r\n")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Ok(Val::Number(42.into())),
+        );
+    }
+
+    #[test]
+    fn eval_hex_and_binary_literals() {
+        assert_eq!(
+            parse("0xFF + 0b1_0000 + 1_000")
+                .unwrap()
+                .eval(&mut Env::default()),
+            Ok(Val::Number(1271.into())),
+        );
+    }
+
+    #[test]
+    fn malformed_number_is_parse_error() {
+        assert_eq!(
+            parse("let a = 0b12").unwrap_err(),
+            ParseError {
+                message: "invalid number literal 0b12".to_string(),
+                span: 8..12,
+            },
+        );
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn oversized_number_is_parse_error() {
+        assert_eq!(
+            parse("let a = 1\nlet b = 99999999999999999999").unwrap_err(),
+            ParseError {
+                message: "number literal out of range for i64".to_string(),
+                span: 18..38,
+            },
+        );
+    }
+
+    /// Source that nests `n` levels deep in each of the ways the parser can nest.
+    fn nested(n: usize) -> Vec<String> {
+        vec![
+            format!("{}1{}", "(".repeat(n), ")".repeat(n)),
+            format!("{}1{}", "[".repeat(n), "]".repeat(n)),
+            format!("{}1{}", "{".repeat(n), "}".repeat(n)),
+            format!("{}1{}", "{a: ".repeat(n), "}".repeat(n)),
+            format!("fn f x => x\n{}1{}", "f(".repeat(n), ")".repeat(n)),
+            format!("{}1{}", "try { ".repeat(n), " } catch e { 1 }".repeat(n)),
+            format!("{}1{}", "match 1 { _ => ".repeat(n), "}".repeat(n)),
+            format!(
+                "enum O {{ S(x), N }}\nmatch N {{ {}N{} => 1, _ => 2 }}",
+                "S(".repeat(n),
+                ")".repeat(n),
+            ),
+            "fn a => ".repeat(n) + "1",
+            "~".repeat(n) + "1",
+            "2 ** ".repeat(n) + "1",
+            format!(
+                "fn f a b => a\nlet x = 1\n{}1{}",
+                "x.f(".repeat(n),
+                ")".repeat(n)
+            ),
+        ]
+    }
+
+    fn chained(n: usize) -> Vec<String> {
+        vec![
+            "0".to_string() + &" + 1".repeat(n),
+            "1".to_string() + &" == 1".repeat(n),
+            "fn f x => x\n1".to_string() + &" |> f".repeat(n),
+            "[1]".to_string() + &"[0]".repeat(n),
+            "[]".to_string() + &".len()".repeat(n),
+        ]
+    }
+
+    /// `levels` chains of `links` operators inside each other, once as the first operand of the
+    /// chain around them and once as the last.
+    fn stacked_chains(levels: usize, links: usize) -> Vec<String> {
+        let chain = " + 1".repeat(links);
+        vec![
+            format!(
+                "{}0{}",
+                "(".repeat(levels),
+                format!("{})", chain).repeat(levels)
+            ),
+            format!(
+                "{}0{}",
+                format!("0{} + (", chain).repeat(levels),
+                ")".repeat(levels)
+            ),
+        ]
+    }
+
+    #[test]
+    fn parse_and_run_deep_nesting() {
+        for source in nested(utils::MAX_DEPTH - 4) {
+            let program = parse(&source).unwrap();
+            let mut env = Env::default();
+
+            let _ = program.infer(&env);
+            let _ = program.eval(&mut env);
+            let _ = Interpreter::with_backend(Backend::Vm).eval(&program);
+            let _ = lint(&program);
+            assert_eq!(format(&program.format()), Ok(program.format()));
+            assert_eq!(syntax::parse(&source).errors(), []);
+        }
+    }
+
+    #[test]
+    fn too_deep_nesting_is_parse_error() {
+        for source in nested(utils::MAX_DEPTH + 1)
+            .into_iter()
+            .chain(nested(10_000))
+        {
+            let error = parse(&source).unwrap_err();
+            assert_eq!(
+                error.message, "nested more than 64 levels deep",
+                "{}",
+                source
+            );
+            assert!(error.span.end <= source.len());
+
+            assert!(syntax::parse(&source)
+                .errors()
+                .iter()
+                .any(|error| error.message == "nested more than 64 levels deep"));
+        }
+    }
+
+    #[test]
+    fn parse_long_chains() {
+        for source in chained(1000) {
+            let program = parse(&source).unwrap();
+            assert_eq!(format(&program.format()), Ok(program.format()));
+            assert_eq!(syntax::parse(&source).errors(), []);
+        }
+    }
+
+    #[test]
+    fn run_long_chains() {
+        assert_eq!(
+            parse(&chained(50)[0]).unwrap().eval(&mut Env::default()),
+            Ok(Val::Number(50.into())),
+        );
+
+        for source in chained(50) {
+            let program = parse(&source).unwrap();
+            let mut env = Env::default();
+
+            let _ = program.infer(&env);
+            let _ = program.eval(&mut env);
+            let _ = Interpreter::with_backend(Backend::Vm).eval(&program);
+            let _ = lint(&program);
+        }
+    }
+
+    #[test]
+    fn too_long_chains_are_parse_errors() {
+        for source in chained(utils::MAX_CHAIN + 1)
+            .into_iter()
+            .chain(chained(10_000))
+            .chain(stacked_chains(20, 100))
+        {
+            let error = parse(&source).unwrap_err();
+            assert_eq!(
+                error.message, "more than 1024 operations chained together",
+                "{}",
+                source
+            );
+            assert!(error.span.end <= source.len());
+
+            assert!(syntax::parse(&source)
+                .errors()
+                .iter()
+                .any(|error| error.message == "more than 1024 operations chained together"));
+        }
+
+        for source in stacked_chains(5, 100) {
+            assert!(parse(&source).is_ok(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn backtracking_over_broken_nesting_takes_linear_time() {
+        // Each of these used to take twice as long for every level.
+        for source in [
+            "f(".repeat(50),
+            "a |> f(".repeat(50),
+            "a {".repeat(50),
+            "{a".repeat(50),
+            "a try { ".repeat(50),
+            format!("{}let{}", "a {".repeat(50), "}".repeat(50)),
+            "f g h (".repeat(50),
+            "f g h [".repeat(50),
+            "f g h (1) [".repeat(50),
+        ] {
+            assert!(parse(&source).is_err());
+        }
+    }
+
+    #[test]
+    fn survive_fuzz_regressions() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fuzz/regressions");
+
+        // The same as the fuzz target does with each input.
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let source = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+
+            if let Ok(program) = parse(&source) {
+                let _ = program.format();
+                let _ = lint(&program);
+            }
+            let _ = syntax::parse(&source);
+            let _ = format(&source);
+            let _ = is_incomplete(&source);
+            let _ = highlight(&source);
+        }
     }
 }
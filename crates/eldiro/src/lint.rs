@@ -0,0 +1,671 @@
+//! Finds code that is probably a mistake even though it runs, such as bindings that are never
+//! used, and with an environment to check against, names that fail when they're reached because
+//! nothing defines them.
+
+use crate::compat::prelude::*;
+use crate::compat::HashSet;
+use crate::env::Env;
+use crate::expr::{Block, Expr, Pattern};
+use crate::import::Import;
+#[cfg(feature = "std")]
+use crate::import::Module;
+use crate::program::Program;
+use crate::stmt::Stmt;
+use crate::symbol::Symbol;
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+use core::fmt;
+use core::ops::Range;
+
+/// Something [`lint`] found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub kind: LintKind,
+    pub severity: Severity,
+    pub message: String,
+    /// The byte range of the offending code in the source that was parsed.
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LintKind {
+    UnusedBinding,
+    UnusedFunction,
+    /// A `let` that redefines a name from earlier in the same scope.
+    Shadowing,
+    /// A name that neither the program nor its environment defines, which is always an error.
+    UndefinedName,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LintOptions {
+    /// Makes shadowing an error rather than a warning.
+    pub strict: bool,
+    /// Lints the program as a module that other files import, which can use anything it defines
+    /// at the top level, so none of that is reported as unused.
+    pub module: bool,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Reports bindings and functions that are never used, and bindings that shadow a name from the
+/// same scope, all as warnings.
+///
+/// Names starting with `_` are meant to go unused, and `test_` functions are used by the test
+/// runner, so neither is reported as unused.
+pub fn lint(program: &Program) -> Vec<Diagnostic> {
+    lint_with(program, LintOptions::default())
+}
+
+/// Like [`lint`], but with control over how strict to be.
+pub fn lint_with(program: &Program, options: LintOptions) -> Vec<Diagnostic> {
+    Linter {
+        options,
+        ..Linter::default()
+    }
+    .run(program)
+}
+
+/// Like [`lint_with`], but also reports names that nothing defines as errors, since each of those
+/// fails when it's reached. Names visible from `env` count as defined, and so do the ones an
+/// `import` exposes, which are found by reading the imported files relative to `env`'s current
+/// file. Nothing after an import that can't be read is reported, since it could define anything
+/// and it will fail on its own when it runs.
+pub fn lint_in(program: &Program, options: LintOptions, env: &Env) -> Vec<Diagnostic> {
+    Linter {
+        options,
+        globals: Some(env.names().into_iter().map(Symbol::intern).collect()),
+        #[cfg(feature = "std")]
+        module: (env.imports_allowed() && !env.is_deterministic()).then(|| env.module()),
+        ..Linter::default()
+    }
+    .run(program)
+}
+
+#[derive(Debug)]
+struct Def {
+    name: Symbol,
+    /// Where to report the definition if it goes unused, which is nowhere for parameters.
+    report: Option<(LintKind, Range<usize>)>,
+    used: bool,
+}
+
+#[derive(Debug, Default)]
+struct Scope {
+    defs: Vec<Def>,
+    /// Names used inside function bodies that weren't defined yet, along with where. Functions can
+    /// use things defined after them, as long as that happens before they are called.
+    pending: Vec<(Symbol, Range<usize>)>,
+    /// Whether an import that couldn't be read came earlier in the scope, which could have
+    /// defined any name.
+    open: bool,
+}
+
+#[derive(Debug, Default)]
+struct Linter {
+    options: LintOptions,
+    scopes: Vec<Scope>,
+    /// The functions whose bodies are being linted, innermost last, so that recursion doesn't
+    /// count as a use.
+    funcs: Vec<Symbol>,
+    /// What the environment defines, when names that nothing defines should be reported.
+    globals: Option<HashSet<Symbol>>,
+    /// Where imports are resolved from, if they're allowed to be.
+    #[cfg(feature = "std")]
+    module: Option<Arc<Module>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter {
+    fn run(mut self, program: &Program) -> Vec<Diagnostic> {
+        self.scopes.push(Scope::default());
+        self.stmts(&program.stmts);
+        self.pop_scope();
+
+        self.diagnostics
+            .sort_by_key(|diagnostic| diagnostic.span.start);
+        self.diagnostics
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::BindingDef(binding_def) => {
+                self.expr(&binding_def.val);
+
+                let scope = self.scopes.last().expect("there is always a scope");
+                if scope.defs.iter().any(|def| def.name == binding_def.name) {
+                    let severity = if self.options.strict {
+                        Severity::Error
+                    } else {
+                        Severity::Warning
+                    };
+
+                    self.diagnostics.push(Diagnostic {
+                        kind: LintKind::Shadowing,
+                        severity,
+                        message: format!(
+                            "'{}' shadows an earlier definition in the same scope",
+                            binding_def.name
+                        ),
+                        span: binding_def.span.0.clone(),
+                    });
+                }
+
+                self.define(
+                    binding_def.name,
+                    Some((LintKind::UnusedBinding, binding_def.span.0.clone())),
+                );
+            }
+            Stmt::FuncDef(func_def) => {
+                let is_test = func_def.name.as_str().starts_with("test_");
+                self.define(
+                    func_def.name,
+                    (!is_test).then(|| (LintKind::UnusedFunction, func_def.span.0.clone())),
+                );
+
+                self.funcs.push(func_def.name);
+                self.scopes.push(Scope::default());
+                for param in &func_def.params {
+                    if let Some(default) = &param.default {
+                        self.expr(default);
+                    }
+                    self.define(param.name, None);
+                }
+                self.stmt(&func_def.body);
+                self.pop_scope();
+                self.funcs.pop();
+            }
+            // Variants are part of a type rather than values of their own, so they don't have to be
+            // used.
+            Stmt::EnumDef(enum_def) => {
+                for variant in &enum_def.variants {
+                    self.define(variant.name, None);
+                }
+            }
+            Stmt::Import(import) => match self.imported(import) {
+                Some(names) => {
+                    for name in names {
+                        self.define(name, None);
+                    }
+                }
+                None => {
+                    self.scopes
+                        .last_mut()
+                        .expect("there is always a scope")
+                        .open = true
+                }
+            },
+            Stmt::Throw(val, _) | Stmt::Expr(val) => self.expr(val),
+            Stmt::Assign(assign) => {
+                self.expr(&assign.val);
+
+                let start = assign.span.0.start;
+                self.use_name(assign.name, start..start + assign.name.as_str().len());
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Number(..)
+            | Expr::Bool(..)
+            | Expr::Str(..)
+            | Expr::Unit(_)
+            | Expr::Nil(_)
+            | Expr::Placeholder(_) => {}
+            Expr::List(items, _) | Expr::Tuple(items, _) => {
+                for item in items {
+                    self.expr(item);
+                }
+            }
+            Expr::Operation { lhs, rhs, .. } => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            Expr::Unary { operand, .. } | Expr::Field { base: operand, .. } => self.expr(operand),
+            Expr::Record(fields, _) => {
+                for (_, val) in fields {
+                    self.expr(val);
+                }
+            }
+            Expr::BindingUsage(binding_usage) => {
+                self.use_name(binding_usage.name, binding_usage.span.0.clone());
+            }
+            Expr::Block(block) => self.block(block),
+            Expr::FuncCall(func_call) => {
+                self.use_name(func_call.callee, func_call.span.0.clone());
+
+                for param in &func_call.params {
+                    self.expr(param);
+                }
+                for (_, val) in &func_call.named {
+                    self.expr(val);
+                }
+            }
+            Expr::Index { base, index, .. } => {
+                self.expr(base);
+                self.expr(index);
+            }
+            Expr::Match(m) => {
+                self.expr(&m.val);
+
+                for arm in &m.arms {
+                    self.scopes.push(Scope::default());
+                    self.pattern(&arm.pattern, &m.span.0);
+                    self.expr(&arm.body);
+                    self.pop_scope();
+                }
+            }
+            Expr::For(for_loop) => {
+                self.expr(&for_loop.iterable);
+
+                self.scopes.push(Scope::default());
+                self.define(for_loop.name, None);
+                self.block(&for_loop.body);
+                self.pop_scope();
+            }
+            Expr::TryCatch(try_catch) => {
+                self.block(&try_catch.body);
+
+                self.scopes.push(Scope::default());
+                self.define(try_catch.name, None);
+                self.block(&try_catch.handler);
+                self.pop_scope();
+            }
+        }
+    }
+
+    /// `span` is where names used in the pattern are reported, since patterns don't keep spans of
+    /// their own.
+    fn pattern(&mut self, pattern: &Pattern, span: &Range<usize>) {
+        match pattern {
+            Pattern::Binding(name) => self.define(*name, None),
+            Pattern::Variant { name, fields } => {
+                self.use_name(*name, span.clone());
+
+                for field in fields {
+                    self.pattern(field, span);
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) => {}
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.scopes.push(Scope::default());
+        self.stmts(&block.stmts);
+        self.pop_scope();
+    }
+
+    fn define(&mut self, name: Symbol, report: Option<(LintKind, Range<usize>)>) {
+        self.scopes
+            .last_mut()
+            .expect("there is always a scope")
+            .defs
+            .push(Def {
+                name,
+                report,
+                used: false,
+            });
+    }
+
+    fn use_name(&mut self, name: Symbol, span: Range<usize>) {
+        if self.funcs.last() == Some(&name) {
+            return;
+        }
+
+        let def = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.defs.iter_mut().rev().find(|def| def.name == name));
+
+        match def {
+            Some(def) => def.used = true,
+            None if !self.funcs.is_empty() => {
+                self.scopes
+                    .last_mut()
+                    .expect("there is always a scope")
+                    .pending
+                    .push((name, span));
+            }
+            None => self.report_undefined(name, span),
+        }
+    }
+
+    /// Reports `name` if it should have been defined by now, unless the environment defines it or
+    /// an import in scope could have.
+    fn report_undefined(&mut self, name: Symbol, span: Range<usize>) {
+        let defined = match &self.globals {
+            Some(globals) => globals.contains(&name),
+            None => return,
+        };
+        if defined || self.scopes.iter().any(|scope| scope.open) {
+            return;
+        }
+
+        self.diagnostics.push(Diagnostic {
+            kind: LintKind::UndefinedName,
+            severity: Severity::Error,
+            message: format!("'{}' is not defined", name),
+            span,
+        });
+    }
+
+    /// The names `import` defines, or `None` if they can't be known without running it.
+    #[cfg(feature = "std")]
+    fn imported(&self, import: &Import) -> Option<Vec<Symbol>> {
+        import.defined_names(self.module.as_ref()?)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn imported(&self, _: &Import) -> Option<Vec<Symbol>> {
+        None
+    }
+
+    /// Ends the innermost scope, reporting what it defined but never used.
+    fn pop_scope(&mut self) {
+        let mut scope = self.scopes.pop().expect("there is always a scope");
+
+        // A pending name refers to the last definition with that name in the scope, since that's
+        // the one that will be there by the time the function using it is called.
+        scope.pending.retain(|(name, _)| {
+            match scope.defs.iter_mut().rev().find(|def| def.name == *name) {
+                Some(def) => {
+                    def.used = true;
+                    false
+                }
+                None => true,
+            }
+        });
+
+        match self.scopes.last_mut() {
+            _ if scope.open => {}
+            Some(parent) => parent.pending.extend(scope.pending),
+            None => {
+                for (name, span) in scope.pending {
+                    self.report_undefined(name, span);
+                }
+            }
+        }
+
+        // An importer can use whatever a module defines at the top level.
+        if self.options.module && self.scopes.is_empty() {
+            return;
+        }
+
+        for def in scope.defs {
+            let (kind, span) = match def.report {
+                Some(report) if !def.used && !def.name.as_str().starts_with('_') => report,
+                _ => continue,
+            };
+
+            let what = match kind {
+                LintKind::UnusedBinding => "binding",
+                LintKind::UnusedFunction => "function",
+                LintKind::Shadowing => unreachable!("shadowing is reported when it happens"),
+                LintKind::UndefinedName => unreachable!("definitions are never undefined"),
+            };
+
+            self.diagnostics.push(Diagnostic {
+                kind,
+                severity: Severity::Warning,
+                message: format!("unused {} '{}'", what, def.name),
+                span,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(s: &str) -> Vec<String> {
+        lint(&crate::parse(s).unwrap())
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    fn undefined_in(s: &str, env: &Env) -> Vec<String> {
+        lint_in(&crate::parse(s).unwrap(), LintOptions::default(), env)
+            .into_iter()
+            .filter(|diagnostic| diagnostic.kind == LintKind::UndefinedName)
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn report_unused_binding_and_function() {
+        assert_eq!(
+            lint(&crate::parse("let a = 1\nfn f x => x\nlet b = 2\nb").unwrap()),
+            vec![
+                Diagnostic {
+                    kind: LintKind::UnusedBinding,
+                    severity: Severity::Warning,
+                    message: "unused binding 'a'".to_string(),
+                    span: 0..9,
+                },
+                Diagnostic {
+                    kind: LintKind::UnusedFunction,
+                    severity: Severity::Warning,
+                    message: "unused function 'f'".to_string(),
+                    span: 10..21,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn shadowed_binding_is_unused() {
+        assert_eq!(
+            messages("let a = 1\n{ let a = 2\na }"),
+            ["unused binding 'a'"]
+        );
+        assert_eq!(
+            messages("let a = 1\n{ let a = a + 1\na }"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn functions_can_use_later_definitions() {
+        assert_eq!(
+            messages("fn f => g()\nfn g => limit\nlet limit = 3\nf()"),
+            Vec::<String>::new(),
+        );
+    }
+
+    #[test]
+    fn recursion_is_not_a_use() {
+        assert_eq!(messages("fn loop n => loop(n)"), ["unused function 'loop'"]);
+    }
+
+    #[test]
+    fn skip_params_underscores_and_tests() {
+        assert_eq!(
+            messages("fn test_it unused => ()\nlet _ignored = 1\ntry { 1 } catch e { 2 }"),
+            Vec::<String>::new(),
+        );
+    }
+
+    #[test]
+    fn report_in_nested_scopes() {
+        assert_eq!(
+            messages("fn f x => { let y = x\nx }\nf(1)"),
+            ["unused binding 'y'"]
+        );
+    }
+
+    #[test]
+    fn report_shadowing_in_same_scope() {
+        let program = crate::parse("let a = 1\nlet a = a + 1\nfn f x => let x = 2\nf(a)").unwrap();
+
+        assert_eq!(
+            lint(&program),
+            vec![
+                Diagnostic {
+                    kind: LintKind::Shadowing,
+                    severity: Severity::Warning,
+                    message: "'a' shadows an earlier definition in the same scope".to_string(),
+                    span: 10..23,
+                },
+                Diagnostic {
+                    kind: LintKind::Shadowing,
+                    severity: Severity::Warning,
+                    message: "'x' shadows an earlier definition in the same scope".to_string(),
+                    span: 34..43,
+                },
+                Diagnostic {
+                    kind: LintKind::UnusedBinding,
+                    severity: Severity::Warning,
+                    message: "unused binding 'x'".to_string(),
+                    span: 34..43,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn shadowing_is_an_error_when_strict() {
+        let program = crate::parse("let a = 1\nlet a = a\na").unwrap();
+
+        assert_eq!(
+            lint_with(
+                &program,
+                LintOptions {
+                    strict: true,
+                    ..LintOptions::default()
+                }
+            )
+            .iter()
+            .map(|diagnostic| diagnostic.severity)
+            .collect::<Vec<_>>(),
+            [Severity::Error],
+        );
+    }
+
+    #[test]
+    fn modules_can_leave_top_level_definitions_unused() {
+        let program =
+            crate::parse("fn square x => x * x\nlet pi = 3\nfn f => { let t = 1\n2 }").unwrap();
+        let options = LintOptions {
+            module: true,
+            ..LintOptions::default()
+        };
+
+        assert_eq!(
+            lint_with(&program, options)
+                .into_iter()
+                .map(|diagnostic| diagnostic.message)
+                .collect::<Vec<_>>(),
+            ["unused binding 't'"],
+        );
+    }
+
+    #[test]
+    fn report_undefined_names_as_errors() {
+        let program = crate::parse("undefined_thing + 1\nnope(1)").unwrap();
+
+        assert_eq!(
+            lint_in(&program, LintOptions::default(), &Env::default()),
+            vec![
+                Diagnostic {
+                    kind: LintKind::UndefinedName,
+                    severity: Severity::Error,
+                    message: "'undefined_thing' is not defined".to_string(),
+                    span: 0..15,
+                },
+                Diagnostic {
+                    kind: LintKind::UndefinedName,
+                    severity: Severity::Error,
+                    message: "'nope' is not defined".to_string(),
+                    span: 20..27,
+                },
+            ],
+        );
+        assert_eq!(lint(&program), Vec::new());
+    }
+
+    #[test]
+    fn names_from_env_and_program_are_defined() {
+        let env = Env::default();
+
+        assert_eq!(
+            undefined_in(
+                "enum Shape { Dot, Circle(r) }\nfn area s => match s { Circle(r) => r * r, _ => 0 }\n\
+                 fn f => g(len([area(Dot)]))\nfn g x => { let y = x\ny }\nf()",
+                &env,
+            ),
+            Vec::<String>::new(),
+        );
+        assert_eq!(
+            undefined_in("fn f => missing\nf()\nlater()\nfn later => 1", &env),
+            ["'missing' is not defined", "'later' is not defined"],
+        );
+        assert_eq!(
+            undefined_in("{ let inner = 1\ninner }\ninner", &env),
+            ["'inner' is not defined"],
+        );
+        assert_eq!(
+            undefined_in("match 1 { Nowhere => 1, _ => 2 }", &env),
+            ["'Nowhere' is not defined"],
+        );
+    }
+
+    #[test]
+    fn names_from_env_are_defined_when_they_are_added() {
+        let mut env = Env::default();
+        assert_eq!(
+            undefined_in("sleep_ms(1)", &env),
+            ["'sleep_ms' is not defined"]
+        );
+
+        env.allow_sleep();
+        assert_eq!(undefined_in("sleep_ms(1)", &env), Vec::<String>::new());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn imports_define_what_their_files_do() {
+        let dir = std::env::temp_dir().join(format!("eldiro-lint-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("lib.eld"), "import \"deep.eld\"\nlet ten = 10\n").unwrap();
+        std::fs::write(dir.join("deep.eld"), "fn square x => x * x\n").unwrap();
+
+        let mut env = Env::default();
+        env.set_current_file(dir.join("main.eld"));
+        env.allow_imports();
+
+        assert_eq!(
+            undefined_in("import \"lib.eld\"\nsquare(ten) + eleven", &env),
+            ["'eleven' is not defined"],
+        );
+        assert_eq!(
+            undefined_in("{ import \"missing.eld\"\nanything }\nelse", &env),
+            ["'else' is not defined"],
+        );
+        assert_eq!(
+            undefined_in("import \"lib.eld\"\nanything", &Env::default()),
+            Vec::<String>::new(),
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use crate::compat::prelude::*;
+use crate::env::Env;
+use crate::error::RuntimeError;
+use crate::val::Val;
+use alloc::sync::Arc;
+use core::fmt;
+
+type NativeFn = dyn Fn(&Env, &[Val]) -> Result<Val, RuntimeError> + Send + Sync;
+
+/// A function implemented in Rust that can be called from eldiro code.
+#[derive(Clone)]
+pub(crate) struct Native {
+    name: String,
+    arity: usize,
+    func: Arc<NativeFn>,
+    /// Whether calling it with the same arguments always gives the same result.
+    deterministic: bool,
+}
+
+impl Native {
+    pub(crate) fn new(
+        name: impl Into<String>,
+        arity: usize,
+        func: impl Fn(&Env, &[Val]) -> Result<Val, String> + Send + Sync + 'static,
+    ) -> Self {
+        Self::new_raising(name, arity, move |env, args| {
+            func(env, args).map_err(RuntimeError::Message)
+        })
+    }
+
+    /// Like [`Native::new`], but for functions that fail with something other than a message.
+    pub(crate) fn new_raising(
+        name: impl Into<String>,
+        arity: usize,
+        func: impl Fn(&Env, &[Val]) -> Result<Val, RuntimeError> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            arity,
+            func: Arc::new(func),
+            deterministic: true,
+        }
+    }
+
+    /// Marks the function as one whose results can't be reproduced, such as one that does I/O,
+    /// so that it can't be called in a deterministic environment.
+    pub(crate) fn nondeterministic(mut self) -> Self {
+        self.deterministic = false;
+        self
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub(crate) fn call(&self, env: &Env, args: &[Val]) -> Result<Val, RuntimeError> {
+        if args.len() != self.arity {
+            return Err(format!("expected {} parameters, got {}", self.arity, args.len(),).into());
+        }
+        if !self.deterministic && env.is_deterministic() {
+            return Err(format!(
+                "'{}' can't be called in deterministic mode, since its results can't be reproduced",
+                self.name
+            )
+            .into());
+        }
+
+        (self.func)(env, args)
+    }
+}
+
+impl fmt::Debug for Native {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl PartialEq for Native {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Arc::ptr_eq(&self.func, &other.func)
+    }
+}
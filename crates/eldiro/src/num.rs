@@ -0,0 +1,328 @@
+use crate::compat::prelude::*;
+use core::fmt;
+
+#[cfg(not(feature = "bigint"))]
+type Repr = i64;
+
+#[cfg(feature = "bigint")]
+type Repr = num_bigint::BigInt;
+
+/// An integer, which is an `i64` unless the `bigint` feature is enabled, in which case it can be
+/// arbitrarily large.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Int(Repr);
+
+impl Int {
+    /// Parses a number literal, which is decimal unless it starts with `0x`, `0o` or `0b`, and
+    /// may have `_` between digits.
+    pub(crate) fn parse(literal: &str) -> Result<Self, String> {
+        Self::parse_with_sign(literal, false)
+    }
+
+    /// Parses a number written the way a literal is, with an optional `-` in front.
+    pub(crate) fn parse_signed(s: &str) -> Result<Self, String> {
+        match s.strip_prefix('-') {
+            Some(literal) => Self::parse_with_sign(literal, true),
+            None => Self::parse(s),
+        }
+    }
+
+    fn parse_with_sign(literal: &str, negative: bool) -> Result<Self, String> {
+        let (radix, digits) = match literal.get(..2) {
+            Some("0x") => (16, &literal[2..]),
+            Some("0o") => (8, &literal[2..]),
+            Some("0b") => (2, &literal[2..]),
+            _ => (10, literal),
+        };
+
+        let digits: String = digits.chars().filter(|&c| c != '_').collect();
+
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            return Err(format!("invalid number literal {}", literal));
+        }
+
+        #[cfg(feature = "bigint")]
+        if digits.len() > MAX_DIGITS {
+            return Err(format!(
+                "number literal has more than {} digits",
+                MAX_DIGITS
+            ));
+        }
+
+        // Negating after reading the digits would overflow on the smallest i64.
+        let digits = if negative {
+            format!("-{}", digits)
+        } else {
+            digits
+        };
+
+        from_digits(&digits, radix)
+            .map(Self)
+            .ok_or_else(|| "number literal out of range for i64".to_string())
+    }
+}
+
+/// How many digits a literal can have. Reading one takes time quadratic in its length, so without
+/// a limit a long enough literal would stall the parser.
+#[cfg(feature = "bigint")]
+const MAX_DIGITS: usize = 10_000;
+
+#[cfg(not(feature = "bigint"))]
+fn from_digits(digits: &str, radix: u32) -> Option<Repr> {
+    i64::from_str_radix(digits, radix).ok()
+}
+
+#[cfg(feature = "bigint")]
+fn from_digits(digits: &str, radix: u32) -> Option<Repr> {
+    num_bigint::BigInt::parse_bytes(digits.as_bytes(), radix)
+}
+
+#[cfg(not(feature = "bigint"))]
+impl Int {
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        Some(self.0)
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        u64::try_from(self.0).ok()
+    }
+
+    pub(crate) fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Self)
+    }
+
+    pub(crate) fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Self)
+    }
+
+    pub(crate) fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        self.0.checked_mul(rhs.0).map(Self)
+    }
+
+    /// Returns `None` on overflow or if `rhs` is zero.
+    pub(crate) fn checked_div(&self, rhs: &Self) -> Option<Self> {
+        self.0.checked_div(rhs.0).map(Self)
+    }
+
+    /// Returns `None` on overflow or if `rhs` is zero.
+    pub(crate) fn checked_rem(&self, rhs: &Self) -> Option<Self> {
+        self.0.checked_rem(rhs.0).map(Self)
+    }
+
+    pub(crate) fn bitand(&self, rhs: &Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+
+    pub(crate) fn bitor(&self, rhs: &Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+
+    pub(crate) fn bitxor(&self, rhs: &Self) -> Self {
+        Self(self.0 ^ rhs.0)
+    }
+
+    pub(crate) fn not(&self) -> Self {
+        Self(!self.0)
+    }
+
+    /// Returns `None` on overflow or if `rhs` is negative.
+    pub(crate) fn checked_pow(&self, rhs: &Self) -> Option<Self> {
+        self.0.checked_pow(u32::try_from(rhs.0).ok()?).map(Self)
+    }
+
+    /// Returns `None` if `rhs` is negative or at least 64.
+    pub(crate) fn checked_shl(&self, rhs: &Self) -> Option<Self> {
+        self.0.checked_shl(u32::try_from(rhs.0).ok()?).map(Self)
+    }
+
+    /// Returns `None` if `rhs` is negative or at least 64.
+    pub(crate) fn checked_shr(&self, rhs: &Self) -> Option<Self> {
+        self.0.checked_shr(u32::try_from(rhs.0).ok()?).map(Self)
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl Int {
+    pub fn is_zero(&self) -> bool {
+        self.0 == Repr::from(0)
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        i64::try_from(&self.0).ok()
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        u64::try_from(&self.0).ok()
+    }
+
+    pub(crate) fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        Some(Self(&self.0 + &rhs.0))
+    }
+
+    pub(crate) fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        Some(Self(&self.0 - &rhs.0))
+    }
+
+    pub(crate) fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        Some(Self(&self.0 * &rhs.0))
+    }
+
+    /// Returns `None` if `rhs` is zero.
+    pub(crate) fn checked_div(&self, rhs: &Self) -> Option<Self> {
+        if rhs.is_zero() {
+            None
+        } else {
+            Some(Self(&self.0 / &rhs.0))
+        }
+    }
+
+    /// Returns `None` if `rhs` is zero.
+    pub(crate) fn checked_rem(&self, rhs: &Self) -> Option<Self> {
+        if rhs.is_zero() {
+            None
+        } else {
+            Some(Self(&self.0 % &rhs.0))
+        }
+    }
+
+    pub(crate) fn bitand(&self, rhs: &Self) -> Self {
+        Self(&self.0 & &rhs.0)
+    }
+
+    pub(crate) fn bitor(&self, rhs: &Self) -> Self {
+        Self(&self.0 | &rhs.0)
+    }
+
+    pub(crate) fn bitxor(&self, rhs: &Self) -> Self {
+        Self(&self.0 ^ &rhs.0)
+    }
+
+    pub(crate) fn not(&self) -> Self {
+        Self(!&self.0)
+    }
+
+    /// Returns `None` if `rhs` is negative or doesn't fit in a `u32`.
+    pub(crate) fn checked_pow(&self, rhs: &Self) -> Option<Self> {
+        Some(Self(self.0.pow(u32::try_from(&rhs.0).ok()?)))
+    }
+
+    /// Returns `None` if `rhs` is negative or doesn't fit in a `u32`.
+    pub(crate) fn checked_shl(&self, rhs: &Self) -> Option<Self> {
+        Some(Self(&self.0 << u32::try_from(&rhs.0).ok()?))
+    }
+
+    /// Returns `None` if `rhs` is negative or doesn't fit in a `u32`.
+    pub(crate) fn checked_shr(&self, rhs: &Self) -> Option<Self> {
+        Some(Self(&self.0 >> u32::try_from(&rhs.0).ok()?))
+    }
+}
+
+impl From<i64> for Int {
+    fn from(n: i64) -> Self {
+        Self(Repr::from(n))
+    }
+}
+
+impl fmt::Display for Int {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_display() {
+        assert_eq!(Int::parse("1234").unwrap().to_string(), "1234");
+        assert_eq!(
+            Int::parse("12a"),
+            Err("invalid number literal 12a".to_string()),
+        );
+    }
+
+    #[test]
+    fn parse_with_radix_and_separators() {
+        assert_eq!(Int::parse("0xFF"), Ok(Int::from(255)));
+        assert_eq!(Int::parse("0o77"), Ok(Int::from(63)));
+        assert_eq!(Int::parse("0b1010"), Ok(Int::from(10)));
+        assert_eq!(Int::parse("1_000_000"), Ok(Int::from(1_000_000)));
+        assert_eq!(Int::parse("0x_dead_beef"), Ok(Int::from(0xdead_beef)));
+    }
+
+    #[test]
+    fn parse_signed() {
+        assert_eq!(Int::parse_signed("-0x10"), Ok(Int::from(-16)));
+        assert_eq!(Int::parse_signed("42"), Ok(Int::from(42)));
+        assert!(Int::parse_signed("--1").is_err());
+        assert!(Int::parse_signed("-").is_err());
+        assert!(Int::parse("-1").is_err());
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn parse_smallest_i64() {
+        assert_eq!(
+            Int::parse_signed("-9223372036854775808"),
+            Ok(Int::from(i64::MIN))
+        );
+    }
+
+    #[test]
+    fn reject_digits_outside_radix() {
+        assert!(Int::parse("0b102").is_err());
+        assert!(Int::parse("0x").is_err());
+        assert!(Int::parse("0o8").is_err());
+    }
+
+    #[test]
+    fn divide_by_zero() {
+        assert_eq!(Int::from(1).checked_div(&Int::from(0)), None);
+    }
+
+    #[test]
+    fn bitwise_ops() {
+        let (a, b) = (Int::from(0b1100), Int::from(0b1010));
+
+        assert_eq!(a.bitand(&b), Int::from(0b1000));
+        assert_eq!(a.bitor(&b), Int::from(0b1110));
+        assert_eq!(a.bitxor(&b), Int::from(0b0110));
+        assert_eq!(a.not(), Int::from(-13));
+        assert_eq!(a.checked_shl(&Int::from(2)), Some(Int::from(0b110000)));
+        assert_eq!(a.checked_shr(&Int::from(2)), Some(Int::from(0b11)));
+        assert_eq!(a.checked_shl(&Int::from(-1)), None);
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn add_overflows_i64() {
+        assert_eq!(Int::from(i64::MAX).checked_add(&Int::from(1)), None);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn add_grows_past_i64() {
+        assert_eq!(
+            Int::from(i64::MAX)
+                .checked_add(&Int::from(1))
+                .unwrap()
+                .to_string(),
+            "9223372036854775808",
+        );
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn reject_overlong_literal() {
+        assert!(Int::parse(&"9".repeat(MAX_DIGITS)).is_ok());
+        assert_eq!(
+            Int::parse(&"9".repeat(MAX_DIGITS + 1)),
+            Err("number literal has more than 10000 digits".to_string()),
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! Callbacks for watching evaluation from the outside, such as to measure coverage or keep an
+//! audit log.
+
+use crate::error::RuntimeError;
+use crate::stmt::Stmt;
+use crate::val::Val;
+use core::fmt;
+
+/// Something told about what evaluation does as it happens. Register one with
+/// [`Interpreter::add_observer`](crate::Interpreter::add_observer).
+///
+/// Every callback does nothing by default, so implementations only need the ones they care
+/// about. Observers are shared with the host, and with any threads evaluating in environments
+/// made from the one they were added to, so they take `&self` and keep what they record behind a
+/// `Mutex` or atomics.
+pub trait EvalObserver: fmt::Debug + Send + Sync {
+    /// Called before each statement runs, including those inside blocks and function bodies.
+    /// Only code that the tree-walking backend evaluates has statements to report.
+    fn on_stmt_start(&self, _stmt: &Stmt) {}
+
+    /// Called when a function, builtin or variant constructor is called with `args`, once any
+    /// `_`s have been filled in. Named arguments aren't included.
+    fn on_call(&self, _callee: &str, _args: &[Val]) {}
+
+    /// Called whenever a name is bound to a value, whether by `let`, `const`, a parameter, a
+    /// loop, a pattern or a `catch`.
+    fn on_binding_created(&self, _name: &str, _val: &Val) {}
+
+    /// Called when a program run by an [`Interpreter`](crate::Interpreter) fails.
+    fn on_error(&self, _error: &RuntimeError) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{Backend, Interpreter};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Default)]
+    struct Log(Mutex<Vec<String>>);
+
+    impl EvalObserver for Log {
+        fn on_stmt_start(&self, stmt: &Stmt) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("stmt {}", crate::fmt::stmt(stmt)));
+        }
+
+        fn on_call(&self, callee: &str, args: &[Val]) {
+            let args = args.iter().map(Val::to_string).collect::<Vec<_>>();
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("call {}({})", callee, args.join(", ")));
+        }
+
+        fn on_binding_created(&self, name: &str, val: &Val) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("bind {} = {}", name, val));
+        }
+
+        fn on_error(&self, error: &RuntimeError) {
+            self.0.lock().unwrap().push(format!("error {}", error));
+        }
+    }
+
+    fn observe(backend: Backend, s: &str) -> Vec<String> {
+        let log = Arc::new(Log::default());
+        let mut interpreter = Interpreter::with_backend(backend);
+        interpreter.add_observer(log.clone());
+
+        let _ = interpreter.eval(&crate::parse(s).unwrap());
+        let log = log.0.lock().unwrap().clone();
+        log
+    }
+
+    #[test]
+    fn report_statements_calls_and_bindings() {
+        assert_eq!(
+            observe(Backend::TreeWalk, "let a = 1\nfn f x => x + a\nf(2)"),
+            [
+                "stmt let a = 1",
+                "bind a = 1",
+                "stmt fn f x => x + a",
+                "stmt f(2)",
+                "call f(2)",
+                "bind x = 2",
+                "stmt x + a",
+            ],
+        );
+    }
+
+    #[test]
+    fn report_errors_once() {
+        assert_eq!(
+            observe(Backend::TreeWalk, "fn f => { 1 + true }\nf()"),
+            [
+                "stmt fn f => {\n    1 + true\n}",
+                "stmt f()",
+                "call f()",
+                "stmt {\n    1 + true\n}",
+                "stmt 1 + true",
+                "error cannot apply + to number and bool",
+            ],
+        );
+    }
+
+    #[test]
+    fn vm_reports_everything_but_statements() {
+        assert_eq!(
+            observe(Backend::Vm, "let a = 1\nfn f x => x + a\nf(2)\nlen([])"),
+            ["bind a = 1", "call f(2)", "bind x = 2", "call len([])"],
+        );
+    }
+
+    #[test]
+    fn report_calls_from_builtins() {
+        assert_eq!(
+            observe(Backend::TreeWalk, "fn inc x => x + 1\nmap([1], inc)")[2..],
+            [
+                "call map([1], inc(_))",
+                "call inc(1)",
+                "bind x = 1",
+                "stmt x + 1"
+            ],
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Rewrites syntax trees into simpler ones that evaluate to the same thing.
+
+use crate::compat::prelude::*;
+use crate::expr::{Block, Expr, Number, Op, UnaryOp};
+use crate::num::Int;
+use crate::program::Program;
+use crate::span::Span;
+use crate::stmt::Stmt;
+use crate::visit::{self, VisitorMut};
+
+pub(crate) fn program(program: &mut Program) {
+    visit::walk_program_mut(&mut Optimizer, program);
+    drop_unused_literals(&mut program.stmts);
+}
+
+struct Optimizer;
+
+impl VisitorMut for Optimizer {
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        visit::walk_block_mut(self, block);
+        drop_unused_literals(&mut block.stmts);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        visit::walk_expr_mut(self, expr);
+
+        match expr {
+            Expr::Operation { lhs, rhs, op, span } => {
+                if let Some(folded) = fold(lhs, rhs, op, span) {
+                    *expr = folded;
+                }
+            }
+            Expr::Unary { op, operand, span } => {
+                if let Expr::Number(Number(n), _) = operand.as_ref() {
+                    let folded = match op {
+                        UnaryOp::BitNot => Some(n.not()),
+                        // Overflow is left for evaluation to report.
+                        UnaryOp::Neg => Int::from(0).checked_sub(n),
+                    };
+
+                    if let Some(n) = folded {
+                        *expr = Expr::Number(Number(n), span.clone());
+                    }
+                }
+            }
+            // A block that only holds an expression can't define anything, so its scope doesn't
+            // matter.
+            Expr::Block(Block { stmts, .. }) => {
+                if let [Stmt::Expr(inner)] = stmts.as_mut_slice() {
+                    *expr = inner.clone();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Drops literals whose value is thrown away because they aren't the last statement.
+fn drop_unused_literals(stmts: &mut Vec<Stmt>) {
+    let last = stmts.len().saturating_sub(1);
+    let mut idx = 0;
+    stmts.retain(|stmt| {
+        let keep = idx == last || !matches!(stmt, Stmt::Expr(expr) if is_literal(expr));
+        idx += 1;
+        keep
+    });
+}
+
+fn is_literal(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Number(..) | Expr::Bool(..) | Expr::Str(..) | Expr::Unit(_) | Expr::Nil(_)
+    )
+}
+
+/// Evaluates an operation on two literals ahead of time. Anything that would fail or overflow at
+/// runtime is left alone, so that it still fails when the program runs. The result takes the
+/// `span` of the whole operation.
+fn fold(lhs: &Expr, rhs: &Expr, op: &Op, span: &Span) -> Option<Expr> {
+    if !is_literal(lhs) || !is_literal(rhs) {
+        return None;
+    }
+
+    match op {
+        Op::Coalesce if matches!(lhs, Expr::Nil(_)) => return Some(rhs.clone()),
+        Op::Coalesce => return Some(lhs.clone()),
+        Op::Eq => return Some(Expr::Bool(lhs == rhs, span.clone())),
+        Op::Ne => return Some(Expr::Bool(lhs != rhs, span.clone())),
+        _ => {}
+    }
+
+    let (lhs, rhs) = match (lhs, rhs) {
+        (Expr::Number(Number(lhs), _), Expr::Number(Number(rhs), _)) => (lhs, rhs),
+        _ => return None,
+    };
+
+    let number = |n: Option<Int>| n.map(|n| Expr::Number(Number(n), span.clone()));
+
+    match op {
+        Op::Add => number(lhs.checked_add(rhs)),
+        Op::Sub => number(lhs.checked_sub(rhs)),
+        Op::Mul => number(lhs.checked_mul(rhs)),
+        Op::Div => number(lhs.checked_div(rhs)),
+        Op::Rem => number(lhs.checked_rem(rhs)),
+        Op::Pow => number(lhs.checked_pow(rhs)),
+        Op::Lt => Some(Expr::Bool(lhs < rhs, span.clone())),
+        Op::Le => Some(Expr::Bool(lhs <= rhs, span.clone())),
+        Op::Gt => Some(Expr::Bool(lhs > rhs, span.clone())),
+        Op::Ge => Some(Expr::Bool(lhs >= rhs, span.clone())),
+        Op::BitAnd => number(Some(lhs.bitand(rhs))),
+        Op::BitOr => number(Some(lhs.bitor(rhs))),
+        Op::BitXor => number(Some(lhs.bitxor(rhs))),
+        Op::Shl => number(lhs.checked_shl(rhs)),
+        Op::Shr => number(lhs.checked_shr(rhs)),
+        // There is no literal for a range to fold into.
+        Op::Range | Op::RangeInclusive => None,
+        Op::Eq | Op::Ne | Op::Coalesce => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Env;
+    use crate::expr::BindingUsage;
+
+    fn optimized(s: &str) -> Program {
+        let mut program = crate::parse(s).unwrap();
+        self::program(&mut program);
+        program
+    }
+
+    #[test]
+    fn fold_nested_arithmetic() {
+        assert_eq!(
+            optimized("1 + 2 * 3 == 7").stmts,
+            vec![Stmt::Expr(Expr::Bool(true, Span::default()))],
+        );
+    }
+
+    #[test]
+    fn only_fold_literal_operands() {
+        assert_eq!(
+            optimized("x + 2 * 3").stmts,
+            vec![Stmt::Expr(Expr::Operation {
+                lhs: Box::new(Expr::BindingUsage(BindingUsage {
+                    name: "x".into(),
+                    span: Span::default(),
+                })),
+                rhs: Box::new(Expr::Number(Number(6.into()), Span::default())),
+                op: Op::Add,
+                span: Span::default(),
+            })],
+        );
+    }
+
+    #[test]
+    fn fold_bitwise_ops() {
+        assert_eq!(
+            optimized("~1 & 6 | 1 << 4").stmts,
+            vec![Stmt::Expr(Expr::Number(Number(22.into()), Span::default()))],
+        );
+    }
+
+    #[test]
+    fn leave_division_by_zero_for_runtime() {
+        assert_eq!(
+            optimized("1 / 0").stmts,
+            crate::parse("1 / 0").unwrap().stmts,
+        );
+    }
+
+    #[test]
+    fn folded_literals_cover_what_they_replace() {
+        let program = optimized("let a = (1 + 2) * ~0");
+
+        match &program.stmts[0] {
+            Stmt::BindingDef(binding_def) => {
+                assert_eq!(
+                    binding_def.val,
+                    Expr::Number(Number((-3).into()), Span::default())
+                );
+                assert_eq!(&program.source()[binding_def.val.span()], "(1 + 2) * ~0");
+            }
+            stmt => panic!("expected a binding definition, got {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn collapse_expression_blocks() {
+        assert_eq!(
+            optimized("fn f x => { 1\n\"unused\"\n{ x } }").stmts,
+            crate::parse("fn f x => x").unwrap().stmts,
+        );
+    }
+
+    #[test]
+    fn keep_blocks_with_bindings() {
+        let source = "{ let a = 1\na }";
+        assert_eq!(optimized(source).stmts, crate::parse(source).unwrap().stmts);
+    }
+
+    #[test]
+    fn semantics_are_preserved() {
+        for source in [
+            "1 + 2 * 3 - 4 / 2",
+            r#""a" == "a""#,
+            "let a = { 1 + 1 }\n{ let a = 3\na } * a",
+            "fn sq x => { x * x }\nsq(2 + 3)",
+            "3\n4\n5",
+            "{ 7 } == 7",
+            "assert(1 + 1 == 3, \"bad maths\")",
+            "2 + true",
+        ] {
+            let expected = crate::parse(source).unwrap().eval(&mut Env::default());
+            assert_eq!(
+                optimized(source).eval(&mut Env::default()),
+                expected,
+                "{}",
+                source
+            );
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Counts how often each function is called and how long the calls take. Only calls are counted
+//! without the `os` feature, since there's no clock to time them with.
+
+use crate::compat::prelude::*;
+use crate::compat::{HashMap, Mutex};
+use crate::symbol::Symbol;
+use alloc::sync::Arc;
+use core::time::Duration;
+
+/// How often a function was called while profiling, and how long those calls took.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuncProfile {
+    pub name: String,
+    pub calls: u64,
+    /// The time spent in the function, including the calls it made. Time spent in a recursive
+    /// call is only counted once, as part of the outermost call.
+    pub total: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Entry {
+    calls: u64,
+    total: Duration,
+    /// How many calls to the function are in progress.
+    active: usize,
+}
+
+/// The counters behind [`Env::enable_profiling`](crate::Env::enable_profiling), which every
+/// scope made from the environment shares.
+#[derive(Debug, Default)]
+pub(crate) struct Profiler {
+    entries: Mutex<HashMap<Symbol, Entry>>,
+}
+
+impl Profiler {
+    /// Counts a call to `callee`, timing it until the returned guard is dropped.
+    pub(crate) fn start(self: &Arc<Self>, callee: Symbol) -> Timer {
+        let mut entries = self.entries.lock();
+        let entry = entries.entry(callee).or_default();
+        entry.calls += 1;
+        entry.active += 1;
+
+        Timer {
+            profiler: Arc::clone(self),
+            callee,
+            start: Stopwatch::start(),
+        }
+    }
+
+    /// Every function called so far, those that took longest first.
+    pub(crate) fn report(&self) -> Vec<FuncProfile> {
+        let mut report = self
+            .entries
+            .lock()
+            .iter()
+            .map(|(name, entry)| FuncProfile {
+                name: name.to_string(),
+                calls: entry.calls,
+                total: entry.total,
+            })
+            .collect::<Vec<_>>();
+
+        report.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.name.cmp(&b.name)));
+        report
+    }
+}
+
+/// Adds the time since a call started to its function when dropped, however the call ends.
+pub(crate) struct Timer {
+    profiler: Arc<Profiler>,
+    callee: Symbol,
+    start: Stopwatch,
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let mut entries = self.profiler.entries.lock();
+        let entry = entries
+            .get_mut(&self.callee)
+            .expect("started calls have entries");
+
+        entry.active -= 1;
+        if entry.active == 0 {
+            entry.total += self.start.elapsed();
+        }
+    }
+}
+
+#[cfg(feature = "os")]
+struct Stopwatch(std::time::Instant);
+
+#[cfg(feature = "os")]
+impl Stopwatch {
+    fn start() -> Self {
+        Self(std::time::Instant::now())
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+#[cfg(not(feature = "os"))]
+struct Stopwatch;
+
+#[cfg(not(feature = "os"))]
+impl Stopwatch {
+    fn start() -> Self {
+        Self
+    }
+
+    fn elapsed(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::env::Env;
+    use crate::interpreter::{Backend, Interpreter};
+
+    fn calls(backend: Backend, s: &str) -> Vec<(String, u64)> {
+        let mut interpreter = Interpreter::with_backend(backend);
+        interpreter.env_mut().enable_profiling();
+        let _ = interpreter.eval(&crate::parse(s).unwrap());
+
+        let mut calls = interpreter
+            .env()
+            .profile()
+            .into_iter()
+            .map(|profile| (profile.name, profile.calls))
+            .collect::<Vec<_>>();
+        calls.sort();
+        calls
+    }
+
+    #[test]
+    fn count_calls_to_each_function() {
+        let fib = "fn fib n => match n < 2 { true => n, false => fib(n - 1) + fib(n - 2) }\nfib(5)";
+
+        for backend in [Backend::TreeWalk, Backend::Vm] {
+            assert_eq!(calls(backend, fib), [("fib".to_string(), 15)]);
+        }
+    }
+
+    #[test]
+    fn count_failed_calls_and_calls_from_builtins() {
+        assert_eq!(
+            calls(
+                Backend::TreeWalk,
+                "fn inc x => x + 1\nmap([1, 2], inc)\ninc(true)"
+            ),
+            [("inc".to_string(), 3), ("map".to_string(), 1)],
+        );
+    }
+
+    #[test]
+    fn nothing_is_recorded_unless_enabled() {
+        let mut env = Env::default();
+        crate::parse("fn f => 1\nf()")
+            .unwrap()
+            .eval(&mut env)
+            .unwrap();
+
+        assert_eq!(env.profile(), []);
+    }
+
+    #[cfg(feature = "os")]
+    #[test]
+    fn recursive_time_is_counted_once() {
+        use super::*;
+
+        let profiler = Arc::new(Profiler::default());
+        let before = std::time::Instant::now();
+        let outer = profiler.start("f".into());
+        let inner = profiler.start("f".into());
+        std::thread::sleep(Duration::from_millis(5));
+        drop(inner);
+        drop(outer);
+        let elapsed = before.elapsed();
+
+        let report = profiler.report();
+        assert_eq!(report[0].calls, 2);
+        assert!(report[0].total >= Duration::from_millis(5));
+        assert!(report[0].total <= elapsed);
+    }
+}
@@ -0,0 +1,405 @@
+use crate::compat::prelude::*;
+use crate::compile::Chunk;
+use crate::env::Env;
+use crate::error::{ParseError, RuntimeError, TypeError};
+use crate::expr::{CallStyle, Expr, FuncCall};
+use crate::func_def::FuncDef;
+use crate::reparse::TextEdit;
+use crate::span::Span;
+use crate::stmt::Stmt;
+use crate::symbol::Symbol;
+use crate::utils;
+use crate::val::Val;
+use crate::visit::{self, VisitorMut};
+use alloc::sync::Arc;
+use core::ops::Range;
+
+/// A sequence of statements, such as the contents of a file.
+///
+/// The program keeps the source text it was parsed from, which the spans of its statements and
+/// expressions point into. Like spans, the source is ignored when comparing programs.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub(crate) shebang: Option<String>,
+    pub(crate) stmts: Vec<Stmt>,
+    source: Arc<str>,
+}
+
+impl PartialEq for Program {
+    fn eq(&self, other: &Self) -> bool {
+        self.shebang == other.shebang && self.stmts == other.stmts
+    }
+}
+
+/// A program lowered to bytecode once so that it can be evaluated many times, such as a formula
+/// run against different inputs. Make one with [`Program::compile`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledProgram {
+    chunk: Chunk,
+    source: Arc<str>,
+}
+
+/// The result of running a single `test_` function.
+#[derive(Debug, PartialEq)]
+pub struct TestOutcome {
+    pub name: String,
+    pub result: Result<(), RuntimeError>,
+}
+
+impl Program {
+    pub(crate) fn new(s: &str) -> Result<(&str, Self), String> {
+        let (remainder, stmts) = utils::parse_str(|tokens| utils::lines(Stmt::new, tokens), s)?;
+
+        Ok((remainder, Self::from_stmts(s, stmts)))
+    }
+
+    /// Puts together a program from statements parsed out of `s`.
+    pub(crate) fn from_stmts(s: &str, mut stmts: Vec<Stmt>) -> Self {
+        // The lexer skips the shebang line, but it's kept so that formatting can write it back.
+        let shebang = if s.starts_with("#!") {
+            let end = s.find('\n').unwrap_or(s.len());
+            Some(s[..end].trim_end_matches('\r').to_string())
+        } else {
+            None
+        };
+
+        // Functions outlive the program once they're stored in an environment, so they get a
+        // handle on the source of their own.
+        let source: Arc<str> = s.into();
+        let mut attach = AttachSource(source.clone());
+        for stmt in &mut stmts {
+            attach.visit_stmt_mut(stmt);
+        }
+
+        Self {
+            shebang,
+            stmts,
+            source,
+        }
+    }
+
+    pub fn stmts(&self) -> &[Stmt] {
+        &self.stmts
+    }
+
+    /// The source text the program was parsed from. Slicing it with the span of any statement or
+    /// expression in the program, such as [`Expr::span`], gives back exactly what was written.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The source, shared with the functions that the program defines.
+    #[cfg(feature = "std")]
+    pub(crate) fn shared_source(&self) -> &Arc<str> {
+        &self.source
+    }
+
+    /// Parses the program's source again after `edit`, reusing the statements that the edit
+    /// didn't change instead of parsing them from scratch. The result is the same as parsing the
+    /// edited source with [`crate::parse`], but editors can afford it on every keystroke.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range of `edit` is out of bounds of the source or doesn't fall on character
+    /// boundaries.
+    pub fn reparse(&self, edit: &TextEdit) -> Result<Program, ParseError> {
+        crate::reparse::program(self, edit)
+    }
+
+    /// Renders the program as canonical source text, with one statement per line. Comments
+    /// aren't part of a `Program`, so use [`crate::format`] to keep them.
+    pub fn format(&self) -> String {
+        crate::fmt::program(self)
+    }
+
+    /// Renders the syntax tree as an indented outline, for seeing how source was parsed.
+    pub fn dump_ast(&self) -> String {
+        crate::dump::program(self)
+    }
+
+    /// Simplifies the program without changing what it does, such as by folding operations on
+    /// literals.
+    pub fn optimize(&mut self) {
+        crate::optimize::program(self);
+    }
+
+    /// Looks for type errors without running the program, such as calling a function with the
+    /// wrong number of arguments.
+    pub fn check(&self) -> Vec<TypeError> {
+        crate::typecheck::program(self)
+    }
+
+    /// Infers the type of the last statement, as it would be if the program were evaluated in
+    /// `env`. A function name on its own gives the type of the function, such as
+    /// `fn(number, number) -> number`.
+    pub fn infer(&self, env: &Env) -> Result<String, Vec<TypeError>> {
+        crate::typecheck::infer(self, env)
+    }
+
+    /// The inferred type of every `let`, `const` and `fn` in the program, including those inside
+    /// blocks and functions, along with the byte range of each definition. Editors use this to
+    /// show types on hover.
+    pub fn definition_types(&self, env: &Env) -> Vec<(Range<usize>, String)> {
+        crate::typecheck::definitions(self, env)
+    }
+
+    /// Lowers the program to bytecode for the VM backend, so that evaluating it again doesn't
+    /// need to go back to the syntax tree. Call [`Program::optimize`] first to fold constants.
+    pub fn compile(&self) -> CompiledProgram {
+        CompiledProgram {
+            chunk: crate::compile::program(self),
+            source: self.source.clone(),
+        }
+    }
+
+    /// Evaluates every statement in order, returning the value of the last one.
+    ///
+    /// An error whose span points into source other than this program's, such as from calling a
+    /// function that another program defined, says so with [`RuntimeError::source`].
+    pub fn eval(&self, env: &mut Env) -> Result<Val, RuntimeError> {
+        let mut result = Val::Unit;
+
+        for stmt in &self.stmts {
+            result = stmt
+                .eval(env)
+                .map_err(|error| error.relative_to(&self.source))?;
+        }
+
+        Ok(result)
+    }
+
+    /// Evaluates the program, then calls each top-level function whose name starts with `test_`.
+    ///
+    /// A test passes if calling it does not produce an error, so failures usually come from
+    /// `assert`.
+    pub fn run_tests(&self, env: &mut Env) -> Result<Vec<TestOutcome>, RuntimeError> {
+        self.eval(env)?;
+
+        let outcomes = self
+            .test_names()
+            .map(|name| TestOutcome {
+                name: name.to_string(),
+                result: Expr::FuncCall(FuncCall {
+                    callee: Symbol::intern(name),
+                    style: CallStyle::Direct,
+                    named: Vec::new(),
+                    span: Span::default(),
+                    params: Vec::new(),
+                })
+                .eval(env)
+                .map(|_| ())
+                .map_err(|error| error.relative_to(&self.source)),
+            })
+            .collect();
+
+        Ok(outcomes)
+    }
+
+    fn test_names(&self) -> impl Iterator<Item = &str> {
+        self.stmts.iter().filter_map(|stmt| match stmt {
+            Stmt::FuncDef(func_def) if func_def.name.as_str().starts_with("test_") => {
+                Some(func_def.name.as_str())
+            }
+            _ => None,
+        })
+    }
+}
+
+struct AttachSource(Arc<str>);
+
+impl VisitorMut for AttachSource {
+    fn visit_func_def_mut(&mut self, func_def: &mut FuncDef) {
+        func_def.source = Some(self.0.clone());
+        visit::walk_func_def_mut(self, func_def);
+    }
+}
+
+impl CompiledProgram {
+    /// Evaluates the program in `env`, which can be fresh or already hold bindings set with
+    /// [`Env::set_binding`].
+    pub fn eval(&self, env: &mut Env) -> Result<Val, RuntimeError> {
+        crate::vm::run(&self.chunk, env).map_err(|error| error.relative_to(&self.source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_program_with_multiple_stmts() {
+        let (s, program) = Program::new("\nlet a = 1\nlet b = a + 1\nb\n").unwrap();
+
+        assert_eq!(s, "");
+        assert_eq!(program.stmts.len(), 3);
+        assert_eq!(program.eval(&mut Env::default()), Ok(Val::Number(2.into())));
+    }
+
+    #[test]
+    fn spans_point_back_into_the_source() {
+        #[derive(Default)]
+        struct Spans(Vec<Range<usize>>);
+
+        impl visit::Visitor for Spans {
+            fn visit_expr(&mut self, expr: &Expr) {
+                self.0.push(expr.span());
+                visit::walk_expr(self, expr);
+            }
+        }
+
+        let program = crate::parse(
+            "let a = [1, (true, nil)]\nfn f x => {\n    throw ~x.y\n}\nf({y: \"s\"})[0]",
+        )
+        .unwrap();
+        let source = program.source();
+
+        let mut spans = Spans::default();
+        visit::walk_program(&mut spans, &program);
+
+        assert_eq!(
+            program
+                .stmts()
+                .iter()
+                .map(|stmt| &source[stmt.span()])
+                .collect::<Vec<_>>(),
+            [
+                "let a = [1, (true, nil)]",
+                "fn f x => {\n    throw ~x.y\n}",
+                "f({y: \"s\"})[0]",
+            ],
+        );
+        assert_eq!(
+            spans
+                .0
+                .into_iter()
+                .map(|span| &source[span])
+                .collect::<Vec<_>>(),
+            [
+                "[1, (true, nil)]",
+                "1",
+                "(true, nil)",
+                "true",
+                "nil",
+                "{\n    throw ~x.y\n}",
+                "~x.y",
+                "x.y",
+                "x",
+                "f({y: \"s\"})[0]",
+                "f({y: \"s\"})",
+                "{y: \"s\"}",
+                "\"s\"",
+                "0",
+            ],
+        );
+    }
+
+    #[test]
+    fn reject_stmts_on_the_same_line() {
+        for (source, at) in [
+            ("1 + 2 3", "3"),
+            ("(1)(2)", "("),
+            ("fn add a b => a + b\nadd(_, b: 2)(1)", "("),
+            ("let a = 1 let b = 2", "let"),
+        ] {
+            let start = source.rfind(at).unwrap();
+            assert_eq!(
+                crate::parse(source),
+                Err(ParseError {
+                    message: "expected a line break before this statement".to_string(),
+                    span: start..start + at.len(),
+                }),
+                "{}",
+                source,
+            );
+        }
+
+        assert!(crate::parse("{ let a = 1 a }").is_err());
+        assert_eq!(
+            crate::parse("1 + 2\n3\n{ let a = 1\na }").map(|program| program.stmts.len()),
+            Ok(3),
+        );
+    }
+
+    #[test]
+    fn same_line_after_function_body_is_an_argument() {
+        // A name followed by more on the same line is a call, so this `f(2)` is passed to `x`.
+        let program = crate::parse("fn f x => x f(2)").unwrap();
+        assert_eq!(program.stmts.len(), 1);
+        assert_eq!(
+            crate::parse("fn f x => x\nf(2)").map(|program| program.stmts.len()),
+            Ok(2),
+        );
+    }
+
+    #[test]
+    fn eval_empty_program() {
+        let (_, program) = Program::new("").unwrap();
+        assert_eq!(program.eval(&mut Env::default()), Ok(Val::Unit));
+    }
+
+    #[test]
+    fn eval_compiled_program_against_different_inputs() {
+        let compiled = crate::parse("fn total n => n * price\ntotal(quantity)")
+            .unwrap()
+            .compile();
+
+        for (price, quantity, total) in [(3, 2, 6), (5, 4, 20)] {
+            let mut env = Env::default();
+            env.set_binding("price", Val::Number(price.into())).unwrap();
+            env.set_binding("quantity", Val::Number(quantity.into()))
+                .unwrap();
+
+            assert_eq!(compiled.eval(&mut env), Ok(Val::Number(total.into())));
+        }
+
+        assert_eq!(
+            compiled.eval(&mut Env::default()),
+            Err(RuntimeError::Message(
+                "binding with name 'quantity' does not exist".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn skip_shebang_line() {
+        let (s, program) = Program::new("#!/usr/bin/env eldiro\nlet a = 5\na\n").unwrap();
+
+        assert_eq!(s, "");
+        assert_eq!(program.eval(&mut Env::default()), Ok(Val::Number(5.into())));
+    }
+
+    #[test]
+    fn shebang_is_only_skipped_at_start() {
+        let (s, _) = Program::new("1\n#!/usr/bin/env eldiro\n").unwrap();
+        assert_eq!(s, "#!/usr/bin/env eldiro\n");
+    }
+
+    #[test]
+    fn run_passing_and_failing_tests() {
+        let (_, program) = Program::new(
+            r#"
+fn double x => x * 2
+fn test_double => assert(double(2) == 4, "2 doubled is 4")
+fn test_broken => assert(double(2) == 5, "2 doubled is 5")
+fn helper => assert(false, "not a test")
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            program.run_tests(&mut Env::default()),
+            Ok(vec![
+                TestOutcome {
+                    name: "test_double".into(),
+                    result: Ok(()),
+                },
+                TestOutcome {
+                    name: "test_broken".into(),
+                    result: Err(RuntimeError::Message(
+                        "assertion failed: 2 doubled is 5".to_string()
+                    )),
+                },
+            ]),
+        );
+    }
+}
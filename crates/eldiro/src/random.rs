@@ -0,0 +1,74 @@
+//! The random numbers behind the `random` builtin.
+
+use crate::compat::Mutex;
+
+/// A SplitMix64 generator, which is small and fast but predictable from its output, so it's only
+/// fit for things like games.
+#[derive(Debug)]
+pub(crate) struct Rng {
+    state: Mutex<u64>,
+}
+
+impl Rng {
+    /// A generator that gives the same numbers every time it is made with the same seed.
+    pub(crate) fn seeded(seed: u64) -> Self {
+        Self {
+            state: Mutex::new(seed),
+        }
+    }
+
+    /// A generator seeded differently each time it is made. Without the `std` feature there's
+    /// nothing to seed it from, so it always starts from the same seed.
+    pub(crate) fn unseeded() -> Self {
+        #[cfg(feature = "std")]
+        let seed = {
+            use std::hash::{BuildHasher, RandomState};
+            RandomState::new().hash_one(0)
+        };
+        #[cfg(not(feature = "std"))]
+        let seed = 0;
+
+        Self::seeded(seed)
+    }
+
+    fn next(&self) -> u64 {
+        let mut state = self.state.lock();
+        *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number from 0 up to but not including `bound`, which must not be zero.
+    pub(crate) fn below(&self, bound: u64) -> u64 {
+        ((u128::from(self.next()) * u128::from(bound)) >> 64) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_numbers() {
+        let (a, b) = (Rng::seeded(7), Rng::seeded(7));
+
+        let a: Vec<_> = (0..5).map(|_| a.below(1000)).collect();
+        let b: Vec<_> = (0..5).map(|_| b.below(1000)).collect();
+
+        assert_eq!(a, b);
+        assert!(a.iter().all(|&n| n < 1000));
+    }
+
+    #[test]
+    fn different_seeds_give_different_numbers() {
+        let (a, b) = (Rng::seeded(1), Rng::seeded(2));
+
+        assert_ne!(
+            (0..5).map(|_| a.below(u64::MAX)).collect::<Vec<_>>(),
+            (0..5).map(|_| b.below(u64::MAX)).collect::<Vec<_>>(),
+        );
+    }
+}
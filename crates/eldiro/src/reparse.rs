@@ -0,0 +1,336 @@
+//! Parsing a program again after an edit, reusing the statements the edit didn't touch.
+
+use crate::compat::prelude::*;
+use crate::compat::HashMap;
+use crate::error::ParseError;
+use crate::expr::{Block, Expr};
+use crate::lexer::{self, Token, TokenKind};
+use crate::program::Program;
+use crate::span::Span;
+use crate::stmt::Stmt;
+use crate::utils;
+use crate::visit::{self, VisitorMut};
+use core::ops::Range;
+
+/// A change to source text: the bytes in `range` are replaced with `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+pub(crate) fn program(old: &Program, edit: &TextEdit) -> Result<Program, ParseError> {
+    reparse(old, edit).map(|(program, _)| program)
+}
+
+/// Does the work of [`program`], also counting how many statements were reused.
+fn reparse(old: &Program, edit: &TextEdit) -> Result<(Program, usize), ParseError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("reparse").entered();
+
+    let mut source = old.source().to_string();
+    source.replace_range(edit.range.clone(), &edit.text);
+
+    let tokens = lexer::lex(&source);
+    crate::check_tokens(&source, &tokens)?;
+
+    let old_tokens = lexer::lex(old.source());
+    let old_stmts = stmt_tokens(old.stmts(), &old_tokens);
+
+    // Forgets where any earlier parse went too deep.
+    utils::too_deep();
+    let mut stmts = Vec::new();
+    let mut reused = 0;
+    let mut rest = &tokens[..];
+
+    while let Some(first) = rest.first() {
+        // Like `utils::lines`, only the first statement may share a line with what came before.
+        if !stmts.is_empty() && !first.newline_before {
+            break;
+        }
+
+        let start = first.span.start;
+        let old_stmt = old_offset(start, edit)
+            .and_then(|offset| Some((offset, old_stmts.get(&offset)?)))
+            .filter(|(offset, (_, old_rest, len))| {
+                unchanged(
+                    &old_rest[..],
+                    rest,
+                    *len,
+                    start.wrapping_sub(*offset) as isize,
+                )
+            });
+
+        match old_stmt {
+            Some((offset, (stmt, _, len))) => {
+                let mut stmt = (*stmt).clone();
+                Shift(start.wrapping_sub(offset) as isize).visit_stmt_mut(&mut stmt);
+
+                stmts.push(stmt);
+                reused += 1;
+                rest = &rest[*len..];
+            }
+            None => match Stmt::new(rest) {
+                Ok((new_rest, stmt)) => {
+                    stmts.push(stmt);
+                    rest = new_rest;
+                }
+                Err(_) => break,
+            },
+        }
+    }
+
+    if let Some(first) = rest.first() {
+        return Err(crate::stopped_at(&source, first.span.start));
+    }
+
+    Ok((Program::from_stmts(&source, stmts), reused))
+}
+
+/// Finds the tokens of each statement, keyed by where its first token starts. Each entry holds
+/// the statement, the tokens from its first one to the end of the program and how many of them
+/// belong to the statement.
+fn stmt_tokens<'a>(
+    stmts: &'a [Stmt],
+    tokens: &'a [Token],
+) -> HashMap<usize, (&'a Stmt, &'a [Token], usize)> {
+    // The span of a parenthesized expression leaves out the parentheses, so going by spans alone
+    // a statement like `(a)` would lose its `(` to the statement before and its `)` to the one
+    // after. Between two statements, the `)`s come first and belong to the earlier one.
+    let mut starts = Vec::with_capacity(stmts.len());
+    let mut idx = 0;
+
+    for stmt in stmts {
+        let span = stmt.span();
+        while tokens
+            .get(idx)
+            .is_some_and(|token| token.span.start < span.start && token.kind == TokenKind::RParen)
+        {
+            idx += 1;
+        }
+
+        starts.push(idx);
+        idx += tokens[idx..].partition_point(|token| token.span.start < span.end);
+    }
+
+    starts
+        .iter()
+        .zip(starts.iter().skip(1).chain([&tokens.len()]))
+        .zip(stmts)
+        .filter_map(|((&start, &end), stmt)| {
+            let first = tokens.get(start)?;
+            Some((first.span.start, (stmt, &tokens[start..], end - start)))
+        })
+        .collect()
+}
+
+/// Maps an offset in the edited source back to the same place before the edit, if it isn't part
+/// of the inserted text.
+fn old_offset(offset: usize, edit: &TextEdit) -> Option<usize> {
+    if offset < edit.range.start {
+        Some(offset)
+    } else if offset >= edit.range.start + edit.text.len() {
+        Some(offset - edit.text.len() + edit.range.len())
+    } else {
+        None
+    }
+}
+
+/// Whether the `len` tokens of a statement are the same before and after the edit, apart from
+/// having moved by `shift` bytes. So is the token after them, which the statement's parser looked
+/// at to decide that it ended there.
+fn unchanged(old: &[Token], new: &[Token], len: usize, shift: isize) -> bool {
+    if new.len() < len {
+        return false;
+    }
+
+    let same = |old: &Token, new: &Token| {
+        old.kind == new.kind
+            && old.newline_before == new.newline_before
+            && old.doc == new.doc
+            && old.span.start.wrapping_add_signed(shift) == new.span.start
+            && old.span.end.wrapping_add_signed(shift) == new.span.end
+    };
+
+    old[..len]
+        .iter()
+        .zip(&new[..len])
+        .all(|(old, new)| same(old, new))
+        && match (old.get(len), new.get(len)) {
+            (Some(old), Some(new)) => same(old, new),
+            (None, None) => true,
+            _ => false,
+        }
+}
+
+/// Moves every span in a statement by the same number of bytes.
+struct Shift(isize);
+
+impl Shift {
+    fn shift(&self, span: &mut Span) {
+        span.0 = span.0.start.wrapping_add_signed(self.0)..span.0.end.wrapping_add_signed(self.0);
+    }
+}
+
+impl VisitorMut for Shift {
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::BindingDef(binding_def) => self.shift(&mut binding_def.span),
+            Stmt::FuncDef(func_def) => self.shift(&mut func_def.span),
+            Stmt::EnumDef(enum_def) => self.shift(&mut enum_def.span),
+            Stmt::Import(import) => self.shift(&mut import.span),
+            Stmt::Throw(_, span) => self.shift(span),
+            Stmt::Assign(assign) => self.shift(&mut assign.span),
+            Stmt::Expr(_) => {}
+        }
+        visit::walk_stmt_mut(self, stmt);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        // Blocks are also reached on their own, as the bodies of `try` and `for`.
+        if !matches!(expr, Expr::Block(_)) {
+            self.shift(expr.span_mut());
+        }
+        visit::walk_expr_mut(self, expr);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        self.shift(&mut block.span);
+        visit::walk_block_mut(self, block);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::visit::Visitor;
+
+    #[derive(Default)]
+    struct Spans(Vec<Range<usize>>);
+
+    impl Visitor for Spans {
+        fn visit_stmt(&mut self, stmt: &Stmt) {
+            self.0.push(stmt.span());
+            visit::walk_stmt(self, stmt);
+        }
+
+        fn visit_expr(&mut self, expr: &Expr) {
+            self.0.push(expr.span());
+            visit::walk_expr(self, expr);
+        }
+
+        fn visit_block(&mut self, block: &Block) {
+            self.0.push(block.span.0.clone());
+            visit::walk_block(self, block);
+        }
+    }
+
+    fn spans(program: &Program) -> Vec<Range<usize>> {
+        let mut spans = Spans::default();
+        visit::walk_program(&mut spans, program);
+        spans.0
+    }
+
+    fn edit(range: Range<usize>, text: &str) -> TextEdit {
+        TextEdit {
+            range,
+            text: text.to_string(),
+        }
+    }
+
+    const SOURCE: &str = "\
+## Doubles.
+fn double x => x * 2
+let a = (1 + 2) * 3 // nine
+(a)
+try { throw a } catch e { [e, double(e)] }
+// Each on its own line.
+for i in [1, 2] {
+    let j = i // the item
+    j += 1
+    i
+}
+let b = match a { 9 => \"nine\", _ => a.x }
+";
+
+    #[test]
+    fn reuse_statements_before_and_after_edit() {
+        let program = crate::parse(SOURCE).unwrap();
+        let start = SOURCE.find("(a)").unwrap();
+
+        let (reparsed, reused) = reparse(&program, &edit(start..start + 3, "a + 10")).unwrap();
+
+        // The statement right before the edit looked at the `(` to know that it had ended.
+        assert_eq!(reused, 4);
+        assert_eq!(reparsed, crate::parse(reparsed.source()).unwrap());
+        assert_eq!(reparsed.source(), SOURCE.replace("(a)", "a + 10"));
+    }
+
+    #[test]
+    fn reparse_edited_header_of_next_statement() {
+        let program = crate::parse("fn f x => x\nf\n1").unwrap();
+
+        // Joining the lines turns `f` and `1` into a call, so only the definition is kept.
+        let (reparsed, reused) = reparse(&program, &edit(13..14, " ")).unwrap();
+
+        assert_eq!(reused, 1);
+        assert_eq!(reparsed.stmts().len(), 2);
+    }
+
+    #[test]
+    fn report_parse_errors_in_edited_source() {
+        let program = crate::parse("let a = 1\nlet b = 2").unwrap();
+
+        assert_eq!(
+            program.reparse(&edit(14..15, "@")),
+            Err(ParseError {
+                message: "expected statement".to_string(),
+                span: 14..15,
+            }),
+        );
+    }
+
+    #[test]
+    fn match_full_parse_after_any_small_edit() {
+        let program = crate::parse(SOURCE).unwrap();
+
+        for start in 0..SOURCE.len() {
+            for (len, text) in [(0, " "), (0, "\n"), (0, "x"), (0, "("), (1, ""), (1, "1")] {
+                let end = (start + len).min(SOURCE.len());
+                let edit = edit(start..end, text);
+
+                let mut source = SOURCE.to_string();
+                source.replace_range(start..end, text);
+
+                let parsed = crate::parse(&source);
+
+                // The syntax tree that tools work from has to agree with what runs, apart from
+                // bad literals, which only the tokens are checked for.
+                let tree = crate::syntax::parse(&source);
+                let lexed = crate::check_tokens(&source, &lexer::lex(&source));
+                assert_eq!(
+                    tree.errors().is_empty() && lexed.is_ok(),
+                    parsed.is_ok(),
+                    "{:?}",
+                    edit,
+                );
+                if let Ok(parsed) = &parsed {
+                    assert_eq!(
+                        tree.root().stmts().count(),
+                        parsed.stmts().len(),
+                        "{:?}",
+                        edit
+                    );
+                }
+
+                match (program.reparse(&edit), parsed) {
+                    (Ok(reparsed), Ok(parsed)) => {
+                        assert_eq!(reparsed, parsed, "{:?}", edit);
+                        assert_eq!(spans(&reparsed), spans(&parsed), "{:?}", edit);
+                    }
+                    (reparsed, parsed) => assert_eq!(reparsed.err(), parsed.err(), "{:?}", edit),
+                }
+            }
+        }
+    }
+}
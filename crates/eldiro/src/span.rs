@@ -0,0 +1,22 @@
+use core::ops::Range;
+
+/// The byte range of source text that a syntax tree node was parsed from.
+///
+/// Spans are ignored when comparing nodes, so that the same code compares equal wherever it's
+/// written.
+#[derive(Debug, Clone, Default)]
+pub struct Span(pub Range<usize>);
+
+impl PartialEq for Span {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Span {}
+
+impl From<Range<usize>> for Span {
+    fn from(range: Range<usize>) -> Self {
+        Self(range)
+    }
+}
@@ -1,23 +1,76 @@
-use std::clone;
-
-use crate::{binding_def::BindingDef, env::Env, expr::Expr, func_def::FuncDef, val::Val};
+use crate::compat::prelude::*;
+use crate::error::RuntimeError;
+use crate::utils;
+use crate::{
+    assign::Assign, binding_def::BindingDef, enum_def::EnumDef, env::Env, expr::Expr,
+    func_def::FuncDef, import::Import, lexer::Token, span::Span, val::Val,
+};
+use core::ops::Range;
 
+/// A statement, which is anything that can appear on its own line in a program or block.
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) enum Stmt {
+#[non_exhaustive]
+pub enum Stmt {
     BindingDef(BindingDef),
     Expr(Expr),
     FuncDef(FuncDef),
+    EnumDef(EnumDef),
+    Import(Import),
+    /// `throw val`, which fails with `val` until a `try` catches it.
+    Throw(Expr, Span),
+    Assign(Assign),
 }
 
 impl Stmt {
-    pub(crate) fn new(s: &str) -> Result<(&str, Self), String> {
-        BindingDef::new(s)
-            .map(|(s, binding_def)| (s, Self::BindingDef(binding_def)))
-            .or_else(|_| FuncDef::new(s).map(|(s, func_def)| (s, Self::FuncDef(func_def))))
-            .or_else(|_| Expr::new(s).map(|(s, expr)| (s, Self::Expr(expr))))
+    pub(crate) fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        BindingDef::new(tokens)
+            .map(|(tokens, binding_def)| (tokens, Self::BindingDef(binding_def)))
+            .or_else(|_| {
+                FuncDef::new(tokens).map(|(tokens, func_def)| (tokens, Self::FuncDef(func_def)))
+            })
+            .or_else(|_| {
+                EnumDef::new(tokens).map(|(tokens, enum_def)| (tokens, Self::EnumDef(enum_def)))
+            })
+            .or_else(|_| Import::new(tokens).map(|(tokens, import)| (tokens, Self::Import(import))))
+            .or_else(|_| Self::new_throw(tokens))
+            .or_else(|_| Assign::new(tokens).map(|(tokens, assign)| (tokens, Self::Assign(assign))))
+            .or_else(|_| Expr::new(tokens).map(|(tokens, expr)| (tokens, Self::Expr(expr))))
     }
 
-    pub(crate) fn eval(&self, env: &mut Env) -> Result<Val, String> {
+    /// The byte range of the source that the statement was parsed from.
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Self::BindingDef(binding_def) => binding_def.span.0.clone(),
+            Self::Expr(expr) => expr.span(),
+            Self::FuncDef(func_def) => func_def.span.0.clone(),
+            Self::EnumDef(enum_def) => enum_def.span.0.clone(),
+            Self::Import(import) => import.span.0.clone(),
+            Self::Throw(_, span) => span.0.clone(),
+            Self::Assign(assign) => assign.span.0.clone(),
+        }
+    }
+
+    fn new_throw(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        let start = tokens;
+        let tokens = utils::keyword("throw", tokens)?;
+        let (tokens, val) = Expr::new(tokens)?;
+
+        Ok((
+            tokens,
+            Self::Throw(val, utils::consumed_span(start, tokens)),
+        ))
+    }
+
+    pub(crate) fn eval(&self, env: &mut Env) -> Result<Val, RuntimeError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("stmt", code = %crate::fmt::stmt(self)).entered();
+
+        env.notify(|observer| observer.on_stmt_start(self));
+
+        if let Some(session) = env.debug_session() {
+            session.before(self, env)?;
+        }
+
         match self {
             Self::BindingDef(binding_def) => {
                 binding_def.eval(env)?;
@@ -27,47 +80,62 @@ impl Stmt {
                 func_def.eval(env)?;
                 Ok(Val::Unit)
             }
+            Self::EnumDef(enum_def) => {
+                enum_def.eval(env)?;
+                Ok(Val::Unit)
+            }
+            Self::Import(import) => {
+                import.eval(env)?;
+                Ok(Val::Unit)
+            }
+            Self::Assign(assign) => {
+                assign.eval(env)?;
+                Ok(Val::Unit)
+            }
             Self::Expr(expr) => expr.eval(env),
+            Self::Throw(val, _) => Err(RuntimeError::Thrown(val.eval(env)?)),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Env, Expr, FuncDef, Stmt, Val};
+    use super::{Expr, FuncDef, Span, Stmt};
     use crate::expr::BindingUsage;
     use crate::expr::{Number, Op};
-    use crate::stmt::BindingDef;
     #[test]
     fn parse_expr() {
         assert_eq!(
-            Stmt::new("1+1"),
+            crate::utils::parse_str(Stmt::new, "1+1"),
             Ok((
                 "",
                 Stmt::Expr(Expr::Operation {
-                    lhs: Box::new(Expr::Number(Number(1))),
-                    rhs: Box::new(Expr::Number(Number(1))),
+                    lhs: Box::new(Expr::Number(Number(1.into()), Span::default())),
+                    rhs: Box::new(Expr::Number(Number(1.into()), Span::default())),
                     op: Op::Add,
+                    span: Span::default(),
                 }),
             )),
         );
     }
     #[test]
     fn parse_func_def() {
-        fn parse_func_def() {
-            assert_eq!(
-                Stmt::new("fn identity x => x"),
-                Ok((
-                    "",
-                    Stmt::FuncDef(FuncDef {
-                        name: "identity".to_string(),
-                        params: vec!["x".to_string()],
-                        body: Box::new(Stmt::Expr(Expr::BindingUsage(BindingUsage {
-                            name: "x".to_string(),
-                        }))),
-                    }),
-                )),
-            );
-        }
+        assert_eq!(
+            crate::utils::parse_str(Stmt::new, "fn identity x => x"),
+            Ok((
+                "",
+                Stmt::FuncDef(FuncDef {
+                    name: "identity".into(),
+                    span: Span::default(),
+                    params: vec!["x".into()],
+                    body: Box::new(Stmt::Expr(Expr::BindingUsage(BindingUsage {
+                        name: "x".into(),
+                        span: Span::default(),
+                    }))),
+                    doc: None,
+                    source: None,
+                }),
+            )),
+        );
     }
 }
@@ -0,0 +1,142 @@
+use crate::compat::prelude::*;
+use crate::compat::{HashMap, LazyLock, Mutex, OnceLock};
+use core::fmt;
+
+/// An interned identifier, which is compared and hashed as an integer.
+///
+/// Interned names are never freed: they live for the rest of the process, since they're shared by
+/// every program and environment that uses them. So a host that interns names it doesn't control,
+/// such as the fields of records built from user data, uses memory for each distinct one for good.
+/// Looking up the name of a symbol takes no lock, so only interning new names contends between
+/// threads.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+/// How many names fit in the first bucket of [`NAMES`], as a power of two. Each bucket after it
+/// is twice as big as the one before.
+const FIRST_BUCKET_BITS: u32 = 6;
+
+/// Enough buckets for every `u32` to be a symbol.
+const BUCKETS: usize = (u32::BITS - FIRST_BUCKET_BITS + 1) as usize;
+
+/// The name of each symbol, in buckets that are allocated as they fill up so that the names
+/// already in them never move and can be read without taking [`INTERNER`].
+static NAMES: [OnceLock<Box<[OnceLock<&'static str>]>>; BUCKETS] =
+    [const { OnceLock::new() }; BUCKETS];
+
+/// Where a symbol's name is kept in [`NAMES`], as the bucket and the index in it.
+fn slot(symbol: Symbol) -> (usize, usize) {
+    let n = u64::from(symbol.0) + (1 << FIRST_BUCKET_BITS);
+    let bits = u64::BITS - 1 - n.leading_zeros();
+
+    (
+        (bits - FIRST_BUCKET_BITS) as usize,
+        (n - (1 << bits)) as usize,
+    )
+}
+
+/// Looks up names that have been interned, so that interning one again gives the same symbol.
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<&'static str, Symbol>,
+}
+
+static INTERNER: LazyLock<Mutex<Interner>> = LazyLock::new(Mutex::default);
+
+impl Symbol {
+    pub fn intern(name: &str) -> Self {
+        let mut interner = INTERNER.lock();
+
+        if let Some(&symbol) = interner.ids.get(name) {
+            return symbol;
+        }
+
+        let symbol = Self(interner.ids.len() as u32);
+        let name: &'static str = Box::leak(name.into());
+
+        let (bucket, idx) = slot(symbol);
+        NAMES[bucket].get_or_init(|| {
+            (0..1usize << (bucket as u32 + FIRST_BUCKET_BITS))
+                .map(|_| OnceLock::new())
+                .collect()
+        })[idx]
+            .get_or_init(|| name);
+        interner.ids.insert(name, symbol);
+
+        symbol
+    }
+
+    pub fn as_str(self) -> &'static str {
+        let (bucket, idx) = slot(self);
+
+        // A symbol only exists once its name has been stored.
+        NAMES[bucket]
+            .get()
+            .and_then(|names| names[idx].get())
+            .expect("symbols are made by interning")
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Self::intern(name)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_name_gives_same_symbol() {
+        assert_eq!(Symbol::intern("abc"), Symbol::intern("abc"));
+        assert_ne!(Symbol::intern("abc"), Symbol::intern("abd"));
+    }
+
+    #[test]
+    fn symbols_map_to_slots_bucket_by_bucket() {
+        assert_eq!(slot(Symbol(0)), (0, 0));
+        assert_eq!(slot(Symbol(63)), (0, 63));
+        assert_eq!(slot(Symbol(64)), (1, 0));
+        assert_eq!(slot(Symbol(191)), (1, 127));
+        assert_eq!(slot(Symbol(192)), (2, 0));
+        assert_eq!(slot(Symbol(u32::MAX)).0, BUCKETS - 1);
+    }
+
+    #[test]
+    fn names_can_be_read_while_others_are_interned() {
+        let names: Vec<_> = (0..1000)
+            .map(|idx| Symbol::intern(&format!("name_{}", idx)))
+            .collect();
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for idx in 0..1000 {
+                    Symbol::intern(&format!("other_{}", idx));
+                }
+            });
+
+            for (idx, symbol) in names.iter().enumerate() {
+                assert_eq!(symbol.as_str(), format!("name_{}", idx));
+            }
+        });
+    }
+
+    #[test]
+    fn symbol_remembers_name() {
+        assert_eq!(Symbol::intern("some_name").as_str(), "some_name");
+        assert_eq!(format!("{:?}", Symbol::intern("x")), r#""x""#);
+    }
+}
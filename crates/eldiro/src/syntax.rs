@@ -0,0 +1,657 @@
+//! A lossless concrete syntax tree, which keeps every byte of the source including whitespace
+//! and comments.
+//!
+//! The tree is split in two, like rowan's: immutable *green* nodes only know their kind, length
+//! and children, so they can be shared, while *red* [`SyntaxNode`]s are built on demand on top of
+//! them and know their parent and absolute position. The typed wrappers in [`ast`] give each kind
+//! of node named accessors.
+//!
+//! Evaluation doesn't use this tree; it is for tools such as the formatter and editors, which
+//! need to see comments and keep working on code with syntax errors.
+
+pub mod ast;
+mod parser;
+
+use crate::compat::prelude::*;
+use crate::error::ParseError;
+use crate::lexer::TokenKind;
+use alloc::sync::Arc;
+use core::fmt;
+use core::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    // Tokens.
+    Ident,
+    Number,
+    Str,
+    Plus,
+    Minus,
+    Star,
+    StarStar,
+    Slash,
+    Percent,
+    EqEq,
+    BangEq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Amp,
+    Pipe,
+    PipeGt,
+    Caret,
+    Shl,
+    Shr,
+    Tilde,
+    QuestionQuestion,
+    QuestionLBracket,
+    Eq,
+    PlusEq,
+    MinusEq,
+    StarEq,
+    SlashEq,
+    PercentEq,
+    FatArrow,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Comma,
+    Colon,
+    Ellipsis,
+    DotDot,
+    DotDotEq,
+    Dot,
+    Whitespace,
+    Comment,
+    Shebang,
+    Unknown,
+
+    // Nodes.
+    Root,
+    BindingDef,
+    FuncDef,
+    ParamList,
+    Param,
+    TypeRef,
+    EnumDef,
+    /// A variant of an enum along with its fields.
+    Variant,
+    Import,
+    ThrowStmt,
+    /// `name += val` and the other compound assignments.
+    AssignStmt,
+    Name,
+    NameRef,
+    Literal,
+    BinaryExpr,
+    PrefixExpr,
+    CallExpr,
+    ArgList,
+    /// `name: val` in the arguments of a call.
+    NamedArg,
+    ListExpr,
+    IndexExpr,
+    /// `receiver.method(args)`.
+    MethodCallExpr,
+    /// `base.name`.
+    FieldExpr,
+    RecordExpr,
+    /// `name: val` in a record.
+    RecordField,
+    ParenExpr,
+    TupleExpr,
+    TryExpr,
+    ForExpr,
+    MatchExpr,
+    MatchArm,
+    Pattern,
+    Block,
+    /// Tokens that the parser couldn't make sense of.
+    Error,
+}
+
+impl SyntaxKind {
+    pub fn is_trivia(self) -> bool {
+        matches!(self, Self::Whitespace | Self::Comment | Self::Shebang)
+    }
+}
+
+impl From<&TokenKind> for SyntaxKind {
+    fn from(kind: &TokenKind) -> Self {
+        match kind {
+            TokenKind::Ident(_) => Self::Ident,
+            TokenKind::Number(_) => Self::Number,
+            TokenKind::Str(_) => Self::Str,
+            TokenKind::Plus => Self::Plus,
+            TokenKind::Minus => Self::Minus,
+            TokenKind::Star => Self::Star,
+            TokenKind::StarStar => Self::StarStar,
+            TokenKind::Slash => Self::Slash,
+            TokenKind::Percent => Self::Percent,
+            TokenKind::EqEq => Self::EqEq,
+            TokenKind::BangEq => Self::BangEq,
+            TokenKind::Lt => Self::Lt,
+            TokenKind::Le => Self::Le,
+            TokenKind::Gt => Self::Gt,
+            TokenKind::Ge => Self::Ge,
+            TokenKind::Amp => Self::Amp,
+            TokenKind::Pipe => Self::Pipe,
+            TokenKind::PipeGt => Self::PipeGt,
+            TokenKind::Caret => Self::Caret,
+            TokenKind::Shl => Self::Shl,
+            TokenKind::Shr => Self::Shr,
+            TokenKind::Tilde => Self::Tilde,
+            TokenKind::QuestionQuestion => Self::QuestionQuestion,
+            TokenKind::QuestionLBracket => Self::QuestionLBracket,
+            TokenKind::Eq => Self::Eq,
+            TokenKind::PlusEq => Self::PlusEq,
+            TokenKind::MinusEq => Self::MinusEq,
+            TokenKind::StarEq => Self::StarEq,
+            TokenKind::SlashEq => Self::SlashEq,
+            TokenKind::PercentEq => Self::PercentEq,
+            TokenKind::FatArrow => Self::FatArrow,
+            TokenKind::LParen => Self::LParen,
+            TokenKind::RParen => Self::RParen,
+            TokenKind::LBrace => Self::LBrace,
+            TokenKind::RBrace => Self::RBrace,
+            TokenKind::LBracket => Self::LBracket,
+            TokenKind::RBracket => Self::RBracket,
+            TokenKind::Comma => Self::Comma,
+            TokenKind::Colon => Self::Colon,
+            TokenKind::Ellipsis => Self::Ellipsis,
+            TokenKind::DotDot => Self::DotDot,
+            TokenKind::DotDotEq => Self::DotDotEq,
+            TokenKind::Dot => Self::Dot,
+            TokenKind::Whitespace => Self::Whitespace,
+            TokenKind::Comment => Self::Comment,
+            TokenKind::Shebang => Self::Shebang,
+            TokenKind::Unknown => Self::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    len: usize,
+    children: Vec<GreenElement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GreenToken {
+    kind: SyntaxKind,
+    text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(Arc<GreenToken>),
+}
+
+impl GreenNode {
+    pub fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Self {
+        let len = children.iter().map(GreenElement::text_len).sum();
+        Self {
+            kind,
+            len,
+            children,
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text_len(&self) -> usize {
+        self.len
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+}
+
+impl GreenToken {
+    pub fn new(kind: SyntaxKind, text: impl Into<String>) -> Self {
+        Self {
+            kind,
+            text: text.into(),
+        }
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl GreenElement {
+    pub fn text_len(&self) -> usize {
+        match self {
+            Self::Node(node) => node.len,
+            Self::Token(token) => token.text.len(),
+        }
+    }
+}
+
+/// A node in the syntax tree that knows where it is.
+#[derive(Clone)]
+pub struct SyntaxNode(Arc<NodeData>);
+
+struct NodeData {
+    green: Arc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    offset: usize,
+}
+
+#[derive(Clone)]
+pub struct SyntaxToken {
+    green: Arc<GreenToken>,
+    parent: SyntaxNode,
+    offset: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Arc<GreenNode>) -> Self {
+        Self(Arc::new(NodeData {
+            green,
+            parent: None,
+            offset: 0,
+        }))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind
+    }
+
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn text_range(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.len
+    }
+
+    /// The exact source text this node covers.
+    pub fn text(&self) -> String {
+        self.tokens().iter().map(SyntaxToken::text).collect()
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode> {
+        self.0.parent.clone()
+    }
+
+    pub fn children_with_tokens(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+
+        self.0
+            .green
+            .children
+            .iter()
+            .map(|child| {
+                let element = match child {
+                    GreenElement::Node(green) => SyntaxElement::Node(Self(Arc::new(NodeData {
+                        green: Arc::clone(green),
+                        parent: Some(self.clone()),
+                        offset,
+                    }))),
+                    GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken {
+                        green: Arc::clone(green),
+                        parent: self.clone(),
+                        offset,
+                    }),
+                };
+
+                offset += child.text_len();
+                element
+            })
+            .collect()
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = SyntaxNode> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+    }
+
+    /// Every token under this node in source order, including trivia.
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = Vec::new();
+
+        for element in self.children_with_tokens() {
+            match element {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+
+        tokens
+    }
+
+    /// Renders the tree with one element per line, such as `Literal@4..5`, for debugging.
+    pub fn debug_tree(&self) -> String {
+        let mut out = String::new();
+        self.write_debug_tree(&mut out, 0);
+        out
+    }
+
+    fn write_debug_tree(&self, out: &mut String, depth: usize) {
+        *out += &format!("{}{:?}\n", "  ".repeat(depth), self);
+
+        for element in self.children_with_tokens() {
+            match element {
+                SyntaxElement::Node(node) => node.write_debug_tree(out, depth + 1),
+                SyntaxElement::Token(token) => {
+                    *out += &format!("{}{:?}\n", "  ".repeat(depth + 1), token);
+                }
+            }
+        }
+    }
+}
+
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0.green, &other.0.green) && self.0.offset == other.0.offset
+    }
+}
+
+impl fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}@{:?}", self.kind(), self.text_range())
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn text_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text.len()
+    }
+
+    pub fn parent(&self) -> SyntaxNode {
+        self.parent.clone()
+    }
+}
+
+impl PartialEq for SyntaxToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.green, &other.green) && self.offset == other.offset
+    }
+}
+
+impl fmt::Debug for SyntaxToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?}@{:?} {:?}",
+            self.kind(),
+            self.text_range(),
+            self.text()
+        )
+    }
+}
+
+/// Builds green trees from a flat sequence of tokens and node boundaries.
+#[derive(Default)]
+struct GreenBuilder {
+    parents: Vec<(SyntaxKind, usize)>,
+    children: Vec<GreenElement>,
+}
+
+impl GreenBuilder {
+    fn start_node(&mut self, kind: SyntaxKind) {
+        self.parents.push((kind, self.children.len()));
+    }
+
+    /// Marks a point that a node can later be started at, which is how a binary expression
+    /// wraps its left-hand side after the operator has been seen.
+    fn checkpoint(&self) -> usize {
+        self.children.len()
+    }
+
+    fn start_node_at(&mut self, checkpoint: usize, kind: SyntaxKind) {
+        self.parents.push((kind, checkpoint));
+    }
+
+    fn token(&mut self, kind: SyntaxKind, text: &str) {
+        self.children
+            .push(GreenElement::Token(Arc::new(GreenToken::new(kind, text))));
+    }
+
+    fn finish_node(&mut self) {
+        let (kind, first_child) = self.parents.pop().unwrap();
+        let children = self.children.split_off(first_child);
+
+        self.children
+            .push(GreenElement::Node(Arc::new(GreenNode::new(kind, children))));
+    }
+
+    fn finish(mut self) -> Arc<GreenNode> {
+        match self.children.pop() {
+            Some(GreenElement::Node(root)) if self.children.is_empty() => root,
+            _ => panic!("syntax tree should have exactly one root node"),
+        }
+    }
+}
+
+/// The result of parsing source into a syntax tree, which always succeeds; problems are recorded
+/// as [`SyntaxKind::Error`] nodes and in [`Parse::errors`].
+#[derive(Debug, Clone)]
+pub struct Parse {
+    green: Arc<GreenNode>,
+    errors: Vec<ParseError>,
+}
+
+impl Parse {
+    pub fn syntax(&self) -> SyntaxNode {
+        SyntaxNode::new_root(Arc::clone(&self.green))
+    }
+
+    pub fn root(&self) -> ast::Root {
+        ast::AstNode::cast(self.syntax()).unwrap()
+    }
+
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+}
+
+pub fn parse(s: &str) -> Parse {
+    parser::parse(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::AstNode;
+
+    #[test]
+    fn tree_is_lossless() {
+        for source in [
+            "",
+            "let a = 1 + 2 // sum\n",
+            "#!/usr/bin/env eldiro\nfn add x y => {\n    x + y\n}\nadd 1 2",
+            "let = @@ } \"unterminated",
+        ] {
+            assert_eq!(parse(source).syntax().text(), source);
+        }
+    }
+
+    #[test]
+    fn debug_tree_of_binding_def() {
+        assert_eq!(
+            parse("let a = 1 // one").syntax().debug_tree(),
+            r#"Root@0..16
+  BindingDef@0..9
+    Ident@0..3 "let"
+    Whitespace@3..4 " "
+    Name@4..5
+      Ident@4..5 "a"
+    Whitespace@5..6 " "
+    Eq@6..7 "="
+    Whitespace@7..8 " "
+    Literal@8..9
+      Number@8..9 "1"
+  Whitespace@9..10 " "
+  Comment@10..16 "// one"
+"#,
+        );
+    }
+
+    #[test]
+    fn red_nodes_know_their_parents() {
+        let root = parse("{ 1 }").syntax();
+        let block = root.children().next().unwrap();
+        let literal = block.children().next().unwrap();
+
+        assert_eq!(literal.kind(), SyntaxKind::Literal);
+        assert_eq!(literal.text_range(), 2..3);
+        assert_eq!(literal.parent(), Some(block.clone()));
+        assert_eq!(ast::Block::cast(block).unwrap().stmts().count(), 1);
+    }
+
+    #[test]
+    fn tell_records_from_blocks() {
+        let kinds = |s| {
+            parse(s)
+                .syntax()
+                .children()
+                .map(|node| node.kind())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(kinds("{x: 1}"), [SyntaxKind::RecordExpr]);
+        assert_eq!(kinds("{ x }"), [SyntaxKind::Block]);
+        assert_eq!(kinds("p.x"), [SyntaxKind::FieldExpr]);
+        assert_eq!(kinds("p.x()"), [SyntaxKind::MethodCallExpr]);
+    }
+
+    #[test]
+    fn recover_from_errors() {
+        let parse = parse("let a = 1\n@\nlet b = 2");
+
+        assert_eq!(parse.root().stmts().count(), 2);
+        assert_eq!(
+            parse.errors(),
+            [ParseError {
+                message: "expected statement".to_string(),
+                span: 10..11,
+            }],
+        );
+    }
+
+    #[test]
+    fn require_line_breaks_between_stmts() {
+        let parse = parse("let a = 1 a\n{ 1 2 }");
+
+        assert_eq!(parse.root().stmts().count(), 3);
+        assert_eq!(
+            parse.errors(),
+            [
+                ParseError {
+                    message: "expected a line break before this statement".to_string(),
+                    span: 10..11,
+                },
+                ParseError {
+                    message: "expected a line break before this statement".to_string(),
+                    span: 16..17,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn report_keywords_used_as_names() {
+        let parse = parse("fn double x > x * 2\nlet b @ 2");
+
+        assert_eq!(
+            parse.errors(),
+            [
+                ParseError {
+                    message: "expected statement".to_string(),
+                    span: 26..27,
+                },
+                ParseError {
+                    message: "`fn` is a reserved keyword".to_string(),
+                    span: 0..2,
+                },
+                ParseError {
+                    message: "`let` is a reserved keyword".to_string(),
+                    span: 20..23,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn require_single_commas_between_items() {
+        for source in [
+            "[1 2]",
+            "[, 1]",
+            "[1,, 2]",
+            "[1,]",
+            "f(a, b,)",
+            "(1 x)",
+            "{ a: 1 b: 2 }",
+        ] {
+            assert!(!parse(source).errors().is_empty(), "{}", source);
+        }
+
+        for source in [
+            "[1, 2]",
+            "f(a, b: 1)",
+            "match x { 1 => a, _ => b, }",
+            "match x { 1 => a\n_ => b }",
+        ] {
+            assert_eq!(parse(source).errors(), [], "{}", source);
+        }
+    }
+
+    #[test]
+    fn recover_from_broken_function_heads() {
+        for source in ["fn f...=>", "fn f x={)=>"] {
+            let parse = parse(source);
+
+            assert_eq!(parse.syntax().text(), source);
+            assert!(!parse.errors().is_empty());
+        }
+    }
+
+    #[test]
+    fn stop_nesting_at_depth_limit() {
+        let source = format!("{}1{}", "(".repeat(100), ")".repeat(100));
+        let parse = parse(&source);
+
+        assert_eq!(parse.syntax().text(), source);
+        assert_eq!(
+            parse.errors()[0],
+            ParseError {
+                message: "nested more than 64 levels deep".to_string(),
+                span: 64..65,
+            },
+        );
+    }
+}
@@ -0,0 +1,824 @@
+//! Typed views over [`SyntaxNode`]s. Accessors return `None` when the source has a syntax error
+//! where the piece should be.
+
+use super::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken};
+use crate::compat::prelude::*;
+
+pub trait AstNode: Sized {
+    fn cast(node: SyntaxNode) -> Option<Self>;
+    fn syntax(&self) -> &SyntaxNode;
+}
+
+macro_rules! ast_node {
+    ($name:ident) => {
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct $name(SyntaxNode);
+
+        impl AstNode for $name {
+            fn cast(node: SyntaxNode) -> Option<Self> {
+                (node.kind() == SyntaxKind::$name).then(|| Self(node))
+            }
+
+            fn syntax(&self) -> &SyntaxNode {
+                &self.0
+            }
+        }
+    };
+}
+
+ast_node!(Root);
+ast_node!(BindingDef);
+ast_node!(FuncDef);
+ast_node!(EnumDef);
+ast_node!(Variant);
+ast_node!(Import);
+ast_node!(ThrowStmt);
+ast_node!(AssignStmt);
+ast_node!(Param);
+ast_node!(TypeRef);
+ast_node!(Name);
+ast_node!(NameRef);
+ast_node!(Literal);
+ast_node!(BinaryExpr);
+ast_node!(PrefixExpr);
+ast_node!(CallExpr);
+ast_node!(NamedArg);
+ast_node!(ListExpr);
+ast_node!(IndexExpr);
+ast_node!(MethodCallExpr);
+ast_node!(FieldExpr);
+ast_node!(RecordExpr);
+ast_node!(RecordField);
+ast_node!(ParenExpr);
+ast_node!(TupleExpr);
+ast_node!(TryExpr);
+ast_node!(ForExpr);
+ast_node!(MatchExpr);
+ast_node!(MatchArm);
+ast_node!(Pattern);
+ast_node!(Block);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    BindingDef(BindingDef),
+    FuncDef(FuncDef),
+    EnumDef(EnumDef),
+    Import(Import),
+    Throw(ThrowStmt),
+    Assign(AssignStmt),
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Binary(BinaryExpr),
+    Prefix(PrefixExpr),
+    Literal(Literal),
+    NameRef(NameRef),
+    Call(CallExpr),
+    List(ListExpr),
+    Index(IndexExpr),
+    MethodCall(MethodCallExpr),
+    Field(FieldExpr),
+    Record(RecordExpr),
+    Paren(ParenExpr),
+    Tuple(TupleExpr),
+    Try(TryExpr),
+    For(ForExpr),
+    Match(MatchExpr),
+    Block(Block),
+}
+
+impl AstNode for Stmt {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        match node.kind() {
+            SyntaxKind::BindingDef => Some(Self::BindingDef(BindingDef(node))),
+            SyntaxKind::FuncDef => Some(Self::FuncDef(FuncDef(node))),
+            SyntaxKind::EnumDef => Some(Self::EnumDef(EnumDef(node))),
+            SyntaxKind::Import => Some(Self::Import(Import(node))),
+            SyntaxKind::ThrowStmt => Some(Self::Throw(ThrowStmt(node))),
+            SyntaxKind::AssignStmt => Some(Self::Assign(AssignStmt(node))),
+            _ => Expr::cast(node).map(Self::Expr),
+        }
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Self::BindingDef(node) => node.syntax(),
+            Self::FuncDef(node) => node.syntax(),
+            Self::EnumDef(node) => node.syntax(),
+            Self::Import(node) => node.syntax(),
+            Self::Throw(node) => node.syntax(),
+            Self::Assign(node) => node.syntax(),
+            Self::Expr(node) => node.syntax(),
+        }
+    }
+}
+
+impl AstNode for Expr {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        match node.kind() {
+            SyntaxKind::BinaryExpr => Some(Self::Binary(BinaryExpr(node))),
+            SyntaxKind::PrefixExpr => Some(Self::Prefix(PrefixExpr(node))),
+            SyntaxKind::Literal => Some(Self::Literal(Literal(node))),
+            SyntaxKind::NameRef => Some(Self::NameRef(NameRef(node))),
+            SyntaxKind::CallExpr => Some(Self::Call(CallExpr(node))),
+            SyntaxKind::ListExpr => Some(Self::List(ListExpr(node))),
+            SyntaxKind::IndexExpr => Some(Self::Index(IndexExpr(node))),
+            SyntaxKind::MethodCallExpr => Some(Self::MethodCall(MethodCallExpr(node))),
+            SyntaxKind::FieldExpr => Some(Self::Field(FieldExpr(node))),
+            SyntaxKind::RecordExpr => Some(Self::Record(RecordExpr(node))),
+            SyntaxKind::ParenExpr => Some(Self::Paren(ParenExpr(node))),
+            SyntaxKind::TupleExpr => Some(Self::Tuple(TupleExpr(node))),
+            SyntaxKind::TryExpr => Some(Self::Try(TryExpr(node))),
+            SyntaxKind::ForExpr => Some(Self::For(ForExpr(node))),
+            SyntaxKind::MatchExpr => Some(Self::Match(MatchExpr(node))),
+            SyntaxKind::Block => Some(Self::Block(Block(node))),
+            _ => None,
+        }
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Self::Binary(node) => node.syntax(),
+            Self::Prefix(node) => node.syntax(),
+            Self::Literal(node) => node.syntax(),
+            Self::NameRef(node) => node.syntax(),
+            Self::Call(node) => node.syntax(),
+            Self::List(node) => node.syntax(),
+            Self::Index(node) => node.syntax(),
+            Self::MethodCall(node) => node.syntax(),
+            Self::Field(node) => node.syntax(),
+            Self::Record(node) => node.syntax(),
+            Self::Paren(node) => node.syntax(),
+            Self::Tuple(node) => node.syntax(),
+            Self::Try(node) => node.syntax(),
+            Self::For(node) => node.syntax(),
+            Self::Match(node) => node.syntax(),
+            Self::Block(node) => node.syntax(),
+        }
+    }
+}
+
+fn child<N: AstNode>(node: &SyntaxNode) -> Option<N> {
+    node.children().find_map(N::cast)
+}
+
+fn first_token(node: &SyntaxNode, accept: impl Fn(SyntaxKind) -> bool) -> Option<SyntaxToken> {
+    node.children_with_tokens()
+        .into_iter()
+        .find_map(|element| match element {
+            SyntaxElement::Token(token) if accept(token.kind()) => Some(token),
+            _ => None,
+        })
+}
+
+impl Root {
+    pub fn stmts(&self) -> impl Iterator<Item = Stmt> {
+        self.0.children().filter_map(Stmt::cast)
+    }
+}
+
+impl BindingDef {
+    /// Whether this is a `const` rather than a `let`.
+    pub fn is_const(&self) -> bool {
+        first_token(&self.0, |kind| !kind.is_trivia()).is_some_and(|token| token.text() == "const")
+    }
+
+    pub fn name(&self) -> Option<Name> {
+        child(&self.0)
+    }
+
+    pub fn ty(&self) -> Option<TypeRef> {
+        child(&self.0)
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl FuncDef {
+    pub fn name(&self) -> Option<Name> {
+        child(&self.0)
+    }
+
+    pub fn params(&self) -> Vec<Param> {
+        self.0
+            .children()
+            .find(|node| node.kind() == SyntaxKind::ParamList)
+            .map(|params| params.children().filter_map(Param::cast).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn body(&self) -> Option<Stmt> {
+        self.0
+            .children()
+            .filter(|node| !matches!(node.kind(), SyntaxKind::Name | SyntaxKind::ParamList))
+            .find_map(Stmt::cast)
+    }
+}
+
+impl Import {
+    /// The path's string literal token, including its quotes.
+    pub fn path(&self) -> Option<SyntaxToken> {
+        first_token(&self.0, |kind| kind == SyntaxKind::Str)
+    }
+}
+
+impl ThrowStmt {
+    pub fn value(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl AssignStmt {
+    pub fn target(&self) -> Option<NameRef> {
+        child(&self.0)
+    }
+
+    /// The compound assignment token, such as `+=`.
+    pub fn op(&self) -> Option<SyntaxToken> {
+        first_token(&self.0, |kind| !kind.is_trivia())
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        self.0.children().filter_map(Expr::cast).nth(1)
+    }
+}
+
+impl Param {
+    pub fn name(&self) -> Option<Name> {
+        child(&self.0)
+    }
+
+    pub fn ty(&self) -> Option<TypeRef> {
+        child(&self.0)
+    }
+
+    pub fn default(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    /// Whether this is a `...rest` parameter.
+    pub fn is_rest(&self) -> bool {
+        first_token(&self.0, |kind| kind == SyntaxKind::Ellipsis).is_some()
+    }
+}
+
+impl TypeRef {
+    pub fn text(&self) -> String {
+        self.0.text()
+    }
+}
+
+impl Name {
+    pub fn text(&self) -> String {
+        self.0.text()
+    }
+}
+
+impl NameRef {
+    pub fn name(&self) -> String {
+        self.0.text()
+    }
+}
+
+impl Literal {
+    pub fn token(&self) -> SyntaxToken {
+        first_token(&self.0, |kind| !kind.is_trivia()).unwrap()
+    }
+}
+
+impl BinaryExpr {
+    pub fn lhs(&self) -> Option<Expr> {
+        self.0.children().find_map(Expr::cast)
+    }
+
+    pub fn rhs(&self) -> Option<Expr> {
+        self.0.children().filter_map(Expr::cast).nth(1)
+    }
+
+    pub fn op(&self) -> Option<SyntaxToken> {
+        first_token(&self.0, |kind| !kind.is_trivia())
+    }
+}
+
+impl PrefixExpr {
+    pub fn op(&self) -> Option<SyntaxToken> {
+        first_token(&self.0, |kind| !kind.is_trivia())
+    }
+
+    pub fn operand(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl CallExpr {
+    pub fn callee(&self) -> Option<NameRef> {
+        child(&self.0)
+    }
+
+    pub fn args(&self) -> Vec<Expr> {
+        self.0
+            .children()
+            .find(|node| node.kind() == SyntaxKind::ArgList)
+            .map(|args| args.children().filter_map(Expr::cast).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn named_args(&self) -> Vec<NamedArg> {
+        self.0
+            .children()
+            .find(|node| node.kind() == SyntaxKind::ArgList)
+            .map(|args| args.children().filter_map(NamedArg::cast).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl NamedArg {
+    pub fn name(&self) -> Option<Name> {
+        child(&self.0)
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl ListExpr {
+    pub fn items(&self) -> impl Iterator<Item = Expr> {
+        self.0.children().filter_map(Expr::cast)
+    }
+}
+
+impl IndexExpr {
+    pub fn base(&self) -> Option<Expr> {
+        self.0.children().find_map(Expr::cast)
+    }
+
+    pub fn index(&self) -> Option<Expr> {
+        self.0.children().filter_map(Expr::cast).nth(1)
+    }
+
+    /// Whether this is a nil-safe `?[` index.
+    pub fn is_safe(&self) -> bool {
+        first_token(&self.0, |kind| kind == SyntaxKind::QuestionLBracket).is_some()
+    }
+}
+
+impl MethodCallExpr {
+    pub fn receiver(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    /// The name after the `.`, which comes after the receiver even when that is a name too.
+    pub fn method(&self) -> Option<NameRef> {
+        self.0.children().skip(1).find_map(NameRef::cast)
+    }
+
+    pub fn args(&self) -> Vec<Expr> {
+        self.0
+            .children()
+            .find(|node| node.kind() == SyntaxKind::ArgList)
+            .map(|args| args.children().filter_map(Expr::cast).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl FieldExpr {
+    pub fn base(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    /// The name after the `.`, which comes after the base even when that is a name too.
+    pub fn field(&self) -> Option<NameRef> {
+        self.0.children().skip(1).find_map(NameRef::cast)
+    }
+}
+
+impl RecordExpr {
+    pub fn fields(&self) -> impl Iterator<Item = RecordField> {
+        self.0.children().filter_map(RecordField::cast)
+    }
+}
+
+impl RecordField {
+    pub fn name(&self) -> Option<Name> {
+        child(&self.0)
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl ParenExpr {
+    pub fn inner(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+/// `()` is a tuple with no items, which is the unit value.
+impl TupleExpr {
+    pub fn items(&self) -> impl Iterator<Item = Expr> {
+        self.0.children().filter_map(Expr::cast)
+    }
+}
+
+impl TryExpr {
+    pub fn body(&self) -> Option<Block> {
+        child(&self.0)
+    }
+
+    /// The name that the handler sees the error as.
+    pub fn name(&self) -> Option<Name> {
+        child(&self.0)
+    }
+
+    pub fn handler(&self) -> Option<Block> {
+        self.0.children().filter_map(Block::cast).nth(1)
+    }
+}
+
+impl EnumDef {
+    pub fn name(&self) -> Option<Name> {
+        child(&self.0)
+    }
+
+    pub fn variants(&self) -> impl Iterator<Item = Variant> {
+        self.0.children().filter_map(Variant::cast)
+    }
+}
+
+impl Variant {
+    pub fn name(&self) -> Option<Name> {
+        child(&self.0)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = Name> {
+        self.0.children().filter_map(Name::cast).skip(1)
+    }
+}
+
+impl ForExpr {
+    /// The name that the body sees each item as.
+    pub fn name(&self) -> Option<Name> {
+        child(&self.0)
+    }
+
+    pub fn iterable(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn body(&self) -> Option<Block> {
+        self.0.children().filter_map(Block::cast).last()
+    }
+}
+
+impl MatchExpr {
+    /// The value being matched.
+    pub fn val(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+
+    pub fn arms(&self) -> impl Iterator<Item = MatchArm> {
+        self.0.children().filter_map(MatchArm::cast)
+    }
+}
+
+impl MatchArm {
+    pub fn pattern(&self) -> Option<Pattern> {
+        child(&self.0)
+    }
+
+    pub fn body(&self) -> Option<Expr> {
+        child(&self.0)
+    }
+}
+
+impl Pattern {
+    /// The patterns for the fields of a variant pattern, which are empty for anything else.
+    pub fn fields(&self) -> impl Iterator<Item = Pattern> {
+        self.0.children().filter_map(Pattern::cast)
+    }
+}
+
+impl Block {
+    pub fn stmts(&self) -> impl Iterator<Item = Stmt> {
+        self.0.children().filter_map(Stmt::cast)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn access_func_def_parts() {
+        let root = super::super::parse("fn add x: number, y => x + y").root();
+        let func_def = match root.stmts().next() {
+            Some(Stmt::FuncDef(func_def)) => func_def,
+            stmt => panic!("expected function definition, got {:?}", stmt),
+        };
+
+        assert_eq!(func_def.name().unwrap().text(), "add");
+        let params = func_def.params();
+        assert_eq!(
+            params
+                .iter()
+                .map(|param| param.name().unwrap().text())
+                .collect::<Vec<_>>(),
+            ["x", "y"],
+        );
+        assert_eq!(params[0].ty().unwrap().text(), "number");
+        assert!(params[1].ty().is_none());
+        assert!(params[1].default().is_none());
+
+        let body = match func_def.body() {
+            Some(Stmt::Expr(Expr::Binary(body))) => body,
+            body => panic!("expected binary expression, got {:?}", body),
+        };
+        assert_eq!(body.op().unwrap().text(), "+");
+        assert_eq!(body.rhs().unwrap().syntax().text(), "y");
+    }
+
+    #[test]
+    fn access_method_call() {
+        let root = super::super::parse("xs.take(2).len()").root();
+        let outer = match root.stmts().next() {
+            Some(Stmt::Expr(Expr::MethodCall(outer))) => outer,
+            stmt => panic!("expected method call, got {:?}", stmt),
+        };
+        let inner = match outer.receiver() {
+            Some(Expr::MethodCall(inner)) => inner,
+            receiver => panic!("expected method call, got {:?}", receiver),
+        };
+
+        assert_eq!(outer.method().unwrap().syntax().text(), "len");
+        assert!(outer.args().is_empty());
+        assert_eq!(inner.receiver().unwrap().syntax().text(), "xs");
+        assert_eq!(inner.method().unwrap().syntax().text(), "take");
+        assert_eq!(inner.args().len(), 1);
+    }
+
+    #[test]
+    fn access_record_fields() {
+        let root = super::super::parse("{x: 1, y: [2]}.y").root();
+        let field = match root.stmts().next() {
+            Some(Stmt::Expr(Expr::Field(field))) => field,
+            stmt => panic!("expected field access, got {:?}", stmt),
+        };
+        let record = match field.base() {
+            Some(Expr::Record(record)) => record,
+            base => panic!("expected record, got {:?}", base),
+        };
+        let fields: Vec<_> = record
+            .fields()
+            .map(|field| {
+                (
+                    field.name().unwrap().syntax().text().to_string(),
+                    field.value().unwrap().syntax().text().to_string(),
+                )
+            })
+            .collect();
+
+        assert_eq!(field.field().unwrap().syntax().text(), "y");
+        assert_eq!(
+            fields,
+            [
+                ("x".to_string(), "1".to_string()),
+                ("y".to_string(), "[2]".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn access_enum_and_match_parts() {
+        let root =
+            super::super::parse("enum E { A(x, y), B }\nmatch e { A(x, _) => x\nB => 0 }").root();
+        let mut stmts = root.stmts();
+        let enum_def = match stmts.next() {
+            Some(Stmt::EnumDef(enum_def)) => enum_def,
+            stmt => panic!("expected enum definition, got {:?}", stmt),
+        };
+        let m = match stmts.next() {
+            Some(Stmt::Expr(Expr::Match(m))) => m,
+            stmt => panic!("expected match, got {:?}", stmt),
+        };
+
+        assert_eq!(enum_def.name().unwrap().text(), "E");
+        let variants: Vec<_> = enum_def
+            .variants()
+            .map(|variant| {
+                (
+                    variant.name().unwrap().text().to_string(),
+                    variant
+                        .fields()
+                        .map(|field| field.text().to_string())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            variants,
+            [
+                ("A".to_string(), vec!["x".to_string(), "y".to_string()]),
+                ("B".to_string(), Vec::new()),
+            ],
+        );
+
+        assert_eq!(m.val().unwrap().syntax().text(), "e");
+        let arms: Vec<_> = m.arms().collect();
+        assert_eq!(arms.len(), 2);
+        assert_eq!(arms[0].pattern().unwrap().syntax().text(), "A(x, _)");
+        assert_eq!(arms[0].pattern().unwrap().fields().count(), 2);
+        assert_eq!(arms[1].body().unwrap().syntax().text(), "0");
+    }
+
+    #[test]
+    fn pipeline_is_binary_expr() {
+        let root = super::super::parse("xs |> f(1) == 2").root();
+        let comparison = match root.stmts().next() {
+            Some(Stmt::Expr(Expr::Binary(comparison))) => comparison,
+            stmt => panic!("expected binary expression, got {:?}", stmt),
+        };
+        let pipeline = match comparison.lhs() {
+            Some(Expr::Binary(pipeline)) => pipeline,
+            lhs => panic!("expected binary expression, got {:?}", lhs),
+        };
+
+        assert_eq!(pipeline.op().unwrap().text(), "|>");
+        assert_eq!(pipeline.rhs().unwrap().syntax().text(), "f(1)");
+    }
+
+    #[test]
+    fn access_param_default() {
+        let root = super::super::parse("fn f x = [1, (2)], y => x").root();
+        let func_def = match root.stmts().next() {
+            Some(Stmt::FuncDef(func_def)) => func_def,
+            stmt => panic!("expected function definition, got {:?}", stmt),
+        };
+
+        let params = func_def.params();
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[0].default().unwrap().syntax().text(), "[1, (2)]");
+        assert!(params[1].default().is_none());
+    }
+
+    #[test]
+    fn access_rest_param() {
+        let root = super::super::parse("fn f x ...xs => xs").root();
+        let func_def = match root.stmts().next() {
+            Some(Stmt::FuncDef(func_def)) => func_def,
+            stmt => panic!("expected function definition, got {:?}", stmt),
+        };
+
+        let params = func_def.params();
+        assert!(!params[0].is_rest());
+        assert!(params[1].is_rest());
+        assert_eq!(params[1].name().unwrap().text(), "xs");
+    }
+
+    #[test]
+    fn access_named_args() {
+        let root = super::super::parse("area(3, height: 4)").root();
+        let call = match root.stmts().next() {
+            Some(Stmt::Expr(Expr::Call(call))) => call,
+            stmt => panic!("expected call, got {:?}", stmt),
+        };
+
+        assert_eq!(call.args().len(), 1);
+        let named = call.named_args();
+        assert_eq!(named.len(), 1);
+        assert_eq!(named[0].name().unwrap().text(), "height");
+        assert_eq!(named[0].value().unwrap().syntax().text(), "4");
+    }
+
+    #[test]
+    fn tell_const_from_let() {
+        let root = super::super::parse("const a = 1\nlet b = 2").root();
+        let consts: Vec<_> = root
+            .stmts()
+            .map(|stmt| match stmt {
+                Stmt::BindingDef(binding_def) => binding_def.is_const(),
+                stmt => panic!("expected binding definition, got {:?}", stmt),
+            })
+            .collect();
+
+        assert_eq!(consts, [true, false]);
+    }
+
+    #[test]
+    fn access_prefix_operand() {
+        let root = super::super::parse("~x & 1").root();
+
+        let lhs = match root.stmts().next() {
+            Some(Stmt::Expr(Expr::Binary(binary))) => binary.lhs(),
+            stmt => panic!("expected binary expression, got {:?}", stmt),
+        };
+
+        match lhs {
+            Some(Expr::Prefix(prefix)) => {
+                assert_eq!(prefix.op().unwrap().text(), "~");
+                assert_eq!(prefix.operand().unwrap().syntax().text(), "x");
+            }
+            lhs => panic!("expected prefix expression, got {:?}", lhs),
+        }
+    }
+
+    #[test]
+    fn tell_parens_from_tuples() {
+        let root = super::super::parse("(1 + 2)\n(a, b)").root();
+        let mut stmts = root.stmts();
+
+        match stmts.next() {
+            Some(Stmt::Expr(Expr::Paren(paren))) => {
+                assert_eq!(paren.inner().unwrap().syntax().text(), "1 + 2");
+            }
+            stmt => panic!("expected parenthesized expression, got {:?}", stmt),
+        }
+
+        match stmts.next() {
+            Some(Stmt::Expr(Expr::Tuple(tuple))) => assert_eq!(tuple.items().count(), 2),
+            stmt => panic!("expected tuple, got {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn access_index_parts() {
+        let root = super::super::parse("xs[0]?[i]").root();
+
+        let outer = match root.stmts().next() {
+            Some(Stmt::Expr(Expr::Index(index))) => index,
+            stmt => panic!("expected index, got {:?}", stmt),
+        };
+        assert!(outer.is_safe());
+        assert_eq!(outer.index().unwrap().syntax().text(), "i");
+
+        match outer.base() {
+            Some(Expr::Index(inner)) => {
+                assert!(!inner.is_safe());
+                assert_eq!(inner.base().unwrap().syntax().text(), "xs");
+            }
+            base => panic!("expected index, got {:?}", base),
+        }
+    }
+
+    #[test]
+    fn access_try_parts() {
+        let root = super::super::parse("try { throw 1 } catch e { e }").root();
+
+        let try_expr = match root.stmts().next() {
+            Some(Stmt::Expr(Expr::Try(try_expr))) => try_expr,
+            stmt => panic!("expected try, got {:?}", stmt),
+        };
+        assert_eq!(try_expr.name().unwrap().text(), "e");
+        assert_eq!(try_expr.handler().unwrap().syntax().text(), "{ e }");
+
+        match try_expr.body().unwrap().stmts().next() {
+            Some(Stmt::Throw(throw)) => assert_eq!(throw.value().unwrap().syntax().text(), "1"),
+            stmt => panic!("expected throw, got {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn access_assign_parts() {
+        let root = super::super::parse("total *= 2 + 1").root();
+
+        let assign = match root.stmts().next() {
+            Some(Stmt::Assign(assign)) => assign,
+            stmt => panic!("expected assignment, got {:?}", stmt),
+        };
+        assert_eq!(assign.target().unwrap().name(), "total");
+        assert_eq!(assign.op().unwrap().text(), "*=");
+        assert_eq!(assign.value().unwrap().syntax().text(), "2 + 1");
+    }
+
+    #[test]
+    fn access_for_parts() {
+        let root = super::super::parse("for x in xs { x }").root();
+
+        let for_expr = match root.stmts().next() {
+            Some(Stmt::Expr(Expr::For(for_expr))) => for_expr,
+            stmt => panic!("expected for, got {:?}", stmt),
+        };
+        assert_eq!(for_expr.name().unwrap().text(), "x");
+        assert_eq!(for_expr.iterable().unwrap().syntax().text(), "xs");
+        assert_eq!(for_expr.body().unwrap().syntax().text(), "{ x }");
+    }
+
+    #[test]
+    fn access_call_args() {
+        // The space means `(2)` is a grouped argument rather than the start of `add(...)`.
+        let root = super::super::parse("add 1 (2)").root();
+
+        match root.stmts().next() {
+            Some(Stmt::Expr(Expr::Call(call))) => {
+                assert_eq!(call.callee().unwrap().name(), "add");
+                assert_eq!(call.args().len(), 2);
+            }
+            stmt => panic!("expected call, got {:?}", stmt),
+        }
+    }
+}
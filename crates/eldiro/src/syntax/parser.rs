@@ -0,0 +1,1029 @@
+//! A recursive descent parser that follows the same grammar as the one in [`crate::stmt`] and
+//! [`crate::expr`], but builds a [`GreenNode`](super::GreenNode) tree and skips over anything it
+//! doesn't understand instead of stopping.
+
+use super::{GreenBuilder, Parse, SyntaxKind};
+use crate::assign;
+use crate::compat::prelude::*;
+use crate::error::ParseError;
+use crate::expr::{Op, NEG_BINDING_POWER, PIPELINE_BINDING_POWER};
+use crate::lexer::{self, Token, TokenKind};
+use crate::utils;
+use core::mem;
+
+pub(super) fn parse(s: &str) -> Parse {
+    let mut parser = Parser {
+        source: s,
+        tokens: lexer::lex_lossless(s),
+        pos: 0,
+        builder: GreenBuilder::default(),
+        errors: Vec::new(),
+        reserved: Vec::new(),
+        in_block_head: false,
+        depth: 0,
+        links: 0,
+        height: 0,
+    };
+
+    parser.builder.start_node(SyntaxKind::Root);
+
+    let mut after_stmt = false;
+    while parser.current().is_some() {
+        after_stmt = parser.line(after_stmt);
+    }
+
+    parser.eat_trivia();
+    parser.builder.finish_node();
+    parser.errors.append(&mut parser.reserved);
+
+    Parse {
+        green: parser.builder.finish(),
+        errors: parser.errors,
+    }
+}
+
+struct Parser<'s> {
+    source: &'s str,
+    tokens: Vec<Token>,
+    pos: usize,
+    builder: GreenBuilder,
+    errors: Vec<ParseError>,
+    /// Keywords used as names, which come after the other errors because something that doesn't
+    /// parse, like `let a @ 1`, usually makes its keyword look like one too.
+    reserved: Vec<ParseError>,
+    /// Whether the value of a `match` or `for` is being parsed, where a `{` starts the arms or
+    /// the body rather than an argument.
+    in_block_head: bool,
+    /// How many expressions, patterns and function bodies are being parsed inside each other.
+    depth: usize,
+    /// How many operators, indexes and the like are chained together in what is being parsed.
+    links: usize,
+    /// How tall the tallest chain is that has been parsed since the innermost one began.
+    height: usize,
+}
+
+impl Parser<'_> {
+    /// Parses a statement, which has to start on a line of its own if it comes `after_stmt` that
+    /// parsed without errors. Returns whether this one did. After an error, or when nothing that
+    /// could start a statement is here, the error already reported says enough.
+    fn line(&mut self, after_stmt: bool) -> bool {
+        if after_stmt && !self.newline_before_current() && self.at_stmt_start() {
+            self.error(utils::SAME_LINE_STMTS);
+        }
+
+        let errors = self.errors.len();
+        self.stmt();
+        self.errors.len() == errors
+    }
+
+    fn at_stmt_start(&self) -> bool {
+        self.at_binding_def()
+            || self.at_func_def()
+            || self.at_enum_def()
+            || self.at_import()
+            || self.at_keyword(0, "throw")
+            || self.at_assign()
+            || self.at_expr_start()
+    }
+
+    fn stmt(&mut self) {
+        if self.at_binding_def() {
+            self.binding_def();
+        } else if self.at_func_def() {
+            self.func_def();
+        } else if self.at_enum_def() {
+            self.enum_def();
+        } else if self.at_import() {
+            self.import();
+        } else if self.at_keyword(0, "throw") {
+            self.start_node(SyntaxKind::ThrowStmt);
+            self.bump();
+            self.expr_or_error();
+            self.builder.finish_node();
+        } else if self.at_assign() {
+            self.start_node(SyntaxKind::AssignStmt);
+            self.ident(SyntaxKind::NameRef);
+            self.bump();
+            self.expr_or_error();
+            self.builder.finish_node();
+        } else if self.at_expr_start() {
+            self.expr(0);
+        } else {
+            self.error("expected statement");
+            self.start_node(SyntaxKind::Error);
+            self.bump();
+            self.builder.finish_node();
+        }
+    }
+
+    fn at_enum_def(&self) -> bool {
+        self.at_keyword(0, "enum")
+            && self.nth_is(1, |kind| matches!(kind, TokenKind::Ident(_)))
+            && self.nth_is(2, |kind| *kind == TokenKind::LBrace)
+    }
+
+    fn at_assign(&self) -> bool {
+        self.nth_is(0, |kind| matches!(kind, TokenKind::Ident(_)))
+            && self.nth_is(1, |kind| assign::compound_op(kind).is_some())
+    }
+
+    fn at_import(&self) -> bool {
+        self.at_keyword(0, "import") && self.nth_is(1, |kind| matches!(kind, TokenKind::Str(_)))
+    }
+
+    fn at_binding_def(&self) -> bool {
+        (self.at_keyword(0, "let") || self.at_keyword(0, "const"))
+            && self.nth_is(1, |kind| matches!(kind, TokenKind::Ident(_)))
+            && self.nth_is(2, |kind| matches!(kind, TokenKind::Eq | TokenKind::Colon))
+    }
+
+    fn at_func_def(&self) -> bool {
+        if !self.at_keyword(0, "fn") || !self.nth_is(1, |kind| matches!(kind, TokenKind::Ident(_)))
+        {
+            return false;
+        }
+
+        let mut n = 2;
+        loop {
+            let ellipsis = usize::from(self.nth_is(n, |kind| *kind == TokenKind::Ellipsis));
+            if !self.nth_is(n + ellipsis, |kind| matches!(kind, TokenKind::Ident(_))) {
+                break;
+            }
+            n += ellipsis + 1;
+
+            if self.nth_is(n, |kind| *kind == TokenKind::Colon)
+                && self.nth_is(n + 1, |kind| matches!(kind, TokenKind::Ident(_)))
+            {
+                n += 2;
+            }
+
+            // A default runs up to the next comma or the arrow that isn't inside brackets.
+            if self.nth_is(n, |kind| *kind == TokenKind::Eq) {
+                n += 1;
+                let mut depth = 0usize;
+
+                while let Some(kind) = self.nth(n) {
+                    match kind {
+                        TokenKind::Comma | TokenKind::FatArrow if depth == 0 => break,
+                        TokenKind::LParen
+                        | TokenKind::LBrace
+                        | TokenKind::LBracket
+                        | TokenKind::QuestionLBracket => depth += 1,
+                        TokenKind::RParen | TokenKind::RBrace | TokenKind::RBracket => {
+                            depth = depth.saturating_sub(1)
+                        }
+                        _ => {}
+                    }
+                    n += 1;
+                }
+            }
+
+            if self.nth_is(n, |kind| *kind == TokenKind::Comma) {
+                n += 1;
+            }
+        }
+
+        self.nth_is(n, |kind| *kind == TokenKind::FatArrow)
+    }
+
+    fn binding_def(&mut self) {
+        self.start_node(SyntaxKind::BindingDef);
+        self.bump();
+        self.name();
+        self.annotation();
+
+        if self.current() == Some(&TokenKind::Eq) {
+            self.bump();
+        } else {
+            self.error("expected =");
+        }
+
+        self.expr_or_error();
+        self.builder.finish_node();
+    }
+
+    fn func_def(&mut self) {
+        self.start_node(SyntaxKind::FuncDef);
+        self.bump();
+        self.name();
+
+        self.start_node(SyntaxKind::ParamList);
+        while self.nth_is(0, |kind| {
+            matches!(kind, TokenKind::Ident(_) | TokenKind::Ellipsis)
+        }) {
+            self.start_node(SyntaxKind::Param);
+
+            if self.current() == Some(&TokenKind::Ellipsis) {
+                self.bump();
+            }
+
+            self.name();
+            self.annotation();
+
+            if self.current() == Some(&TokenKind::Eq) {
+                self.bump();
+                self.expr_or_error();
+            }
+            self.builder.finish_node();
+
+            if self.current() == Some(&TokenKind::Comma) {
+                self.bump();
+            }
+        }
+        self.builder.finish_node();
+
+        // The lookahead in `at_func_def` skips over defaults without parsing them, so a broken
+        // default can take the `=>` with it.
+        if self.current() == Some(&TokenKind::FatArrow) {
+            self.bump();
+        } else {
+            self.error("expected =>");
+        }
+
+        if self.current().is_none() {
+            self.error("expected function body");
+        } else if self.enter() {
+            self.stmt();
+            self.depth -= 1;
+        }
+
+        self.builder.finish_node();
+    }
+
+    fn enum_def(&mut self) {
+        self.start_node(SyntaxKind::EnumDef);
+        self.bump();
+        self.name();
+        self.bump();
+
+        let mut after_item = false;
+        loop {
+            match self.current() {
+                Some(TokenKind::RBrace) => {
+                    self.bump();
+                    break;
+                }
+                Some(TokenKind::Comma) => {
+                    self.comma(&mut after_item, TokenKind::RBrace, "expected }")
+                }
+                Some(TokenKind::Ident(_)) => {
+                    self.item(&mut after_item);
+                    self.variant();
+                }
+                _ => {
+                    self.error("expected }");
+                    break;
+                }
+            }
+        }
+
+        self.builder.finish_node();
+    }
+
+    fn variant(&mut self) {
+        self.start_node(SyntaxKind::Variant);
+        self.name();
+
+        if self.current() == Some(&TokenKind::LParen) {
+            self.bump();
+
+            let mut after_item = false;
+            loop {
+                match self.current() {
+                    Some(TokenKind::RParen) => {
+                        self.bump();
+                        break;
+                    }
+                    Some(TokenKind::Comma) => {
+                        self.comma(&mut after_item, TokenKind::RParen, "expected )")
+                    }
+                    Some(TokenKind::Ident(_)) => {
+                        self.item(&mut after_item);
+                        self.name();
+                    }
+                    _ => {
+                        self.error("expected )");
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.builder.finish_node();
+    }
+
+    fn import(&mut self) {
+        self.start_node(SyntaxKind::Import);
+        self.bump();
+        self.bump();
+        self.builder.finish_node();
+    }
+
+    /// A `: type` after a name, if there is one.
+    fn annotation(&mut self) {
+        if self.current() != Some(&TokenKind::Colon) {
+            return;
+        }
+
+        self.bump();
+
+        if self.nth_is(0, |kind| matches!(kind, TokenKind::Ident(_))) {
+            self.start_node(SyntaxKind::TypeRef);
+            self.bump();
+            self.builder.finish_node();
+        } else {
+            self.error("expected type");
+        }
+    }
+
+    fn name(&mut self) {
+        self.ident(SyntaxKind::Name);
+    }
+
+    /// A `Name` or `NameRef` node around the identifier here, which can't be a keyword.
+    fn ident(&mut self, kind: SyntaxKind) {
+        if let Some(TokenKind::Ident(ident)) = self.current() {
+            if crate::KEYWORDS.contains(&ident.as_str()) {
+                let error = self.error_here(&format!("`{}` is a reserved keyword", ident));
+                self.reserved.push(error);
+            }
+        }
+
+        self.start_node(kind);
+        self.bump();
+        self.builder.finish_node();
+    }
+
+    fn expr_or_error(&mut self) {
+        if self.at_expr_start() {
+            self.expr(0);
+        } else {
+            self.error("expected expression");
+        }
+    }
+
+    fn expr(&mut self, min_binding_power: u8) {
+        if !self.enter() {
+            return;
+        }
+
+        let outer_height = mem::take(&mut self.height);
+        let checkpoint = self.checkpoint();
+        self.postfix_expr();
+
+        // Each operator wraps everything before it in one more node.
+        let mut links = 0;
+        while let Some((left_binding_power, right_binding_power)) = self
+            .current()
+            .filter(|_| !self.newline_before_current())
+            .and_then(|kind| match kind {
+                TokenKind::PipeGt => Some(PIPELINE_BINDING_POWER),
+                kind => Op::from_token(kind).map(|op| op.binding_power()),
+            })
+        {
+            if left_binding_power < min_binding_power || !self.link() {
+                break;
+            }
+            links += 1;
+
+            self.builder
+                .start_node_at(checkpoint, SyntaxKind::BinaryExpr);
+            self.bump();
+
+            if self.at_expr_start() {
+                self.expr(right_binding_power);
+            } else {
+                self.error("expected expression");
+            }
+
+            self.builder.finish_node();
+        }
+
+        self.depth -= 1;
+        self.links -= links;
+        self.height = outer_height.max(self.height + 1 + links);
+    }
+
+    fn atom(&mut self) {
+        match self.current() {
+            Some(TokenKind::Number(_) | TokenKind::Str(_)) => self.literal(),
+            Some(TokenKind::Ident(ident)) if matches!(ident.as_str(), "true" | "false" | "nil") => {
+                self.literal()
+            }
+            Some(TokenKind::Ident(ident))
+                if ident == "try" && self.nth_is(1, |kind| *kind == TokenKind::LBrace) =>
+            {
+                self.try_expr()
+            }
+            Some(TokenKind::Ident(ident)) if ident == "match" => self.match_expr(),
+            Some(TokenKind::Ident(ident))
+                if ident == "for" && self.nth_is(1, |kind| matches!(kind, TokenKind::Ident(_))) =>
+            {
+                self.for_expr()
+            }
+            Some(TokenKind::Ident(_)) => self.name_ref_or_call(),
+            Some(TokenKind::LBrace)
+                if self.nth_is(1, |kind| matches!(kind, TokenKind::Ident(_)))
+                    && self.nth_is(2, |kind| *kind == TokenKind::Colon) =>
+            {
+                self.record_expr()
+            }
+            Some(TokenKind::LBrace) => self.block(),
+            Some(TokenKind::LBracket) => self.list_expr(),
+            Some(TokenKind::LParen) => self.paren_or_tuple_expr(),
+            Some(TokenKind::Tilde | TokenKind::Minus) => self.prefix_expr(),
+            _ => unreachable!("atom should only be called at the start of an expression"),
+        }
+    }
+
+    /// An atom followed by any number of `[index]`s, `?[index]`s or `.method(args)`s, which have
+    /// to follow it directly.
+    fn postfix_expr(&mut self) {
+        let outer_height = mem::take(&mut self.height);
+        let checkpoint = self.checkpoint();
+        self.atom();
+
+        let mut links = 0;
+        loop {
+            let member = self.at_adjacent(|kind| *kind == TokenKind::Dot);
+            if !member
+                && !self.at_adjacent(|kind| {
+                    matches!(kind, TokenKind::LBracket | TokenKind::QuestionLBracket)
+                })
+                || !self.link()
+            {
+                break;
+            }
+            links += 1;
+
+            if member {
+                self.member(checkpoint);
+                continue;
+            }
+
+            self.builder
+                .start_node_at(checkpoint, SyntaxKind::IndexExpr);
+            self.bump();
+            self.expr_or_error();
+
+            if self.current() == Some(&TokenKind::RBracket) {
+                self.bump();
+            } else {
+                self.error("expected ]");
+            }
+
+            self.builder.finish_node();
+        }
+
+        self.links -= links;
+        self.height = outer_height.max(self.height + links);
+    }
+
+    /// `.name` after an expression, which is a method call if arguments follow the name directly
+    /// and a field access otherwise.
+    fn member(&mut self, checkpoint: usize) {
+        let is_method = self
+            .nth_index(1)
+            .filter(|&idx| matches!(self.tokens[idx].kind, TokenKind::Ident(_)))
+            .and_then(|idx| self.tokens.get(idx + 1))
+            .is_some_and(|token| token.kind == TokenKind::LParen);
+        let kind = if is_method {
+            SyntaxKind::MethodCallExpr
+        } else {
+            SyntaxKind::FieldExpr
+        };
+
+        self.builder.start_node_at(checkpoint, kind);
+        self.bump();
+
+        if matches!(self.current(), Some(TokenKind::Ident(_))) {
+            self.ident(SyntaxKind::NameRef);
+
+            if is_method {
+                self.parenthesized_args();
+            }
+        } else {
+            self.error("expected field or method name");
+        }
+
+        self.builder.finish_node();
+    }
+
+    fn prefix_expr(&mut self) {
+        if !self.enter() {
+            return;
+        }
+
+        self.start_node(SyntaxKind::PrefixExpr);
+        let neg = self.current() == Some(&TokenKind::Minus);
+        self.bump();
+
+        if !self.at_expr_start() {
+            self.error("expected expression");
+        } else if neg {
+            self.expr(NEG_BINDING_POWER);
+        } else {
+            self.postfix_expr();
+        }
+
+        self.builder.finish_node();
+        self.depth -= 1;
+    }
+
+    fn literal(&mut self) {
+        self.start_node(SyntaxKind::Literal);
+        self.bump();
+        self.builder.finish_node();
+    }
+
+    fn name_ref_or_call(&mut self) {
+        let checkpoint = self.checkpoint();
+        self.ident(SyntaxKind::NameRef);
+
+        if self.at_adjacent(|kind| *kind == TokenKind::LParen) {
+            self.builder.start_node_at(checkpoint, SyntaxKind::CallExpr);
+            self.parenthesized_args();
+            self.builder.finish_node();
+        } else if self.at_arg_start() && !self.at_adjacent(|kind| *kind == TokenKind::LBracket) {
+            self.builder.start_node_at(checkpoint, SyntaxKind::CallExpr);
+            self.start_node(SyntaxKind::ArgList);
+
+            while self.at_arg_start() {
+                self.expr(0);
+            }
+
+            self.builder.finish_node();
+            self.builder.finish_node();
+        }
+    }
+
+    /// Whether a juxtaposed argument starts here, which it can't on the next line, at a `-` that
+    /// subtracts or at the `{` that starts the arms of a `match` or the body of a `for`.
+    fn at_arg_start(&self) -> bool {
+        self.at_expr_start()
+            && !self.newline_before_current()
+            && self.current() != Some(&TokenKind::Minus)
+            && !(self.in_block_head && self.current() == Some(&TokenKind::LBrace))
+    }
+
+    fn parenthesized_args(&mut self) {
+        self.start_node(SyntaxKind::ArgList);
+        self.bump();
+
+        let mut after_item = false;
+        loop {
+            match self.current() {
+                Some(TokenKind::RParen) => {
+                    self.bump();
+                    break;
+                }
+                Some(TokenKind::Comma) => {
+                    self.comma(&mut after_item, TokenKind::RParen, "expected )")
+                }
+                Some(TokenKind::Ident(_)) if self.nth_is(1, |kind| *kind == TokenKind::Colon) => {
+                    self.item(&mut after_item);
+                    self.start_node(SyntaxKind::NamedArg);
+                    self.name();
+                    self.bump();
+                    self.expr_or_error();
+                    self.builder.finish_node();
+                }
+                _ if self.at_expr_start() => {
+                    self.item(&mut after_item);
+                    self.expr(0);
+                }
+                _ => {
+                    self.error("expected )");
+                    break;
+                }
+            }
+        }
+
+        self.builder.finish_node();
+    }
+
+    fn record_expr(&mut self) {
+        self.start_node(SyntaxKind::RecordExpr);
+        self.bump();
+
+        let mut after_item = false;
+        loop {
+            match self.current() {
+                Some(TokenKind::RBrace) => {
+                    self.bump();
+                    break;
+                }
+                Some(TokenKind::Comma) => {
+                    self.comma(&mut after_item, TokenKind::RBrace, "expected }")
+                }
+                Some(TokenKind::Ident(_)) if self.nth_is(1, |kind| *kind == TokenKind::Colon) => {
+                    self.item(&mut after_item);
+                    self.start_node(SyntaxKind::RecordField);
+                    self.name();
+                    self.bump();
+                    self.expr_or_error();
+                    self.builder.finish_node();
+                }
+                _ => {
+                    self.error("expected }");
+                    break;
+                }
+            }
+        }
+
+        self.builder.finish_node();
+    }
+
+    fn list_expr(&mut self) {
+        self.start_node(SyntaxKind::ListExpr);
+        self.bump();
+
+        let mut after_item = false;
+        loop {
+            match self.current() {
+                Some(TokenKind::RBracket) => {
+                    self.bump();
+                    break;
+                }
+                Some(TokenKind::Comma) => {
+                    self.comma(&mut after_item, TokenKind::RBracket, "expected ]")
+                }
+                _ if self.at_expr_start() => {
+                    self.item(&mut after_item);
+                    self.expr(0);
+                }
+                _ => {
+                    self.error("expected ]");
+                    break;
+                }
+            }
+        }
+
+        self.builder.finish_node();
+    }
+
+    /// `(x)` groups an expression, while `(a, b)` and `()` are tuples.
+    fn paren_or_tuple_expr(&mut self) {
+        let checkpoint = self.checkpoint();
+        self.bump();
+
+        let mut items = 0;
+        let mut after_item = false;
+
+        loop {
+            match self.current() {
+                Some(TokenKind::RParen) => {
+                    self.bump();
+                    break;
+                }
+                Some(TokenKind::Comma) => {
+                    self.comma(&mut after_item, TokenKind::RParen, "expected )")
+                }
+                _ if self.at_expr_start() => {
+                    self.item(&mut after_item);
+                    self.expr(0);
+                    items += 1;
+                }
+                _ => {
+                    self.error("expected )");
+                    break;
+                }
+            }
+        }
+
+        let kind = if items == 1 {
+            SyntaxKind::ParenExpr
+        } else {
+            SyntaxKind::TupleExpr
+        };
+        self.builder.start_node_at(checkpoint, kind);
+        self.builder.finish_node();
+    }
+
+    fn try_expr(&mut self) {
+        self.start_node(SyntaxKind::TryExpr);
+        self.bump();
+        self.block();
+
+        if self.at_keyword(0, "catch") {
+            self.bump();
+        } else {
+            self.error("expected catch");
+        }
+
+        if self.nth_is(0, |kind| matches!(kind, TokenKind::Ident(_))) {
+            self.name();
+        } else {
+            self.error("expected identifier");
+        }
+
+        if self.current() == Some(&TokenKind::LBrace) {
+            self.block();
+        } else {
+            self.error("expected {");
+        }
+
+        self.builder.finish_node();
+    }
+
+    fn for_expr(&mut self) {
+        self.start_node(SyntaxKind::ForExpr);
+        self.bump();
+        self.name();
+
+        if self.at_keyword(0, "in") {
+            self.bump();
+        } else {
+            self.error("expected in");
+        }
+
+        self.block_head();
+
+        if self.current() == Some(&TokenKind::LBrace) {
+            self.block();
+        } else {
+            self.error("expected {");
+        }
+
+        self.builder.finish_node();
+    }
+
+    /// Parses the expression before the `{` of a `match` or `for`.
+    /// Eats a comma in a list that `close` ends, which like in [`utils::comma_separated`] has to
+    /// come between two items, or else reports `message`.
+    fn comma(&mut self, after_item: &mut bool, close: TokenKind, message: &str) {
+        if !mem::take(after_item) || self.nth_is(1, |kind| *kind == close) {
+            self.error(message);
+        }
+        self.bump();
+    }
+
+    /// Starts an item of a list, which has to be the first one or come after a comma.
+    fn item(&mut self, after_item: &mut bool) {
+        if mem::replace(after_item, true) {
+            self.error("expected ,");
+        }
+    }
+
+    fn block_head(&mut self) {
+        let in_block_head = core::mem::replace(&mut self.in_block_head, true);
+        self.expr_or_error();
+        self.in_block_head = in_block_head;
+    }
+
+    fn match_expr(&mut self) {
+        self.start_node(SyntaxKind::MatchExpr);
+        self.bump();
+
+        self.block_head();
+
+        if self.current() == Some(&TokenKind::LBrace) {
+            self.bump();
+        } else {
+            self.error("expected {");
+            self.builder.finish_node();
+            return;
+        }
+
+        // Arms don't need commas between them, but can have one right after each.
+        let mut after_item = false;
+        loop {
+            match self.current() {
+                Some(TokenKind::RBrace) => {
+                    self.bump();
+                    break;
+                }
+                Some(TokenKind::Comma) => {
+                    if !mem::take(&mut after_item) {
+                        self.error("expected }");
+                    }
+                    self.bump();
+                }
+                Some(TokenKind::Number(_) | TokenKind::Str(_) | TokenKind::Ident(_)) => {
+                    after_item = true;
+                    self.start_node(SyntaxKind::MatchArm);
+                    self.pattern();
+
+                    if self.current() == Some(&TokenKind::FatArrow) {
+                        self.bump();
+                        self.expr_or_error();
+                    } else {
+                        self.error("expected =>");
+                    }
+
+                    self.builder.finish_node();
+                }
+                _ => {
+                    self.error("expected }");
+                    break;
+                }
+            }
+        }
+
+        self.builder.finish_node();
+    }
+
+    fn pattern(&mut self) {
+        if !self.enter() {
+            return;
+        }
+
+        self.start_node(SyntaxKind::Pattern);
+        self.bump();
+
+        if self.current() == Some(&TokenKind::LParen) {
+            self.bump();
+
+            let mut after_item = false;
+            loop {
+                match self.current() {
+                    Some(TokenKind::RParen) => {
+                        self.bump();
+                        break;
+                    }
+                    Some(TokenKind::Comma) => {
+                        self.comma(&mut after_item, TokenKind::RParen, "expected )")
+                    }
+                    Some(TokenKind::Number(_) | TokenKind::Str(_) | TokenKind::Ident(_)) => {
+                        self.item(&mut after_item);
+                        self.pattern();
+                    }
+                    _ => {
+                        self.error("expected )");
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.builder.finish_node();
+        self.depth -= 1;
+    }
+
+    fn block(&mut self) {
+        self.start_node(SyntaxKind::Block);
+        self.bump();
+
+        let mut after_stmt = false;
+        loop {
+            match self.current() {
+                Some(TokenKind::RBrace) => {
+                    self.bump();
+                    break;
+                }
+                Some(_) => after_stmt = self.line(after_stmt),
+                None => {
+                    self.error("expected }");
+                    break;
+                }
+            }
+        }
+
+        self.builder.finish_node();
+    }
+
+    /// Goes one level of nesting deeper, which the caller undoes once it's done. Past
+    /// [`utils::MAX_DEPTH`] it fails instead, like the other parser does, and wraps the current
+    /// token in an error node so that parsing still moves forward.
+    fn enter(&mut self) -> bool {
+        if self.depth < utils::MAX_DEPTH {
+            self.depth += 1;
+            return true;
+        }
+
+        self.error(&format!(
+            "nested more than {} levels deep",
+            utils::MAX_DEPTH
+        ));
+        self.start_node(SyntaxKind::Error);
+        self.bump();
+        self.builder.finish_node();
+        false
+    }
+
+    /// Adds one more link to the chains being parsed, which the caller undoes once it's done. If
+    /// that could make the tree taller than [`utils::MAX_CHAIN`] it fails instead, the same way
+    /// [`Parser::enter`] does.
+    fn link(&mut self) -> bool {
+        if self.links + self.height < utils::MAX_CHAIN {
+            self.links += 1;
+            return true;
+        }
+
+        self.error(&format!(
+            "more than {} operations chained together",
+            utils::MAX_CHAIN
+        ));
+        self.start_node(SyntaxKind::Error);
+        self.bump();
+        self.builder.finish_node();
+        false
+    }
+
+    /// The index of the `n`th token from here that isn't trivia.
+    fn nth_index(&self, n: usize) -> Option<usize> {
+        (self.pos..self.tokens.len())
+            .filter(|&idx| !self.tokens[idx].kind.is_trivia())
+            .nth(n)
+    }
+
+    fn current(&self) -> Option<&TokenKind> {
+        self.nth_index(0).map(|idx| &self.tokens[idx].kind)
+    }
+
+    fn nth(&self, n: usize) -> Option<&TokenKind> {
+        self.nth_index(n).map(|idx| &self.tokens[idx].kind)
+    }
+
+    fn nth_is(&self, n: usize, accept: impl Fn(&TokenKind) -> bool) -> bool {
+        self.nth(n).is_some_and(accept)
+    }
+
+    fn at_keyword(&self, n: usize, word: &str) -> bool {
+        self.nth_is(
+            n,
+            |kind| matches!(kind, TokenKind::Ident(ident) if ident == word),
+        )
+    }
+
+    fn at_expr_start(&self) -> bool {
+        self.nth_is(0, |kind| {
+            matches!(
+                kind,
+                TokenKind::Number(_)
+                    | TokenKind::Str(_)
+                    | TokenKind::Ident(_)
+                    | TokenKind::LBrace
+                    | TokenKind::LBracket
+                    | TokenKind::LParen
+                    | TokenKind::Tilde
+                    | TokenKind::Minus
+            )
+        })
+    }
+
+    /// Whether the very next token, with no trivia before it, is accepted.
+    fn at_adjacent(&self, accept: impl Fn(&TokenKind) -> bool) -> bool {
+        self.tokens
+            .get(self.pos)
+            .is_some_and(|token| accept(&token.kind))
+    }
+
+    fn newline_before_current(&self) -> bool {
+        self.nth_index(0)
+            .is_some_and(|idx| self.tokens[idx].newline_before)
+    }
+
+    fn eat_trivia(&mut self) {
+        while let Some(token) = self.tokens.get(self.pos) {
+            if !token.kind.is_trivia() {
+                break;
+            }
+
+            self.builder.token(
+                SyntaxKind::from(&token.kind),
+                &self.source[token.span.clone()],
+            );
+            self.pos += 1;
+        }
+    }
+
+    fn bump(&mut self) {
+        self.eat_trivia();
+
+        let token = &self.tokens[self.pos];
+        self.builder.token(
+            SyntaxKind::from(&token.kind),
+            &self.source[token.span.clone()],
+        );
+        self.pos += 1;
+    }
+
+    fn start_node(&mut self, kind: SyntaxKind) {
+        self.eat_trivia();
+        self.builder.start_node(kind);
+    }
+
+    fn checkpoint(&mut self) -> usize {
+        self.eat_trivia();
+        self.builder.checkpoint()
+    }
+
+    fn error(&mut self, message: &str) {
+        let error = self.error_here(message);
+        self.errors.push(error);
+    }
+
+    /// An error pointing at the next token, or at the end if there are no more.
+    fn error_here(&self, message: &str) -> ParseError {
+        let span = match self.nth_index(0) {
+            Some(idx) => self.tokens[idx].span.clone(),
+            None => self.source.len()..self.source.len(),
+        };
+
+        ParseError {
+            message: message.to_string(),
+            span,
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::compat::prelude::*;
+use crate::lexer::{Token, TokenKind};
+use crate::utils;
+use crate::val::Val;
+use core::fmt;
+
+/// The type named in an annotation such as `let x: number = 5`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Type {
+    Number,
+    Bool,
+    Str,
+    List,
+    Tuple,
+    Unit,
+    Nil,
+    Func,
+    Record,
+    Variant,
+    Range,
+}
+
+impl Type {
+    const ALL: [Self; 11] = [
+        Self::Number,
+        Self::Bool,
+        Self::Str,
+        Self::List,
+        Self::Tuple,
+        Self::Unit,
+        Self::Nil,
+        Self::Func,
+        Self::Record,
+        Self::Variant,
+        Self::Range,
+    ];
+
+    pub(crate) fn new(tokens: &[Token]) -> Result<(&[Token], Self), String> {
+        match tokens.split_first() {
+            Some((
+                Token {
+                    kind: TokenKind::Ident(name),
+                    ..
+                },
+                tokens,
+            )) => Self::ALL
+                .into_iter()
+                .find(|ty| ty.name() == name)
+                .map(|ty| (tokens, ty))
+                .ok_or_else(|| format!("unknown type `{}`", name)),
+            _ => Err("expected type".to_string()),
+        }
+    }
+
+    /// Parses a `: type` annotation if there is one.
+    pub(crate) fn new_annotation(tokens: &[Token]) -> Result<(&[Token], Option<Self>), String> {
+        match utils::expect(TokenKind::Colon, tokens) {
+            Ok(tokens) => Self::new(tokens).map(|(tokens, ty)| (tokens, Some(ty))),
+            Err(_) => Ok((tokens, None)),
+        }
+    }
+
+    /// The type of a value.
+    pub(crate) fn of(val: &Val) -> Self {
+        match val {
+            Val::Number(_) => Self::Number,
+            Val::Bool(_) => Self::Bool,
+            Val::Str(_) => Self::Str,
+            Val::List(_) => Self::List,
+            Val::Tuple(_) => Self::Tuple,
+            Val::Unit => Self::Unit,
+            Val::Nil => Self::Nil,
+            Val::Func(_) => Self::Func,
+            Val::Record(_) => Self::Record,
+            Val::Variant { .. } => Self::Variant,
+            Val::Range { .. } => Self::Range,
+        }
+    }
+
+    /// The name that annotations use, which is the same as [`Val::type_name`].
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Number => "number",
+            Self::Bool => "bool",
+            Self::Str => "string",
+            Self::List => "list",
+            Self::Tuple => "tuple",
+            Self::Unit => "unit",
+            Self::Nil => "nil",
+            Self::Func => "function",
+            Self::Record => "record",
+            Self::Variant => "variant",
+            Self::Range => "range",
+        }
+    }
+
+    /// Checks that `val` has this type, describing `what` was expected to have it otherwise.
+    pub(crate) fn check(self, val: &Val, what: impl fmt::Display) -> Result<(), String> {
+        if val.type_name() == self.name() {
+            Ok(())
+        } else {
+            Err(format!(
+                "expected {} to be {}, got {}",
+                what,
+                self,
+                val.type_name()
+            ))
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_annotation() {
+        assert_eq!(
+            utils::parse_str(Type::new_annotation, ": string"),
+            Ok(("", Some(Type::Str))),
+        );
+        assert_eq!(
+            utils::parse_str(Type::new_annotation, "= 1"),
+            Ok(("= 1", None))
+        );
+    }
+
+    #[test]
+    fn parse_unknown_type() {
+        assert_eq!(
+            utils::parse_str(Type::new_annotation, ": integer"),
+            Err("unknown type `integer`".to_string()),
+        );
+    }
+
+    #[test]
+    fn check_val_against_type() {
+        assert_eq!(Type::Nil.check(&Val::Nil, "x"), Ok(()));
+        assert_eq!(
+            Type::Number.check(&Val::Bool(true), "'x'"),
+            Err("expected 'x' to be number, got bool".to_string()),
+        );
+    }
+}
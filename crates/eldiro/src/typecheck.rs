@@ -0,0 +1,1252 @@
+//! Type inference over a [`Program`], which finds type errors before it runs, such as adding a
+//! number to a list or calling a function with the wrong number of arguments.
+//!
+//! Types are inferred in the style of Hindley-Milner, so annotations are never required: with
+//! `fn add x y => x + y`, both parameters have to be numbers, and so does the result. Functions
+//! that work on anything, like `fn id x => x`, stay generic and can be called with different types.
+//!
+//! The language is dynamic though, so only what is certain gets reported. Anything whose type
+//! can't be known without running the program, like an item taken out of a list or a name that an
+//! import defines, is let through.
+
+use crate::compat::prelude::*;
+use crate::compat::{HashMap, HashSet};
+use crate::env::{Env, Func};
+use crate::error::TypeError;
+use crate::expr::{Block, Expr, FuncCall, Op, Pattern, UnaryOp};
+use crate::func_def::{self, Param};
+use crate::program::Program;
+use crate::stmt::Stmt;
+use crate::symbol::Symbol;
+use crate::ty::Type;
+use core::mem;
+use core::ops::Range;
+
+pub(crate) fn program(program: &Program) -> Vec<TypeError> {
+    let env = Env::default();
+    let mut checker = Checker::new(&env);
+
+    for stmt in &program.stmts {
+        checker.stmt(stmt);
+    }
+
+    checker.errors
+}
+
+/// The type of every `let`, `const` and `fn` in `program`, as if it were evaluated in `env`, by
+/// the span of its definition.
+pub(crate) fn definitions(program: &Program, env: &Env) -> Vec<(Range<usize>, String)> {
+    let mut checker = Checker::new(env);
+
+    for stmt in &program.stmts {
+        checker.stmt(stmt);
+    }
+
+    let defs = mem::take(&mut checker.defs);
+    defs.into_iter()
+        .map(|(span, ty)| (span, checker.show(&ty)))
+        .collect()
+}
+
+/// Infers the type of the last statement in `program` as if it were evaluated in `env`. A lone
+/// function name gives the type of the function rather than of calling it.
+pub(crate) fn infer(program: &Program, env: &Env) -> Result<String, Vec<TypeError>> {
+    let mut checker = Checker::new(env);
+
+    let ty = match program.stmts.split_last() {
+        Some((last, init)) => {
+            for stmt in init {
+                checker.stmt(stmt);
+            }
+
+            let func = match last {
+                Stmt::Expr(Expr::BindingUsage(binding_usage)) => {
+                    match checker.lookup(binding_usage.name) {
+                        Some(Named::Func { scheme, .. }) => Some(checker.instantiate(&scheme)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+
+            func.unwrap_or_else(|| checker.stmt(last))
+        }
+        None => UNIT,
+    };
+
+    if checker.errors.is_empty() {
+        Ok(checker.show(&ty))
+    } else {
+        Err(checker.errors)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    /// A type that isn't known yet, which is an index into [`Checker::subst`].
+    Var(usize),
+    Con(Type),
+    Func(Vec<Ty>, Box<Ty>),
+}
+
+const NUMBER: Ty = Ty::Con(Type::Number);
+const BOOL: Ty = Ty::Con(Type::Bool);
+const UNIT: Ty = Ty::Con(Type::Unit);
+const VARIANT: Ty = Ty::Con(Type::Variant);
+
+/// A function type that is generic over `vars`, which are replaced with fresh variables each time
+/// the function is used.
+#[derive(Debug, Clone)]
+struct Scheme {
+    vars: Vec<usize>,
+    ty: Ty,
+}
+
+/// What the checker knows about a name.
+#[derive(Debug, Clone)]
+enum Named {
+    Binding(Ty),
+    /// A binding defined with `const`, which can't be defined again in the same scope.
+    Const(Ty),
+    Func {
+        /// The names of the parameters, which are empty for natives.
+        params: Vec<Symbol>,
+        /// How many arguments a call needs, since parameters at the end may have defaults.
+        required: usize,
+        /// How many arguments a call can pass, which is unlimited with a rest parameter. The
+        /// extra arguments aren't checked, since they end up in a list.
+        max: Option<usize>,
+        scheme: Scheme,
+    },
+}
+
+struct Checker<'env> {
+    /// Where names are looked up once they aren't found in `scopes`.
+    env: &'env Env<'env>,
+    scopes: Vec<HashMap<Symbol, Named>>,
+    /// What each type variable has been found to be.
+    subst: Vec<Option<Ty>>,
+    /// Functions from `env` whose types are being inferred, so that recursion among them ends.
+    inferring: HashSet<Symbol>,
+    errors: Vec<TypeError>,
+    /// The type of each binding and function defined so far, by the span of its definition.
+    defs: Vec<(Range<usize>, Ty)>,
+}
+
+impl<'env> Checker<'env> {
+    fn new(env: &'env Env<'env>) -> Self {
+        Self {
+            env,
+            scopes: vec![HashMap::new()],
+            subst: Vec::new(),
+            inferring: HashSet::new(),
+            errors: Vec::new(),
+            defs: Vec::new(),
+        }
+    }
+
+    /// Checks a statement and returns the type of its value.
+    fn stmt(&mut self, stmt: &Stmt) -> Ty {
+        match stmt {
+            Stmt::BindingDef(binding_def) => {
+                let found = self.expr(&binding_def.val);
+
+                let ty = match binding_def.ty {
+                    Some(expected) => {
+                        if !self.unify(&Ty::Con(expected), &found) {
+                            let message = format!(
+                                "expected '{}' to be {}, got {}",
+                                binding_def.name,
+                                expected,
+                                self.show(&found)
+                            );
+                            self.error(message, Some(binding_def.span.0.clone()));
+                        }
+
+                        Ty::Con(expected)
+                    }
+                    None => found,
+                };
+
+                let span = Some(binding_def.span.0.clone());
+                self.check_redefinable(binding_def.name, span);
+                self.defs.push((binding_def.span.0.clone(), ty.clone()));
+
+                let named = if binding_def.constant {
+                    Named::Const(ty)
+                } else {
+                    Named::Binding(ty)
+                };
+                self.define(binding_def.name, named);
+                UNIT
+            }
+            Stmt::FuncDef(func_def) => {
+                self.check_redefinable(func_def.name, Some(func_def.span.0.clone()));
+
+                let func = self.func(func_def.name, &func_def.params, &func_def.body);
+                if let Named::Func { scheme, .. } = &func {
+                    let ty = self.instantiate(scheme);
+                    self.defs.push((func_def.span.0.clone(), ty));
+                }
+                self.define(func_def.name, func);
+                UNIT
+            }
+            Stmt::EnumDef(enum_def) => {
+                let span = Some(enum_def.span.0.clone());
+
+                for variant in &enum_def.variants {
+                    self.check_redefinable(variant.name, span.clone());
+
+                    let named = if variant.fields.is_empty() {
+                        Named::Binding(VARIANT)
+                    } else {
+                        self.constructor(variant.fields.len())
+                    };
+                    self.define(variant.name, named);
+                }
+
+                UNIT
+            }
+            Stmt::Import(_) => UNIT,
+            Stmt::Throw(val, _) => {
+                self.expr(val);
+                self.fresh()
+            }
+            Stmt::Assign(assign) => {
+                let val = self.expr(&assign.val);
+                let span = assign.span.0.clone();
+
+                match self.lookup(assign.name) {
+                    Some(Named::Binding(ty)) => {
+                        self.operation(&assign.op, ty, val, &span);
+                    }
+                    Some(Named::Const(_)) => {
+                        let message = format!("cannot assign to constant '{}'", assign.name);
+                        self.error(message, Some(span));
+                    }
+                    Some(Named::Func { .. }) => {
+                        let message = format!("cannot assign to function '{}'", assign.name);
+                        self.error(message, Some(span));
+                    }
+                    None => {}
+                }
+
+                UNIT
+            }
+            Stmt::Expr(expr) => self.expr(expr),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Ty {
+        match expr {
+            Expr::Number(..) => NUMBER,
+            Expr::Bool(..) => BOOL,
+            Expr::Str(..) => Ty::Con(Type::Str),
+            Expr::Unit(_) => UNIT,
+            Expr::Nil(_) => Ty::Con(Type::Nil),
+            Expr::Placeholder(_) => self.fresh(),
+            Expr::List(items, _) => {
+                self.exprs(items);
+                Ty::Con(Type::List)
+            }
+            Expr::Tuple(items, _) => {
+                self.exprs(items);
+                Ty::Con(Type::Tuple)
+            }
+            Expr::Record(fields, _) => {
+                for (_, val) in fields {
+                    self.expr(val);
+                }
+
+                Ty::Con(Type::Record)
+            }
+            Expr::Field { base, name, span } => {
+                let base = self.expr(base);
+
+                if !self.unify(&base, &Ty::Con(Type::Record)) {
+                    let message = format!("cannot access field '{}' of {}", name, self.show(&base));
+                    self.error(message, Some(span.0.clone()));
+                }
+
+                self.fresh()
+            }
+            Expr::Operation { lhs, rhs, op, span } => {
+                let lhs = self.expr(lhs);
+                let rhs = self.expr(rhs);
+                self.operation(op, lhs, rhs, &span.0)
+            }
+            Expr::Unary { op, operand, span } => {
+                let operand = self.expr(operand);
+
+                match op {
+                    UnaryOp::BitNot | UnaryOp::Neg => {
+                        if !self.unify(&operand, &NUMBER) {
+                            let message = format!(
+                                "cannot apply {} to {}",
+                                crate::fmt::unary_op_symbol(op),
+                                self.show(&operand)
+                            );
+                            self.error(message, Some(span.0.clone()));
+                        }
+
+                        NUMBER
+                    }
+                }
+            }
+            Expr::BindingUsage(binding_usage) => match self.lookup(binding_usage.name) {
+                Some(Named::Binding(ty) | Named::Const(ty)) => ty,
+                // A bare function name calls it if it needs no arguments, and is the function
+                // itself otherwise.
+                Some(Named::Func {
+                    required, scheme, ..
+                }) => match self.instantiate(&scheme) {
+                    Ty::Func(_, ret) if required == 0 => *ret,
+                    _ => Ty::Con(Type::Func),
+                },
+                None => self.fresh(),
+            },
+            Expr::Block(block) => self.block(block),
+            Expr::FuncCall(func_call) => self.func_call(func_call),
+            Expr::Index {
+                base,
+                index,
+                safe,
+                span,
+            } => {
+                let base = self.expr(base);
+                let index = self.expr(index);
+
+                match (self.resolve(&base), self.resolve(&index)) {
+                    // Indexing or slicing a string gives a string, and slicing a list gives a
+                    // list, unless `?[]` gives nil instead.
+                    (Ty::Con(Type::Str), _) if !*safe => Ty::Con(Type::Str),
+                    (Ty::Con(Type::List), Ty::Con(Type::Range)) if !*safe => Ty::Con(Type::List),
+                    (Ty::Var(_) | Ty::Con(Type::List | Type::Tuple | Type::Str), _) => self.fresh(),
+                    (Ty::Con(Type::Nil), _) if *safe => self.fresh(),
+                    (base, _) => {
+                        let message = format!("cannot index into {}", self.show(&base));
+                        self.error(message, Some(span.0.clone()));
+                        self.fresh()
+                    }
+                }
+            }
+            Expr::Match(m) => {
+                let val = self.expr(&m.val);
+                let mut arms = Vec::new();
+
+                for arm in &m.arms {
+                    self.scopes.push(HashMap::new());
+                    self.pattern(&arm.pattern, &val);
+                    arms.push(self.expr(&arm.body));
+                    self.scopes.pop();
+                }
+
+                // The arms only have a type in common if they all agree on it.
+                match arms.split_first() {
+                    Some((first, rest)) if rest.iter().all(|arm| self.unify(first, arm)) => {
+                        first.clone()
+                    }
+                    _ => self.fresh(),
+                }
+            }
+            Expr::For(for_loop) => {
+                let iterable = self.expr(&for_loop.iterable);
+                let item = match self.resolve(&iterable) {
+                    Ty::Con(Type::Str) => Ty::Con(Type::Str),
+                    Ty::Con(Type::Range) => NUMBER,
+                    Ty::Var(_) | Ty::Con(Type::List | Type::Tuple | Type::Record) => self.fresh(),
+                    iterable => {
+                        let message = format!("cannot iterate over {}", self.show(&iterable));
+                        self.error(message, Some(for_loop.iterable.span()));
+                        self.fresh()
+                    }
+                };
+
+                self.scopes.push(HashMap::new());
+                self.define(for_loop.name, Named::Binding(item));
+                self.block(&for_loop.body);
+                self.scopes.pop();
+
+                UNIT
+            }
+            Expr::TryCatch(try_catch) => {
+                let body = self.block(&try_catch.body);
+
+                self.scopes.push(HashMap::new());
+                let error = self.fresh();
+                self.define(try_catch.name, Named::Binding(error));
+                let handler = self.block(&try_catch.handler);
+                self.scopes.pop();
+
+                if self.unify(&body, &handler) {
+                    body
+                } else {
+                    self.fresh()
+                }
+            }
+        }
+    }
+
+    fn operation(&mut self, op: &Op, lhs: Ty, rhs: Ty, span: &Range<usize>) -> Ty {
+        match op {
+            Op::Eq | Op::Ne => BOOL,
+            Op::Lt | Op::Le | Op::Gt | Op::Ge => {
+                match (self.resolve(&lhs), self.resolve(&rhs)) {
+                    (Ty::Var(_), Ty::Var(_)) => {}
+                    // Only numbers and strings can be ordered, so the other side has to match.
+                    (Ty::Var(_), Ty::Con(Type::Number | Type::Str))
+                    | (Ty::Con(Type::Number | Type::Str), Ty::Var(_)) => {
+                        self.unify(&lhs, &rhs);
+                    }
+                    (Ty::Var(_), _) | (_, Ty::Var(_)) => {}
+                    (lhs, rhs) => {
+                        if lhs != rhs || !matches!(lhs, Ty::Con(Type::Number | Type::Str)) {
+                            let message = format!(
+                                "cannot compare {} with {}",
+                                self.show(&lhs),
+                                self.show(&rhs)
+                            );
+                            self.error(message, Some(span.clone()));
+                        }
+                    }
+                }
+
+                BOOL
+            }
+            Op::Coalesce => match self.resolve(&lhs) {
+                Ty::Con(Type::Nil) => rhs,
+                Ty::Var(_) => self.fresh(),
+                _ => lhs,
+            },
+            _ => {
+                let lhs_is_number = self.unify(&lhs, &NUMBER);
+                let rhs_is_number = self.unify(&rhs, &NUMBER);
+
+                if !lhs_is_number || !rhs_is_number {
+                    let message = format!(
+                        "cannot apply {} to {} and {}",
+                        crate::fmt::op_symbol(op),
+                        self.show(&lhs),
+                        self.show(&rhs)
+                    );
+                    self.error(message, Some(span.clone()));
+                }
+
+                match op {
+                    Op::Range | Op::RangeInclusive => Ty::Con(Type::Range),
+                    _ => NUMBER,
+                }
+            }
+        }
+    }
+
+    /// Defines what `pattern` binds, given that it is matched against a value of type `ty`.
+    fn pattern(&mut self, pattern: &Pattern, ty: &Ty) {
+        match pattern {
+            Pattern::Wildcard | Pattern::Literal(_) => {}
+            Pattern::Binding(name) => self.define(*name, Named::Binding(ty.clone())),
+            Pattern::Variant { name, fields } => {
+                if let Some(Named::Func {
+                    required,
+                    max: Some(max),
+                    ..
+                }) = self.lookup(*name)
+                {
+                    if required == max && max != fields.len() {
+                        let message = format!(
+                            "pattern gives {} fields of '{}', which has {}",
+                            fields.len(),
+                            name,
+                            max
+                        );
+                        self.error(message, None);
+                    }
+                }
+
+                for field in fields {
+                    let field_ty = self.fresh();
+                    self.pattern(field, &field_ty);
+                }
+            }
+        }
+    }
+
+    /// The constructor of a variant with `arity` fields, which takes anything.
+    fn constructor(&mut self, arity: usize) -> Named {
+        let params = (0..arity).map(|_| self.fresh()).collect();
+        let ty = Ty::Func(params, Box::new(VARIANT));
+
+        Named::Func {
+            params: Vec::new(),
+            required: arity,
+            max: Some(arity),
+            scheme: self.generalize(&ty),
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) -> Vec<Ty> {
+        exprs.iter().map(|expr| self.expr(expr)).collect()
+    }
+
+    fn block(&mut self, block: &Block) -> Ty {
+        self.scopes.push(HashMap::new());
+
+        let mut ty = UNIT;
+        for stmt in &block.stmts {
+            ty = self.stmt(stmt);
+        }
+
+        self.scopes.pop();
+        ty
+    }
+
+    /// Infers the type of a function from its body, generalizing over whatever its parameters
+    /// aren't constrained to be.
+    fn func(&mut self, name: Symbol, params: &[Param], body: &Stmt) -> Named {
+        let param_tys: Vec<_> = params
+            .iter()
+            .map(|param| match param.ty {
+                Some(ty) => Ty::Con(ty),
+                None if param.rest => Ty::Con(Type::List),
+                None => self.fresh(),
+            })
+            .collect();
+        let ret = self.fresh();
+        let ty = Ty::Func(param_tys.clone(), Box::new(ret.clone()));
+        let param_names: Vec<_> = params.iter().map(|param| param.name).collect();
+        let required = func_def::required_params(params);
+        let max = func_def::max_params(params);
+
+        // Defined before checking the body so that recursive calls are checked too.
+        self.define(
+            name,
+            Named::Func {
+                params: param_names.clone(),
+                required,
+                max,
+                scheme: Scheme {
+                    vars: Vec::new(),
+                    ty: ty.clone(),
+                },
+            },
+        );
+
+        self.scopes.push(HashMap::new());
+        for (param, param_ty) in params.iter().zip(param_tys) {
+            if let Some(default) = &param.default {
+                let found = self.expr(default);
+
+                if !self.unify(&param_ty, &found) {
+                    let message = format!(
+                        "expected default of parameter '{}' of '{}' to be {}, got {}",
+                        param.name,
+                        name,
+                        self.show(&param_ty),
+                        self.show(&found)
+                    );
+                    self.error(message, Some(default.span()));
+                }
+            }
+
+            self.define(param.name, Named::Binding(param_ty));
+        }
+        let body = self.stmt(body);
+        self.scopes.pop();
+
+        // A recursive call may already have pinned down the result differently, which only fails
+        // when the program runs, if at all.
+        self.unify(&ret, &body);
+
+        self.scopes
+            .last_mut()
+            .expect("there is always a scope")
+            .remove(&name);
+
+        Named::Func {
+            params: param_names,
+            required,
+            max,
+            scheme: self.generalize(&ty),
+        }
+    }
+
+    fn func_call(&mut self, func_call: &FuncCall) -> Ty {
+        let args = self.exprs(&func_call.params);
+        let named: Vec<_> = func_call
+            .named
+            .iter()
+            .map(|(name, val)| (*name, self.expr(val)))
+            .collect();
+        let span = Some(func_call.span.0.clone());
+
+        let (names, required, max, scheme) = match self.lookup(func_call.callee) {
+            Some(Named::Func {
+                params,
+                required,
+                max,
+                scheme,
+            }) => (params, required, max, scheme),
+            // A binding can hold a partial application, which takes anything.
+            Some(Named::Binding(ty) | Named::Const(ty)) => {
+                if !self.unify(&ty, &Ty::Con(Type::Func)) {
+                    self.error(format!("'{}' is not a function", func_call.callee), span);
+                }
+
+                return self.fresh();
+            }
+            None => return self.fresh(),
+        };
+
+        let (params, ret) = match self.instantiate(&scheme) {
+            Ty::Func(params, ret) => (params, *ret),
+            _ => unreachable!("functions always have function types"),
+        };
+
+        // Named arguments can fill in any parameter, so counting them only tells whether there
+        // are too many.
+        let given = args.len() + named.len();
+        if named.is_empty() || max.is_some_and(|max| given > max) {
+            if let Err(error) =
+                func_def::check_arity(func_call.callee, required, max, given, &func_call.span)
+            {
+                self.error(error.to_string(), span);
+                return self.fresh();
+            }
+        }
+
+        // Extra arguments go to the rest parameter, if there is one, so they aren't checked.
+        let checked = max.unwrap_or(params.len() - 1);
+        let mut given: Vec<_> = args.into_iter().take(checked).enumerate().collect();
+
+        for (name, arg) in named {
+            match names[..checked.min(names.len())]
+                .iter()
+                .position(|param| *param == name)
+            {
+                Some(idx) if given.iter().any(|(given, _)| *given == idx) => {
+                    let message = format!(
+                        "parameter '{}' of '{}' was given twice",
+                        name, func_call.callee
+                    );
+                    self.error(message, span.clone());
+                }
+                Some(idx) => given.push((idx, arg)),
+                None if names.is_empty() => {
+                    let message = format!("'{}' doesn't take named arguments", func_call.callee);
+                    self.error(message, span.clone());
+                }
+                None => {
+                    let message = format!("'{}' has no parameter '{}'", func_call.callee, name);
+                    self.error(message, span.clone());
+                }
+            }
+        }
+
+        for (idx, name) in names.iter().enumerate().take(required) {
+            if !given.iter().any(|(given, _)| *given == idx) {
+                let message = format!(
+                    "missing argument for parameter '{}' of '{}'",
+                    name, func_call.callee
+                );
+                self.error(message, span.clone());
+            }
+        }
+
+        for (idx, arg) in &given {
+            let (idx, param) = (*idx, &params[*idx]);
+            if !self.unify(param, arg) {
+                let what = match names.get(idx) {
+                    Some(name) => format!("parameter '{}'", name),
+                    None => format!("argument {}", idx + 1),
+                };
+                let message = format!(
+                    "expected {} of '{}' to be {}, got {}",
+                    what,
+                    func_call.callee,
+                    self.show(param),
+                    self.show(arg)
+                );
+                self.error(message, span.clone());
+            }
+        }
+
+        if func_call
+            .params
+            .iter()
+            .any(|param| matches!(param, Expr::Placeholder(_)))
+        {
+            Ty::Con(Type::Func)
+        } else {
+            ret
+        }
+    }
+
+    fn lookup(&mut self, name: Symbol) -> Option<Named> {
+        if let Some(named) = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name).cloned())
+        {
+            return Some(named);
+        }
+
+        if let Ok(val) = self.env.get_binding(name) {
+            return Some(Named::Binding(Ty::Con(Type::of(&val))));
+        }
+
+        let named = match self.env.get_func(name).ok()? {
+            // Natives have no types of their own, so they accept and return anything.
+            Func::Native(native) => {
+                let params = (0..native.arity()).map(|_| self.fresh()).collect();
+                let ty = Ty::Func(params, Box::new(self.fresh()));
+
+                Named::Func {
+                    params: Vec::new(),
+                    required: native.arity(),
+                    max: Some(native.arity()),
+                    scheme: self.generalize(&ty),
+                }
+            }
+            Func::Constructor { arity, .. } => self.constructor(arity),
+            Func::User { params, body, .. } => {
+                if !self.inferring.insert(name) {
+                    return None;
+                }
+
+                // The body can only see what the environment defines, not what the program does.
+                let scopes = mem::replace(&mut self.scopes, vec![HashMap::new()]);
+                let func = self.func(name, &params, &body);
+                self.scopes = scopes;
+
+                self.inferring.remove(&name);
+                func
+            }
+        };
+
+        self.scopes[0].insert(name, named.clone());
+        Some(named)
+    }
+
+    fn check_redefinable(&mut self, name: Symbol, span: Option<Range<usize>>) {
+        let scope = self.scopes.last().expect("there is always a scope");
+
+        if let Some(Named::Const(_)) = scope.get(&name) {
+            self.error(format!("cannot redefine constant '{}'", name), span);
+        }
+    }
+
+    fn define(&mut self, name: Symbol, named: Named) {
+        self.scopes
+            .last_mut()
+            .expect("there is always a scope")
+            .insert(name, named);
+    }
+
+    fn fresh(&mut self) -> Ty {
+        self.subst.push(None);
+        Ty::Var(self.subst.len() - 1)
+    }
+
+    /// Follows type variables that have been found to be something.
+    fn resolve(&self, ty: &Ty) -> Ty {
+        let mut ty = ty.clone();
+
+        while let Ty::Var(var) = ty {
+            match &self.subst[var] {
+                Some(found) => ty = found.clone(),
+                None => break,
+            }
+        }
+
+        ty
+    }
+
+    /// Like [`Checker::resolve`], but all the way through function types.
+    fn zonk(&self, ty: &Ty) -> Ty {
+        match self.resolve(ty) {
+            Ty::Func(params, ret) => Ty::Func(
+                params.iter().map(|param| self.zonk(param)).collect(),
+                Box::new(self.zonk(&ret)),
+            ),
+            ty => ty,
+        }
+    }
+
+    /// Makes two types the same if they can be, leaving everything as it was if they can't.
+    fn unify(&mut self, a: &Ty, b: &Ty) -> bool {
+        let mut bound = Vec::new();
+        let unified = self.unify_into(a, b, &mut bound);
+
+        if !unified {
+            for var in bound {
+                self.subst[var] = None;
+            }
+        }
+
+        unified
+    }
+
+    fn unify_into(&mut self, a: &Ty, b: &Ty, bound: &mut Vec<usize>) -> bool {
+        match (self.resolve(a), self.resolve(b)) {
+            (Ty::Var(a), Ty::Var(b)) if a == b => true,
+            (Ty::Var(var), ty) | (ty, Ty::Var(var)) => {
+                if free_vars(&self.zonk(&ty)).contains(&var) {
+                    return false;
+                }
+
+                self.subst[var] = Some(ty);
+                bound.push(var);
+                true
+            }
+            (Ty::Con(a), Ty::Con(b)) => a == b,
+            (Ty::Func(a_params, a_ret), Ty::Func(b_params, b_ret)) => {
+                a_params.len() == b_params.len()
+                    && a_params
+                        .iter()
+                        .zip(&b_params)
+                        .all(|(a, b)| self.unify_into(a, b, bound))
+                    && self.unify_into(&a_ret, &b_ret, bound)
+            }
+            _ => false,
+        }
+    }
+
+    fn generalize(&self, ty: &Ty) -> Scheme {
+        let ty = self.zonk(ty);
+
+        let mut in_scope = HashSet::new();
+        for named in self.scopes.iter().flat_map(HashMap::values) {
+            match named {
+                Named::Binding(ty) | Named::Const(ty) => in_scope.extend(free_vars(&self.zonk(ty))),
+                Named::Func { scheme, .. } => in_scope.extend(
+                    free_vars(&self.zonk(&scheme.ty))
+                        .into_iter()
+                        .filter(|var| !scheme.vars.contains(var)),
+                ),
+            }
+        }
+
+        let mut vars = free_vars(&ty);
+        vars.retain(|var| !in_scope.contains(var));
+
+        Scheme { vars, ty }
+    }
+
+    fn instantiate(&mut self, scheme: &Scheme) -> Ty {
+        let fresh: HashMap<_, _> = scheme.vars.iter().map(|&var| (var, self.fresh())).collect();
+
+        replace_vars(&self.zonk(&scheme.ty), &fresh)
+    }
+
+    /// Writes a type the way annotations and `:type` do, naming unknown types `a`, `b` and so on.
+    fn show(&self, ty: &Ty) -> String {
+        let ty = self.zonk(ty);
+        let names: HashMap<_, _> = free_vars(&ty)
+            .into_iter()
+            .enumerate()
+            .map(|(idx, var)| (var, var_name(idx)))
+            .collect();
+
+        show(&ty, &names)
+    }
+
+    fn error(&mut self, message: String, span: Option<Range<usize>>) {
+        self.errors.push(TypeError { message, span });
+    }
+}
+
+/// The type variables in `ty`, in the order they first appear.
+fn free_vars(ty: &Ty) -> Vec<usize> {
+    fn collect(ty: &Ty, vars: &mut Vec<usize>) {
+        match ty {
+            Ty::Var(var) => {
+                if !vars.contains(var) {
+                    vars.push(*var);
+                }
+            }
+            Ty::Con(_) => {}
+            Ty::Func(params, ret) => {
+                for param in params {
+                    collect(param, vars);
+                }
+                collect(ret, vars);
+            }
+        }
+    }
+
+    let mut vars = Vec::new();
+    collect(ty, &mut vars);
+    vars
+}
+
+fn replace_vars(ty: &Ty, replacements: &HashMap<usize, Ty>) -> Ty {
+    match ty {
+        Ty::Var(var) => replacements.get(var).cloned().unwrap_or(Ty::Var(*var)),
+        Ty::Con(_) => ty.clone(),
+        Ty::Func(params, ret) => Ty::Func(
+            params
+                .iter()
+                .map(|param| replace_vars(param, replacements))
+                .collect(),
+            Box::new(replace_vars(ret, replacements)),
+        ),
+    }
+}
+
+fn var_name(idx: usize) -> String {
+    let letter = char::from(b'a' + (idx % 26) as u8);
+
+    match idx / 26 {
+        0 => letter.to_string(),
+        n => format!("{}{}", letter, n),
+    }
+}
+
+fn show(ty: &Ty, names: &HashMap<usize, String>) -> String {
+    match ty {
+        Ty::Var(var) => names[var].clone(),
+        Ty::Con(ty) => ty.to_string(),
+        Ty::Func(params, ret) => format!(
+            "fn({}) -> {}",
+            params
+                .iter()
+                .map(|param| show(param, names))
+                .collect::<Vec<_>>()
+                .join(", "),
+            show(ret, names)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(s: &str) -> Vec<TypeError> {
+        crate::parse(s).unwrap().check()
+    }
+
+    fn messages(s: &str) -> Vec<String> {
+        check(s).into_iter().map(|error| error.message).collect()
+    }
+
+    #[test]
+    fn well_typed_program_has_no_errors() {
+        assert_eq!(
+            check("fn add x: number, y: number => x + y\nlet a = add(1, 2)\n[a, a][0] ?? 1"),
+            Vec::new(),
+        );
+    }
+
+    #[test]
+    fn add_number_to_block() {
+        assert_eq!(
+            check("let a = 1 + { [] }"),
+            vec![TypeError {
+                message: "cannot apply + to number and list".to_string(),
+                span: Some(8..18),
+            }],
+        );
+    }
+
+    #[test]
+    fn check_compound_assignments() {
+        assert_eq!(
+            check("let s = \"a\"\ns -= 1\nconst n = 1\nn += 1"),
+            vec![
+                TypeError {
+                    message: "cannot apply - to string and number".to_string(),
+                    span: Some(12..18),
+                },
+                TypeError {
+                    message: "cannot assign to constant 'n'".to_string(),
+                    span: Some(31..37),
+                },
+            ],
+        );
+        assert_eq!(check("let n = 1\nn %= 2 + 1"), Vec::new());
+    }
+
+    #[test]
+    fn point_at_code_without_an_operator_of_its_own() {
+        assert_eq!(
+            check("let s: string = 1\nfor i in true { ~\"a\" }")
+                .into_iter()
+                .map(|error| error.span)
+                .collect::<Vec<_>>(),
+            [Some(0..17), Some(27..31), Some(34..38)],
+        );
+    }
+
+    #[test]
+    fn call_non_function() {
+        assert_eq!(
+            check("let x = 1\nx(2)"),
+            vec![TypeError {
+                message: "'x' is not a function".to_string(),
+                span: Some(10..14),
+            }],
+        );
+    }
+
+    #[test]
+    fn call_with_wrong_arity() {
+        assert_eq!(
+            messages("fn f a b => a\nf 1\nassert(true)"),
+            [
+                "function `f` expects 2 arguments but got 1",
+                "function `assert` expects 2 arguments but got 1",
+            ],
+        );
+    }
+
+    #[test]
+    fn call_with_wrong_argument_type() {
+        assert_eq!(
+            messages("fn inc n: number => n + 1\ninc \"one\""),
+            ["expected parameter 'n' of 'inc' to be number, got string"],
+        );
+    }
+
+    #[test]
+    fn binding_with_wrong_annotation() {
+        assert_eq!(
+            messages("let b: bool = 1 < 2\nlet s: string = ()"),
+            ["expected 's' to be string, got unit"],
+        );
+    }
+
+    #[test]
+    fn call_with_defaults() {
+        assert_eq!(
+            messages("fn f x, y = 1 => x + y\nf(1)\nf(1, 2)\nf()\nf(1, \"2\")"),
+            [
+                "function `f` expects 1 to 2 arguments but got 0",
+                "expected parameter 'y' of 'f' to be number, got string",
+            ],
+        );
+        assert_eq!(
+            messages("fn f y: string = 1 => y"),
+            ["expected default of parameter 'y' of 'f' to be string, got number"],
+        );
+    }
+
+    #[test]
+    fn call_with_rest() {
+        assert_eq!(
+            messages("fn f x: number, ...xs => xs\nf(1, \"a\", true)\nf(\"a\")\nf()\nf(1) < 2"),
+            [
+                "expected parameter 'x' of 'f' to be number, got string",
+                "function `f` expects at least 1 argument but got 0",
+                "cannot compare list with number",
+            ],
+        );
+    }
+
+    #[test]
+    fn call_with_named_args() {
+        assert_eq!(
+            messages(
+                "fn f x: number, y: string => y\nf(y: \"a\", x: 1)\nf(y: 1, x: 1)\nf(1, x: 2)\nf(y: \"a\", z: 1)"
+            ),
+            [
+                "expected parameter 'y' of 'f' to be string, got number",
+                "parameter 'x' of 'f' was given twice",
+                "missing argument for parameter 'y' of 'f'",
+                "'f' has no parameter 'z'",
+                "missing argument for parameter 'x' of 'f'",
+            ],
+        );
+    }
+
+    #[test]
+    fn call_partial_application() {
+        assert_eq!(
+            messages(
+                "fn add x y => x + y\nlet inc = add(1, _)\ninc(2) + 1\nadd(\"a\", _)\ninc + 1"
+            ),
+            [
+                "expected parameter 'x' of 'add' to be number, got string",
+                "cannot apply + to function and number",
+            ],
+        );
+        assert_eq!(
+            messages("fn add x y => x + y\nfn apply f x => f(x)\napply(add(1, _), 2)\napply(1, 2)"),
+            ["expected parameter 'f' of 'apply' to be function, got number"],
+        );
+    }
+
+    #[test]
+    fn redefine_const() {
+        assert_eq!(
+            messages("const max = 3\n{ let max = 4 }\nlet max = 5"),
+            ["cannot redefine constant 'max'"],
+        );
+        assert_eq!(
+            messages("const max = 3\nfn max => 6"),
+            ["cannot redefine constant 'max'"],
+        );
+    }
+
+    #[test]
+    fn track_types_through_bindings_and_scopes() {
+        assert_eq!(
+            messages("let s = \"a\"\n{ let s = 1\ns < 2 }\ns < 2"),
+            ["cannot compare string with number"],
+        );
+    }
+
+    #[test]
+    fn let_unknown_types_through() {
+        assert_eq!(
+            check("fn f x => x + 1\nf(1) + 1\nunknown(true)\ntry { 1 } catch e { e + 1 }"),
+            Vec::new(),
+        );
+    }
+
+    #[test]
+    fn infer_without_annotations() {
+        assert_eq!(
+            messages("fn add x y => x + y\nadd(1, \"2\")"),
+            ["expected parameter 'y' of 'add' to be number, got string"],
+        );
+        assert_eq!(
+            messages("fn twice f => f\nfn wrap x => twice(x) < \"a\"\nwrap(1)"),
+            ["expected parameter 'x' of 'wrap' to be string, got number"],
+        );
+    }
+
+    #[test]
+    fn generic_functions_take_anything() {
+        assert_eq!(
+            check("fn id x => x\nid(1) + 1\nid(\"a\") < \"b\""),
+            Vec::new()
+        );
+        assert_eq!(
+            messages("fn id x => x\nid(true) + 1"),
+            ["cannot apply + to bool and number"],
+        );
+    }
+
+    #[test]
+    fn infer_types() {
+        let mut env = Env::default();
+        crate::parse("fn add x y => x + y\nfn pick a b => a\nlet s = \"s\"")
+            .unwrap()
+            .eval(&mut env)
+            .unwrap();
+
+        let infer = |s: &str| crate::parse(s).unwrap().infer(&env);
+
+        assert_eq!(infer("add"), Ok("fn(number, number) -> number".to_string()));
+        assert_eq!(infer("pick"), Ok("fn(a, b) -> a".to_string()));
+        assert_eq!(infer("pick(s, 1)"), Ok("string".to_string()));
+        assert_eq!(
+            infer("fn f n => n < 1\nf"),
+            Ok("fn(number) -> bool".to_string())
+        );
+        assert_eq!(infer("now_ms"), Ok("fn() -> a".to_string()));
+        assert_eq!(
+            infer("add s"),
+            Err(vec![TypeError {
+                message: "function `add` expects 2 arguments but got 1".to_string(),
+                span: Some(0..5),
+            }]),
+        );
+    }
+
+    #[test]
+    fn infer_types_of_definitions() {
+        assert_eq!(
+            crate::parse("let a = 1\nfn f x => { let b = [x]\nb }\nconst c = f(\"c\")")
+                .unwrap()
+                .definition_types(&Env::default()),
+            [
+                (0..9, "number".to_string()),
+                (22..33, "list".to_string()),
+                (10..37, "fn(a) -> list".to_string()),
+                (38..54, "list".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn infer_recursive_function() {
+        let infer = |s: &str| crate::parse(s).unwrap().infer(&Env::default());
+
+        assert_eq!(
+            infer("fn count n => { count(n - 1) }\ncount"),
+            Ok("fn(number) -> a".to_string()),
+        );
+    }
+
+    #[test]
+    fn index_into_number() {
+        assert_eq!(
+            messages("let n = 1\nn[0]\nnil?[0]"),
+            ["cannot index into number"]
+        );
+    }
+
+    #[test]
+    fn infer_constructors_and_match() {
+        let infer = |s: &str| crate::parse(s).unwrap().infer(&Env::default());
+
+        assert_eq!(
+            infer(
+                "enum Opt { Some(x), None }\nfn get o => match o { Some(x) => x + 1, None => 0 }\nget",
+            ),
+            Ok("fn(a) -> number".to_string()),
+        );
+        assert_eq!(
+            messages("enum Opt { Some(x), None }\nSome(1, 2)\nmatch None { Some(a, b) => 0 }"),
+            [
+                "function `Some` expects 1 argument but got 2",
+                "pattern gives 2 fields of 'Some', which has 1",
+            ],
+        );
+    }
+
+    #[test]
+    fn access_field_of_non_record() {
+        assert_eq!(
+            messages(
+                "let p = {x: 1}
+p.x + 1
+[p].x"
+            ),
+            ["cannot access field 'x' of list"]
+        );
+    }
+
+    #[test]
+    fn index_strings() {
+        assert_eq!(
+            messages("let s = \"abc\"\ns[0] + 1\ns[0..2] + 1\ns?[0] ?? 1\n[1][0..1] + 1"),
+            [
+                "cannot apply + to string and number",
+                "cannot apply + to string and number",
+                "cannot apply + to list and number",
+            ],
+        );
+    }
+
+    #[test]
+    fn check_for_loops() {
+        assert_eq!(
+            messages("for c in \"ab\" { c + 1 }\nfor x in 1 { x }\nfor x in [1] { x + 1 }"),
+            [
+                "cannot apply + to string and number",
+                "cannot iterate over number",
+            ],
+        );
+        assert_eq!(
+            messages("for n in 0..3 { n + \"a\" }\nlet r: range = 1..=\"b\""),
+            [
+                "cannot apply + to number and string",
+                "cannot apply ..= to number and string",
+            ],
+        );
+    }
+}
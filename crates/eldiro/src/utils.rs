@@ -1,164 +1,522 @@
-pub(crate) fn take_while(accept: impl Fn(char) -> bool, s: &str) -> (&str, &str) {
-    let extracted_end = s
-        .char_indices()
-        .find_map(|(idx, c)| if accept(c) { None } else { Some(idx) })
-        .unwrap_or_else(|| s.len());
+use crate::compat::prelude::*;
+use crate::lexer::{self, Token, TokenKind};
+use crate::span::Span;
+use alloc::collections::BTreeMap;
+use core::ops::Range;
 
-    let extracted = &s[..extracted_end];
-    let remainder = &s[extracted_end..];
-    (remainder, extracted)
+/// Runs a token parser over `s`, mapping the tokens it didn't consume back to the rest of `s`.
+pub(crate) fn parse_str<T>(
+    parser: impl Fn(&[Token]) -> Result<(&[Token], T), String>,
+    s: &str,
+) -> Result<(&str, T), String> {
+    let tokens = lexer::lex(s);
+    let (remainder, parsed) = parser(&tokens)?;
+
+    let consumed = remainder.first().map_or(s.len(), |token| token.span.start);
+    Ok((&s[consumed..], parsed))
 }
+
 pub(crate) fn sequence<T>(
-    parser: impl Fn(&str) -> Result<(&str, T), String>,
-    separator_parser: impl Fn(&str) -> (&str, &str),
-    mut s: &str,
-) -> Result<(&str, Vec<T>), String> {
+    parser: impl Fn(&[Token]) -> Result<(&[Token], T), String>,
+    mut tokens: &[Token],
+) -> Result<(&[Token], Vec<T>), String> {
     let mut items = Vec::new();
 
-    while let Ok((new_s, item)) = parser(s) {
-        s = new_s;
+    while let Ok((new_tokens, item)) = parser(tokens) {
+        tokens = new_tokens;
         items.push(item);
+    }
+
+    Ok((tokens, items))
+}
+
+/// The error for a statement that starts on the same line as the one before it ends.
+pub(crate) const SAME_LINE_STMTS: &str = "expected a line break before this statement";
 
-        let (new_s, _) = separator_parser(s);
-        s = new_s;
+/// Parses statements one after another like [`sequence`], but stops before any that doesn't start
+/// on a line of its own, since nothing else separates two statements. Otherwise `1 + 2 3` would
+/// quietly be two of them.
+pub(crate) fn lines<T>(
+    parser: impl Fn(&[Token]) -> Result<(&[Token], T), String>,
+    mut tokens: &[Token],
+) -> Result<(&[Token], Vec<T>), String> {
+    let mut items = Vec::new();
+
+    while items.is_empty() || tokens.first().is_some_and(|token| token.newline_before) {
+        match parser(tokens) {
+            Ok((new_tokens, item)) => {
+                tokens = new_tokens;
+                items.push(item);
+            }
+            Err(_) => break,
+        }
     }
 
-    Ok((s, items))
+    Ok((tokens, items))
 }
 
 pub(crate) fn sequence1<T>(
-    parser: impl Fn(&str) -> Result<(&str, T), String>,
-    separator_parser: impl Fn(&str) -> (&str, &str),
-    s: &str,
-) -> Result<(&str, Vec<T>), String> {
-    let (s, sequence) = sequence(parser, separator_parser, s)?;
+    parser: impl Fn(&[Token]) -> Result<(&[Token], T), String>,
+    tokens: &[Token],
+) -> Result<(&[Token], Vec<T>), String> {
+    let (tokens, sequence) = sequence(parser, tokens)?;
 
     if sequence.is_empty() {
         Err("expected a sequence with more than one item".to_string())
     } else {
-        Ok((s, sequence))
+        Ok((tokens, sequence))
     }
 }
 
-pub(crate) fn tag<'a, 'b>(starting_text: &'a str, s: &'b str) -> Result<&'b str, String> {
-    if s.starts_with(starting_text) {
-        Ok(&s[starting_text.len()..])
-    } else {
-        Err(format!("expected {}", starting_text))
+/// Parses items separated by commas up to and including `close`, such as the arguments after the
+/// `(` of a call.
+pub(crate) fn comma_separated<T>(
+    parser: impl Fn(&[Token]) -> Result<(&[Token], T), String>,
+    close: TokenKind,
+    mut tokens: &[Token],
+) -> Result<(&[Token], Vec<T>), String> {
+    let mut items = Vec::new();
+
+    loop {
+        if let Ok(new_tokens) = expect(close.clone(), tokens) {
+            return Ok((new_tokens, items));
+        }
+
+        if !items.is_empty() {
+            tokens = expect(TokenKind::Comma, tokens)?;
+        }
+
+        let (new_tokens, item) = parser(tokens)?;
+        tokens = new_tokens;
+        items.push(item);
     }
 }
-pub(crate) fn extract_op(s: &str) -> (&str, &str) {
-    let op = &s[0..1];
-    if !(op == "+" || op == "-" || op == "*" || op == "/") {
-        panic!("bad operation\n")
+
+pub(crate) fn expect(kind: TokenKind, tokens: &[Token]) -> Result<&[Token], String> {
+    match tokens.split_first() {
+        Some((token, tokens)) if token.kind == kind => Ok(tokens),
+        _ => Err(format!("expected {}", kind)),
     }
+}
 
-    (&s[1..], op)
+pub(crate) fn keyword<'t>(word: &str, tokens: &'t [Token]) -> Result<&'t [Token], String> {
+    match tokens.split_first() {
+        Some((
+            Token {
+                kind: TokenKind::Ident(ident),
+                ..
+            },
+            tokens,
+        )) if ident == word => Ok(tokens),
+        _ => Err(format!("expected {}", word)),
+    }
 }
 
-fn take_while1(
-    accept: impl Fn(char) -> bool,
-    s: &str,
-    error_msg: String,
-) -> Result<(&str, &str), String> {
-    let (remainder, extracted) = take_while(accept, s);
+/// Extracts an identifier that can be used as a name, so anything but a keyword.
+pub(crate) fn extract_ident(tokens: &[Token]) -> Result<(&[Token], &str), String> {
+    match tokens.split_first() {
+        Some((
+            Token {
+                kind: TokenKind::Ident(ident),
+                ..
+            },
+            _,
+        )) if crate::KEYWORDS.contains(&ident.as_str()) => {
+            Err(format!("`{}` is a reserved keyword", ident))
+        }
+        Some((
+            Token {
+                kind: TokenKind::Ident(ident),
+                ..
+            },
+            tokens,
+        )) => Ok((tokens, ident)),
+        _ => Err("expected identifier".to_string()),
+    }
+}
 
-    if extracted.is_empty() {
-        Err(error_msg)
-    } else {
-        Ok((remainder, extracted))
+/// The span covered by the tokens consumed between `before` and `after`, where `after` is what's
+/// left of `before` once something has been parsed from it.
+pub(crate) fn consumed_span(before: &[Token], after: &[Token]) -> Span {
+    let consumed = &before[..before.len() - after.len()];
+
+    match (consumed.first(), consumed.last()) {
+        (Some(first), Some(last)) => Span(first.span.start..last.span.end),
+        _ => Span::default(),
     }
 }
 
-const WHITESPACE: &[char] = &[' ', '\n'];
+/// How deeply expressions, patterns and function bodies can be nested inside each other. Any
+/// deeper and parsing fails rather than overflowing the stack, both here and in everything that
+/// walks the tree afterwards.
+pub(crate) const MAX_DEPTH: usize = 64;
+
+/// How tall chains of operators, pipes, indexes and method calls can make the tree, counting
+/// every chain the parser is inside at once and everything that hangs off them. Each link of a
+/// chain wraps everything before it in one more node, so a chain makes as deep a tree as nesting
+/// does, but chains are built in a loop and long ones are common, so they get a limit of their
+/// own that is much larger.
+pub(crate) const MAX_CHAIN: usize = 1024;
 
-pub(crate) fn extract_whitespace(s: &str) -> (&str, &str) {
-    take_while(|c| WHITESPACE.contains(&c), s)
+/// What the parser keeps track of while it is inside a top-level statement, which it forgets
+/// once it is back out.
+struct Nesting {
+    depth: usize,
+    /// The links of every chain being parsed.
+    links: usize,
+    /// How tall the tallest thing is that has been parsed since the innermost [`Depth`] was
+    /// made.
+    height: usize,
+    /// Where the parser last went past [`MAX_DEPTH`] or [`MAX_CHAIN`] and the error for it, kept
+    /// until [`too_deep`] is called.
+    too_deep_at: Option<(Range<usize>, String)>,
+    /// How many times the parser has gone past [`MAX_DEPTH`] or [`MAX_CHAIN`] in this statement.
+    limits_hit: usize,
+    /// What failed to parse with [`once`], by what it was and the address and length of its
+    /// tokens.
+    failed: BTreeMap<(Memo, usize, usize), Failure>,
 }
 
-pub(crate) fn extract_whitespace1(s: &str) -> Result<(&str, &str), String> {
-    take_while1(
-        |c| WHITESPACE.contains(&c),
-        s,
-        "expected whitespace".to_string(),
-    )
+/// Something that failed to parse, and whether that was only because of where it was.
+struct Failure {
+    /// How deep and how far into chains the parser was, if the failure came from going past a
+    /// limit. Parsing the same tokens fails again anywhere at least as deep and as far in, and
+    /// anywhere at all otherwise.
+    limited_at: Option<(usize, usize)>,
+    message: String,
 }
 
-pub(crate) fn extract_digits(s: &str) -> Result<(&str, &str), String> {
-    take_while1(|c| c.is_ascii_digit(), s, "expected digits".to_string())
+/// The things that [`once`] can keep from being parsed more than once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Memo {
+    Block,
+    Parenthesized,
+    List,
+    CallArgs,
 }
 
-pub(crate) fn extract_ident(s: &str) -> Result<(&str, &str), String> {
-    let input_starts_with_alphabetic = s
-        .chars()
-        .next()
-        .map(|c| c.is_ascii_alphabetic())
-        .unwrap_or(false);
+impl Nesting {
+    const fn new() -> Self {
+        Self {
+            depth: 0,
+            links: 0,
+            height: 0,
+            too_deep_at: None,
+            limits_hit: 0,
+            failed: BTreeMap::new(),
+        }
+    }
+}
 
-    if input_starts_with_alphabetic {
-        Ok(take_while(|c| c.is_ascii_alphanumeric(), s))
-    } else {
-        Err("expected identifier".to_string())
+#[cfg(feature = "std")]
+std::thread_local! {
+    static NESTING: core::cell::RefCell<Nesting> = const { core::cell::RefCell::new(Nesting::new()) };
+}
+
+/// Without `std` there are no thread locals, so every thread shares this, and only one of them
+/// can be parsing at a time.
+#[cfg(not(feature = "std"))]
+static NESTING: crate::compat::Mutex<Nesting> = crate::compat::Mutex::new(Nesting::new());
+
+fn with_nesting<T>(f: impl FnOnce(&mut Nesting) -> T) -> T {
+    #[cfg(feature = "std")]
+    return NESTING.with(|nesting| f(&mut nesting.borrow_mut()));
+    #[cfg(not(feature = "std"))]
+    return f(&mut NESTING.lock());
+}
+
+/// Levels of nesting and links of chains that last until this is dropped. Every parser that can
+/// end up calling itself enters one first, and every loop that wraps what it has parsed so far in
+/// something else starts a chain and adds a link each time round.
+///
+/// A node is as tall as its tallest child plus its own levels and links. Checking each new link
+/// against the tallest child parsed so far catches a chain built on something tall, and counting
+/// the links of the chains around it catches something tall built inside a chain.
+pub(crate) struct Depth {
+    levels: usize,
+    links: usize,
+    /// How tall the tallest thing parsed before this was made is.
+    outer_height: usize,
+}
+
+impl Depth {
+    pub(crate) fn enter(tokens: &[Token]) -> Result<Self, String> {
+        let mut depth = Self::chain();
+        depth.deeper(tokens)?;
+        Ok(depth)
+    }
+
+    /// Starts a chain without going any deeper.
+    pub(crate) fn chain() -> Self {
+        Self {
+            levels: 0,
+            links: 0,
+            outer_height: with_nesting(|nesting| core::mem::take(&mut nesting.height)),
+        }
+    }
+
+    /// Goes one level deeper, failing if that is deeper than [`MAX_DEPTH`].
+    fn deeper(&mut self, tokens: &[Token]) -> Result<(), String> {
+        self.levels += 1;
+        count(tokens, |nesting| {
+            nesting.depth += 1;
+            (nesting.depth > MAX_DEPTH)
+                .then(|| format!("nested more than {} levels deep", MAX_DEPTH))
+        })
     }
+
+    /// Adds one more link to the chain, failing if that could make the tree taller than
+    /// [`MAX_CHAIN`].
+    pub(crate) fn link(&mut self, tokens: &[Token]) -> Result<(), String> {
+        self.links += 1;
+        count(tokens, |nesting| {
+            nesting.links += 1;
+            (nesting.links + nesting.height > MAX_CHAIN)
+                .then(|| format!("more than {} operations chained together", MAX_CHAIN))
+        })
+    }
+}
+
+/// Counts something with `add`, which gives an error if that went past a limit. The error is
+/// recorded as being at the start of `tokens`.
+fn count(tokens: &[Token], add: impl FnOnce(&mut Nesting) -> Option<String>) -> Result<(), String> {
+    with_nesting(|nesting| match add(nesting) {
+        Some(message) => {
+            nesting.limits_hit += 1;
+            if let Some(token) = tokens.first() {
+                nesting.too_deep_at = Some((token.span.clone(), message.clone()));
+            }
+            Err(message)
+        }
+        None => Ok(()),
+    })
+}
+
+impl Drop for Depth {
+    fn drop(&mut self) {
+        with_nesting(|nesting| {
+            nesting.depth -= self.levels;
+            nesting.links -= self.links;
+            nesting.height = self
+                .outer_height
+                .max(nesting.height + self.levels + self.links);
+
+            if nesting.depth == 0 && nesting.links == 0 {
+                nesting.height = 0;
+                nesting.limits_hit = 0;
+                nesting.failed.clear();
+            }
+        });
+    }
+}
+
+/// Where parsing last went past [`MAX_DEPTH`] or [`MAX_CHAIN`] and the error for it, if it has
+/// since this was last called.
+pub(crate) fn too_deep() -> Option<(Range<usize>, String)> {
+    with_nesting(|nesting| nesting.too_deep_at.take())
+}
+
+/// Runs `parser`, which parses the kind of thing `memo` names, unless it already failed on the
+/// same tokens in this statement somewhere it would fail again. A name followed by a block or
+/// brackets could be a call with them as its argument, so they are parsed once for that and
+/// again as whatever follows the name, and so on for every name in a row. Without this, input
+/// like `f g h (` repeated would take three times as long for every repeat.
+pub(crate) fn once<'a, T>(
+    memo: Memo,
+    parser: impl FnOnce(&'a [Token]) -> Result<(&'a [Token], T), String>,
+    tokens: &'a [Token],
+) -> Result<(&'a [Token], T), String> {
+    let key = (memo, tokens.as_ptr() as usize, tokens.len());
+    let (before, failed) = with_nesting(|nesting| {
+        let failed = nesting.failed.get(&key).and_then(|failure| {
+            let again = match failure.limited_at {
+                Some((depth, links)) => nesting.depth >= depth && nesting.links >= links,
+                None => true,
+            };
+            again.then(|| failure.message.clone())
+        });
+        ((nesting.limits_hit, nesting.depth, nesting.links), failed)
+    });
+    if let Some(message) = failed {
+        return Err(message);
+    }
+
+    parser(tokens).inspect_err(|message| {
+        with_nesting(|nesting| {
+            let (limits_hit, depth, links) = before;
+            let failure = Failure {
+                limited_at: (nesting.limits_hit > limits_hit).then_some((depth, links)),
+                message: message.clone(),
+            };
+            nesting.failed.insert(key, failure);
+        });
+    })
+}
+
+/// Succeeds without consuming anything if the next token is on the same line as the previous
+/// one.
+pub(crate) fn same_line(tokens: &[Token]) -> Result<&[Token], String> {
+    match tokens.first() {
+        Some(token) if !token.newline_before => Ok(tokens),
+        _ => Err("expected more on the same line".to_string()),
+    }
+}
+
+/// Whether `s` looks like the start of a statement that continues on a later line, because it has
+/// unclosed brackets or strings, or ends with an operator or with a doc comment, which documents
+/// whatever comes after it.
+pub(crate) fn is_incomplete(s: &str) -> bool {
+    let mut depth = 0i32;
+    let mut in_string = false;
+    let mut last = None;
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => in_string = !in_string,
+            '\\' if in_string => {
+                chars.next();
+            }
+            '/' if !in_string && chars.peek() == Some(&'/') => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+                continue;
+            }
+            '#' if !in_string && chars.peek() == Some(&'#') => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+                last = Some('#');
+                continue;
+            }
+            '{' | '(' | '[' if !in_string => depth += 1,
+            '}' | ')' | ']' if !in_string => depth -= 1,
+            _ => {}
+        }
+
+        if !c.is_whitespace() {
+            last = Some(c);
+        }
+    }
+
+    if in_string || depth > 0 {
+        return true;
+    }
+
+    matches!(
+        last,
+        Some(
+            '+' | '-'
+                | '*'
+                | '/'
+                | '='
+                | '<'
+                | '>'
+                | '!'
+                | '&'
+                | '|'
+                | '^'
+                | '~'
+                | '?'
+                | ','
+                | '('
+                | '['
+                | '#'
+        )
+    )
 }
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn extract_newlines_or_spaces() {
-        assert_eq!(extract_whitespace(" \n   \n\nabc"), ("abc", " \n   \n\n"));
+    fn expect_symbol() {
+        assert_eq!(
+            parse_str(
+                |tokens| expect(TokenKind::Eq, tokens).map(|t| (t, ())),
+                "= a"
+            ),
+            Ok(("a", ())),
+        );
+    }
+
+    #[test]
+    fn keyword_must_be_whole_ident() {
+        assert_eq!(
+            parse_str(|tokens| keyword("let", tokens).map(|t| (t, ())), "letter"),
+            Err("expected let".to_string()),
+        );
     }
 
     #[test]
-    fn do_not_extract_spaces1_when_input_does_not_start_with_them() {
+    fn extract_ident_from_tokens() {
         assert_eq!(
-            extract_whitespace1("blah"),
-            Err("expected whitespace".to_string()),
+            parse_str(
+                |tokens| extract_ident(tokens).map(|(t, ident)| (t, ident.to_string())),
+                "abcdEFG stop",
+            ),
+            Ok(("stop", "abcdEFG".to_string())),
         );
     }
 
     #[test]
-    fn extract_alphabetic_ident() {
-        assert_eq!(extract_ident("abcdEFG stop"), Ok((" stop", "abcdEFG")));
+    fn keywords_are_not_idents() {
+        assert_eq!(
+            parse_str(
+                |tokens| extract_ident(tokens).map(|(t, ident)| (t, ident.to_string())),
+                "fn",
+            ),
+            Err("`fn` is a reserved keyword".to_string()),
+        );
     }
 
     #[test]
-    fn extract_alphanumeric_ident() {
-        assert_eq!(extract_ident("foobar1()"), Ok(("()", "foobar1")));
+    fn sequence_stops_at_first_failure() {
+        assert_eq!(
+            parse_str(
+                |tokens| sequence(
+                    |tokens| extract_ident(tokens).map(|(t, ident)| (t, ident.to_string())),
+                    tokens,
+                ),
+                "a b 1 c",
+            ),
+            Ok(("1 c", vec!["a".to_string(), "b".to_string()])),
+        );
     }
 
     #[test]
-    fn extract_one_digit() {
-        assert_eq!(extract_digits("1+2"), Ok(("+2", "1")));
+    fn next_line_is_not_same_line() {
+        assert!(parse_str(|tokens| same_line(tokens).map(|t| (t, ())), "\n1").is_err());
     }
 
     #[test]
-    fn extract_multiple_digits() {
-        assert_eq!(extract_digits("10-20"), Ok(("-20", "10")));
+    fn unclosed_block_is_incomplete() {
+        assert!(is_incomplete("fn f x => {\n    let y = x"));
     }
 
     #[test]
-    fn extract_digits_with_no_remainder() {
-        assert_eq!(extract_digits("100"), Ok(("", "100")));
+    fn trailing_operator_is_incomplete() {
+        assert!(is_incomplete("let a = 1 +  "));
     }
+
     #[test]
-    fn extract_op_test() {
-        assert_eq!(extract_op("+2"), ("2", "+"));
+    fn unterminated_string_is_incomplete() {
+        assert!(is_incomplete(r#"let s = "abc"#));
     }
 
     #[test]
-    #[should_panic]
-    fn extract_op_panic() {
-        extract_op("\\2");
+    fn closed_block_is_complete() {
+        assert!(!is_incomplete("fn f x => {\n    x\n}"));
     }
 
     #[test]
-    fn extract_whilespace_test() {
-        assert_eq!(extract_whitespace("  1+2"), ("1+2", "  "))
+    fn comments_are_ignored() {
+        assert!(is_incomplete("let a = 1 + // more below"));
+        assert!(!is_incomplete("let a = 1 // {"));
+        assert!(is_incomplete("## Adds one to {x}.\n## More."));
+        assert!(!is_incomplete("## Adds one.\nfn inc x => x + 1"));
     }
+
     #[test]
-    fn tag_word() {
-        assert_eq!(tag("let", "let a"), Ok(" a"));
+    fn braces_inside_strings_are_ignored() {
+        assert!(!is_incomplete(r#"let s = "{\"""#));
     }
 }
@@ -1,16 +1,345 @@
-use std::fmt;
+use crate::compat::prelude::*;
+use crate::func_def::Partial;
+use crate::num::Int;
+use crate::symbol::Symbol;
+use core::{fmt, ops, slice, str};
 
+/// A value produced by evaluating eldiro code.
 #[derive(Debug, PartialEq, Clone)]
 pub enum Val {
-    Number(i32),
+    Number(Int),
+    Bool(bool),
+    Str(String),
+    List(Vec<Val>),
+    Tuple(Vec<Val>),
     Unit,
+    /// The absence of a value, such as from indexing past the end of a list with `?[`.
+    Nil,
+    /// A function along with some of its arguments, made by a call like `add(2, _)`.
+    Func(Partial),
+    /// `{x: 1, y: 2}`, with the fields sorted by name so that records with the same fields are
+    /// equal however they were written.
+    Record(Vec<(Symbol, Val)>),
+    /// One of the variants of an enum, such as `Rect(2, 3)` from `enum Shape { Rect(w, h) }`.
+    Variant {
+        ty: Symbol,
+        name: Symbol,
+        fields: Vec<Val>,
+    },
+    /// `start..end`, or `start..=end` if `inclusive`.
+    Range {
+        start: Int,
+        end: Int,
+        inclusive: bool,
+    },
 }
 
+impl Val {
+    /// The name of this value's type, for error messages.
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            Val::Number(_) => "number",
+            Val::Bool(_) => "bool",
+            Val::Str(_) => "string",
+            Val::List(_) => "list",
+            Val::Tuple(_) => "tuple",
+            Val::Unit => "unit",
+            Val::Nil => "nil",
+            Val::Func(_) => "function",
+            Val::Record(_) => "record",
+            Val::Variant { .. } => "variant",
+            Val::Range { .. } => "range",
+        }
+    }
+
+    /// Whether this is a function or holds one somewhere inside it.
+    pub(crate) fn has_func(&self) -> bool {
+        match self {
+            Val::Func(_) => true,
+            Val::List(items) | Val::Tuple(items) | Val::Variant { fields: items, .. } => {
+                items.iter().any(Val::has_func)
+            }
+            Val::Record(fields) => fields.iter().any(|(_, val)| val.has_func()),
+            _ => false,
+        }
+    }
+
+    /// Makes a record, failing if a field is given twice.
+    pub(crate) fn record(mut fields: Vec<(Symbol, Val)>) -> Result<Self, String> {
+        fields.sort_by_key(|(name, _)| name.as_str());
+
+        match fields.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            Some(pair) => Err(format!("field '{}' was given twice", pair[0].0)),
+            None => Ok(Val::Record(fields)),
+        }
+    }
+
+    /// Looks up the field called `name`, failing if there isn't one.
+    pub(crate) fn field(&self, name: Symbol) -> Result<Val, String> {
+        match self {
+            Val::Record(fields) => fields
+                .iter()
+                .find(|(field, _)| *field == name)
+                .map(|(_, val)| val.clone())
+                .ok_or_else(|| format!("record has no field '{}'", name)),
+            _ => Err(format!(
+                "cannot access field '{}' of {}",
+                name,
+                self.type_name()
+            )),
+        }
+    }
+
+    /// Goes through this value one item at a time, failing if it isn't something that can be gone
+    /// through.
+    pub(crate) fn iter(&self) -> Result<Iterable<'_>, String> {
+        match self {
+            Val::List(items) | Val::Tuple(items) => Ok(Iterable::Items(items.iter())),
+            Val::Str(s) => Ok(Iterable::Chars(s.chars())),
+            Val::Record(fields) => Ok(Iterable::Fields(fields.iter())),
+            Val::Range {
+                start,
+                end,
+                inclusive,
+            } => Ok(Iterable::Range {
+                next: Some(start.clone()),
+                end: end.clone(),
+                inclusive: *inclusive,
+            }),
+            _ => Err(format!("cannot iterate over {}", self.type_name())),
+        }
+    }
+
+    /// Looks up the item at `index`, returning `None` if there isn't one.
+    ///
+    /// Strings are indexed by character rather than by byte, and each character is a string of
+    /// its own. Lists and strings can also be sliced with a range such as `1..3`, which gives the
+    /// items from the start of the range up to its end. A slice is only there if it starts no
+    /// later than it ends and ends no later than the length, and no index is ever negative.
+    pub(crate) fn index(&self, index: &Val) -> Result<Option<Val>, String> {
+        match (self, index) {
+            (Val::List(items) | Val::Tuple(items), Val::Number(n)) => {
+                Ok(position(n).and_then(|n| items.get(n)).cloned())
+            }
+            (Val::Str(s), Val::Number(n)) => Ok(position(n)
+                .and_then(|n| s.chars().nth(n))
+                .map(|c| Val::Str(c.to_string()))),
+            (Val::List(items), Val::Range { .. }) => {
+                Ok(slice(index, items.len()).map(|range| Val::List(items[range].to_vec())))
+            }
+            (Val::Str(s), Val::Range { .. }) => Ok(slice(index, s.chars().count())
+                .map(|range| Val::Str(s.chars().skip(range.start).take(range.len()).collect()))),
+            (Val::List(_) | Val::Tuple(_) | Val::Str(_), _) => Err(format!(
+                "cannot index {} with {}",
+                self.type_name(),
+                index.type_name()
+            )),
+            _ => Err(format!("cannot index into {}", self.type_name())),
+        }
+    }
+
+    /// The number of items that indexing can reach, if this can be indexed.
+    pub(crate) fn indexable_len(&self) -> Option<usize> {
+        match self {
+            Val::List(items) | Val::Tuple(items) => Some(items.len()),
+            Val::Str(s) => Some(s.chars().count()),
+            _ => None,
+        }
+    }
+}
+
+fn position(n: &Int) -> Option<usize> {
+    n.to_u64().and_then(|n| usize::try_from(n).ok())
+}
+
+/// The indices that `range` covers in something of length `len`, if they are all there.
+fn slice(range: &Val, len: usize) -> Option<ops::Range<usize>> {
+    let (start, end, inclusive) = match range {
+        Val::Range {
+            start,
+            end,
+            inclusive,
+        } => (position(start)?, position(end)?, *inclusive),
+        _ => return None,
+    };
+    let end = if inclusive { end.checked_add(1)? } else { end };
+
+    (start <= end && end <= len).then_some(start..end)
+}
+
+/// What `for` loops and builtins such as `map` go through: the items of a list or tuple, the
+/// characters of a string as strings of their own, or the fields of a record as `(name, value)`
+/// tuples in the order of their names.
+#[derive(Debug, Clone)]
+pub(crate) enum Iterable<'a> {
+    Items(slice::Iter<'a, Val>),
+    Chars(str::Chars<'a>),
+    Fields(slice::Iter<'a, (Symbol, Val)>),
+    /// The numbers of a range, with `next` being `None` once counting past it would overflow.
+    Range {
+        next: Option<Int>,
+        end: Int,
+        inclusive: bool,
+    },
+}
+
+impl Iterator for Iterable<'_> {
+    type Item = Val;
+
+    fn next(&mut self) -> Option<Val> {
+        match self {
+            Self::Items(items) => items.next().cloned(),
+            Self::Chars(chars) => chars.next().map(|c| Val::Str(c.to_string())),
+            Self::Fields(fields) => fields.next().map(|(name, val)| {
+                Val::Tuple(vec![Val::Str(name.as_str().to_string()), val.clone()])
+            }),
+            Self::Range {
+                next,
+                end,
+                inclusive,
+            } => {
+                let n = next.take()?;
+                if n > *end || (n == *end && !*inclusive) {
+                    return None;
+                }
+
+                *next = n.checked_add(&1.into());
+                Some(Val::Number(n))
+            }
+        }
+    }
+}
+
+/// Shows values the way they would be written in source, so strings get quotes and escapes.
+/// REPLs usually print nothing at all for [`Val::Unit`].
 impl fmt::Display for Val {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Val::Number(n) => write!(f, "{}", n),
-            Val::Unit => write!(f, "Unit"),
+            Val::Bool(b) => write!(f, "{}", b),
+            Val::Str(s) => {
+                let mut string = String::new();
+                crate::fmt::write_string(&mut string, s);
+                write!(f, "{}", string)
+            }
+            Val::List(items) => write_items(f, "[", items, "]"),
+            Val::Tuple(items) => write_items(f, "(", items, ")"),
+            Val::Unit => write!(f, "()"),
+            Val::Nil => write!(f, "nil"),
+            Val::Func(partial) => write!(f, "{}", partial),
+            Val::Record(fields) => {
+                write!(f, "{{")?;
+
+                for (idx, (name, val)) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}: {}", name, val)?;
+                }
+
+                write!(f, "}}")
+            }
+            Val::Variant { name, fields, .. } if fields.is_empty() => write!(f, "{}", name),
+            Val::Variant { name, fields, .. } => {
+                write!(f, "{}", name)?;
+                write_items(f, "(", fields, ")")
+            }
+            Val::Range {
+                start,
+                end,
+                inclusive,
+            } => write!(
+                f,
+                "{}{}{}",
+                start,
+                if *inclusive { "..=" } else { ".." },
+                end
+            ),
         }
     }
 }
+
+fn write_items(f: &mut fmt::Formatter<'_>, open: &str, items: &[Val], close: &str) -> fmt::Result {
+    write!(f, "{}", open)?;
+
+    for (idx, item) in items.iter().enumerate() {
+        if idx > 0 {
+            write!(f, ", ")?;
+        }
+
+        write!(f, "{}", item)?;
+    }
+
+    write!(f, "{}", close)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_like_source() {
+        assert_eq!(Val::Number((-5).into()).to_string(), "-5");
+        assert_eq!(
+            Val::Str("say \"hi\"".to_string()).to_string(),
+            r#""say \"hi\"""#
+        );
+        assert_eq!(
+            Val::List(vec![
+                Val::Number(1.into()),
+                Val::Tuple(vec![Val::Bool(true), Val::Str("a".to_string())]),
+                Val::Unit,
+            ])
+            .to_string(),
+            r#"[1, (true, "a"), ()]"#,
+        );
+    }
+
+    #[test]
+    fn records_are_equal_whatever_the_field_order() {
+        let point = |x: i64, y: i64| {
+            Val::record(vec![
+                ("y".into(), Val::Number(y.into())),
+                ("x".into(), Val::Number(x.into())),
+            ])
+            .unwrap()
+        };
+
+        assert_eq!(point(1, 2).to_string(), "{x: 1, y: 2}");
+        assert_eq!(
+            Val::record(vec![
+                ("x".into(), Val::Number(1.into())),
+                ("y".into(), Val::Number(2.into())),
+            ]),
+            Ok(point(1, 2)),
+        );
+        assert_ne!(point(1, 2), point(2, 1));
+        assert_eq!(point(1, 2).field("y".into()), Ok(Val::Number(2.into())));
+        assert_eq!(
+            point(1, 2).field("z".into()),
+            Err("record has no field 'z'".to_string())
+        );
+    }
+
+    #[test]
+    fn iterate_over_items_chars_and_fields() {
+        let items = |val: Val| val.iter().map(|items| items.collect::<Vec<_>>());
+
+        assert_eq!(
+            items(Val::Str("hé".to_string())),
+            Ok(vec![Val::Str("h".to_string()), Val::Str("é".to_string())]),
+        );
+        assert_eq!(
+            items(Val::record(vec![("b".into(), Val::Nil), ("a".into(), Val::Unit)]).unwrap()),
+            Ok(vec![
+                Val::Tuple(vec![Val::Str("a".to_string()), Val::Unit]),
+                Val::Tuple(vec![Val::Str("b".to_string()), Val::Nil]),
+            ]),
+        );
+        assert_eq!(
+            items(Val::Bool(true)),
+            Err("cannot iterate over bool".to_string())
+        );
+    }
+}
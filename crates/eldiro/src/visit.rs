@@ -0,0 +1,328 @@
+//! Traversal of the syntax tree, for tools that only care about some kinds of node.
+//!
+//! Implement [`Visitor`] (or [`VisitorMut`] to change the tree) and override the methods for the
+//! nodes you're interested in. The default methods call the matching `walk_` function, which
+//! visits the node's children; call it yourself from an overridden method to keep going deeper.
+//!
+//! ```
+//! use eldiro::ast::BindingUsage;
+//! use eldiro::visit::{self, Visitor};
+//!
+//! #[derive(Default)]
+//! struct Names(Vec<String>);
+//!
+//! impl Visitor for Names {
+//!     fn visit_binding_usage(&mut self, binding_usage: &BindingUsage) {
+//!         self.0.push(binding_usage.name.to_string());
+//!     }
+//! }
+//!
+//! let program = eldiro::parse("let a = b + { c }").unwrap();
+//! let mut names = Names::default();
+//! visit::walk_program(&mut names, &program);
+//!
+//! assert_eq!(names.0, ["b", "c"]);
+//! ```
+
+use crate::binding_def::BindingDef;
+use crate::expr::{BindingUsage, Block, Expr, FuncCall};
+use crate::func_def::FuncDef;
+use crate::import::Import;
+use crate::program::Program;
+use crate::stmt::Stmt;
+
+pub trait Visitor {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_binding_def(&mut self, binding_def: &BindingDef) {
+        walk_binding_def(self, binding_def);
+    }
+
+    fn visit_func_def(&mut self, func_def: &FuncDef) {
+        walk_func_def(self, func_def);
+    }
+
+    fn visit_import(&mut self, _import: &Import) {}
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_binding_usage(&mut self, _binding_usage: &BindingUsage) {}
+
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block);
+    }
+
+    fn visit_func_call(&mut self, func_call: &FuncCall) {
+        walk_func_call(self, func_call);
+    }
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for stmt in &program.stmts {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Stmt) {
+    match stmt {
+        Stmt::BindingDef(binding_def) => visitor.visit_binding_def(binding_def),
+        Stmt::Expr(expr) => visitor.visit_expr(expr),
+        Stmt::FuncDef(func_def) => visitor.visit_func_def(func_def),
+        Stmt::EnumDef(_) => {}
+        Stmt::Import(import) => visitor.visit_import(import),
+        Stmt::Throw(val, _) => visitor.visit_expr(val),
+        Stmt::Assign(assign) => visitor.visit_expr(&assign.val),
+    }
+}
+
+pub fn walk_binding_def<V: Visitor + ?Sized>(visitor: &mut V, binding_def: &BindingDef) {
+    visitor.visit_expr(&binding_def.val);
+}
+
+pub fn walk_func_def<V: Visitor + ?Sized>(visitor: &mut V, func_def: &FuncDef) {
+    for default in func_def
+        .params
+        .iter()
+        .filter_map(|param| param.default.as_ref())
+    {
+        visitor.visit_expr(default);
+    }
+    visitor.visit_stmt(&func_def.body);
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Number(..)
+        | Expr::Bool(..)
+        | Expr::Str(..)
+        | Expr::Unit(_)
+        | Expr::Nil(_)
+        | Expr::Placeholder(_) => {}
+        Expr::Index { base, index, .. } => {
+            visitor.visit_expr(base);
+            visitor.visit_expr(index);
+        }
+        Expr::Operation { lhs, rhs, .. } => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+        Expr::Unary { operand, .. } | Expr::Field { base: operand, .. } => {
+            visitor.visit_expr(operand)
+        }
+        Expr::BindingUsage(binding_usage) => visitor.visit_binding_usage(binding_usage),
+        Expr::Block(block) => visitor.visit_block(block),
+        Expr::TryCatch(try_catch) => {
+            visitor.visit_block(&try_catch.body);
+            visitor.visit_block(&try_catch.handler);
+        }
+        Expr::For(for_loop) => {
+            visitor.visit_expr(&for_loop.iterable);
+            visitor.visit_block(&for_loop.body);
+        }
+        Expr::Match(m) => {
+            visitor.visit_expr(&m.val);
+            for arm in &m.arms {
+                visitor.visit_expr(&arm.body);
+            }
+        }
+        Expr::FuncCall(func_call) => visitor.visit_func_call(func_call),
+        Expr::List(items, _) | Expr::Tuple(items, _) => {
+            for item in items {
+                visitor.visit_expr(item);
+            }
+        }
+        Expr::Record(fields, _) => {
+            for (_, val) in fields {
+                visitor.visit_expr(val);
+            }
+        }
+    }
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &Block) {
+    for stmt in &block.stmts {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_func_call<V: Visitor + ?Sized>(visitor: &mut V, func_call: &FuncCall) {
+    for param in &func_call.params {
+        visitor.visit_expr(param);
+    }
+    for (_, val) in &func_call.named {
+        visitor.visit_expr(val);
+    }
+}
+
+/// Like [`Visitor`], but with mutable access to each node so that it can be rewritten.
+pub trait VisitorMut {
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt);
+    }
+
+    fn visit_binding_def_mut(&mut self, binding_def: &mut BindingDef) {
+        walk_binding_def_mut(self, binding_def);
+    }
+
+    fn visit_func_def_mut(&mut self, func_def: &mut FuncDef) {
+        walk_func_def_mut(self, func_def);
+    }
+
+    fn visit_import_mut(&mut self, _import: &mut Import) {}
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+
+    fn visit_binding_usage_mut(&mut self, _binding_usage: &mut BindingUsage) {}
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_func_call_mut(&mut self, func_call: &mut FuncCall) {
+        walk_func_call_mut(self, func_call);
+    }
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut Program) {
+    for stmt in &mut program.stmts {
+        visitor.visit_stmt_mut(stmt);
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Stmt) {
+    match stmt {
+        Stmt::BindingDef(binding_def) => visitor.visit_binding_def_mut(binding_def),
+        Stmt::Expr(expr) => visitor.visit_expr_mut(expr),
+        Stmt::FuncDef(func_def) => visitor.visit_func_def_mut(func_def),
+        Stmt::EnumDef(_) => {}
+        Stmt::Import(import) => visitor.visit_import_mut(import),
+        Stmt::Throw(val, _) => visitor.visit_expr_mut(val),
+        Stmt::Assign(assign) => visitor.visit_expr_mut(&mut assign.val),
+    }
+}
+
+pub fn walk_binding_def_mut<V: VisitorMut + ?Sized>(visitor: &mut V, binding_def: &mut BindingDef) {
+    visitor.visit_expr_mut(&mut binding_def.val);
+}
+
+pub fn walk_func_def_mut<V: VisitorMut + ?Sized>(visitor: &mut V, func_def: &mut FuncDef) {
+    for default in func_def
+        .params
+        .iter_mut()
+        .filter_map(|param| param.default.as_mut())
+    {
+        visitor.visit_expr_mut(default);
+    }
+    visitor.visit_stmt_mut(&mut func_def.body);
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Number(..)
+        | Expr::Bool(..)
+        | Expr::Str(..)
+        | Expr::Unit(_)
+        | Expr::Nil(_)
+        | Expr::Placeholder(_) => {}
+        Expr::Index { base, index, .. } => {
+            visitor.visit_expr_mut(base);
+            visitor.visit_expr_mut(index);
+        }
+        Expr::Operation { lhs, rhs, .. } => {
+            visitor.visit_expr_mut(lhs);
+            visitor.visit_expr_mut(rhs);
+        }
+        Expr::Unary { operand, .. } | Expr::Field { base: operand, .. } => {
+            visitor.visit_expr_mut(operand)
+        }
+        Expr::BindingUsage(binding_usage) => visitor.visit_binding_usage_mut(binding_usage),
+        Expr::Block(block) => visitor.visit_block_mut(block),
+        Expr::TryCatch(try_catch) => {
+            visitor.visit_block_mut(&mut try_catch.body);
+            visitor.visit_block_mut(&mut try_catch.handler);
+        }
+        Expr::For(for_loop) => {
+            visitor.visit_expr_mut(&mut for_loop.iterable);
+            visitor.visit_block_mut(&mut for_loop.body);
+        }
+        Expr::Match(m) => {
+            visitor.visit_expr_mut(&mut m.val);
+            for arm in &mut m.arms {
+                visitor.visit_expr_mut(&mut arm.body);
+            }
+        }
+        Expr::FuncCall(func_call) => visitor.visit_func_call_mut(func_call),
+        Expr::List(items, _) | Expr::Tuple(items, _) => {
+            for item in items {
+                visitor.visit_expr_mut(item);
+            }
+        }
+        Expr::Record(fields, _) => {
+            for (_, val) in fields {
+                visitor.visit_expr_mut(val);
+            }
+        }
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut Block) {
+    for stmt in &mut block.stmts {
+        visitor.visit_stmt_mut(stmt);
+    }
+}
+
+pub fn walk_func_call_mut<V: VisitorMut + ?Sized>(visitor: &mut V, func_call: &mut FuncCall) {
+    for param in &mut func_call.params {
+        visitor.visit_expr_mut(param);
+    }
+    for (_, val) in &mut func_call.named {
+        visitor.visit_expr_mut(val);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct CountCalls(usize);
+
+    impl Visitor for CountCalls {
+        fn visit_func_call(&mut self, func_call: &FuncCall) {
+            self.0 += 1;
+            walk_func_call(self, func_call);
+        }
+    }
+
+    struct Rename;
+
+    impl VisitorMut for Rename {
+        fn visit_binding_usage_mut(&mut self, binding_usage: &mut BindingUsage) {
+            binding_usage.name = binding_usage.name.as_str().to_uppercase().as_str().into();
+        }
+    }
+
+    #[test]
+    fn visit_calls_everywhere() {
+        let program = crate::parse("fn f x => g(h(x))\nlet a = { f 1 }").unwrap();
+
+        let mut count = CountCalls::default();
+        walk_program(&mut count, &program);
+
+        assert_eq!(count.0, 3);
+    }
+
+    #[test]
+    fn rewrite_binding_usages() {
+        let mut program = crate::parse("let a = b + f(c)").unwrap();
+        walk_program_mut(&mut Rename, &mut program);
+
+        assert_eq!(program.format(), "let a = B + f(C)\n");
+    }
+}
@@ -0,0 +1,557 @@
+//! A stack machine that runs bytecode from [`compile`](crate::compile).
+
+use crate::assign;
+use crate::compat::prelude::*;
+use crate::compile::{self, Chunk, Instr};
+use crate::env::{Env, Func};
+use crate::error::RuntimeError;
+use crate::expr::{self, Pattern};
+use crate::func_def::{self, Partial};
+use crate::span::Span;
+use crate::symbol::Symbol;
+use crate::val::Val;
+
+pub(crate) fn run(chunk: &Chunk, env: &mut Env) -> Result<Val, RuntimeError> {
+    let mut stack = Vec::new();
+
+    for instr in &chunk.code {
+        match instr {
+            Instr::Push(val) => stack.push(val.clone()),
+            Instr::Load(name) => {
+                let val = match env.get_binding(*name) {
+                    Ok(val) => val,
+                    Err(error_msg) => match env.get_func(*name) {
+                        Ok(func) => match Partial::of(*name, &func) {
+                            Some(partial) => Val::Func(partial),
+                            None => call(*name, &[], &[], &Span::default(), env)?,
+                        },
+                        Err(_) => return Err(error_msg),
+                    },
+                };
+
+                stack.push(val);
+            }
+            Instr::Store(name) => {
+                let val = pop(&mut stack);
+                env.define_binding(*name, val, false)?;
+            }
+            Instr::StoreConst(name) => {
+                let val = pop(&mut stack);
+                env.define_binding(*name, val, true)?;
+            }
+            Instr::Doc(name, doc) => env.set_doc(*name, doc),
+            Instr::Check { name, ty } => {
+                let val = stack
+                    .last()
+                    .expect("compiled code should never underflow the stack");
+                ty.check(val, format_args!("'{}'", name))?;
+            }
+            Instr::DefineFunc { name, func, doc } => {
+                env.define_func(*name, func.clone(), doc.as_deref())?
+            }
+            Instr::DefineEnum(enum_def) => enum_def.eval(env)?,
+            Instr::Import(import) => import.eval(env)?,
+            Instr::BinOp(op, span) => {
+                let rhs = pop(&mut stack);
+                let lhs = pop(&mut stack);
+                stack.push(op.apply(lhs, rhs, span)?);
+            }
+            Instr::List(len) => {
+                let items = stack.split_off(stack.len() - len);
+                stack.push(Val::List(items));
+            }
+            Instr::Tuple(len) => {
+                let items = stack.split_off(stack.len() - len);
+                stack.push(Val::Tuple(items));
+            }
+            Instr::Record(names) => {
+                let vals = stack.split_off(stack.len() - names.len());
+                stack.push(Val::record(names.iter().copied().zip(vals).collect())?);
+            }
+            Instr::Field(name) => {
+                let base = pop(&mut stack);
+                stack.push(base.field(*name)?);
+            }
+            Instr::Coalesce(rhs) => match pop(&mut stack) {
+                Val::Nil => stack.push(run(rhs, env)?),
+                lhs => stack.push(lhs),
+            },
+            Instr::Index { index, safe, span } => {
+                let base = pop(&mut stack);
+                stack.push(expr::index_into(base, || run(index, env), *safe, span)?);
+            }
+            Instr::UnaryOp(op) => {
+                let operand = pop(&mut stack);
+                stack.push(op.apply(operand)?);
+            }
+            Instr::Call {
+                callee,
+                args,
+                named,
+                span,
+            } => stack.push(call(*callee, args, named, span, env)?),
+            Instr::Block(block) => stack.push(env.in_nested_scope(|scope| run(block, scope))?),
+            Instr::Try {
+                body,
+                name,
+                handler,
+            } => {
+                let val = env
+                    .in_nested_scope(|scope| run(body, scope))
+                    .or_else(|error| expr::caught(env, *name, error, |env| run(handler, env)))?;
+                stack.push(val);
+            }
+            Instr::Match(arms) => {
+                let val = pop(&mut stack);
+                stack.push(run_match(val, arms, env)?);
+            }
+            Instr::For { name, body } => {
+                let iterable = pop(&mut stack);
+
+                for item in iterable.iter()? {
+                    env.in_nested_scope(|body_env| {
+                        body_env.store_binding(*name, item);
+                        run(body, body_env)
+                    })?;
+                }
+
+                stack.push(Val::Unit);
+            }
+            Instr::Assign { name, op, span } => {
+                let val = pop(&mut stack);
+                assign::apply(env, *name, op, val, span)?;
+            }
+            Instr::Throw => return Err(RuntimeError::Thrown(pop(&mut stack))),
+            Instr::Pop => {
+                pop(&mut stack);
+            }
+        }
+    }
+
+    Ok(pop(&mut stack))
+}
+
+fn run_match(val: Val, arms: &[(Pattern, Chunk)], env: &mut Env) -> Result<Val, RuntimeError> {
+    for (pattern, body) in arms {
+        let mut bindings = Vec::new();
+
+        if pattern.matches(&val, &mut bindings)? {
+            return env.in_nested_scope(|arm_env| {
+                for (name, val) in bindings {
+                    arm_env.store_binding(name, val);
+                }
+
+                run(body, arm_env)
+            });
+        }
+    }
+
+    Err(expr::no_match(&val))
+}
+
+fn pop(stack: &mut Vec<Val>) -> Val {
+    stack
+        .pop()
+        .expect("compiled code should never underflow the stack")
+}
+
+fn call(
+    callee: Symbol,
+    args: &[Option<Chunk>],
+    named: &[(Symbol, Chunk)],
+    span: &Span,
+    env: &mut Env,
+) -> Result<Val, RuntimeError> {
+    if let Some(Val::Func(partial)) = env.find_binding(callee) {
+        let partial = partial.clone();
+        partial.check_args(callee, args.len(), named.len(), span)?;
+
+        let args = args
+            .iter()
+            .flatten()
+            .map(|arg| run(arg, env))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (args, named) = partial.fill(args);
+        func_def::check_args(partial.callee, &partial.func, args.len(), named.len(), span)?;
+
+        let scope = func_def::captured_scope(&partial, env);
+        return call_func(
+            partial.callee,
+            &partial.func,
+            &scope,
+            args,
+            named,
+            span,
+            env,
+        );
+    }
+
+    let (func, _) = env.find_func_and_scope(callee)?;
+    func_def::check_args(callee, func, args.len(), named.len(), span)?;
+
+    let named = |env: &mut Env| {
+        named
+            .iter()
+            .map(|(name, val)| Ok((*name, run(val, env)?)))
+            .collect::<Result<Vec<_>, RuntimeError>>()
+    };
+
+    if args.iter().any(Option::is_none) {
+        let func = env.get_func(callee)?;
+        let args = args
+            .iter()
+            .map(|arg| arg.as_ref().map(|arg| run(arg, env)).transpose())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        return Ok(Val::Func(Partial::new(callee, func, args, named(env)?)?));
+    }
+
+    let args = args
+        .iter()
+        .flatten()
+        .map(|arg| run(arg, env))
+        .collect::<Result<Vec<_>, _>>()?;
+    let named = named(env)?;
+
+    // Running the arguments can't define anything in `env`, so the function is still there.
+    let (func, scope) = env.find_func_and_scope(callee)?;
+    call_func(callee, func, scope, args, named, span, env)
+}
+
+/// Calls `func`, which goes by the name `callee`, from `env` at `span`, running its body in a
+/// child of `scope`.
+fn call_func(
+    callee: Symbol,
+    func: &Func,
+    scope: &Env,
+    args: Vec<Val>,
+    named: Vec<(Symbol, Val)>,
+    span: &Span,
+    env: &Env,
+) -> Result<Val, RuntimeError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("call", callee = %callee).entered();
+
+    env.notify(|observer| observer.on_call(callee.as_str(), &args));
+    let _timer = env.profiler().map(|profiler| profiler.start(callee));
+
+    let (params, compiled, body) = match func {
+        Func::User {
+            params,
+            body,
+            compiled,
+            ..
+        } => (params, compiled, body),
+        Func::Native(native) => return native.call(env, &args),
+        Func::Constructor { ty, variant, .. } => {
+            return Ok(Val::Variant {
+                ty: *ty,
+                name: *variant,
+                fields: args,
+            })
+        }
+    };
+
+    let mut child_env = scope.create_child();
+    child_env.enter_call(callee, env, span)?;
+
+    func_def::bind_args(
+        callee,
+        params,
+        args,
+        named,
+        &mut child_env,
+        |default, env| run(&compile::expr_chunk(default), env),
+    )
+    // Functions defined by the tree-walker, such as in imported files, only have their syntax.
+    .and_then(|()| match compiled {
+        Some(compiled) => run(compiled, &mut child_env),
+        None => run(&compile::stmt(body), &mut child_env),
+    })
+    .map_err(|error| func.locate(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(s: &str) -> Result<Val, RuntimeError> {
+        run(
+            &compile::program(&crate::parse(s).unwrap()),
+            &mut Env::default(),
+        )
+    }
+
+    #[test]
+    fn run_arithmetic() {
+        assert_eq!(eval("1 + 2 * 3 - 4"), Ok(Val::Number(3.into())));
+    }
+
+    #[test]
+    fn run_bitwise_ops() {
+        assert_eq!(eval("~5 & 0xff ^ 1 << 3"), Ok(Val::Number(242.into())));
+    }
+
+    #[test]
+    fn run_list_and_tuple() {
+        assert_eq!(
+            eval("let a = 2\n[1, a, (a * 2, a)]"),
+            Ok(Val::List(vec![
+                Val::Number(1.into()),
+                Val::Number(2.into()),
+                Val::Tuple(vec![Val::Number(4.into()), Val::Number(2.into())]),
+            ])),
+        );
+    }
+
+    #[test]
+    fn run_index_and_coalesce() {
+        assert_eq!(
+            eval("let xs = [1, 2]\n(xs[1], xs?[2] ?? 3, 4 ?? 1 / 0)"),
+            Ok(Val::Tuple(vec![
+                Val::Number(2.into()),
+                Val::Number(3.into()),
+                Val::Number(4.into()),
+            ])),
+        );
+    }
+
+    #[test]
+    fn run_try_catch() {
+        assert_eq!(
+            eval("let a = try { let b = 1\nthrow b + 1 } catch e { e * 10 }\na"),
+            Ok(Val::Number(20.into())),
+        );
+        assert_eq!(eval("throw ()"), Err(RuntimeError::Thrown(Val::Unit)));
+    }
+
+    #[test]
+    fn run_for_loop() {
+        assert_eq!(
+            eval("let x = 0\nfor x in [1, 2] { throw x + 10 }"),
+            Err(RuntimeError::Thrown(Val::Number(11.into()))),
+        );
+        assert_eq!(
+            eval("let x = 0\nfor x in [1, 2] { x }\nx"),
+            Ok(Val::Number(0.into()))
+        );
+    }
+
+    #[test]
+    fn run_panic_with_call_stack() {
+        assert_eq!(
+            eval("fn inner => panic(\"deep\")\nfn outer => { inner() }\nouter()"),
+            Err(RuntimeError::Panic {
+                message: "deep".to_string(),
+                stack: vec!["inner".to_string(), "outer".to_string()],
+            }),
+        );
+    }
+
+    #[test]
+    fn run_annotated_bindings_and_params() {
+        assert_eq!(
+            eval("let n: number = 2\nfn twice x: number => x * 2\ntwice(n)"),
+            Ok(Val::Number(4.into())),
+        );
+        assert_eq!(
+            eval("let s: string = ()"),
+            Err(RuntimeError::Message(
+                "expected 's' to be string, got unit".to_string()
+            )),
+        );
+        assert_eq!(
+            eval("fn twice x: number => x * 2\ntwice [1]"),
+            Err(RuntimeError::Message(
+                "expected parameter 'x' of 'twice' to be number, got list".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn fill_in_default_params() {
+        assert_eq!(
+            eval("fn add x, y = x * 2 => x + y\nadd(1) + add(1, 1)"),
+            Ok(Val::Number(5.into())),
+        );
+    }
+
+    #[test]
+    fn collect_rest_args() {
+        assert_eq!(
+            eval("fn second ...xs => xs[1]\nsecond(1, 2, 3)"),
+            Ok(Val::Number(2.into())),
+        );
+    }
+
+    #[test]
+    fn match_named_args() {
+        assert_eq!(
+            eval("fn sub x, y = 10 => x - y\nsub(y: 1, x: 3) + sub(x: 20)"),
+            Ok(Val::Number(12.into())),
+        );
+        assert_eq!(
+            eval("fn sub x y => x - y\nsub(1, 2, 3, y: 3)"),
+            Err(RuntimeError::Arity {
+                callee: "sub".to_string(),
+                min: 2,
+                max: Some(2),
+                got: 4,
+                span: 20..38,
+            }),
+        );
+    }
+
+    #[test]
+    fn apply_partially() {
+        assert_eq!(
+            eval("fn add x y => x + y\nlet inc = add(1, _)\ninc(inc(2))"),
+            Ok(Val::Number(4.into())),
+        );
+        assert_eq!(
+            eval("fn add x => x + 1\nlet h = add(_)\n{ fn add x => 0\nh(5) }"),
+            Ok(Val::Number(6.into())),
+        );
+        assert_eq!(
+            eval("fn adder n => {\nfn inner a b => a + b\ninner(n, _)\n}\nlet add = adder(10)\nadd(1)"),
+            Ok(Val::Number(11.into())),
+        );
+        assert_eq!(
+            eval(
+                "fn sub x y, z = 0 => x - y - z\nlet f = sub(_, 1, x: 2)\nlet g = sub(_, y: 3)\n(f(5), g(10))"
+            ),
+            Ok(Val::Tuple(vec![
+                Val::Number((-4).into()),
+                Val::Number(7.into())
+            ])),
+        );
+    }
+
+    #[test]
+    fn run_records() {
+        assert_eq!(
+            eval("{x: 1}.z"),
+            Err(RuntimeError::Message("record has no field 'z'".to_string())),
+        );
+        assert_eq!(
+            eval("let p = {y: 2, x: 1}\n(p.x + p.y, p == {x: 1, y: 2})"),
+            Ok(Val::Tuple(vec![Val::Number(3.into()), Val::Bool(true)])),
+        );
+    }
+
+    #[test]
+    fn run_enums_and_match() {
+        assert_eq!(
+            eval(
+                "enum Opt { Some(x), None }
+fn get o => match o { Some(x) => x, None => 0 }
+[get(Some(2)), get(None)]"
+            ),
+            Ok(Val::List(vec![
+                Val::Number(2.into()),
+                Val::Number(0.into())
+            ])),
+        );
+        assert_eq!(
+            eval("match 1 { 2 => 0 }"),
+            Err(RuntimeError::Message("no pattern matches 1".to_string())),
+        );
+    }
+
+    #[test]
+    fn refuse_to_redefine_const() {
+        assert_eq!(
+            eval("const a = 1\nlet a = 2"),
+            Err(RuntimeError::Message(
+                "cannot redefine constant 'a'".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn run_func_calls() {
+        assert_eq!(
+            eval("fn add x y => x + y\nfn twice x => add(x, x)\ntwice 21"),
+            Ok(Val::Number(42.into())),
+        );
+    }
+
+    #[test]
+    fn call_funcs_defined_without_compiling() {
+        let mut env = Env::default();
+        crate::parse("fn double x => x * 2")
+            .unwrap()
+            .eval(&mut env)
+            .unwrap();
+
+        assert_eq!(
+            run(
+                &compile::program(&crate::parse("double(21)").unwrap()),
+                &mut env
+            ),
+            Ok(Val::Number(42.into())),
+        );
+    }
+
+    #[test]
+    fn blocks_have_their_own_scope() {
+        assert_eq!(
+            eval("let a = { let b = 5\nb * 2 }\nb"),
+            Err(RuntimeError::Message(
+                "binding with name 'b' does not exist".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn args_are_evaluated_in_caller_scope() {
+        assert_eq!(
+            eval("let x = 5\nfn second x y => y\nsecond(1, x)"),
+            Ok(Val::Number(5.into())),
+        );
+    }
+
+    #[test]
+    fn function_names_are_values() {
+        assert_eq!(
+            eval("fn inc x => x + 1\nlet f = inc\n(f(1), map([1, 2], inc))"),
+            Ok(Val::Tuple(vec![
+                Val::Number(2.into()),
+                Val::List(vec![Val::Number(2.into()), Val::Number(3.into())]),
+            ])),
+        );
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn report_overflow() {
+        assert_eq!(
+            eval("9223372036854775807 + 1"),
+            Err(RuntimeError::Overflow {
+                op: crate::expr::Op::Add
+            }),
+        );
+    }
+
+    #[test]
+    fn report_division_by_zero() {
+        assert_eq!(
+            eval("7 / 0"),
+            Err(RuntimeError::DivisionByZero { span: 0..5 }),
+        );
+    }
+
+    #[test]
+    fn report_arity_errors() {
+        assert_eq!(
+            eval("fn id x => x\nid(1, 2)"),
+            Err(RuntimeError::Arity {
+                callee: "id".to_string(),
+                min: 1,
+                max: Some(1),
+                got: 2,
+                span: 13..21,
+            }),
+        );
+    }
+}